@@ -1,57 +1,186 @@
+use std::collections::BTreeSet;
+use std::env;
 use std::fs;
-use std::path::Path;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::process::{exit, Command};
+use std::time::SystemTime;
+
+#[path = "src/backend/vulkan/glslc.rs"]
+mod glslc;
+
+use glslc::CompileOptions;
+
+/// Files whose changes invalidate every compiled module
+const BUILD_INPUTS: &[&str] = &["build.rs", "src/backend/vulkan/glslc.rs"];
+
+/// A compiled variant, one entry of the embedded shader table
+struct CompiledShader {
+    name: String,
+    stage: &'static str,
+    defines: Vec<String>,
+    output_path: PathBuf,
+}
 
 fn main() {
-    let shader_dir = "./shaders";
-    let output_dir = "./cshaders";
+    let shader_dir = Path::new("shaders");
+    let output_dir = Path::new(&env::var("OUT_DIR").expect("OUT_DIR is not set")).join("shaders");
     println!("Building shaders...");
+    if !glslc_available() {
+        exit(1);
+    }
 
-    // Create output directory if it doesn't exist
-    fs::create_dir_all(output_dir).expect("Failed to create output directory");
+    let mut sources = Vec::new();
+    find_sources(shader_dir, &mut sources);
+    sources.sort();
 
     let mut compilation_failed = false;
-
-    // Iterate through files in the shader directory
-    for entry in fs::read_dir(shader_dir).expect("Failed to read shader directory") {
-        let entry = entry.expect("Failed to read directory entry");
-        let path = entry.path();
-
-        if path.is_file() {
-            if let Some(extension) = path.extension() {
-                if extension == "frag" || extension == "glsl" || extension == "vert" {
-                    if !compile_shader(&path, output_dir) {
-                        compilation_failed = true;
-                    }
-                }
-            }
+    let mut compiled = Vec::new();
+    // Sources and the includes from their depfiles. Other files don't rerun the build script, after adding a shader
+    // touch `build.rs` or an existing source.
+    let mut tracked = BTreeSet::new();
+    for path in sources.iter() {
+        if !compile_source(shader_dir, path, &output_dir, &mut compiled, &mut tracked) {
+            compilation_failed = true;
         }
     }
+    for path in tracked {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
 
     if compilation_failed {
         eprintln!("Shader compilation failed. Build aborted.");
         exit(1);
     } else {
-        println!("Shaders built successfully in {}!", output_dir);
+        println!("Shaders built successfully in {}!", output_dir.display());
+    }
+    write_embedded_shaders(&compiled);
+}
+
+/// Prints how to get glslc if it can't be started, a failing compile would only report the spawn error
+fn glslc_available() -> bool {
+    match Command::new("glslc").arg("--version").output() {
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            println!("cargo:warning=glslc was not found, shaders can't be compiled");
+            eprintln!("glslc was not found on PATH. It ships with the Vulkan SDK (https://vulkan.lunarg.com/sdk/home) and");
+            eprintln!("with shaderc (https://github.com/google/shaderc), install one of them and add its bin directory to PATH.");
+            false
+        }
+        _ => true,
     }
-    println!("cargo:rerun-if-changed=shaders");
 }
 
-fn compile_shader(shader_path: &Path, output_dir: &str) -> bool {
-    let file_stem = shader_path.file_stem().unwrap().to_str().unwrap();
-    let output_path = format!("{}/{}.spv", output_dir, file_stem);
+fn find_sources(dir: &Path, sources: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).expect("Failed to read shader directory") {
+        let path = entry.expect("Failed to read directory entry").path();
+        if path.is_dir() {
+            find_sources(&path, sources);
+        } else if glslc::is_shader_source(&path) {
+            sources.push(path);
+        }
+    }
+}
 
-    let output = Command::new("glslc")
-        .arg("-I")
-        .arg(shader_path.parent().unwrap())
-        .arg(shader_path)
-        .arg("-o")
-        .arg(&output_path)
+/// Compiles every variant of the source that is out of date, returns false if one of them failed
+fn compile_source(
+    shader_dir: &Path,
+    path: &Path,
+    output_dir: &Path,
+    compiled: &mut Vec<CompiledShader>,
+    tracked: &mut BTreeSet<PathBuf>,
+) -> bool {
+    tracked.insert(path.to_path_buf());
+    let relative = path.strip_prefix(shader_dir).unwrap_or(path);
+    let source = fs::read_to_string(path).expect("Failed to read shader source");
+    let options = match CompileOptions::parse(&source) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("Failed to compile: {}", path.display());
+            eprintln!("{}", error);
+            return false;
+        }
+    };
+    let name = relative.with_extension("").to_string_lossy().replace('\\', "/");
+
+    let mut succeeded = true;
+    for (variant, defines) in options.variants().into_iter().enumerate() {
+        let output_path = glslc::output_path(output_dir, relative, variant);
+        let depfile_path = output_path.with_extension("spv.d");
+        if !is_up_to_date(&output_path, &depfile_path) && !compile_shader(path, &output_path, &depfile_path, &options, &defines) {
+            succeeded = false;
+            continue;
+        }
+        tracked.extend(glslc::parse_depfile(&fs::read_to_string(&depfile_path).unwrap_or_default()));
+        compiled.push(CompiledShader {
+            name: glslc::permutation_name(&name, &defines),
+            stage: glslc::stage_flag_name(path).unwrap_or("empty()"),
+            defines,
+            output_path,
+        });
+    }
+    succeeded
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// The module is newer than the source, its includes from the depfile and the build script
+fn is_up_to_date(output_path: &Path, depfile_path: &Path) -> bool {
+    let (Some(output_time), Ok(depfile)) = (modified(output_path), fs::read_to_string(depfile_path)) else {
+        return false;
+    };
+    let dependencies = glslc::parse_depfile(&depfile);
+    !dependencies.is_empty()
+        && dependencies
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(BUILD_INPUTS.iter().map(Path::new))
+            .all(|dependency| modified(dependency).is_some_and(|time| time <= output_time))
+}
+
+/// Writes the table `shaders::EMBEDDED_SHADERS` includes, every compiled module as `include_bytes!` with an absolute
+/// path so binaries don't depend on the working directory
+fn write_embedded_shaders(compiled: &[CompiledShader]) {
+    let mut table = String::from("&[\n");
+    for shader in compiled {
+        table += &format!(
+            "    EmbeddedShader {{ name: {:?}, stage: ash::vk::ShaderStageFlags::{}, defines: &{:?}, code: include_bytes!({:?}) }},\n",
+            shader.name,
+            shader.stage,
+            shader.defines,
+            shader.output_path.to_str().unwrap()
+        );
+    }
+    table += "]\n";
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is not set");
+    fs::write(Path::new(&out_dir).join("embedded_shaders.rs"), table).expect("Failed to write the embedded shader table");
+}
+
+fn compile_shader(shader_path: &Path, output_path: &Path, depfile_path: &Path, options: &CompileOptions, defines: &[String]) -> bool {
+    fs::create_dir_all(output_path.parent().unwrap()).expect("Failed to create output directory");
+    let output = match glslc::glslc_command(shader_path, output_path, options, defines)
+        .arg("-MD")
+        .arg("-MF")
+        .arg(depfile_path)
         .output()
-        .expect("Failed to execute glslc");
+    {
+        Ok(output) => output,
+        Err(error) => {
+            eprintln!("Failed to run glslc for {}: {}", shader_path.display(), error);
+            return false;
+        }
+    };
 
     if output.status.success() {
-        println!("Compiled: {} -> {}", shader_path.display(), output_path);
+        // Warnings only show up in the cargo output this way
+        for line in String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+        {
+            println!("cargo:warning={}", line);
+        }
+        println!("Compiled: {} -> {}", shader_path.display(), output_path.display());
         true
     } else {
         eprintln!("Failed to compile: {}", shader_path.display());
@@ -0,0 +1,66 @@
+use crate::image::RgbaImage;
+
+/// Converts a normalized channel the same way UNORM attachments do, round to nearest
+pub fn to_unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+pub fn from_unorm8(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+/// RGBA8 color attachment with a 32 bit float depth attachment
+pub struct Framebuffer {
+    color: RgbaImage,
+    depth: Vec<f32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            color: RgbaImage::new(width, height),
+            depth: vec![1.0; (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.color.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.color.height
+    }
+
+    pub fn clear(&mut self, color: [f32; 4], depth: f32) {
+        let pixel = color.map(to_unorm8);
+        for chunk in self.color.pixels.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&pixel);
+        }
+        self.depth.fill(depth);
+    }
+
+    pub fn color(&self) -> &RgbaImage {
+        &self.color
+    }
+
+    pub fn into_color(self) -> RgbaImage {
+        self.color
+    }
+
+    pub fn depth(&self, x: u32, y: u32) -> f32 {
+        self.depth[(y * self.width() + x) as usize]
+    }
+
+    pub fn set_depth(&mut self, x: u32, y: u32, depth: f32) {
+        let width = self.width();
+        self.depth[(y * width + x) as usize] = depth;
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [f32; 4] {
+        self.color.pixel(x, y).map(from_unorm8)
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [f32; 4]) {
+        self.color.set_pixel(x, y, color.map(to_unorm8));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod framebuffer;
+pub mod rasterizer;
+pub mod renderer;
@@ -0,0 +1,285 @@
+use crate::backend::cpu::framebuffer::Framebuffer;
+
+/// Screen coordinates are snapped to 1/256 of a pixel like on most GPUs, coverage is decided on exact integers
+pub const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_HALF: i64 = 1 << (SUBPIXEL_BITS - 1);
+const W_EPSILON: f32 = 1e-6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareOp {
+    Never,
+    Less,
+    Equal,
+    LessOrEqual,
+    Greater,
+    NotEqual,
+    GreaterOrEqual,
+    Always,
+}
+
+impl CompareOp {
+    pub fn test(self, value: f32, reference: f32) -> bool {
+        match self {
+            CompareOp::Never => false,
+            CompareOp::Less => value < reference,
+            CompareOp::Equal => value == reference,
+            CompareOp::LessOrEqual => value <= reference,
+            CompareOp::Greater => value > reference,
+            CompareOp::NotEqual => value != reference,
+            CompareOp::GreaterOrEqual => value >= reference,
+            CompareOp::Always => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Replace,
+    /// Matches the Vulkan pipeline: color is src_alpha / one_minus_src_alpha, alpha is one / zero
+    Alpha,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CullMode {
+    None,
+    Front,
+    Back,
+}
+
+/// Winding in framebuffer coordinates (y pointing down), same convention as `vk::FrontFace`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontFace {
+    CounterClockwise,
+    Clockwise,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RasterState {
+    /// `None` disables both the depth test and depth writes
+    pub depth_test: Option<CompareOp>,
+    pub depth_write: bool,
+    pub blend: BlendMode,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+}
+
+impl Default for RasterState {
+    fn default() -> Self {
+        Self {
+            depth_test: None,
+            depth_write: false,
+            blend: BlendMode::Alpha,
+            cull_mode: CullMode::None,
+            front_face: FrontFace::Clockwise,
+        }
+    }
+}
+
+/// Clip space position (Vulkan conventions, 0 <= z <= w) with `N` attributes passed to the fragment shader
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex<const N: usize> {
+    pub position: [f32; 4],
+    pub attributes: [f32; N],
+}
+
+impl<const N: usize> Vertex<N> {
+    pub fn new(position: [f32; 4], attributes: [f32; N]) -> Self {
+        Self { position, attributes }
+    }
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let mut result = *self;
+        for (value, other) in result.position.iter_mut().zip(other.position.iter()) {
+            *value += (other - *value) * t;
+        }
+        for (value, other) in result.attributes.iter_mut().zip(other.attributes.iter()) {
+            *value += (other - *value) * t;
+        }
+        result
+    }
+}
+
+/// Clip volume planes, a position is inside when every distance is non negative
+const CLIP_PLANES: [fn(&[f32; 4]) -> f32; 7] = [
+    |p| p[3] + p[0],
+    |p| p[3] - p[0],
+    |p| p[3] + p[1],
+    |p| p[3] - p[1],
+    |p| p[2],
+    |p| p[3] - p[2],
+    |p| p[3] - W_EPSILON,
+];
+
+/// Sutherland-Hodgman against the clip volume, returns a convex polygon with the winding of the input
+fn clip_triangle<const N: usize>(triangle: &[Vertex<N>; 3]) -> Vec<Vertex<N>> {
+    let mut polygon = triangle.to_vec();
+    for plane in CLIP_PLANES.iter() {
+        if polygon.iter().all(|vertex| plane(&vertex.position) >= 0.0) {
+            continue;
+        }
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for (index, current) in polygon.iter().enumerate() {
+            let next = &polygon[(index + 1) % polygon.len()];
+            let current_distance = plane(&current.position);
+            let next_distance = plane(&next.position);
+            if current_distance >= 0.0 {
+                clipped.push(*current);
+            }
+            if (current_distance >= 0.0) != (next_distance >= 0.0) {
+                clipped.push(current.lerp(next, current_distance / (current_distance - next_distance)));
+            }
+        }
+        polygon = clipped;
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+    polygon
+}
+
+struct ScreenVertex<const N: usize> {
+    x: i64,
+    y: i64,
+    z: f32,
+    inv_w: f32,
+    attributes: [f32; N],
+}
+
+impl<const N: usize> ScreenVertex<N> {
+    fn from_clip(vertex: &Vertex<N>, width: u32, height: u32) -> Self {
+        let inv_w = 1.0 / vertex.position[3];
+        let snap = |ndc: f32, size: u32| ((ndc + 1.0) * 0.5 * size as f32 * (1 << SUBPIXEL_BITS) as f32).round() as i64;
+        Self {
+            x: snap(vertex.position[0] * inv_w, width),
+            y: snap(vertex.position[1] * inv_w, height),
+            z: vertex.position[2] * inv_w,
+            inv_w,
+            attributes: vertex.attributes,
+        }
+    }
+}
+
+/// Positive when `p` is on the interior side of `a -> b` for clockwise triangles in framebuffer coordinates
+fn edge_function(ax: i64, ay: i64, bx: i64, by: i64, px: i64, py: i64) -> i64 {
+    (bx - ax) * (py - ay) - (by - ay) * (px - ax)
+}
+
+/// Top-left fill rule: pixel centers exactly on an edge only belong to the triangle if the edge is a top or left edge
+fn is_top_left(ax: i64, ay: i64, bx: i64, by: i64) -> bool {
+    let (dx, dy) = (bx - ax, by - ay);
+    (dy == 0 && dx > 0) || dy < 0
+}
+
+fn blend(mode: BlendMode, source: [f32; 4], destination: [f32; 4]) -> [f32; 4] {
+    match mode {
+        BlendMode::Replace => source,
+        BlendMode::Alpha => {
+            let alpha = source[3];
+            [
+                source[0] * alpha + destination[0] * (1.0 - alpha),
+                source[1] * alpha + destination[1] * (1.0 - alpha),
+                source[2] * alpha + destination[2] * (1.0 - alpha),
+                alpha,
+            ]
+        }
+    }
+}
+
+/// Draws a triangle list, every 3 vertices form a triangle. `shader` receives the perspective correct
+/// interpolated attributes and returns the fragment color.
+pub fn draw_triangles<const N: usize, F>(framebuffer: &mut Framebuffer, state: &RasterState, vertices: &[Vertex<N>], mut shader: F)
+where
+    F: FnMut(&[f32; N]) -> [f32; 4],
+{
+    for triangle in vertices.chunks_exact(3) {
+        draw_triangle(framebuffer, state, &[triangle[0], triangle[1], triangle[2]], &mut shader);
+    }
+}
+
+pub fn draw_triangle<const N: usize, F>(framebuffer: &mut Framebuffer, state: &RasterState, triangle: &[Vertex<N>; 3], shader: &mut F)
+where
+    F: FnMut(&[f32; N]) -> [f32; 4],
+{
+    let polygon = clip_triangle(triangle);
+    for index in 1..polygon.len().saturating_sub(1) {
+        rasterize(framebuffer, state, [&polygon[0], &polygon[index], &polygon[index + 1]], shader);
+    }
+}
+
+fn rasterize<const N: usize, F>(framebuffer: &mut Framebuffer, state: &RasterState, triangle: [&Vertex<N>; 3], shader: &mut F)
+where
+    F: FnMut(&[f32; N]) -> [f32; 4],
+{
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let mut v = triangle.map(|vertex| ScreenVertex::from_clip(vertex, width, height));
+
+    let mut area = edge_function(v[0].x, v[0].y, v[1].x, v[1].y, v[2].x, v[2].y);
+    if area == 0 {
+        return;
+    }
+    let clockwise = area > 0;
+    let front_facing = clockwise == (state.front_face == FrontFace::Clockwise);
+    let culled = match state.cull_mode {
+        CullMode::None => false,
+        CullMode::Front => front_facing,
+        CullMode::Back => !front_facing,
+    };
+    if culled {
+        return;
+    }
+    // Edge functions are positive inside from here on
+    if !clockwise {
+        v.swap(1, 2);
+        area = -area;
+    }
+
+    let edges = [(1, 2), (2, 0), (0, 1)];
+    let bias = edges.map(|(a, b)| if is_top_left(v[a].x, v[a].y, v[b].x, v[b].y) { 0 } else { -1 });
+
+    let to_pixel = |coordinate: i64| coordinate >> SUBPIXEL_BITS;
+    let min_x = to_pixel(v.iter().map(|vertex| vertex.x).min().unwrap()).max(0);
+    let max_x = to_pixel(v.iter().map(|vertex| vertex.x).max().unwrap()).min(width as i64 - 1);
+    let min_y = to_pixel(v.iter().map(|vertex| vertex.y).min().unwrap()).max(0);
+    let max_y = to_pixel(v.iter().map(|vertex| vertex.y).max().unwrap()).min(height as i64 - 1);
+
+    for y in min_y..=max_y {
+        let py = (y << SUBPIXEL_BITS) + SUBPIXEL_HALF;
+        for x in min_x..=max_x {
+            let px = (x << SUBPIXEL_BITS) + SUBPIXEL_HALF;
+            let weights = [0, 1, 2].map(|edge| {
+                let (a, b) = edges[edge];
+                edge_function(v[a].x, v[a].y, v[b].x, v[b].y, px, py)
+            });
+            if (0..3).any(|edge| weights[edge] + bias[edge] < 0) {
+                continue;
+            }
+
+            let barycentric = weights.map(|weight| weight as f32 / area as f32);
+            let depth = barycentric[0] * v[0].z + barycentric[1] * v[1].z + barycentric[2] * v[2].z;
+            let (pixel_x, pixel_y) = (x as u32, y as u32);
+            if let Some(compare_op) = state.depth_test {
+                if !compare_op.test(depth, framebuffer.depth(pixel_x, pixel_y)) {
+                    continue;
+                }
+            }
+
+            // Perspective correct: interpolate attribute / w and 1 / w linearly in screen space
+            let perspective = [0, 1, 2].map(|index| barycentric[index] * v[index].inv_w);
+            let normalization = 1.0 / (perspective[0] + perspective[1] + perspective[2]);
+            let mut attributes = [0.0; N];
+            for (index, attribute) in attributes.iter_mut().enumerate() {
+                *attribute = (perspective[0] * v[0].attributes[index]
+                    + perspective[1] * v[1].attributes[index]
+                    + perspective[2] * v[2].attributes[index])
+                    * normalization;
+            }
+
+            let color = shader(&attributes);
+            let blended = blend(state.blend, color, framebuffer.pixel(pixel_x, pixel_y));
+            framebuffer.set_pixel(pixel_x, pixel_y, blended);
+            if state.depth_test.is_some() && state.depth_write {
+                framebuffer.set_depth(pixel_x, pixel_y, depth);
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::backend::cpu::framebuffer::Framebuffer;
+use crate::backend::cpu::rasterizer::{draw_triangles, RasterState, Vertex};
+use crate::backend::renderer::{validate_draw, Backend, BufferHandle, ColorVertex, Command, CommandList, Error, Renderer, ResourceSlots};
+use crate::image::RgbaImage;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use winit::window::Window;
+
+type WindowSurface = softbuffer::Surface<Arc<Window>, Arc<Window>>;
+
+/// `Renderer` on top of the software rasterizer, uses the same fixed function state as the Vulkan backend.
+/// Frames are presented by copying the framebuffer into a `softbuffer` surface of the window.
+pub struct CpuRenderer {
+    framebuffer: Framebuffer,
+    state: RasterState,
+    buffers: ResourceSlots<Vec<Vertex<4>>>,
+    surface: Option<WindowSurface>,
+}
+
+impl CpuRenderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            framebuffer: Framebuffer::new(width, height),
+            state: RasterState::default(),
+            buffers: ResourceSlots::new(),
+            surface: None,
+        }
+    }
+
+    /// Renderer presenting to the window, `width` and `height` should match its inner size
+    pub fn windowed(window: Arc<Window>, width: u32, height: u32) -> Result<Self, Error> {
+        let context = softbuffer::Context::new(window.clone())?;
+        let mut renderer = Self::new(width, height);
+        renderer.surface = Some(softbuffer::Surface::new(&context, window)?);
+        renderer.resize(width, height)?;
+        Ok(renderer)
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+}
+
+impl Renderer for CpuRenderer {
+    fn backend(&self) -> Backend {
+        Backend::Cpu
+    }
+
+    fn extent(&self) -> (u32, u32) {
+        (self.framebuffer.width(), self.framebuffer.height())
+    }
+
+    fn create_vertex_buffer(&mut self, vertices: &[ColorVertex]) -> Result<BufferHandle, Error> {
+        let vertices = vertices.iter().map(|vertex| Vertex::new(vertex.position, vertex.color)).collect();
+        Ok(self.buffers.insert(vertices))
+    }
+
+    fn destroy_buffer(&mut self, buffer: BufferHandle) -> Result<(), Error> {
+        self.buffers.remove(buffer).map(|_| ())
+    }
+
+    fn submit(&mut self, commands: &CommandList) -> Result<(), Error> {
+        self.framebuffer.clear(commands.clear_color(), 1.0);
+        for command in commands.commands() {
+            match *command {
+                Command::Draw {
+                    buffer,
+                    first_vertex,
+                    vertex_count,
+                } => {
+                    let vertices = self.buffers.get(buffer)?;
+                    validate_draw(buffer, vertices.len(), first_vertex, vertex_count)?;
+                    let range = first_vertex as usize..(first_vertex + vertex_count) as usize;
+                    draw_triangles(&mut self.framebuffer, &self.state, &vertices[range], |color| *color);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_pixels(&mut self) -> Result<RgbaImage, Error> {
+        Ok(self.framebuffer.color().clone())
+    }
+
+    fn present(&mut self) -> Result<(), Error> {
+        let surface = self.surface.as_mut().ok_or(Error::NoWindow)?;
+        // Surfaces can't be zero sized, there is nothing to show anyway
+        if self.framebuffer.width() == 0 || self.framebuffer.height() == 0 {
+            return Ok(());
+        }
+        let mut buffer = surface.buffer_mut()?;
+        // The surface is resized along with the framebuffer, both have the same size
+        for (pixel, color) in buffer.iter_mut().zip(self.framebuffer.color().pixels.chunks_exact(4)) {
+            *pixel = (color[0] as u32) << 16 | (color[1] as u32) << 8 | color[2] as u32;
+        }
+        buffer.present()?;
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        let (Some(surface_width), Some(surface_height)) = (NonZeroU32::new(width), NonZeroU32::new(height)) else {
+            return Ok(());
+        };
+        if (width, height) != self.extent() {
+            self.framebuffer = Framebuffer::new(width, height);
+        }
+        if let Some(surface) = self.surface.as_mut() {
+            surface.resize(surface_width, surface_height)?;
+        }
+        Ok(())
+    }
+
+    fn set_vsync(&mut self, _vsync: bool) {}
+}
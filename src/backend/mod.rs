@@ -1 +1,3 @@
+pub mod cpu;
+pub mod renderer;
 pub mod vulkan;
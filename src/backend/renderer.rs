@@ -0,0 +1,293 @@
+use crate::backend::cpu::renderer::CpuRenderer;
+use crate::backend::vulkan;
+use crate::backend::vulkan::buffer::Pod;
+use crate::backend::vulkan::renderer::VulkanRenderer;
+use crate::image::RgbaImage;
+use log::{info, warn};
+use std::fmt;
+use std::sync::Arc;
+use winit::window::Window;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Vulkan,
+    Cpu,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackendPreference {
+    /// Vulkan if it can be initialized, otherwise the CPU rasterizer
+    Auto,
+    Vulkan,
+    Cpu,
+}
+
+/// Clip space position and RGBA color, the layout matches `shaders/color_vshader.vert`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorVertex {
+    pub position: [f32; 4],
+    pub color: [f32; 4],
+}
+
+impl ColorVertex {
+    pub fn new(position: [f32; 4], color: [f32; 4]) -> Self {
+        Self { position, color }
+    }
+}
+
+// Two float arrays without padding
+unsafe impl Pod for ColorVertex {}
+
+crate::impl_vertex!(ColorVertex { position, color });
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferHandle(usize);
+
+#[derive(Debug)]
+pub enum Error {
+    /// Creating or using the Vulkan backend failed
+    Vulkan(vulkan::errors::Error),
+    /// The buffer was destroyed or belongs to another renderer
+    InvalidBuffer(BufferHandle),
+    /// The draw reads vertices past the end of the buffer
+    DrawOutOfBounds {
+        buffer: BufferHandle,
+        first_vertex: u32,
+        vertex_count: u32,
+    },
+    /// Presenting needs a renderer created with a window
+    NoWindow,
+    /// Presenting the CPU backend's frame to the window failed
+    Presentation(softbuffer::SoftBufferError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Vulkan(error) => write!(f, "Vulkan backend failed: {}", error),
+            Error::InvalidBuffer(buffer) => write!(f, "Invalid buffer {:?}", buffer),
+            Error::DrawOutOfBounds {
+                buffer,
+                first_vertex,
+                vertex_count,
+            } => write!(f, "Draw of {} vertices from {} is out of bounds of buffer {:?}", vertex_count, first_vertex, buffer),
+            Error::NoWindow => write!(f, "The renderer was created without a window"),
+            Error::Presentation(error) => write!(f, "Failed to present to the window: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Vulkan(error) => Some(error),
+            Error::Presentation(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<vulkan::errors::Error> for Error {
+    fn from(error: vulkan::errors::Error) -> Self {
+        Error::Vulkan(error)
+    }
+}
+
+impl From<softbuffer::SoftBufferError> for Error {
+    fn from(error: softbuffer::SoftBufferError) -> Self {
+        Error::Presentation(error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// Triangle list of `vertex_count` vertices starting at `first_vertex`
+    Draw {
+        buffer: BufferHandle,
+        first_vertex: u32,
+        vertex_count: u32,
+    },
+}
+
+/// Backend independent recording of a frame, executed by `Renderer::submit`
+#[derive(Clone, Debug)]
+pub struct CommandList {
+    clear_color: [f32; 4],
+    commands: Vec<Command>,
+}
+
+impl CommandList {
+    pub fn new(clear_color: [f32; 4]) -> Self {
+        Self {
+            clear_color,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn draw(&mut self, buffer: BufferHandle, first_vertex: u32, vertex_count: u32) {
+        self.commands.push(Command::Draw {
+            buffer,
+            first_vertex,
+            vertex_count,
+        });
+    }
+
+    pub fn clear_color(&self) -> [f32; 4] {
+        self.clear_color
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+}
+
+/// Storage for backend resources addressed by `BufferHandle`, freed slots are reused
+pub struct ResourceSlots<T> {
+    slots: Vec<Option<T>>,
+}
+
+impl<T> ResourceSlots<T> {
+    pub fn new() -> Self {
+        Self { slots: Vec::new() }
+    }
+
+    pub fn insert(&mut self, resource: T) -> BufferHandle {
+        match self.slots.iter().position(|slot| slot.is_none()) {
+            Some(index) => {
+                self.slots[index] = Some(resource);
+                BufferHandle(index)
+            }
+            None => {
+                self.slots.push(Some(resource));
+                BufferHandle(self.slots.len() - 1)
+            }
+        }
+    }
+
+    pub fn get(&self, handle: BufferHandle) -> Result<&T, Error> {
+        self.slots
+            .get(handle.0)
+            .and_then(|slot| slot.as_ref())
+            .ok_or(Error::InvalidBuffer(handle))
+    }
+
+    pub fn remove(&mut self, handle: BufferHandle) -> Result<T, Error> {
+        self.slots
+            .get_mut(handle.0)
+            .and_then(|slot| slot.take())
+            .ok_or(Error::InvalidBuffer(handle))
+    }
+}
+
+impl<T> Default for ResourceSlots<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Checks that a draw stays inside a buffer of `buffer_length` vertices
+pub fn validate_draw(buffer: BufferHandle, buffer_length: usize, first_vertex: u32, vertex_count: u32) -> Result<(), Error> {
+    if first_vertex as usize + vertex_count as usize > buffer_length {
+        return Err(Error::DrawOutOfBounds {
+            buffer,
+            first_vertex,
+            vertex_count,
+        });
+    }
+    Ok(())
+}
+
+/// Rendering device implemented by every backend. Frames are recorded into a `CommandList`,
+/// submitted and read back as RGBA8 or presented to a window without the caller knowing which backend is active.
+pub trait Renderer {
+    fn backend(&self) -> Backend;
+
+    /// Width and height of the render target
+    fn extent(&self) -> (u32, u32);
+
+    fn create_vertex_buffer(&mut self, vertices: &[ColorVertex]) -> Result<BufferHandle, Error>;
+
+    /// The buffer must not be used by commands submitted afterwards
+    fn destroy_buffer(&mut self, buffer: BufferHandle) -> Result<(), Error>;
+
+    /// Clears the target and executes the commands. Blocks until the frame is finished.
+    fn submit(&mut self, commands: &CommandList) -> Result<(), Error>;
+
+    /// Contents of the render target after the last submit
+    fn read_pixels(&mut self) -> Result<RgbaImage, Error>;
+
+    /// Shows the render target after the last submit in the window, `Error::NoWindow` for renderers created without one.
+    /// Nothing is presented while the window is minimized.
+    fn present(&mut self) -> Result<(), Error>;
+
+    /// Resizes the render target and the presentation, call on window resize events. The contents are undefined
+    /// until the next submit. A zero sized window keeps the current target.
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Error>;
+
+    /// Limits presentation to the display refresh rate, on by default. The CPU backend presents without vsync control.
+    fn set_vsync(&mut self, vsync: bool);
+}
+
+pub struct RendererConfig {
+    pub preference: BackendPreference,
+    pub width: u32,
+    pub height: u32,
+    /// Enables the Khronos validation layer on the Vulkan backend
+    pub validation: bool,
+    /// Window to present to, its inner size should match `width` and `height`
+    pub window: Option<Arc<Window>>,
+}
+
+impl RendererConfig {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            preference: BackendPreference::Auto,
+            width,
+            height,
+            validation: false,
+            window: None,
+        }
+    }
+
+    pub fn preference(mut self, preference: BackendPreference) -> Self {
+        self.preference = preference;
+        self
+    }
+
+    pub fn validation(mut self, validation: bool) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    pub fn window(mut self, window: Arc<Window>) -> Self {
+        self.window = Some(window);
+        self
+    }
+}
+
+/// Creates the preferred backend, presenting to `config.window` if there is one. With `BackendPreference::Auto`
+/// a failing Vulkan initialization (no loader, no suitable device, ...) is logged and the CPU backend is used instead.
+pub fn create_renderer(config: &RendererConfig) -> Result<Box<dyn Renderer>, Error> {
+    let create_cpu = || match &config.window {
+        Some(window) => CpuRenderer::windowed(window.clone(), config.width, config.height),
+        None => Ok(CpuRenderer::new(config.width, config.height)),
+    };
+    let create_vulkan = || match &config.window {
+        Some(window) => VulkanRenderer::windowed(window.clone(), config.width, config.height, config.validation),
+        None => VulkanRenderer::headless(config.width, config.height, config.validation),
+    };
+    let renderer: Box<dyn Renderer> = match config.preference {
+        BackendPreference::Cpu => Box::new(create_cpu()?),
+        BackendPreference::Vulkan => Box::new(create_vulkan()?),
+        BackendPreference::Auto => match create_vulkan() {
+            Ok(renderer) => Box::new(renderer),
+            Err(error) => {
+                warn!("Vulkan backend unavailable, falling back to the CPU backend: {}", error);
+                Box::new(create_cpu()?)
+            }
+        },
+    };
+    info!("Using the {:?} backend", renderer.backend());
+    Ok(renderer)
+}
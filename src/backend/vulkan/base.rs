@@ -1,18 +1,20 @@
 use crate::backend::vulkan::errors::Error;
-use crate::backend::vulkan::errors::Error::ValidationLayerNotSupported;
+use crate::backend::vulkan::errors::Error::{ExtensionNotSupported, ValidationLayerNotSupported};
 use crate::backend::vulkan::utils::{to_c_str, to_c_str_array, to_version};
-use crate::fatal_unwrap_e;
-use crate::{fatal_assert, platform_surface_extension};
+use crate::platform_surface_extension;
 use ash::vk;
 use ash::Entry;
 use ash::Instance;
 use ash::{ext, khr};
-use eta_algorithms::data_structs::array::Array;
 use log::{error, info, trace, warn};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::ffi::{c_char, c_void, CStr, CString};
 use std::ptr::{null, null_mut};
 
+/// Forwards debug messenger messages to the logger.
+///
+/// # Safety
+/// Called by the Vulkan loader, `p_callback_data` has to point to valid callback data.
 pub unsafe extern "system" fn debug_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_type: vk::DebugUtilsMessageTypeFlagsEXT,
@@ -68,7 +70,13 @@ impl<'a> BaseConfigBuilder<'a> {
         self
     }
 
-    pub fn use_core_vulkan_extensions(mut self) -> Self {
+    pub fn use_core_vulkan_extensions(self) -> Self {
+        self
+    }
+
+    /// Only requests the debug utils extension, no surface extensions are needed without a window
+    pub fn use_headless_vulkan_extensions(mut self) -> Self {
+        self.vulkan_extensions = Some(&["VK_EXT_debug_utils"]);
         self
     }
     pub fn vulkan_extensions(mut self, extensions: &'a [&'a str]) -> Self {
@@ -98,14 +106,17 @@ impl<'a> BaseConfigBuilder<'a> {
 
             // If None then the default is set at initialization. Reason is efficiency.
             // Default is initialized as static CStr. The internal type is CString. We save copy of the default.
-            vulkan_extensions: match self.vulkan_extensions {
-                Some(extensions) => Some(to_c_str_array(extensions.iter())),
-                None => None,
-            },
+            vulkan_extensions: self.vulkan_extensions.map(|extensions| to_c_str_array(extensions.iter())),
         }
     }
 }
 
+impl Default for BaseConfigBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct BaseConfig {
     pub application_name: CString,
     pub engine_name: CString,
@@ -122,14 +133,9 @@ impl BaseConfig {
     /// - `Ok(())` if all layers are found
     /// - `Err(layer_name)` if a layer is not found
     pub fn validate_layer_availability(&self, ash_entry: &Entry) -> Result<(), Error> {
-        let validation_properties = unsafe {
-            fatal_unwrap_e!(
-                ash_entry.enumerate_instance_layer_properties(),
-                "Failed to enumerate instance layer properties {}"
-            )
-        };
+        let validation_properties = unsafe { ash_entry.enumerate_instance_layer_properties()? };
 
-        if validation_properties.len() == 0 {
+        if validation_properties.is_empty() {
             return Ok(());
         }
 
@@ -146,13 +152,33 @@ impl BaseConfig {
         Ok(())
     }
 
-    pub fn to_application_info(&self) -> vk::ApplicationInfo {
+    /// Validates the instance extensions that are requested to be enabled
+    /// # Returns
+    /// - `Ok(())` if all extensions are found
+    /// - `Err(ExtensionNotSupported(extension))` with the first missing extension
+    pub fn validate_extension_availability(&self, ash_entry: &Entry, extensions: &[*const c_char]) -> Result<(), Error> {
+        let extension_properties = unsafe { ash_entry.enumerate_instance_extension_properties(None)? };
+        let available: HashSet<&CStr> = extension_properties
+            .iter()
+            .map(|extension| unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) })
+            .collect();
+
+        for extension in extensions.iter() {
+            let extension = unsafe { CStr::from_ptr(*extension) };
+            if !available.contains(extension) {
+                return Err(ExtensionNotSupported(extension.to_owned()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_application_info(&self) -> vk::ApplicationInfo<'_> {
         vk::ApplicationInfo {
             s_type: vk::StructureType::APPLICATION_INFO,
             p_next: null(),
-            p_application_name: self.application_name.as_ptr() as *const i8,
+            p_application_name: self.application_name.as_ptr(),
             application_version: self.application_version,
-            p_engine_name: self.engine_name.as_ptr() as *const i8,
+            p_engine_name: self.engine_name.as_ptr(),
             engine_version: self.engine_version,
             api_version: self.vulkan_api_version,
             _marker: Default::default(),
@@ -166,11 +192,13 @@ pub struct Base {
     pub vulkan_instance: Instance,
     pub utils_instance: ext::debug_utils::Instance,
     pub debug_messenger: vk::DebugUtilsMessengerEXT,
+    /// Requested instance API version, device functionality beyond it is not available
+    pub api_version: u32,
 }
 
 impl Base {
     pub fn new(config: BaseConfig) -> Result<Self, Error> {
-        let ash_instance = unsafe { fatal_unwrap_e!(Entry::load(), "Failed to create entry {}") };
+        let ash_instance = unsafe { Entry::load()? };
         config.validate_layer_availability(&ash_instance)?;
 
         let application_info = config.to_application_info();
@@ -179,6 +207,7 @@ impl Base {
             Some(extensions) => extensions.iter().map(|layer| layer.as_ptr()).collect(),
             None => core_vulkan_extensions(),
         };
+        config.validate_extension_availability(&ash_instance, &vulkan_extensions)?;
 
         let debug_message_info = vk::DebugUtilsMessengerCreateInfoEXT {
             s_type: vk::StructureType::DEBUG_UTILS_MESSENGER_CREATE_INFO_EXT,
@@ -208,19 +237,15 @@ impl Base {
             pp_enabled_extension_names: vulkan_extensions.as_ptr(),
             _marker: Default::default(),
         };
-        let vulkan_instance = unsafe {
-            fatal_unwrap_e!(
-                ash_instance.create_instance(&vulkan_create_info, None),
-                "Failed to create instance! {}"
-            )
-        };
+        let vulkan_instance = unsafe { ash_instance.create_instance(&vulkan_create_info, None)? };
 
         let utils_instance = ext::debug_utils::Instance::new(&ash_instance, &vulkan_instance);
-        let debug_messenger = unsafe {
-            fatal_unwrap_e!(
-                utils_instance.create_debug_utils_messenger(&debug_message_info, None),
-                "Failed to create debug utils messenger {}"
-            )
+        let debug_messenger = match unsafe { utils_instance.create_debug_utils_messenger(&debug_message_info, None) } {
+            Ok(debug_messenger) => debug_messenger,
+            Err(result) => {
+                unsafe { vulkan_instance.destroy_instance(None) };
+                return Err(result.into());
+            }
         };
 
         Ok(Self {
@@ -228,6 +253,7 @@ impl Base {
             utils_instance,
             debug_messenger,
             vulkan_instance,
+            api_version: config.vulkan_api_version,
         })
     }
 }
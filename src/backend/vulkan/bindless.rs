@@ -0,0 +1,468 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::descriptors::DescriptorWriter;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use ash::vk;
+use log::info;
+use std::ffi::c_void;
+use std::ptr::null;
+
+/// Binding of the combined image sampler array, `layout(set = N, binding = 0) uniform sampler2D textures[];`
+pub const TEXTURE_BINDING: u32 = 0;
+/// Binding of the storage buffer array
+pub const STORAGE_BUFFER_BINDING: u32 = 1;
+/// Binding of the storage image array
+pub const STORAGE_IMAGE_BINDING: u32 = 2;
+
+/// Array sizes of the bindless set, clamped to the update-after-bind limits of the device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BindlessConfig {
+    pub textures: u32,
+    pub storage_buffers: u32,
+    pub storage_images: u32,
+    pub stages: vk::ShaderStageFlags,
+    /// Frames a freed slot stays unused, at least the number of frames in flight
+    pub retire_frames: u32,
+}
+
+impl BindlessConfig {
+    pub fn new(retire_frames: u32) -> Self {
+        Self {
+            textures: 16384,
+            storage_buffers: 4096,
+            storage_images: 1024,
+            stages: vk::ShaderStageFlags::ALL,
+            retire_frames,
+        }
+    }
+
+    pub fn textures(mut self, textures: u32) -> Self {
+        self.textures = textures;
+        self
+    }
+
+    pub fn storage_buffers(mut self, storage_buffers: u32) -> Self {
+        self.storage_buffers = storage_buffers;
+        self
+    }
+
+    pub fn storage_images(mut self, storage_images: u32) -> Self {
+        self.storage_images = storage_images;
+        self
+    }
+
+    pub fn stages(mut self, stages: vk::ShaderStageFlags) -> Self {
+        self.stages = stages;
+        self
+    }
+
+    /// Array sizes the device supports, combined image samplers count as both samplers and sampled images
+    pub fn clamp_to(&self, limits: &vk::PhysicalDeviceVulkan12Properties) -> Self {
+        let textures = self
+            .textures
+            .min(limits.max_per_stage_descriptor_update_after_bind_samplers)
+            .min(limits.max_per_stage_descriptor_update_after_bind_sampled_images)
+            .min(limits.max_descriptor_set_update_after_bind_samplers)
+            .min(limits.max_descriptor_set_update_after_bind_sampled_images);
+        let storage_buffers = self
+            .storage_buffers
+            .min(limits.max_per_stage_descriptor_update_after_bind_storage_buffers)
+            .min(limits.max_descriptor_set_update_after_bind_storage_buffers);
+        let storage_images = self
+            .storage_images
+            .min(limits.max_per_stage_descriptor_update_after_bind_storage_images)
+            .min(limits.max_descriptor_set_update_after_bind_storage_images);
+        Self {
+            textures,
+            storage_buffers,
+            storage_images,
+            ..*self
+        }
+    }
+}
+
+impl Default for BindlessConfig {
+    fn default() -> Self {
+        Self::new(crate::backend::vulkan::frames::DEFAULT_FRAMES_IN_FLIGHT as u32)
+    }
+}
+
+/// Hands out array indices. Freed indices are retired for a number of frames before they are reused, so a slot is
+/// never rewritten while command buffers of earlier frames may still read it.
+#[derive(Clone, Debug)]
+pub struct SlotAllocator {
+    capacity: u32,
+    next: u32,
+    free: Vec<u32>,
+    /// Freed slots with the frame they were freed in
+    retired: Vec<(u64, u32)>,
+    allocated: Vec<bool>,
+    frame: u64,
+    retire_frames: u32,
+}
+
+impl SlotAllocator {
+    pub fn new(capacity: u32, retire_frames: u32) -> Self {
+        Self {
+            capacity,
+            next: 0,
+            free: Vec::new(),
+            retired: Vec::new(),
+            allocated: Vec::new(),
+            frame: 0,
+            retire_frames,
+        }
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Allocated slots, retired ones included
+    pub fn len(&self) -> u32 {
+        self.next - self.free.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `None` if every slot is in use or retired
+    pub fn allocate(&mut self) -> Option<u32> {
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None if self.next < self.capacity => {
+                self.next += 1;
+                self.allocated.push(false);
+                self.next - 1
+            }
+            None => return None,
+        };
+        self.allocated[slot as usize] = true;
+        Some(slot)
+    }
+
+    /// Returns false if the slot is not allocated
+    pub fn free(&mut self, slot: u32) -> bool {
+        match self.allocated.get_mut(slot as usize) {
+            Some(allocated) if *allocated => {
+                *allocated = false;
+                self.retired.push((self.frame, slot));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_allocated(&self, slot: u32) -> bool {
+        self.allocated.get(slot as usize).copied().unwrap_or(false)
+    }
+
+    /// Starts the next frame, slots freed `retire_frames` frames ago become available again
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+        let frame = self.frame;
+        let retire_frames = self.retire_frames as u64;
+        let free = &mut self.free;
+        self.retired.retain(|(freed, slot)| {
+            let reusable = frame - freed >= retire_frames;
+            if reusable {
+                free.push(*slot);
+            }
+            !reusable
+        });
+    }
+}
+
+/// Slot of a combined image sampler in the bindless texture array, the index is passed to shaders
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(u32);
+
+/// Slot of a storage buffer in the bindless buffer array
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferHandle(u32);
+
+/// Slot of a storage image in the bindless storage image array
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StorageImageHandle(u32);
+
+impl TextureHandle {
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+impl BufferHandle {
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+impl StorageImageHandle {
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Global descriptor set holding every texture, storage buffer and storage image in large arrays that shaders index
+/// with handles, e.g. from push constants or material buffers.
+///
+/// The arrays are partially bound and update-after-bind, so slots can be written while the set is bound in command
+/// buffers that are still pending, as long as those don't access the slot. Unwritten slots must not be accessed.
+/// Requires `ContextConfigurator::descriptor_indexing`. Call `next_frame` once per frame after waiting for the frame.
+pub struct BindlessTable {
+    device: Device,
+    config: BindlessConfig,
+    layout: vk::DescriptorSetLayout,
+    pool: vk::DescriptorPool,
+    set: vk::DescriptorSet,
+    textures: SlotAllocator,
+    storage_buffers: SlotAllocator,
+    storage_images: SlotAllocator,
+}
+
+impl BindlessTable {
+    pub fn new(context: &Context, config: BindlessConfig) -> Result<Self, Error> {
+        if !context.device_features().descriptor_indexing {
+            return Err(Error::DeviceFeatureNotEnabled("descriptor_indexing"));
+        }
+        let mut limits = vk::PhysicalDeviceVulkan12Properties::default();
+        let mut properties = vk::PhysicalDeviceProperties2 {
+            p_next: &mut limits as *mut vk::PhysicalDeviceVulkan12Properties as *mut c_void,
+            ..Default::default()
+        };
+        unsafe {
+            context
+                .base()
+                .vulkan_instance
+                .get_physical_device_properties2(context.physical_device().device, &mut properties)
+        };
+        let config = config.clamp_to(&limits);
+
+        let mut table = Self {
+            device: context.device().clone(),
+            config,
+            layout: vk::DescriptorSetLayout::null(),
+            pool: vk::DescriptorPool::null(),
+            set: vk::DescriptorSet::null(),
+            textures: SlotAllocator::new(config.textures, config.retire_frames),
+            storage_buffers: SlotAllocator::new(config.storage_buffers, config.retire_frames),
+            storage_images: SlotAllocator::new(config.storage_images, config.retire_frames),
+        };
+        table.create_set()?;
+        info!(
+            "Created bindless table with {} textures, {} storage buffers and {} storage images",
+            config.textures, config.storage_buffers, config.storage_images
+        );
+        Ok(table)
+    }
+
+    fn create_set(&mut self) -> Result<(), Error> {
+        // Arrays of size zero are not allowed, empty kinds still get a binding so the binding numbers stay fixed
+        let arrays = [
+            (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, self.config.textures.max(1)),
+            (vk::DescriptorType::STORAGE_BUFFER, self.config.storage_buffers.max(1)),
+            (vk::DescriptorType::STORAGE_IMAGE, self.config.storage_images.max(1)),
+        ];
+        let bindings: Vec<vk::DescriptorSetLayoutBinding> = arrays
+            .iter()
+            .enumerate()
+            .map(|(binding, (descriptor_type, count))| vk::DescriptorSetLayoutBinding {
+                binding: binding as u32,
+                descriptor_type: *descriptor_type,
+                descriptor_count: *count,
+                stage_flags: self.config.stages,
+                p_immutable_samplers: null(),
+                _marker: Default::default(),
+            })
+            .collect();
+        let binding_flags = [vk::DescriptorBindingFlags::PARTIALLY_BOUND
+            | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+            | vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING; 3];
+        let binding_flags_info = vk::DescriptorSetLayoutBindingFlagsCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_BINDING_FLAGS_CREATE_INFO,
+            p_next: null(),
+            binding_count: binding_flags.len() as u32,
+            p_binding_flags: binding_flags.as_ptr(),
+            _marker: Default::default(),
+        };
+        let layout_info = vk::DescriptorSetLayoutCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            p_next: &binding_flags_info as *const vk::DescriptorSetLayoutBindingFlagsCreateInfo as *const c_void,
+            flags: vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL,
+            binding_count: bindings.len() as u32,
+            p_bindings: bindings.as_ptr(),
+            _marker: Default::default(),
+        };
+        self.layout = unsafe { self.device.create_descriptor_set_layout(&layout_info, None)? };
+
+        let pool_sizes: Vec<vk::DescriptorPoolSize> = arrays
+            .iter()
+            .map(|(ty, descriptor_count)| vk::DescriptorPoolSize {
+                ty: *ty,
+                descriptor_count: *descriptor_count,
+            })
+            .collect();
+        let pool_info = vk::DescriptorPoolCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+            p_next: null(),
+            flags: vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND,
+            max_sets: 1,
+            pool_size_count: pool_sizes.len() as u32,
+            p_pool_sizes: pool_sizes.as_ptr(),
+            _marker: Default::default(),
+        };
+        self.pool = unsafe { self.device.create_descriptor_pool(&pool_info, None)? };
+
+        let allocate_info = vk::DescriptorSetAllocateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+            p_next: null(),
+            descriptor_pool: self.pool,
+            descriptor_set_count: 1,
+            p_set_layouts: &self.layout,
+            _marker: Default::default(),
+        };
+        self.set = unsafe { self.device.allocate_descriptor_sets(&allocate_info)?[0] };
+        Ok(())
+    }
+
+    /// Layout to put into pipeline layouts at the set index the table is bound to
+    pub fn layout(&self) -> vk::DescriptorSetLayout {
+        self.layout
+    }
+
+    pub fn set(&self) -> vk::DescriptorSet {
+        self.set
+    }
+
+    /// Array sizes after clamping to the device limits
+    pub fn config(&self) -> &BindlessConfig {
+        &self.config
+    }
+
+    pub fn bind(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        pipeline_layout: vk::PipelineLayout,
+        set_index: u32,
+    ) {
+        unsafe {
+            self.device
+                .cmd_bind_descriptor_sets(command_buffer, bind_point, pipeline_layout, set_index, &[self.set], &[])
+        };
+    }
+
+    /// Writes the texture into a free slot, sampled in `SHADER_READ_ONLY_OPTIMAL`
+    pub fn add_texture(&mut self, view: vk::ImageView, sampler: vk::Sampler) -> Result<TextureHandle, Error> {
+        let slot = self.textures.allocate().ok_or(Error::BindlessSlotsExhausted("texture"))?;
+        let handle = TextureHandle(slot);
+        self.update_texture(handle, view, sampler);
+        Ok(handle)
+    }
+
+    /// Points the slot at another texture, earlier frames that are still pending must not read the slot
+    pub fn update_texture(&self, handle: TextureHandle, view: vk::ImageView, sampler: vk::Sampler) {
+        let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        DescriptorWriter::new()
+            .image(
+                TEXTURE_BINDING,
+                handle.0,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                view,
+                layout,
+                sampler,
+            )
+            .update(&self.device, self.set);
+    }
+
+    /// The slot is reused once the frames that may still read it are done
+    pub fn remove_texture(&mut self, handle: TextureHandle) -> bool {
+        self.textures.free(handle.0)
+    }
+
+    pub fn add_storage_buffer(&mut self, buffer: vk::Buffer, offset: vk::DeviceSize, range: vk::DeviceSize) -> Result<BufferHandle, Error> {
+        let slot = self
+            .storage_buffers
+            .allocate()
+            .ok_or(Error::BindlessSlotsExhausted("storage buffer"))?;
+        let handle = BufferHandle(slot);
+        self.update_storage_buffer(handle, buffer, offset, range);
+        Ok(handle)
+    }
+
+    pub fn update_storage_buffer(&self, handle: BufferHandle, buffer: vk::Buffer, offset: vk::DeviceSize, range: vk::DeviceSize) {
+        DescriptorWriter::new()
+            .buffer(
+                STORAGE_BUFFER_BINDING,
+                handle.0,
+                vk::DescriptorType::STORAGE_BUFFER,
+                buffer,
+                offset,
+                range,
+            )
+            .update(&self.device, self.set);
+    }
+
+    pub fn remove_storage_buffer(&mut self, handle: BufferHandle) -> bool {
+        self.storage_buffers.free(handle.0)
+    }
+
+    /// Writes the view into a free slot, accessed in `GENERAL`
+    pub fn add_storage_image(&mut self, view: vk::ImageView) -> Result<StorageImageHandle, Error> {
+        let slot = self
+            .storage_images
+            .allocate()
+            .ok_or(Error::BindlessSlotsExhausted("storage image"))?;
+        let handle = StorageImageHandle(slot);
+        self.update_storage_image(handle, view);
+        Ok(handle)
+    }
+
+    pub fn update_storage_image(&self, handle: StorageImageHandle, view: vk::ImageView) {
+        let layout = vk::ImageLayout::GENERAL;
+        DescriptorWriter::new()
+            .image(
+                STORAGE_IMAGE_BINDING,
+                handle.0,
+                vk::DescriptorType::STORAGE_IMAGE,
+                view,
+                layout,
+                vk::Sampler::null(),
+            )
+            .update(&self.device, self.set);
+    }
+
+    pub fn remove_storage_image(&mut self, handle: StorageImageHandle) -> bool {
+        self.storage_images.free(handle.0)
+    }
+
+    /// Counts as a frame for the retirement of removed slots
+    pub fn next_frame(&mut self) {
+        self.textures.next_frame();
+        self.storage_buffers.next_frame();
+        self.storage_images.next_frame();
+    }
+
+    pub fn texture_count(&self) -> u32 {
+        self.textures.len()
+    }
+
+    pub fn storage_buffer_count(&self) -> u32 {
+        self.storage_buffers.len()
+    }
+
+    pub fn storage_image_count(&self) -> u32 {
+        self.storage_images.len()
+    }
+}
+
+impl Drop for BindlessTable {
+    fn drop(&mut self) {
+        // Destroying the pool frees the set, null handles from a failed `new` are ignored by Vulkan
+        unsafe {
+            self.device.destroy_descriptor_pool(self.pool, None);
+            self.device.destroy_descriptor_set_layout(self.layout, None);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::memory::{Allocation, MemoryUsage};
+use ash::vk;
+use std::mem::size_of;
+use std::ptr::null;
+
+/// Plain data that can be copied to and from GPU memory byte by byte.
+///
+/// # Safety
+/// The type has to be `Copy`, contain no padding, pointers or references, and every bit pattern has to be a valid value.
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for u64 {}
+unsafe impl Pod for i8 {}
+unsafe impl Pod for i16 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for i64 {}
+unsafe impl Pod for f32 {}
+unsafe impl Pod for f64 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// `vk::Buffer` with memory from the context allocator. Host visible buffers (`MemoryUsage::CpuToGpu` and
+/// `MemoryUsage::GpuToCpu`) stay mapped for their whole lifetime and can be accessed with `write_slice`/`read_slice`,
+/// which flush and invalidate non-coherent memory themselves. Access through `mapped_ptr` needs `flush_range` and
+/// `invalidate_range`.
+/// The buffer and its memory are released on drop, the GPU must be done with it by then.
+pub struct Buffer {
+    device: Device,
+    buffer: vk::Buffer,
+    allocation: Option<Allocation>,
+    size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    memory_usage: MemoryUsage,
+}
+
+impl Buffer {
+    pub fn new(context: &Context, size: vk::DeviceSize, usage: vk::BufferUsageFlags, memory_usage: MemoryUsage) -> Result<Self, Error> {
+        Self::with_options(context, size, usage, memory_usage, false)
+    }
+
+    /// `dedicated` gives the buffer its own device memory instead of a range of a shared block
+    pub fn with_options(
+        context: &Context,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        memory_usage: MemoryUsage,
+        dedicated: bool,
+    ) -> Result<Self, Error> {
+        let device = context.device();
+        let mut buffer = Self {
+            device: device.clone(),
+            buffer: vk::Buffer::null(),
+            allocation: None,
+            size,
+            usage,
+            memory_usage,
+        };
+
+        let buffer_create_info = vk::BufferCreateInfo {
+            s_type: vk::StructureType::BUFFER_CREATE_INFO,
+            p_next: null(),
+            flags: vk::BufferCreateFlags::empty(),
+            // Zero sized buffers are invalid
+            size: size.max(1),
+            usage,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: null(),
+            _marker: Default::default(),
+        };
+        buffer.buffer = unsafe { device.create_buffer(&buffer_create_info, None)? };
+        buffer.allocation = Some(buffer.device.allocator().allocate_for_buffer(buffer.buffer, memory_usage, dedicated)?);
+        Ok(buffer)
+    }
+
+    /// Host visible buffer filled with `data`
+    pub fn with_data<T: Pod>(context: &Context, data: &[T], usage: vk::BufferUsageFlags, memory_usage: MemoryUsage) -> Result<Self, Error> {
+        let mut buffer = Self::new(context, size_of_val(data) as vk::DeviceSize, usage, memory_usage)?;
+        buffer.write_slice(0, data)?;
+        Ok(buffer)
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    pub fn usage(&self) -> vk::BufferUsageFlags {
+        self.usage
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory_usage
+    }
+
+    pub fn allocation(&self) -> &Allocation {
+        self.allocation.as_ref().unwrap()
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.mapped_ptr().is_some()
+    }
+
+    /// Start of the persistently mapped memory, `None` for buffers that are not host visible
+    pub fn mapped_ptr(&self) -> Option<*mut u8> {
+        self.allocation.as_ref().and_then(|allocation| allocation.mapped_ptr())
+    }
+
+    fn mapped_range(&self, offset: vk::DeviceSize, size: vk::DeviceSize) -> Result<*mut u8, Error> {
+        let mapped = self.mapped_ptr().ok_or(Error::BufferNotMapped)?;
+        if offset.checked_add(size).is_none_or(|end| end > self.size) {
+            return Err(Error::BufferOutOfRange {
+                offset,
+                size,
+                buffer_size: self.size,
+            });
+        }
+        Ok(unsafe { mapped.add(offset as usize) })
+    }
+
+    /// Makes CPU writes to the byte range visible to the device. Ranges are widened to `nonCoherentAtomSize`,
+    /// coherent memory needs no flush.
+    pub fn flush_range(&self, offset: vk::DeviceSize, size: vk::DeviceSize) -> Result<(), Error> {
+        self.mapped_range(offset, size)?;
+        self.device.allocator().flush(self.allocation(), offset, size)
+    }
+
+    /// Makes device writes to the byte range visible to the CPU, see `flush_range`
+    pub fn invalidate_range(&self, offset: vk::DeviceSize, size: vk::DeviceSize) -> Result<(), Error> {
+        self.mapped_range(offset, size)?;
+        self.device.allocator().invalidate(self.allocation(), offset, size)
+    }
+
+    /// Copies `data` to the buffer starting at the byte `offset`
+    pub fn write_slice<T: Pod>(&mut self, offset: vk::DeviceSize, data: &[T]) -> Result<(), Error> {
+        let size = size_of_val(data);
+        let destination = self.mapped_range(offset, size as vk::DeviceSize)?;
+        // Byte copy, the mapped offset does not have to be aligned for T
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr() as *const u8, destination, size) };
+        self.flush_range(offset, size as vk::DeviceSize)
+    }
+
+    /// Reads `count` values starting at the byte `offset`
+    pub fn read_slice<T: Pod>(&self, offset: vk::DeviceSize, count: usize) -> Result<Vec<T>, Error> {
+        let size = size_of::<T>().checked_mul(count).ok_or(Error::BufferOutOfRange {
+            offset,
+            size: vk::WHOLE_SIZE,
+            buffer_size: self.size,
+        })?;
+        let source = self.mapped_range(offset, size as vk::DeviceSize)?;
+        self.invalidate_range(offset, size as vk::DeviceSize)?;
+        let mut data = Vec::<T>::with_capacity(count);
+        unsafe {
+            std::ptr::copy_nonoverlapping(source, data.as_mut_ptr() as *mut u8, size);
+            // Every bit pattern is a valid T
+            data.set_len(count);
+        }
+        Ok(data)
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_buffer(self.buffer, None) };
+        if let Some(allocation) = self.allocation.take() {
+            self.device.allocator().free(allocation);
+        }
+    }
+}
@@ -1,45 +1,107 @@
 use crate::backend::vulkan::base::Base;
-use crate::backend::vulkan::queue::op_indices::{queue_flags_to_op_index, COMPUTE, COUNT, GRAPHICS, PRESENT, TRANSFER};
-use crate::backend::vulkan::queue::{QueueFamily, QueueHandles, QueueSelections};
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::memory::{Allocator, DEFAULT_BLOCK_SIZE};
+use crate::backend::vulkan::queue::op_indices::{queue_flags_to_op_index, COMPUTE, GRAPHICS, PRESENT, TRANSFER};
+use crate::backend::vulkan::queue::{QueueHandles, QueueSelections};
 use crate::backend::vulkan::surface::Surface;
 use crate::backend::vulkan::utils::to_c_str_array;
-use crate::{fatal_assert, fatal_unwrap, fatal_unwrap_e};
-use ash::vk::{wl_surface, PhysicalDevice, PhysicalDeviceFeatures, SurfaceCapabilitiesKHR};
-use ash::{khr, vk};
+use ash::vk;
+use ash::vk::{PhysicalDevice, PhysicalDeviceFeatures, SurfaceCapabilitiesKHR};
 use eta_algorithms::algorithms::extract_unique_pairs;
-use eta_algorithms::data_structs::array::Array;
-use log::{error, info, trace, warn};
+use log::trace;
 use std::collections::HashSet;
 use std::ffi::{c_char, c_void, CStr, CString};
-use std::mem::transmute;
 use std::ptr::null;
 use winit::raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 
 pub fn default_device_mapper(
-    properties: &vk::PhysicalDeviceProperties,
-    device_features: &PhysicalDeviceFeatures,
+    _properties: &vk::PhysicalDeviceProperties,
+    _device_features: &PhysicalDeviceFeatures,
 ) -> Option<PhysicalDeviceFeatures> {
-    if properties.device_type != vk::PhysicalDeviceType::DISCRETE_GPU {
-        return None;
-    }
     Some(PhysicalDeviceFeatures::default())
 }
 
-pub fn default_queue_mapper(queue_operations: &[u8], queue_family_indices: &[u32], family_count: u32) -> QueueSelections {
+/// Rates the device, the highest rated suitable device is picked by the `Context`
+pub fn default_device_rater(device_info: &PhysicalDeviceInfo) -> u32 {
+    match device_info.properties.device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+        vk::PhysicalDeviceType::CPU => 1,
+        _ => 0,
+    }
+}
+
+pub fn default_queue_mapper(queue_operations: &[u8], queue_family_indices: &[u32], queue_limits: &[u32]) -> Result<QueueSelections, Error> {
     let (operations, family_index) = extract_unique_pairs(queue_operations, queue_family_indices);
-    let mut queue_selections = QueueSelections::new(family_count);
+    let mut queue_selections = QueueSelections::new(queue_limits);
     let zipped = operations.iter().zip(family_index.iter());
 
     for (operation, family) in zipped {
-        fatal_unwrap_e!(
-            queue_selections.insert_operation(*operation, *family),
-            "Failed to insert operation: {}"
-        );
+        queue_selections.insert_operation(*operation, *family)?;
     }
-    queue_selections
+    Ok(queue_selections)
 }
 
-struct SurfaceProperties {
+/// Features beyond `PhysicalDeviceFeatures`, chained into device creation. Required ones are requested on the
+/// `ContextConfigurator` and devices that don't support all of them are skipped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeviceFeatures {
+    /// `vkCmdBeginRendering` without render pass and framebuffer objects, core in Vulkan 1.3
+    pub dynamic_rendering: bool,
+    /// The descriptor indexing subset used by `bindless`: runtime sized, partially bound and update-after-bind arrays
+    /// of sampled images, storage images and storage buffers indexed non-uniformly, core in Vulkan 1.2
+    pub descriptor_indexing: bool,
+}
+
+impl DeviceFeatures {
+    /// Every feature set in `self` is also set in `supported`
+    pub fn supported_by(&self, supported: &DeviceFeatures) -> bool {
+        (!self.dynamic_rendering || supported.dynamic_rendering) && (!self.descriptor_indexing || supported.descriptor_indexing)
+    }
+
+    /// Support of the physical device, limited to what the API version (the lower of instance and device) exposes
+    pub fn query(base: &Base, device: PhysicalDevice, properties: &vk::PhysicalDeviceProperties) -> Self {
+        let api_version = base.api_version.min(properties.api_version);
+        if api_version < vk::API_VERSION_1_2 {
+            return Self::default();
+        }
+        let mut vulkan_13_features = vk::PhysicalDeviceVulkan13Features::default();
+        let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default();
+        // The 1.3 structure is unknown to 1.2 devices and only chained behind the 1.2 one when available
+        if api_version >= vk::API_VERSION_1_3 {
+            vulkan_12_features.p_next = &mut vulkan_13_features as *mut vk::PhysicalDeviceVulkan13Features as *mut c_void;
+        }
+        let mut features = vk::PhysicalDeviceFeatures2 {
+            p_next: &mut vulkan_12_features as *mut vk::PhysicalDeviceVulkan12Features as *mut c_void,
+            ..Default::default()
+        };
+        unsafe { base.vulkan_instance.get_physical_device_features2(device, &mut features) };
+        Self {
+            dynamic_rendering: vulkan_13_features.dynamic_rendering == vk::TRUE,
+            descriptor_indexing: Self::descriptor_indexing_features()
+                .iter()
+                .all(|feature| *feature(&mut vulkan_12_features) == vk::TRUE),
+        }
+    }
+
+    /// Vulkan 1.2 feature bits behind `descriptor_indexing`, as accessors so querying and enabling share the list
+    fn descriptor_indexing_features() -> [for<'a> fn(&'a mut vk::PhysicalDeviceVulkan12Features<'static>) -> &'a mut vk::Bool32; 8] {
+        [
+            |features| &mut features.descriptor_indexing,
+            |features| &mut features.runtime_descriptor_array,
+            |features| &mut features.descriptor_binding_partially_bound,
+            |features| &mut features.descriptor_binding_update_unused_while_pending,
+            |features| &mut features.descriptor_binding_sampled_image_update_after_bind,
+            |features| &mut features.descriptor_binding_storage_image_update_after_bind,
+            |features| &mut features.descriptor_binding_storage_buffer_update_after_bind,
+            |features| &mut features.shader_sampled_image_array_non_uniform_indexing,
+        ]
+    }
+}
+
+pub struct SurfaceProperties {
     pub surface_capabilities: SurfaceCapabilitiesKHR,
     pub formats: Vec<vk::SurfaceFormatKHR>,
     pub present_modes: Vec<vk::PresentModeKHR>,
@@ -47,16 +109,29 @@ struct SurfaceProperties {
 
 pub struct PhysicalDeviceInfo {
     pub device: PhysicalDevice,
+    pub properties: vk::PhysicalDeviceProperties,
+    pub memory_properties: vk::PhysicalDeviceMemoryProperties,
     pub features: PhysicalDeviceFeatures,
-    pub surface_properties: SurfaceProperties,
+    /// Features requested on the configurator, all supported by the device and enabled on the logical device
+    pub device_features: DeviceFeatures,
+    /// `None` for headless contexts
+    pub surface_properties: Option<SurfaceProperties>,
 }
 
+/// Picks the features to enable on a device, `None` skips the device
+pub type DeviceMapper = fn(&vk::PhysicalDeviceProperties, &PhysicalDeviceFeatures) -> Option<PhysicalDeviceFeatures>;
+
+/// Assigns queue operations to families from pairs of an operation and a family supporting it, and the queue count
+/// of every family
+pub type QueueMapper = fn(&[u8], &[u32], &[u32]) -> Result<QueueSelections, Error>;
+
 pub struct ContextConfigurator {
-    device_mapper: fn(&vk::PhysicalDeviceProperties, &PhysicalDeviceFeatures) -> Option<PhysicalDeviceFeatures>,
-    queue_mapper: fn(&[u8], &[u32], u32) -> QueueSelections,
+    device_mapper: DeviceMapper,
+    queue_mapper: QueueMapper,
+    device_rater: fn(&PhysicalDeviceInfo) -> u32,
     device_extensions: Vec<CString>,
-    raw_window_handle: RawWindowHandle,
-    raw_display_handle: RawDisplayHandle,
+    device_features: DeviceFeatures,
+    window_handles: Option<(RawWindowHandle, RawDisplayHandle)>,
 }
 
 impl ContextConfigurator {
@@ -65,23 +140,74 @@ impl ContextConfigurator {
             device_extensions: to_c_str_array(device_extensions.iter()),
             device_mapper: default_device_mapper,
             queue_mapper: default_queue_mapper,
-            raw_window_handle,
-            raw_display_handle,
+            device_rater: default_device_rater,
+            device_features: DeviceFeatures::default(),
+            window_handles: Some((raw_window_handle, raw_display_handle)),
         }
     }
 
-    pub fn create_surface(&self, base: &Base) -> Surface {
-        Surface::new(base, self.raw_window_handle, self.raw_display_handle)
+    /// Configures a context without a window. No surface is created and no present queue is requested,
+    /// the device is only usable for offscreen rendering and compute.
+    pub fn headless(device_extensions: &[&str]) -> Self {
+        Self {
+            device_extensions: to_c_str_array(device_extensions.iter()),
+            device_mapper: default_device_mapper,
+            queue_mapper: default_queue_mapper,
+            device_rater: default_device_rater,
+            device_features: DeviceFeatures::default(),
+            window_handles: None,
+        }
     }
 
-    fn validate_physical_device_extensions(&self, base: &Base, physical_device: PhysicalDevice) -> bool {
-        let device_extensions = unsafe {
-            fatal_unwrap_e!(
-                base.vulkan_instance.enumerate_device_extension_properties(physical_device),
-                "Failed to enumerate device extensions {}"
-            )
-        };
-        let mut extension_req: HashSet<CString> = self.device_extensions.iter().map(|extension| extension.clone()).collect();
+    pub fn is_headless(&self) -> bool {
+        self.window_handles.is_none()
+    }
+
+    pub fn device_mapper(mut self, device_mapper: DeviceMapper) -> Self {
+        self.device_mapper = device_mapper;
+        self
+    }
+
+    pub fn queue_mapper(mut self, queue_mapper: QueueMapper) -> Self {
+        self.queue_mapper = queue_mapper;
+        self
+    }
+
+    pub fn device_rater(mut self, device_rater: fn(&PhysicalDeviceInfo) -> u32) -> Self {
+        self.device_rater = device_rater;
+        self
+    }
+
+    /// Features the device has to support, they are enabled on the logical device
+    pub fn device_features(mut self, device_features: DeviceFeatures) -> Self {
+        self.device_features = device_features;
+        self
+    }
+
+    /// Requires and enables dynamic rendering, see `rendering`
+    pub fn dynamic_rendering(mut self, dynamic_rendering: bool) -> Self {
+        self.device_features.dynamic_rendering = dynamic_rendering;
+        self
+    }
+
+    /// Requires and enables descriptor indexing, see `bindless`
+    pub fn descriptor_indexing(mut self, descriptor_indexing: bool) -> Self {
+        self.device_features.descriptor_indexing = descriptor_indexing;
+        self
+    }
+
+    /// # Returns
+    /// - `Ok(None)` if the configurator is headless
+    pub fn create_surface(&self, base: &Base) -> Result<Option<Surface>, Error> {
+        match self.window_handles {
+            Some((raw_window_handle, raw_display_handle)) => Ok(Some(Surface::new(base, raw_window_handle, raw_display_handle)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn validate_physical_device_extensions(&self, base: &Base, physical_device: PhysicalDevice) -> Result<bool, Error> {
+        let device_extensions = unsafe { base.vulkan_instance.enumerate_device_extension_properties(physical_device)? };
+        let mut extension_req: HashSet<CString> = self.device_extensions.iter().cloned().collect();
         for extension in device_extensions.iter() {
             let extension_name = unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) };
             extension_req.remove(extension_name);
@@ -91,17 +217,21 @@ impl ContextConfigurator {
             for extension in extension_req.iter() {
                 trace!("Missing device extension found: {:?}", extension);
             }
-            return false;
+            return Ok(false);
         }
-        true
+        Ok(true)
     }
 
-    fn obtain_device_surface_properties(&self, physical_device: &PhysicalDevice, surface: &Surface) -> Option<SurfaceProperties> {
-        let surface_capabilities = surface.get_physical_device_surface_capabilities(&physical_device);
-        let formats = surface.get_physical_device_surface_formats(&physical_device);
-        let present_modes = surface.get_physical_device_surface_present_modes(&physical_device);
+    fn obtain_device_surface_properties(
+        &self,
+        physical_device: &PhysicalDevice,
+        surface: &Surface,
+    ) -> Result<Option<SurfaceProperties>, Error> {
+        let surface_capabilities = surface.get_physical_device_surface_capabilities(physical_device)?;
+        let formats = surface.get_physical_device_surface_formats(physical_device)?;
+        let present_modes = surface.get_physical_device_surface_present_modes(physical_device)?;
         if formats.is_empty() || present_modes.is_empty() {
-            return None;
+            return Ok(None);
         }
 
         let properties = SurfaceProperties {
@@ -109,23 +239,18 @@ impl ContextConfigurator {
             formats,
             present_modes,
         };
-        Some(properties)
+        Ok(Some(properties))
     }
 
-    pub fn obtain_physical_devices(&self, base: &Base, surface: &Surface) -> Vec<PhysicalDeviceInfo> {
-        let checked_devices = unsafe {
-            fatal_unwrap_e!(
-                base.vulkan_instance.enumerate_physical_devices(),
-                "Failed to enumerate physical devices! {}"
-            )
-        };
+    pub fn obtain_physical_devices(&self, base: &Base, surface: Option<&Surface>) -> Result<Vec<PhysicalDeviceInfo>, Error> {
+        let checked_devices = unsafe { base.vulkan_instance.enumerate_physical_devices()? };
 
         let mut devices = Vec::with_capacity(checked_devices.len());
 
         for device in checked_devices {
             let properties = unsafe { base.vulkan_instance.get_physical_device_properties(device) };
             let features = unsafe { base.vulkan_instance.get_physical_device_features(device) };
-            if !self.validate_physical_device_extensions(&base, device) {
+            if !self.validate_physical_device_extensions(base, device)? {
                 let name = unsafe { base.vulkan_instance.get_physical_device_properties(device).device_name };
                 trace!("Device {:?} does not support required extensions!", unsafe {
                     CStr::from_ptr(name.as_ptr())
@@ -133,29 +258,55 @@ impl ContextConfigurator {
                 continue;
             }
 
-            if let Some(surface_properties) = self.obtain_device_surface_properties(&device, &surface) {
-                if let Some(features) = (self.device_mapper)(&properties, &features) {
-                    devices.push(PhysicalDeviceInfo {
-                        device,
-                        features,
-                        surface_properties,
-                    });
-                    continue;
-                }
-                let name = unsafe { base.vulkan_instance.get_physical_device_properties(device).device_name };
-                trace!("Device {:?} does not support required features!", unsafe {
-                    CStr::from_ptr(name.as_ptr())
+            let surface_properties = match surface {
+                Some(surface) => match self.obtain_device_surface_properties(&device, surface)? {
+                    Some(surface_properties) => Some(surface_properties),
+                    None => {
+                        trace!("Device {:?} does not support the surface!", unsafe {
+                            CStr::from_ptr(properties.device_name.as_ptr())
+                        });
+                        continue;
+                    }
+                },
+                None => None,
+            };
+
+            if !self.device_features.supported_by(&DeviceFeatures::query(base, device, &properties)) {
+                trace!("Device {:?} does not support the requested device features!", unsafe {
+                    CStr::from_ptr(properties.device_name.as_ptr())
                 });
+                continue;
             }
+
+            if let Some(features) = (self.device_mapper)(&properties, &features) {
+                let memory_properties = unsafe { base.vulkan_instance.get_physical_device_memory_properties(device) };
+                devices.push(PhysicalDeviceInfo {
+                    device,
+                    properties,
+                    memory_properties,
+                    features,
+                    device_features: self.device_features,
+                    surface_properties,
+                });
+                continue;
+            }
+            trace!("Device {:?} does not support required features!", unsafe {
+                CStr::from_ptr(properties.device_name.as_ptr())
+            });
         }
-        devices
+        Ok(devices)
     }
 
-    pub fn obtain_queue_families(&self, base: &Base, physical_device: &PhysicalDevice, surface: &Surface) -> QueueSelections {
+    pub fn obtain_queue_families(
+        &self,
+        base: &Base,
+        physical_device: &PhysicalDevice,
+        surface: Option<&Surface>,
+    ) -> Result<QueueSelections, Error> {
         let queue_families = unsafe { base.vulkan_instance.get_physical_device_queue_family_properties(*physical_device) };
         let mut operations = Vec::<u8>::new();
         let mut family_indices = Vec::new();
-        let family_count = queue_families.len() as u32;
+        let queue_limits: Vec<u32> = queue_families.iter().map(|queue_family| queue_family.queue_count).collect();
         for (index, queue_family) in queue_families.iter().enumerate() {
             if queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
                 operations.push(queue_flags_to_op_index(vk::QueueFlags::GRAPHICS) as u8);
@@ -171,14 +322,18 @@ impl ContextConfigurator {
                 operations.push(queue_flags_to_op_index(vk::QueueFlags::TRANSFER) as u8);
                 family_indices.push(index as u32);
             }
-            let surface_support = surface.get_physical_device_surface_support(*physical_device, index as u32);
+            // Headless contexts never present
+            let surface_support = match surface {
+                Some(surface) => surface.get_physical_device_surface_support(*physical_device, index as u32)?,
+                None => false,
+            };
             if surface_support {
                 operations.push(PRESENT as u8);
                 family_indices.push(index as u32);
             }
         }
 
-        (self.queue_mapper)(operations.as_slice(), family_indices.as_slice(), family_count)
+        (self.queue_mapper)(operations.as_slice(), family_indices.as_slice(), queue_limits.as_slice())
     }
 
     pub fn select_logical_device(
@@ -186,30 +341,48 @@ impl ContextConfigurator {
         base: &Base,
         queue_selections: &QueueSelections,
         physical_device_info: &PhysicalDeviceInfo,
-    ) -> ash::Device {
+    ) -> Result<ash::Device, Error> {
         let queue_creation_info = queue_selections.to_vk_creation_info();
         let device_extension_list: Vec<*const c_char> = self.device_extensions.iter().map(|extension| extension.as_ptr()).collect();
 
+        // Structures are only chained if something in them is enabled, older devices don't know them
+        let device_features = physical_device_info.device_features;
+        let mut p_next: *const c_void = null();
+        let mut vulkan_13_features = vk::PhysicalDeviceVulkan13Features {
+            dynamic_rendering: device_features.dynamic_rendering as vk::Bool32,
+            ..Default::default()
+        };
+        if device_features.dynamic_rendering {
+            vulkan_13_features.p_next = p_next as *mut c_void;
+            p_next = &vulkan_13_features as *const vk::PhysicalDeviceVulkan13Features as *const c_void;
+        }
+        let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default();
+        if device_features.descriptor_indexing {
+            for feature in DeviceFeatures::descriptor_indexing_features() {
+                *feature(&mut vulkan_12_features) = vk::TRUE;
+            }
+            vulkan_12_features.p_next = p_next as *mut c_void;
+            p_next = &vulkan_12_features as *const vk::PhysicalDeviceVulkan12Features as *const c_void;
+        }
+
         let device_create_info = vk::DeviceCreateInfo {
             s_type: vk::StructureType::DEVICE_CREATE_INFO,
-            p_next: null(),
+            p_next,
             flags: Default::default(),
             queue_create_info_count: queue_creation_info.len() as u32,
             p_queue_create_infos: queue_creation_info.as_ptr(),
-            enabled_layer_count: 0,
-            pp_enabled_layer_names: null(),
             enabled_extension_count: device_extension_list.len() as u32,
             pp_enabled_extension_names: device_extension_list.as_ptr(),
             p_enabled_features: &physical_device_info.features as *const PhysicalDeviceFeatures,
-            _marker: Default::default(),
+            // Device layers are deprecated, the layer fields stay zeroed
+            ..Default::default()
         };
 
         let device = unsafe {
             base.vulkan_instance
-                .create_device(physical_device_info.device, &device_create_info, None)
-                .expect("Failed to create device!")
+                .create_device(physical_device_info.device, &device_create_info, None)?
         };
-        device
+        Ok(device)
     }
 }
 
@@ -224,28 +397,119 @@ pub fn obtain_queues(logical_device: &ash::Device, queue_selections: &QueueSelec
     obtained_queues
 }
 
-struct Context {
-    base: Base,
-    surface: Surface,
-    physical_devices: Vec<PhysicalDeviceInfo>,
-    logical_device: ash::Device,
+/// Owns everything needed to drive a single GPU: the surface (unless headless), the chosen physical device,
+/// the logical device and its queues. Every other Vulkan subsystem is created from it and holds a clone of its
+/// `Device`, which keeps the device and the instance alive until the last of them is dropped.
+pub struct Context {
     queue_handles: QueueHandles,
+    queue_selections: QueueSelections,
+    device: Device,
+    physical_device: PhysicalDeviceInfo,
 }
 
 impl Context {
-    pub fn new<F, Q>(base: Base, configurator: ContextConfigurator) -> Self {
-        let surface = configurator.create_surface(&base);
-        // TODO fQueues are not needed. Once logical device is created, the queues should be also created internally
-        let physical_devices = configurator.obtain_physical_devices(&base, &surface);
-        let queue_selections = configurator.obtain_queue_families(&base, &physical_devices[0].device, &surface);
-        let logical_device = configurator.select_logical_device(&base, &queue_selections, &physical_devices[0]);
+    pub fn new(base: Base, configurator: ContextConfigurator) -> Result<Self, Error> {
+        let surface = configurator.create_surface(&base)?;
+        let mut physical_devices = configurator.obtain_physical_devices(&base, surface.as_ref())?;
+        physical_devices.sort_by_key(|device_info| std::cmp::Reverse((configurator.device_rater)(device_info)));
+
+        // Highest rated device that can both render and present (unless headless) wins
+        let mut selected = None;
+        for (index, device_info) in physical_devices.iter().enumerate() {
+            let queue_selections = configurator.obtain_queue_families(&base, &device_info.device, surface.as_ref())?;
+            let presents = surface.is_none() || queue_selections.family_index(PRESENT).is_some();
+            if queue_selections.family_index(GRAPHICS).is_none() || !presents {
+                trace!("Device {:?} is missing a graphics or present queue!", unsafe {
+                    CStr::from_ptr(device_info.properties.device_name.as_ptr())
+                });
+                continue;
+            }
+            selected = Some((index, queue_selections));
+            break;
+        }
+        let (index, queue_selections) = selected.ok_or(Error::NoSuitablePhysicalDevice)?;
+        let physical_device = physical_devices.swap_remove(index);
+
+        let logical_device = configurator.select_logical_device(&base, &queue_selections, &physical_device)?;
         let queue_handles = obtain_queues(&logical_device, &queue_selections);
-        Self {
-            surface,
-            base,
-            logical_device,
-            physical_devices,
+        let allocator = Allocator::from_device(&logical_device, &physical_device, DEFAULT_BLOCK_SIZE);
+        Ok(Self {
             queue_handles,
+            queue_selections,
+            device: Device::new(logical_device, allocator, surface, base),
+            physical_device,
+        })
+    }
+
+    pub fn base(&self) -> &Base {
+        self.device.base()
+    }
+
+    /// `None` for headless contexts
+    pub fn surface(&self) -> Option<&Surface> {
+        self.device.surface()
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.surface().is_none()
+    }
+
+    pub fn physical_device(&self) -> &PhysicalDeviceInfo {
+        &self.physical_device
+    }
+
+    /// Resources clone it to keep the device alive
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    /// Shared by all resources created from the context
+    pub fn allocator(&self) -> &Allocator {
+        self.device.allocator()
+    }
+
+    /// Features enabled on the logical device
+    pub fn device_features(&self) -> DeviceFeatures {
+        self.physical_device.device_features
+    }
+
+    /// Format support of the selected physical device
+    pub fn format_properties(&self, format: vk::Format) -> vk::FormatProperties {
+        unsafe {
+            self.base()
+                .vulkan_instance
+                .get_physical_device_format_properties(self.physical_device.device, format)
         }
     }
+
+    pub fn queue_selections(&self) -> &QueueSelections {
+        &self.queue_selections
+    }
+
+    /// Family index of the queue serving the operation from `queue::op_indices`
+    pub fn queue_family_index(&self, operation: usize) -> Option<u32> {
+        self.queue_selections.family_index(operation)
+    }
+
+    /// Device selection requires a graphics queue, `Error::MissingQueue` only for contexts configured without one
+    pub fn graphics_queue(&self) -> Result<vk::Queue, Error> {
+        self.queue_handles.get(GRAPHICS).ok_or(Error::MissingQueue(GRAPHICS))
+    }
+
+    pub fn compute_queue(&self) -> Option<vk::Queue> {
+        self.queue_handles.get(COMPUTE)
+    }
+
+    pub fn transfer_queue(&self) -> Option<vk::Queue> {
+        self.queue_handles.get(TRANSFER)
+    }
+
+    pub fn present_queue(&self) -> Option<vk::Queue> {
+        self.queue_handles.get(PRESENT)
+    }
+
+    pub fn wait_idle(&self) -> Result<(), Error> {
+        unsafe { self.device.device_wait_idle()? };
+        Ok(())
+    }
 }
@@ -0,0 +1,84 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::{format_aspect_mask, Image, ImageConfig, ImageView};
+use ash::vk;
+use log::info;
+
+/// Candidates when no stencil is needed, best precision first. The combined formats are fallbacks for devices
+/// without `D32_SFLOAT`, their stencil aspect goes unused.
+pub const DEPTH_FORMATS: [vk::Format; 3] = [vk::Format::D32_SFLOAT, vk::Format::D24_UNORM_S8_UINT, vk::Format::D32_SFLOAT_S8_UINT];
+/// Candidates with a stencil aspect, smallest first
+pub const DEPTH_STENCIL_FORMATS: [vk::Format; 2] = [vk::Format::D24_UNORM_S8_UINT, vk::Format::D32_SFLOAT_S8_UINT];
+
+pub fn has_stencil(format: vk::Format) -> bool {
+    format_aspect_mask(format).contains(vk::ImageAspectFlags::STENCIL)
+}
+
+/// First candidate usable as a depth attachment with optimal tiling. `properties` looks up the format properties of
+/// the device, e.g. `Context::format_properties`.
+pub fn choose_depth_format(candidates: &[vk::Format], properties: impl Fn(vk::Format) -> vk::FormatProperties) -> Option<vk::Format> {
+    candidates.iter().copied().find(|format| {
+        properties(*format)
+            .optimal_tiling_features
+            .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+    })
+}
+
+/// Best supported depth format, with a stencil aspect if `stencil` is set
+pub fn select_depth_format(context: &Context, stencil: bool) -> Result<vk::Format, Error> {
+    let candidates: &[vk::Format] = if stencil { &DEPTH_STENCIL_FORMATS } else { &DEPTH_FORMATS };
+    choose_depth_format(candidates, |format| context.format_properties(format)).ok_or(Error::NoSuitableDepthFormat)
+}
+
+/// Depth (and stencil) attachment with its view. Render passes start from `UNDEFINED` since the contents are cleared
+/// every frame, with dynamic rendering the layout is changed with `Image::transition`.
+pub struct DepthBuffer {
+    // Declared before the image so it is destroyed first
+    view: ImageView,
+    image: Image,
+}
+
+impl DepthBuffer {
+    pub fn new(context: &Context, format: vk::Format, extent: vk::Extent2D, samples: vk::SampleCountFlags) -> Result<Self, Error> {
+        if !format_aspect_mask(format).contains(vk::ImageAspectFlags::DEPTH) {
+            return Err(Error::InvalidImageConfig("Depth buffers need a depth format"));
+        }
+        let config = ImageConfig::new_2d(format, extent.width, extent.height)
+            .samples(samples)
+            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+            .dedicated(true);
+        let image = Image::new(context, config)?;
+        let view = image.create_view()?;
+        info!("Created {}x{} depth buffer, {:?}", extent.width, extent.height, format);
+        Ok(Self { view, image })
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// For dynamic rendering, where the layout is tracked through `Image::transition`
+    pub fn image_mut(&mut self) -> &mut Image {
+        &mut self.image
+    }
+
+    pub fn view(&self) -> &ImageView {
+        &self.view
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.image.format()
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        has_stencil(self.format())
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        let extent = self.image.extent();
+        vk::Extent2D {
+            width: extent.width,
+            height: extent.height,
+        }
+    }
+}
@@ -0,0 +1,447 @@
+use crate::backend::vulkan::buffer::Buffer;
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::{ImageView, Sampler};
+use crate::backend::vulkan::texture::Texture;
+use ash::vk;
+use log::trace;
+use std::collections::HashMap;
+use std::ptr::null;
+
+/// One binding of a descriptor set layout
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LayoutBinding {
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// Array size, 1 for single descriptors
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+}
+
+impl LayoutBinding {
+    pub fn new(binding: u32, descriptor_type: vk::DescriptorType, stages: vk::ShaderStageFlags) -> Self {
+        Self {
+            binding,
+            descriptor_type,
+            count: 1,
+            stages,
+        }
+    }
+
+    pub fn count(mut self, count: u32) -> Self {
+        self.count = count;
+        self
+    }
+
+    fn vk_binding(&self) -> vk::DescriptorSetLayoutBinding<'static> {
+        vk::DescriptorSetLayoutBinding {
+            binding: self.binding,
+            descriptor_type: self.descriptor_type,
+            descriptor_count: self.count,
+            stage_flags: self.stages,
+            p_immutable_samplers: null(),
+            _marker: Default::default(),
+        }
+    }
+}
+
+/// Creates every distinct set layout once. Layouts are keyed by their bindings in binding order, so pipelines
+/// asking for the same bindings share the layout handle and are compatible for set binding.
+pub struct DescriptorLayoutCache {
+    device: Device,
+    layouts: HashMap<Vec<LayoutBinding>, vk::DescriptorSetLayout>,
+}
+
+impl DescriptorLayoutCache {
+    pub fn new(context: &Context) -> Self {
+        Self {
+            device: context.device().clone(),
+            layouts: HashMap::new(),
+        }
+    }
+
+    /// Layout with the bindings, created on first use. The cache owns it.
+    pub fn layout(&mut self, bindings: &[LayoutBinding]) -> Result<vk::DescriptorSetLayout, Error> {
+        let mut key = bindings.to_vec();
+        key.sort_by_key(|binding| binding.binding);
+        if let Some(layout) = self.layouts.get(&key) {
+            return Ok(*layout);
+        }
+
+        let vk_bindings: Vec<vk::DescriptorSetLayoutBinding> = key.iter().map(|binding| binding.vk_binding()).collect();
+        let create_info = vk::DescriptorSetLayoutCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            p_next: null(),
+            flags: vk::DescriptorSetLayoutCreateFlags::empty(),
+            binding_count: vk_bindings.len() as u32,
+            p_bindings: vk_bindings.as_ptr(),
+            _marker: Default::default(),
+        };
+        let layout = unsafe { self.device.create_descriptor_set_layout(&create_info, None)? };
+        self.layouts.insert(key, layout);
+        Ok(layout)
+    }
+
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+}
+
+impl Drop for DescriptorLayoutCache {
+    fn drop(&mut self) {
+        for layout in self.layouts.values() {
+            unsafe { self.device.destroy_descriptor_set_layout(*layout, None) };
+        }
+    }
+}
+
+/// How many descriptors of each type a pool gets, relative to its set count
+#[derive(Clone, Debug)]
+pub struct PoolSizes {
+    pub ratios: Vec<(vk::DescriptorType, f32)>,
+    /// Sets of the first pool, every new pool gets 1.5 times more up to `MAX_SETS_PER_POOL`
+    pub initial_sets: u32,
+}
+
+pub const MAX_SETS_PER_POOL: u32 = 4096;
+
+impl PoolSizes {
+    pub fn new(initial_sets: u32) -> Self {
+        Self {
+            ratios: vec![
+                (vk::DescriptorType::UNIFORM_BUFFER, 2.0),
+                (vk::DescriptorType::STORAGE_BUFFER, 2.0),
+                (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 4.0),
+                (vk::DescriptorType::SAMPLED_IMAGE, 2.0),
+                (vk::DescriptorType::STORAGE_IMAGE, 1.0),
+                (vk::DescriptorType::SAMPLER, 1.0),
+                (vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1.0),
+                (vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, 1.0),
+            ],
+            initial_sets,
+        }
+    }
+
+    pub fn ratios(mut self, ratios: &[(vk::DescriptorType, f32)]) -> Self {
+        self.ratios = ratios.to_vec();
+        self
+    }
+
+    /// Descriptor counts of a pool with `sets` sets, at least one of every type
+    pub fn pool_sizes(&self, sets: u32) -> Vec<vk::DescriptorPoolSize> {
+        self.ratios
+            .iter()
+            .map(|(ty, ratio)| vk::DescriptorPoolSize {
+                ty: *ty,
+                descriptor_count: ((sets as f32 * ratio).ceil() as u32).max(1),
+            })
+            .collect()
+    }
+}
+
+impl Default for PoolSizes {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+/// Allocates descriptor sets from a growing list of pools. When a pool runs out another one is created (or a reset
+/// one reused), sets are never freed individually, `reset` returns all of them at once.
+pub struct DescriptorAllocator {
+    device: Device,
+    sizes: PoolSizes,
+    /// Pool sets are allocated from, the last one
+    pools: Vec<vk::DescriptorPool>,
+    /// Reset pools waiting to be reused
+    free_pools: Vec<vk::DescriptorPool>,
+    next_sets: u32,
+}
+
+impl DescriptorAllocator {
+    pub fn new(context: &Context, sizes: PoolSizes) -> Self {
+        Self {
+            device: context.device().clone(),
+            next_sets: sizes.initial_sets.clamp(1, MAX_SETS_PER_POOL),
+            sizes,
+            pools: Vec::new(),
+            free_pools: Vec::new(),
+        }
+    }
+
+    /// Pools created so far, in use or free
+    pub fn pool_count(&self) -> usize {
+        self.pools.len() + self.free_pools.len()
+    }
+
+    pub fn allocate(&mut self, layout: vk::DescriptorSetLayout) -> Result<vk::DescriptorSet, Error> {
+        if let Some(pool) = self.pools.last() {
+            match self.allocate_from(*pool, layout) {
+                Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY) | Err(vk::Result::ERROR_FRAGMENTED_POOL) => {}
+                result => return Ok(result?),
+            }
+        }
+        // Full or no pool yet, a fresh pool failing means the layout doesn't fit any pool
+        let pool = self.next_pool()?;
+        Ok(self.allocate_from(pool, layout)?)
+    }
+
+    /// Returns every set allocated so far to the pools, the GPU must be done with them
+    pub fn reset(&mut self) -> Result<(), Error> {
+        for pool in self.pools.drain(..) {
+            unsafe { self.device.reset_descriptor_pool(pool, vk::DescriptorPoolResetFlags::empty())? };
+            self.free_pools.push(pool);
+        }
+        Ok(())
+    }
+
+    fn allocate_from(&self, pool: vk::DescriptorPool, layout: vk::DescriptorSetLayout) -> Result<vk::DescriptorSet, vk::Result> {
+        let allocate_info = vk::DescriptorSetAllocateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+            p_next: null(),
+            descriptor_pool: pool,
+            descriptor_set_count: 1,
+            p_set_layouts: &layout,
+            _marker: Default::default(),
+        };
+        unsafe { self.device.allocate_descriptor_sets(&allocate_info).map(|sets| sets[0]) }
+    }
+
+    fn next_pool(&mut self) -> Result<vk::DescriptorPool, Error> {
+        let pool = match self.free_pools.pop() {
+            Some(pool) => pool,
+            None => {
+                let sets = self.next_sets;
+                let pool_sizes = self.sizes.pool_sizes(sets);
+                let create_info = vk::DescriptorPoolCreateInfo {
+                    s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+                    p_next: null(),
+                    flags: vk::DescriptorPoolCreateFlags::empty(),
+                    max_sets: sets,
+                    pool_size_count: pool_sizes.len() as u32,
+                    p_pool_sizes: pool_sizes.as_ptr(),
+                    _marker: Default::default(),
+                };
+                let pool = unsafe { self.device.create_descriptor_pool(&create_info, None)? };
+                trace!("Created descriptor pool for {} sets", sets);
+                self.next_sets = (sets + sets / 2).min(MAX_SETS_PER_POOL);
+                pool
+            }
+        };
+        self.pools.push(pool);
+        Ok(pool)
+    }
+}
+
+impl Drop for DescriptorAllocator {
+    fn drop(&mut self) {
+        for pool in self.pools.iter().chain(self.free_pools.iter()) {
+            unsafe { self.device.destroy_descriptor_pool(*pool, None) };
+        }
+    }
+}
+
+/// One `DescriptorAllocator` per frame in flight for sets that are rebuilt every frame. `begin_frame` resets the
+/// pools of the frame, call it once `FramesInFlight::begin_frame` waited for the frame's fence.
+pub struct FrameDescriptorAllocators {
+    allocators: Vec<DescriptorAllocator>,
+    current: usize,
+}
+
+impl FrameDescriptorAllocators {
+    pub fn new(context: &Context, frame_count: usize, sizes: PoolSizes) -> Self {
+        Self {
+            allocators: (0..frame_count).map(|_| DescriptorAllocator::new(context, sizes.clone())).collect(),
+            current: 0,
+        }
+    }
+
+    /// `frame_index` is `FramesInFlight::current_index`
+    pub fn begin_frame(&mut self, frame_index: usize) -> Result<&mut DescriptorAllocator, Error> {
+        self.current = frame_index;
+        let allocator = &mut self.allocators[frame_index];
+        allocator.reset()?;
+        Ok(allocator)
+    }
+
+    pub fn current(&mut self) -> &mut DescriptorAllocator {
+        &mut self.allocators[self.current]
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum WriteInfo {
+    Buffer(usize),
+    Image(usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PendingWrite {
+    binding: u32,
+    array_element: u32,
+    descriptor_type: vk::DescriptorType,
+    info: WriteInfo,
+}
+
+/// Collects descriptor writes and applies them to a set with `update`. The same writer can update several sets.
+#[derive(Clone, Debug, Default)]
+pub struct DescriptorWriter {
+    writes: Vec<PendingWrite>,
+    buffer_infos: Vec<vk::DescriptorBufferInfo>,
+    image_infos: Vec<vk::DescriptorImageInfo>,
+}
+
+impl DescriptorWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn buffer(
+        mut self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: vk::DescriptorType,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    ) -> Self {
+        self.buffer_infos.push(vk::DescriptorBufferInfo { buffer, offset, range });
+        self.writes.push(PendingWrite {
+            binding,
+            array_element,
+            descriptor_type,
+            info: WriteInfo::Buffer(self.buffer_infos.len() - 1),
+        });
+        self
+    }
+
+    /// The whole buffer as a uniform buffer
+    pub fn uniform_buffer(self, binding: u32, buffer: &Buffer) -> Self {
+        self.buffer(binding, 0, vk::DescriptorType::UNIFORM_BUFFER, buffer.handle(), 0, vk::WHOLE_SIZE)
+    }
+
+    /// The whole buffer as a storage buffer
+    pub fn storage_buffer(self, binding: u32, buffer: &Buffer) -> Self {
+        self.buffer(binding, 0, vk::DescriptorType::STORAGE_BUFFER, buffer.handle(), 0, vk::WHOLE_SIZE)
+    }
+
+    /// Image, sampler or both depending on `descriptor_type`, unused handles can be null
+    pub fn image(
+        mut self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: vk::DescriptorType,
+        image_view: vk::ImageView,
+        image_layout: vk::ImageLayout,
+        sampler: vk::Sampler,
+    ) -> Self {
+        self.image_infos.push(vk::DescriptorImageInfo {
+            sampler,
+            image_view,
+            image_layout,
+        });
+        self.writes.push(PendingWrite {
+            binding,
+            array_element,
+            descriptor_type,
+            info: WriteInfo::Image(self.image_infos.len() - 1),
+        });
+        self
+    }
+
+    /// Combined image sampler of a loaded texture
+    pub fn texture(self, binding: u32, texture: &Texture, sampler: &Sampler) -> Self {
+        let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        self.image(
+            binding,
+            0,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            texture.view().handle(),
+            layout,
+            sampler.handle(),
+        )
+    }
+
+    pub fn sampled_image(self, binding: u32, view: &ImageView) -> Self {
+        let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        self.image(
+            binding,
+            0,
+            vk::DescriptorType::SAMPLED_IMAGE,
+            view.handle(),
+            layout,
+            vk::Sampler::null(),
+        )
+    }
+
+    /// Storage images are accessed in `GENERAL`
+    pub fn storage_image(self, binding: u32, view: &ImageView) -> Self {
+        let layout = vk::ImageLayout::GENERAL;
+        self.image(
+            binding,
+            0,
+            vk::DescriptorType::STORAGE_IMAGE,
+            view.handle(),
+            layout,
+            vk::Sampler::null(),
+        )
+    }
+
+    pub fn sampler(self, binding: u32, sampler: &Sampler) -> Self {
+        let layout = vk::ImageLayout::UNDEFINED;
+        self.image(
+            binding,
+            0,
+            vk::DescriptorType::SAMPLER,
+            vk::ImageView::null(),
+            layout,
+            sampler.handle(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.writes.clear();
+        self.buffer_infos.clear();
+        self.image_infos.clear();
+    }
+
+    /// Writes everything collected so far into `set`, the set must not be in use by the GPU
+    pub fn update(&self, device: &ash::Device, set: vk::DescriptorSet) {
+        let writes: Vec<vk::WriteDescriptorSet> = self
+            .writes
+            .iter()
+            .map(|write| {
+                let (p_buffer_info, p_image_info) = match write.info {
+                    WriteInfo::Buffer(index) => (&self.buffer_infos[index] as *const vk::DescriptorBufferInfo, null()),
+                    WriteInfo::Image(index) => (null(), &self.image_infos[index] as *const vk::DescriptorImageInfo),
+                };
+                vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    p_next: null(),
+                    dst_set: set,
+                    dst_binding: write.binding,
+                    dst_array_element: write.array_element,
+                    descriptor_count: 1,
+                    descriptor_type: write.descriptor_type,
+                    p_image_info,
+                    p_buffer_info,
+                    p_texel_buffer_view: null(),
+                    _marker: Default::default(),
+                }
+            })
+            .collect();
+        unsafe { device.update_descriptor_sets(&writes, &[]) };
+    }
+}
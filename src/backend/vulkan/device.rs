@@ -0,0 +1,66 @@
+use crate::backend::vulkan::base::Base;
+use crate::backend::vulkan::memory::Allocator;
+use crate::backend::vulkan::surface::Surface;
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Logical device shared by the `Context` and every resource created from it, dereferences to `ash::Device`.
+/// Clones keep the device, its allocator, the surface and the instance alive, so resources may outlive the context.
+/// Everything is destroyed once the last clone is dropped.
+#[derive(Clone)]
+pub struct Device(Arc<DeviceOwner>);
+
+struct DeviceOwner {
+    // Freed in Drop before the device is destroyed
+    allocator: ManuallyDrop<Allocator>,
+    device: ash::Device,
+    surface: Option<Surface>,
+    base: Base,
+}
+
+impl Device {
+    /// Takes ownership of the device and the objects it was created from, `allocator` has to allocate from `device`
+    pub(crate) fn new(device: ash::Device, allocator: Allocator, surface: Option<Surface>, base: Base) -> Self {
+        Self(Arc::new(DeviceOwner {
+            allocator: ManuallyDrop::new(allocator),
+            device,
+            surface,
+            base,
+        }))
+    }
+
+    /// Shared by all resources created from the context
+    pub fn allocator(&self) -> &Allocator {
+        &self.0.allocator
+    }
+
+    pub fn base(&self) -> &Base {
+        &self.0.base
+    }
+
+    /// `None` for headless contexts
+    pub fn surface(&self) -> Option<&Surface> {
+        self.0.surface.as_ref()
+    }
+}
+
+impl Deref for Device {
+    type Target = ash::Device;
+
+    fn deref(&self) -> &ash::Device {
+        &self.0.device
+    }
+}
+
+impl Drop for DeviceOwner {
+    fn drop(&mut self) {
+        unsafe {
+            // Nothing sensible can be done on failure, the device is destroyed either way
+            let _ = self.device.device_wait_idle();
+            ManuallyDrop::drop(&mut self.allocator);
+            self.device.destroy_device(None);
+        }
+        // surface and base are dropped afterwards in field order
+    }
+}
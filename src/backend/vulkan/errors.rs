@@ -1,4 +1,152 @@
+use ash::vk;
+use std::ffi::CString;
+use std::fmt;
+
 #[derive(Debug)]
 pub enum Error {
+    /// Index of the first requested validation layer that is not available
     ValidationLayerNotSupported(usize),
+    /// Requested instance extension that is not available
+    ExtensionNotSupported(CString),
+    /// The Vulkan loader could not be found or is missing entry points
+    Loading(ash::LoadingError),
+    /// A Vulkan call returned an error code
+    Vulkan(vk::Result),
+    /// The window handle belongs to a platform that has no surface implementation
+    UnsupportedWindowHandle,
+    /// The display handle does not match the platform of the window handle or is incomplete
+    UnsupportedDisplayHandle,
+    /// No physical device passed the extension, surface, feature and queue requirements
+    NoSuitablePhysicalDevice,
+    /// The queue operation (see `queue::op_indices`) was already assigned to a family
+    QueueOperationAlreadyMapped(usize),
+    /// No memory type satisfies the resource requirements and requested properties
+    NoSuitableMemoryType,
+    /// The image format can't be converted to RGBA8 on readback
+    UnsupportedReadbackFormat(vk::Format),
+    /// The operation needs a surface but the context was created headless
+    HeadlessContext,
+    /// The context has no queue for the operation (see `queue::op_indices`)
+    MissingQueue(usize),
+    /// The surface reports no formats a swapchain could be created with
+    NoSurfaceFormats,
+    /// The surface doesn't support the swapchain image usage from `SwapchainConfig::image_usage`
+    UnsupportedSwapchainUsage(vk::ImageUsageFlags),
+    /// Frames in flight have to be between 1 and `frames::MAX_FRAMES_IN_FLIGHT`
+    InvalidFramesInFlight(usize),
+    /// CPU access to a buffer whose memory is not host visible
+    BufferNotMapped,
+    /// The accessed byte range does not fit into the buffer
+    BufferOutOfRange {
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+        buffer_size: vk::DeviceSize,
+    },
+    /// A single copy, e.g. one row of texel blocks, needs more space than the staging ring has
+    StagingTooSmall { size: vk::DeviceSize, capacity: vk::DeviceSize },
+    /// The image description is inconsistent, e.g. a non square cube or multisampling with mips
+    InvalidImageConfig(&'static str),
+    /// The device can't sample the texture format and there is no CPU decoder for it, e.g. ETC2
+    UnsupportedTextureFormat(vk::Format),
+    /// Texture data that failed to decode or doesn't match its description
+    Texture(crate::texture::Error),
+    /// The pipeline description is incomplete or inconsistent, e.g. no vertex shader
+    InvalidPipelineConfig(&'static str),
+    /// None of the depth format candidates can be used as a depth attachment
+    NoSuitableDepthFormat,
+    /// The operation needs a `DeviceFeatures` feature that was not requested on the `ContextConfigurator`
+    DeviceFeatureNotEnabled(&'static str),
+    /// Every slot of the bindless array is allocated
+    BindlessSlotsExhausted(&'static str),
+    /// The SPIR-V module is malformed or uses something reflection doesn't understand
+    InvalidSpirv(&'static str),
+    /// Two shader stages declare different descriptor types for the same binding
+    IncompatibleShaderBindings { set: u32, binding: u32 },
+    /// No shader with the name has an entry point for the stage, an empty stage means any stage
+    ShaderNotFound { name: String, stage: vk::ShaderStageFlags },
+    /// Reading a file failed
+    Io(std::io::Error),
+    /// glslc rejected the shader source, `log` is its error output
+    ShaderCompilationFailed { path: String, log: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ValidationLayerNotSupported(index) => write!(f, "Validation layer at index {} is not supported", index),
+            Error::ExtensionNotSupported(extension) => write!(f, "Instance extension {:?} is not supported", extension),
+            Error::Loading(error) => write!(f, "Failed to load Vulkan: {}", error),
+            Error::Vulkan(result) => write!(f, "Vulkan call failed: {}", result),
+            Error::UnsupportedWindowHandle => write!(f, "Unsupported window handle type"),
+            Error::UnsupportedDisplayHandle => write!(f, "Display handle does not match the window handle"),
+            Error::NoSuitablePhysicalDevice => write!(f, "No suitable physical device found"),
+            Error::QueueOperationAlreadyMapped(operation) => write!(f, "Queue operation {} is already mapped", operation),
+            Error::NoSuitableMemoryType => write!(f, "No suitable memory type found"),
+            Error::UnsupportedReadbackFormat(format) => write!(f, "Format {:?} can't be read back as RGBA8", format),
+            Error::HeadlessContext => write!(f, "Operation requires a surface but the context is headless"),
+            Error::MissingQueue(operation) => write!(f, "The context has no queue for operation {}", operation),
+            Error::NoSurfaceFormats => write!(f, "The surface has no formats"),
+            Error::UnsupportedSwapchainUsage(usage) => write!(f, "The surface does not support swapchain images with usage {:?}", usage),
+            Error::InvalidFramesInFlight(count) => write!(f, "{} frames in flight are not supported", count),
+            Error::BufferNotMapped => write!(f, "Buffer memory is not host visible"),
+            Error::BufferOutOfRange { offset, size, buffer_size } => {
+                write!(f, "Range of {} bytes at offset {} exceeds the buffer size {}", size, offset, buffer_size)
+            }
+            Error::StagingTooSmall { size, capacity } => {
+                write!(f, "Copy of {} bytes does not fit into the staging ring of {} bytes", size, capacity)
+            }
+            Error::InvalidImageConfig(reason) => write!(f, "Invalid image configuration: {}", reason),
+            Error::UnsupportedTextureFormat(format) => {
+                write!(f, "Texture format {:?} is not supported by the device and has no CPU decoder (ETC2, EAC and SNORM BC)", format)
+            }
+            Error::Texture(error) => write!(f, "{}", error),
+            Error::InvalidPipelineConfig(reason) => write!(f, "Invalid pipeline configuration: {}", reason),
+            Error::NoSuitableDepthFormat => write!(f, "No suitable depth format found"),
+            Error::DeviceFeatureNotEnabled(feature) => write!(f, "Device feature {} is not enabled", feature),
+            Error::BindlessSlotsExhausted(kind) => write!(f, "No free bindless {} slots left", kind),
+            Error::InvalidSpirv(reason) => write!(f, "Invalid SPIR-V module: {}", reason),
+            Error::IncompatibleShaderBindings { set, binding } => {
+                write!(f, "Shader stages disagree on the descriptor type of set {} binding {}", set, binding)
+            }
+            Error::ShaderNotFound { name, stage } => write!(f, "No shader {} for stage {:?}", name, stage),
+            Error::Io(error) => write!(f, "I/O error: {}", error),
+            Error::ShaderCompilationFailed { path, log } => write!(f, "Failed to compile {}:\n{}", path, log),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Loading(error) => Some(error),
+            Error::Vulkan(result) => Some(result),
+            Error::Texture(error) => Some(error),
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<vk::Result> for Error {
+    fn from(result: vk::Result) -> Self {
+        Error::Vulkan(result)
+    }
+}
+
+impl From<ash::LoadingError> for Error {
+    fn from(error: ash::LoadingError) -> Self {
+        Error::Loading(error)
+    }
+}
+
+impl From<crate::texture::Error> for Error {
+    fn from(error: crate::texture::Error) -> Self {
+        Error::Texture(error)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
 }
@@ -0,0 +1,217 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::queue::op_indices::GRAPHICS;
+use ash::vk;
+use std::ptr::null;
+
+pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+pub const MAX_FRAMES_IN_FLIGHT: usize = 3;
+
+/// Resources of a single frame, only touched again once its fence is signaled
+pub struct Frame {
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+    image_available: vk::Semaphore,
+    in_flight: vk::Fence,
+}
+
+impl Frame {
+    pub fn command_buffer(&self) -> vk::CommandBuffer {
+        self.command_buffer
+    }
+
+    /// Pass to `Swapchain::acquire_next_image`
+    pub fn image_available(&self) -> vk::Semaphore {
+        self.image_available
+    }
+
+    pub fn fence(&self) -> vk::Fence {
+        self.in_flight
+    }
+}
+
+/// Lets the CPU record up to N frames while the GPU is still working on earlier ones.
+///
+/// Every frame has its own command pool, command buffer, fence and acquire semaphore. The semaphore signaled
+/// on render completion is owned per swapchain image instead, the presentation engine may hold on to it until
+/// the image is acquired again, which is unrelated to the frame order. Images acquired out of order are tracked
+/// with the fence of the frame that last rendered to them.
+///
+/// Usage per frame: `begin_frame`, acquire with `Frame::image_available`, `image_acquired`, record into
+/// `Frame::command_buffer`, `submit`, present waiting on the returned semaphore.
+pub struct FramesInFlight {
+    device: Device,
+    frames: Vec<Frame>,
+    current: usize,
+    render_finished: Vec<vk::Semaphore>,
+    images_in_flight: Vec<vk::Fence>,
+}
+
+impl FramesInFlight {
+    /// `frame_count` has to be between 1 and `MAX_FRAMES_IN_FLIGHT`
+    pub fn new(context: &Context, frame_count: usize) -> Result<Self, Error> {
+        if frame_count == 0 || frame_count > MAX_FRAMES_IN_FLIGHT {
+            return Err(Error::InvalidFramesInFlight(frame_count));
+        }
+        let device = context.device();
+        let mut frames_in_flight = Self {
+            device: device.clone(),
+            frames: Vec::with_capacity(frame_count),
+            current: 0,
+            render_finished: Vec::new(),
+            images_in_flight: Vec::new(),
+        };
+
+        let command_pool_create_info = vk::CommandPoolCreateInfo {
+            s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+            p_next: null(),
+            flags: vk::CommandPoolCreateFlags::TRANSIENT,
+            queue_family_index: context.queue_family_index(GRAPHICS).ok_or(Error::MissingQueue(GRAPHICS))?,
+            _marker: Default::default(),
+        };
+        let semaphore_create_info = vk::SemaphoreCreateInfo {
+            s_type: vk::StructureType::SEMAPHORE_CREATE_INFO,
+            p_next: null(),
+            flags: Default::default(),
+            _marker: Default::default(),
+        };
+        // Signaled so the first wait on every frame returns immediately
+        let fence_create_info = vk::FenceCreateInfo {
+            s_type: vk::StructureType::FENCE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::FenceCreateFlags::SIGNALED,
+            _marker: Default::default(),
+        };
+
+        for _ in 0..frame_count {
+            // Pushed first so Drop cleans up whatever was created if a later call fails
+            frames_in_flight.frames.push(Frame {
+                command_pool: vk::CommandPool::null(),
+                command_buffer: vk::CommandBuffer::null(),
+                image_available: vk::Semaphore::null(),
+                in_flight: vk::Fence::null(),
+            });
+            let frame = frames_in_flight.frames.last_mut().unwrap();
+            unsafe {
+                frame.command_pool = device.create_command_pool(&command_pool_create_info, None)?;
+                let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+                    s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+                    p_next: null(),
+                    command_pool: frame.command_pool,
+                    level: vk::CommandBufferLevel::PRIMARY,
+                    command_buffer_count: 1,
+                    _marker: Default::default(),
+                };
+                frame.command_buffer = device.allocate_command_buffers(&command_buffer_allocate_info)?[0];
+                frame.image_available = device.create_semaphore(&semaphore_create_info, None)?;
+                frame.in_flight = device.create_fence(&fence_create_info, None)?;
+            }
+        }
+        Ok(frames_in_flight)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Index of the current frame, 0 to `frame_count() - 1`
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn current(&self) -> &Frame {
+        &self.frames[self.current]
+    }
+
+    /// Waits until the GPU is done with the resources of the current frame. The fence is not reset yet,
+    /// so skipping the frame (e.g. nothing acquired while minimized) leaves it in a consistent state.
+    pub fn begin_frame(&mut self) -> Result<&Frame, Error> {
+        let frame = &self.frames[self.current];
+        unsafe { self.device.wait_for_fences(&[frame.in_flight], true, u64::MAX)? };
+        Ok(frame)
+    }
+
+    /// Call once the swapchain returned `image_index`. Waits for an earlier frame still rendering to that image,
+    /// resets the frame for recording and returns the semaphore to pass to `submit` and present.
+    /// `image_count` is the current swapchain image count, per image state follows it when the swapchain is recreated.
+    pub fn image_acquired(&mut self, image_index: u32, image_count: usize) -> Result<vk::Semaphore, Error> {
+        self.track_images(image_count)?;
+        let image = image_index as usize;
+        let frame = &self.frames[self.current];
+
+        let image_fence = self.images_in_flight[image];
+        if image_fence != vk::Fence::null() && image_fence != frame.in_flight {
+            unsafe { self.device.wait_for_fences(&[image_fence], true, u64::MAX)? };
+        }
+        self.images_in_flight[image] = frame.in_flight;
+
+        unsafe {
+            self.device.reset_fences(&[frame.in_flight])?;
+            self.device
+                .reset_command_pool(frame.command_pool, vk::CommandPoolResetFlags::empty())?;
+        }
+        Ok(self.render_finished[image])
+    }
+
+    /// Submits the current frame's command buffer. It waits for the acquired image at `wait_stage`,
+    /// signals the image's render finished semaphore and the frame fence. Advances to the next frame.
+    pub fn submit(&mut self, queue: vk::Queue, image_index: u32, wait_stage: vk::PipelineStageFlags) -> Result<(), Error> {
+        let frame = &self.frames[self.current];
+        let render_finished = self.render_finished[image_index as usize];
+        let submit_info = vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            p_next: null(),
+            wait_semaphore_count: 1,
+            p_wait_semaphores: &frame.image_available,
+            p_wait_dst_stage_mask: &wait_stage,
+            command_buffer_count: 1,
+            p_command_buffers: &frame.command_buffer,
+            signal_semaphore_count: 1,
+            p_signal_semaphores: &render_finished,
+            _marker: Default::default(),
+        };
+        unsafe { self.device.queue_submit(queue, &[submit_info], frame.in_flight)? };
+        self.current = (self.current + 1) % self.frames.len();
+        Ok(())
+    }
+
+    /// Swapchain recreation waits for the device, so all tracked fences are signaled by now and can be forgotten
+    fn track_images(&mut self, image_count: usize) -> Result<(), Error> {
+        if self.render_finished.len() == image_count {
+            return Ok(());
+        }
+        self.images_in_flight = vec![vk::Fence::null(); image_count];
+        let semaphore_create_info = vk::SemaphoreCreateInfo {
+            s_type: vk::StructureType::SEMAPHORE_CREATE_INFO,
+            p_next: null(),
+            flags: Default::default(),
+            _marker: Default::default(),
+        };
+        while self.render_finished.len() < image_count {
+            let semaphore = unsafe { self.device.create_semaphore(&semaphore_create_info, None)? };
+            self.render_finished.push(semaphore);
+        }
+        for semaphore in self.render_finished.drain(image_count..) {
+            unsafe { self.device.destroy_semaphore(semaphore, None) };
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FramesInFlight {
+    fn drop(&mut self) {
+        unsafe {
+            // Frames may still be in flight, their sync objects are destroyed even if the wait fails
+            let _ = self.device.device_wait_idle();
+            for frame in self.frames.iter() {
+                self.device.destroy_fence(frame.in_flight, None);
+                self.device.destroy_semaphore(frame.image_available, None);
+                self.device.destroy_command_pool(frame.command_pool, None);
+            }
+            for semaphore in self.render_finished.iter() {
+                self.device.destroy_semaphore(*semaphore, None);
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Shared with build.rs through `#[path]`, so it can only use std.
+//!
+//! Sources configure their compilation with directive comments, which hold for every variant of the file:
+//!
+//! ```glsl
+//! // @target-env vulkan1.2
+//! // @optimize performance
+//! // @define MAX_LIGHTS=16
+//! // @permutation SKINNED
+//! // @permutation SKINNED SHADOWS=2
+//! ```
+//!
+//! `@target-env` is passed as `--target-env`, mesh, task and ray tracing stages default to `vulkan1.2` because they
+//! need SPIR-V 1.4. `@optimize` is `none`, `size` or `performance`. Every `@permutation` adds a variant compiled
+//! with its defines next to the one without, see `permutation_name` for how variants are named.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Source extensions and the `vk::ShaderStageFlags` constant of their stage. `.glsl` files are compiled too, they
+/// name their stage with `#pragma shader_stage`. Includes should use another extension.
+pub const SHADER_STAGES: &[(&str, &str)] = &[
+    ("vert", "VERTEX"),
+    ("tesc", "TESSELLATION_CONTROL"),
+    ("tese", "TESSELLATION_EVALUATION"),
+    ("geom", "GEOMETRY"),
+    ("frag", "FRAGMENT"),
+    ("comp", "COMPUTE"),
+    ("task", "TASK_EXT"),
+    ("mesh", "MESH_EXT"),
+    ("rgen", "RAYGEN_KHR"),
+    ("rint", "INTERSECTION_KHR"),
+    ("rahit", "ANY_HIT_KHR"),
+    ("rchit", "CLOSEST_HIT_KHR"),
+    ("rmiss", "MISS_KHR"),
+    ("rcall", "CALLABLE_KHR"),
+];
+
+fn extension(path: &Path) -> &str {
+    path.extension().and_then(|extension| extension.to_str()).unwrap_or("")
+}
+
+pub fn is_shader_source(path: &Path) -> bool {
+    let extension = extension(path);
+    extension == "glsl" || SHADER_STAGES.iter().any(|(stage_extension, _)| *stage_extension == extension)
+}
+
+/// Name of the stage constant for the source, `None` for `.glsl` files
+pub fn stage_flag_name(path: &Path) -> Option<&'static str> {
+    let extension = extension(path);
+    SHADER_STAGES
+        .iter()
+        .find(|(stage_extension, _)| *stage_extension == extension)
+        .map(|(_, name)| *name)
+}
+
+/// Stages that need SPIR-V 1.4, which Vulkan 1.0 doesn't accept
+fn needs_spirv_1_4(path: &Path) -> bool {
+    matches!(
+        extension(path),
+        "task" | "mesh" | "rgen" | "rint" | "rahit" | "rchit" | "rmiss" | "rcall"
+    )
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Optimization {
+    /// glslc's default, no flag
+    #[default]
+    Default,
+    None,
+    Size,
+    Performance,
+}
+
+impl Optimization {
+    fn flag(self) -> Option<&'static str> {
+        match self {
+            Optimization::Default => None,
+            Optimization::None => Some("-O0"),
+            Optimization::Size => Some("-Os"),
+            Optimization::Performance => Some("-O"),
+        }
+    }
+}
+
+/// Compilation settings of a source, read from its directive comments
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompileOptions {
+    /// `--target-env` value such as `vulkan1.2`
+    pub target_env: Option<String>,
+    pub optimization: Optimization,
+    /// Defines of every variant, `NAME` or `NAME=VALUE`
+    pub defines: Vec<String>,
+    /// Defines of the variants besides the one without, in declaration order
+    pub permutations: Vec<Vec<String>>,
+}
+
+impl CompileOptions {
+    /// Reads the `// @` directives, unknown ones are an error so typos don't go unnoticed
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut options = Self::default();
+        for (index, line) in source.lines().enumerate() {
+            let Some(directive) = line
+                .trim()
+                .strip_prefix("//")
+                .map(str::trim)
+                .and_then(|line| line.strip_prefix('@'))
+            else {
+                continue;
+            };
+            let mut words = directive.split_whitespace();
+            let keyword = words.next();
+            let arguments: Vec<String> = words.map(str::to_string).collect();
+            match (keyword, arguments.as_slice()) {
+                (Some("target-env"), [target_env]) => options.target_env = Some(target_env.clone()),
+                (Some("optimize"), [level]) => {
+                    options.optimization = match level.as_str() {
+                        "none" => Optimization::None,
+                        "size" => Optimization::Size,
+                        "performance" => Optimization::Performance,
+                        _ => return Err(format!("line {}: unknown optimization level {}", index + 1, level)),
+                    }
+                }
+                (Some("define"), defines) if !defines.is_empty() => options.defines.extend_from_slice(defines),
+                (Some("permutation"), defines) if !defines.is_empty() => options.permutations.push(defines.to_vec()),
+                _ => return Err(format!("line {}: invalid directive @{}", index + 1, directive)),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Permutation defines of every variant, the first one has none
+    pub fn variants(&self) -> Vec<Vec<String>> {
+        let mut variants = vec![Vec::new()];
+        variants.extend(self.permutations.iter().cloned());
+        variants
+    }
+}
+
+/// Name of a variant, the shader name followed by its sorted defines: `blur[HORIZONTAL,TAPS=9]`
+pub fn permutation_name(name: &str, defines: &[String]) -> String {
+    if defines.is_empty() {
+        return name.to_string();
+    }
+    let mut defines = defines.to_vec();
+    defines.sort();
+    format!("{}[{}]", name, defines.join(","))
+}
+
+/// Module path of a variant, `relative` is the source path relative to the shader directory
+pub fn output_path(output_dir: &Path, relative: &Path, variant: usize) -> PathBuf {
+    let mut file_name = relative.as_os_str().to_owned();
+    if variant > 0 {
+        file_name.push(format!(".{}", variant));
+    }
+    file_name.push(".spv");
+    output_dir.join(file_name)
+}
+
+/// glslc invocation for a variant of a shader, includes are resolved relative to its directory
+pub fn glslc_command(shader_path: &Path, output_path: &Path, options: &CompileOptions, permutation: &[String]) -> Command {
+    let mut command = Command::new("glslc");
+    command.arg("-I").arg(shader_path.parent().unwrap_or(Path::new(".")));
+    match &options.target_env {
+        Some(target_env) => {
+            command.arg(format!("--target-env={}", target_env));
+        }
+        None if needs_spirv_1_4(shader_path) => {
+            command.arg("--target-env=vulkan1.2");
+        }
+        None => {}
+    }
+    if let Some(flag) = options.optimization.flag() {
+        command.arg(flag);
+    }
+    for define in options.defines.iter().chain(permutation) {
+        command.arg(format!("-D{}", define));
+    }
+    command.arg(shader_path).arg("-o").arg(output_path);
+    command
+}
+
+/// Files listed in a make style depfile as written by `glslc -MD`, the target excluded
+pub fn parse_depfile(contents: &str) -> Vec<PathBuf> {
+    // Windows paths contain a colon after the drive letter, the target ends at the first one followed by whitespace
+    let dependencies = match contents.find(": ").or_else(|| contents.find(":\n")) {
+        Some(end) => &contents[end + 1..],
+        None => return Vec::new(),
+    };
+    let mut paths = Vec::new();
+    let mut path = String::new();
+    let mut characters = dependencies.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' if characters.peek() == Some(&' ') => {
+                path.push(' ');
+                characters.next();
+            }
+            '\\' if matches!(characters.peek(), Some('\n') | Some('\r')) => {}
+            character if character.is_whitespace() => {
+                if !path.is_empty() {
+                    paths.push(PathBuf::from(std::mem::take(&mut path)));
+                }
+            }
+            character => path.push(character),
+        }
+    }
+    if !path.is_empty() {
+        paths.push(PathBuf::from(path));
+    }
+    paths
+}
@@ -0,0 +1,306 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::frames::DEFAULT_FRAMES_IN_FLIGHT;
+use crate::backend::vulkan::glslc::{glslc_command, is_shader_source, output_path, permutation_name, CompileOptions};
+use crate::backend::vulkan::pipeline::{GraphicsPipeline, GraphicsPipelineBuilder, ShaderStage};
+use crate::backend::vulkan::shaders::{shader_name, ShaderLibrary, ShaderOrigin};
+use ash::vk;
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Stage of a shader source extension, empty if the compiled module has to be reflected to know it
+pub fn source_stage(path: &Path) -> vk::ShaderStageFlags {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("vert") => vk::ShaderStageFlags::VERTEX,
+        Some("tesc") => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        Some("tese") => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        Some("geom") => vk::ShaderStageFlags::GEOMETRY,
+        Some("frag") => vk::ShaderStageFlags::FRAGMENT,
+        Some("comp") => vk::ShaderStageFlags::COMPUTE,
+        Some("task") => vk::ShaderStageFlags::TASK_EXT,
+        Some("mesh") => vk::ShaderStageFlags::MESH_EXT,
+        Some("rgen") => vk::ShaderStageFlags::RAYGEN_KHR,
+        Some("rint") => vk::ShaderStageFlags::INTERSECTION_KHR,
+        Some("rahit") => vk::ShaderStageFlags::ANY_HIT_KHR,
+        Some("rchit") => vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+        Some("rmiss") => vk::ShaderStageFlags::MISS_KHR,
+        Some("rcall") => vk::ShaderStageFlags::CALLABLE_KHR,
+        _ => vk::ShaderStageFlags::empty(),
+    }
+}
+
+/// Compiles a variant of the source to `output` with the glslc invocation build.rs uses and returns the module
+pub fn compile_shader(source: &Path, output: &Path, options: &CompileOptions, permutation: &[String]) -> Result<Vec<u8>, Error> {
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let result = glslc_command(source, output, options, permutation).output()?;
+    if !result.status.success() {
+        return Err(Error::ShaderCompilationFailed {
+            path: source.display().to_string(),
+            log: String::from_utf8_lossy(&result.stderr).into_owned(),
+        });
+    }
+    Ok(fs::read(output)?)
+}
+
+/// Finds shader sources below a directory that were added or modified, by polling their modification times
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+}
+
+impl ShaderWatcher {
+    /// Sources that exist already are not reported as changed
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let mut watcher = Self {
+            dir: dir.into(),
+            modified: HashMap::new(),
+        };
+        watcher.changed();
+        watcher
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Sources added or modified since the last call, sorted. Deleted sources are forgotten.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut modified = HashMap::new();
+        if let Err(error) = find_sources(&self.dir, &mut modified) {
+            warn!("Failed to scan {} for shader changes: {}", self.dir.display(), error);
+            return Vec::new();
+        }
+        let mut changed: Vec<PathBuf> = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(*time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.sort();
+        self.modified = modified;
+        changed
+    }
+}
+
+fn find_sources(dir: &Path, sources: &mut HashMap<PathBuf, SystemTime>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_sources(&path, sources)?;
+        } else if is_shader_source(&path) {
+            sources.insert(path.clone(), fs::metadata(&path)?.modified()?);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
+pub struct HotReloadConfig {
+    /// Watched for changed sources
+    pub source_dir: PathBuf,
+    /// Recompiled modules are written here, named like build.rs names them in `OUT_DIR`
+    pub output_dir: PathBuf,
+    /// Minimum time between two scans of `source_dir`
+    pub poll_interval: Duration,
+    /// Frames a replaced pipeline is kept alive for, at least the number of frames in flight
+    pub retire_frames: u32,
+}
+
+impl HotReloadConfig {
+    pub fn new(source_dir: impl Into<PathBuf>, output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            source_dir: source_dir.into(),
+            output_dir: output_dir.into(),
+            poll_interval: Duration::from_millis(500),
+            retire_frames: DEFAULT_FRAMES_IN_FLIGHT as u32,
+        }
+    }
+
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn retire_frames(mut self, retire_frames: u32) -> Self {
+        self.retire_frames = retire_frames;
+        self
+    }
+}
+
+impl Default for HotReloadConfig {
+    /// The shader directory relative to the working directory, modules go to the temporary directory
+    fn default() -> Self {
+        Self::new("shaders", std::env::temp_dir().join("eikon_shaders"))
+    }
+}
+
+/// Pipeline registered with `ShaderHotReload::add_pipeline`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineId(usize);
+
+struct ReloadablePipeline {
+    /// Without the library shaders, they are added on every build
+    builder: GraphicsPipelineBuilder,
+    shaders: Vec<(String, vk::ShaderStageFlags)>,
+    pipeline: GraphicsPipeline,
+    dirty: bool,
+}
+
+/// Recompiles shader sources when they change and rebuilds the pipelines that use them.
+///
+/// Pipelines are registered with a builder and the names of their library shaders. Call `update` once per frame
+/// after the frame's fence was waited for: it recompiles changed sources into the library and rebuilds the affected
+/// pipelines. Compile and build errors are logged and the previous module or pipeline stays in use. Replaced
+/// pipelines are destroyed `retire_frames` frames later, so look up `pipeline(id)` every frame instead of keeping
+/// the handle. Rebuilds reuse the builder's set layouts, layout changes in the shaders need a restart.
+pub struct ShaderHotReload {
+    device: Device,
+    config: HotReloadConfig,
+    watcher: ShaderWatcher,
+    last_poll: Instant,
+    pipelines: Vec<ReloadablePipeline>,
+    /// Replaced pipelines with the frame they were replaced in
+    retired: Vec<(u64, GraphicsPipeline)>,
+    frame: u64,
+}
+
+impl ShaderHotReload {
+    pub fn new(context: &Context, config: HotReloadConfig) -> Self {
+        Self {
+            device: context.device().clone(),
+            watcher: ShaderWatcher::new(&config.source_dir),
+            last_poll: Instant::now(),
+            config,
+            pipelines: Vec::new(),
+            retired: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    pub fn config(&self) -> &HotReloadConfig {
+        &self.config
+    }
+
+    /// Builds the pipeline with the named library shaders as its stages, the builder must not have stages of its own
+    pub fn add_pipeline(
+        &mut self,
+        library: &ShaderLibrary,
+        builder: GraphicsPipelineBuilder,
+        shaders: &[(&str, vk::ShaderStageFlags)],
+    ) -> Result<PipelineId, Error> {
+        let shaders: Vec<_> = shaders.iter().map(|(name, stage)| (shader_name(name), *stage)).collect();
+        let pipeline = build_pipeline(&self.device, library, &builder, &shaders)?;
+        self.pipelines.push(ReloadablePipeline {
+            builder,
+            shaders,
+            pipeline,
+            dirty: false,
+        });
+        Ok(PipelineId(self.pipelines.len() - 1))
+    }
+
+    /// Current version of the pipeline
+    pub fn pipeline(&self, id: PipelineId) -> &GraphicsPipeline {
+        &self.pipelines[id.0].pipeline
+    }
+
+    pub fn pipeline_count(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    /// Replaced pipelines that are not destroyed yet
+    pub fn retired_count(&self) -> usize {
+        self.retired.len()
+    }
+
+    /// Marks every pipeline using a shader of that name for a rebuild on the next `update`, returns how many
+    pub fn invalidate(&mut self, name: &str) -> usize {
+        let name = shader_name(name);
+        let mut count = 0;
+        for pipeline in self.pipelines.iter_mut() {
+            if pipeline.shaders.iter().any(|(shader, _)| *shader == name) {
+                pipeline.dirty = true;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Recompiles every variant of a source below `source_dir` into the library and invalidates the pipelines using
+    /// them. Variants compiled before a failing one stay in the library.
+    pub fn reload(&mut self, library: &mut ShaderLibrary, source: &Path) -> Result<(), Error> {
+        let relative = source.strip_prefix(&self.config.source_dir).unwrap_or(source);
+        let name = shader_name(&relative.with_extension("").to_string_lossy());
+        let options = CompileOptions::parse(&fs::read_to_string(source)?).map_err(|log| Error::ShaderCompilationFailed {
+            path: source.display().to_string(),
+            log,
+        })?;
+        for (variant, defines) in options.variants().iter().enumerate() {
+            let output = output_path(&self.config.output_dir, relative, variant);
+            let code = compile_shader(source, &output, &options, defines)?;
+            let variant_name = permutation_name(&name, defines);
+            library.add_with_origin(&variant_name, source_stage(source), &code, ShaderOrigin::File(output))?;
+            let count = self.invalidate(&variant_name);
+            info!("Reloaded shader {}, rebuilding {} pipelines", variant_name, count);
+        }
+        Ok(())
+    }
+
+    /// Call at the start of every frame once its fence is signaled. Reloads changed sources when the poll interval
+    /// passed, rebuilds invalidated pipelines and destroys retired ones. Returns the number of rebuilt pipelines.
+    pub fn update(&mut self, library: &mut ShaderLibrary) -> usize {
+        self.frame += 1;
+        let frame = self.frame;
+        let retire_frames = self.config.retire_frames as u64;
+        self.retired.retain(|(retired, _)| frame - retired < retire_frames);
+
+        if self.last_poll.elapsed() >= self.config.poll_interval {
+            self.last_poll = Instant::now();
+            for source in self.watcher.changed() {
+                if let Err(error) = self.reload(library, &source) {
+                    error!("{}", error);
+                }
+            }
+        }
+
+        let mut rebuilt = 0;
+        for pipeline in self.pipelines.iter_mut().filter(|pipeline| pipeline.dirty) {
+            pipeline.dirty = false;
+            match build_pipeline(&self.device, library, &pipeline.builder, &pipeline.shaders) {
+                Ok(new_pipeline) => {
+                    let old_pipeline = std::mem::replace(&mut pipeline.pipeline, new_pipeline);
+                    self.retired.push((frame, old_pipeline));
+                    rebuilt += 1;
+                }
+                Err(error) => error!("Failed to rebuild pipeline, keeping the previous one: {}", error),
+            }
+        }
+        rebuilt
+    }
+}
+
+fn build_pipeline(
+    device: &Device,
+    library: &ShaderLibrary,
+    builder: &GraphicsPipelineBuilder,
+    shaders: &[(String, vk::ShaderStageFlags)],
+) -> Result<GraphicsPipeline, Error> {
+    let mut builder = builder.clone();
+    for (name, stage) in shaders {
+        let shader = library.get(name, *stage).ok_or_else(|| Error::ShaderNotFound {
+            name: name.clone(),
+            stage: *stage,
+        })?;
+        builder.stages.push(ShaderStage {
+            stage: *stage,
+            module: shader.module(),
+            entry_point: shader.entry_point().clone(),
+        });
+    }
+    builder.build(device)
+}
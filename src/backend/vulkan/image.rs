@@ -0,0 +1,647 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::memory::{Allocation, MemoryUsage};
+use ash::vk;
+use log::warn;
+use std::ptr::null;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageKind {
+    D2,
+    D2Array,
+    /// Six layers per cube, more than six make a cube array
+    Cube,
+    D3,
+}
+
+/// Mip levels of a full chain down to 1x1x1
+pub fn full_mip_level_count(extent: vk::Extent3D) -> u32 {
+    let largest = extent.width.max(extent.height).max(extent.depth).max(1);
+    u32::BITS - largest.leading_zeros()
+}
+
+/// Size of a mip level, never smaller than 1 in any dimension
+pub fn mip_extent(extent: vk::Extent3D, level: u32) -> vk::Extent3D {
+    vk::Extent3D {
+        width: (extent.width >> level).max(1),
+        height: (extent.height >> level).max(1),
+        depth: (extent.depth >> level).max(1),
+    }
+}
+
+pub fn format_aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => vk::ImageAspectFlags::DEPTH,
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        vk::Format::D16_UNORM_S8_UINT | vk::Format::D24_UNORM_S8_UINT | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+/// Access and pipeline stages of an image in the layout. `source` selects the side of the barrier, it only matters
+/// for layouts like `PRESENT_SRC_KHR` that are waited on differently than they are transitioned to.
+pub fn layout_access(layout: vk::ImageLayout, source: bool) -> (vk::AccessFlags, vk::PipelineStageFlags) {
+    match layout {
+        vk::ImageLayout::UNDEFINED | vk::ImageLayout::PREINITIALIZED => (vk::AccessFlags::empty(), vk::PipelineStageFlags::TOP_OF_PIPE),
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL => (vk::AccessFlags::TRANSFER_READ, vk::PipelineStageFlags::TRANSFER),
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL => (vk::AccessFlags::TRANSFER_WRITE, vk::PipelineStageFlags::TRANSFER),
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => (
+            vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::VERTEX_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COMPUTE_SHADER,
+        ),
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => (
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        ),
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL | vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL => (
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+        ),
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL | vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL => (
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::FRAGMENT_SHADER,
+        ),
+        // Swapchain images are acquired with a semaphore waiting at the color output stage
+        vk::ImageLayout::PRESENT_SRC_KHR if source => (vk::AccessFlags::empty(), vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT),
+        vk::ImageLayout::PRESENT_SRC_KHR => (vk::AccessFlags::empty(), vk::PipelineStageFlags::BOTTOM_OF_PIPE),
+        _ => (
+            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+        ),
+    }
+}
+
+/// Description of an `Image`, start with one of the constructors per `ImageKind`
+#[derive(Clone, Copy, Debug)]
+pub struct ImageConfig {
+    pub kind: ImageKind,
+    pub format: vk::Format,
+    pub extent: vk::Extent3D,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    pub samples: vk::SampleCountFlags,
+    pub usage: vk::ImageUsageFlags,
+    pub memory_usage: MemoryUsage,
+    pub dedicated: bool,
+}
+
+impl ImageConfig {
+    fn with_kind(kind: ImageKind, format: vk::Format, extent: vk::Extent3D, array_layers: u32) -> Self {
+        Self {
+            kind,
+            format,
+            extent,
+            mip_levels: 1,
+            array_layers,
+            samples: vk::SampleCountFlags::TYPE_1,
+            usage: vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            memory_usage: MemoryUsage::GpuOnly,
+            dedicated: false,
+        }
+    }
+
+    pub fn new_2d(format: vk::Format, width: u32, height: u32) -> Self {
+        Self::with_kind(ImageKind::D2, format, vk::Extent3D { width, height, depth: 1 }, 1)
+    }
+
+    pub fn new_2d_array(format: vk::Format, width: u32, height: u32, layers: u32) -> Self {
+        Self::with_kind(ImageKind::D2Array, format, vk::Extent3D { width, height, depth: 1 }, layers)
+    }
+
+    pub fn new_cube(format: vk::Format, size: u32) -> Self {
+        let extent = vk::Extent3D {
+            width: size,
+            height: size,
+            depth: 1,
+        };
+        Self::with_kind(ImageKind::Cube, format, extent, 6)
+    }
+
+    pub fn new_3d(format: vk::Format, width: u32, height: u32, depth: u32) -> Self {
+        Self::with_kind(ImageKind::D3, format, vk::Extent3D { width, height, depth }, 1)
+    }
+
+    /// Cube arrays need a multiple of six layers
+    pub fn array_layers(mut self, array_layers: u32) -> Self {
+        self.array_layers = array_layers;
+        self
+    }
+
+    pub fn mip_levels(mut self, mip_levels: u32) -> Self {
+        self.mip_levels = mip_levels;
+        self
+    }
+
+    pub fn full_mip_chain(mut self) -> Self {
+        self.mip_levels = full_mip_level_count(self.extent);
+        self
+    }
+
+    /// Multisampled images have to be 2D without mips
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Defaults to `SAMPLED | TRANSFER_DST`
+    pub fn usage(mut self, usage: vk::ImageUsageFlags) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn memory_usage(mut self, memory_usage: MemoryUsage) -> Self {
+        self.memory_usage = memory_usage;
+        self
+    }
+
+    /// Own device memory instead of a range of a shared block, worth it for large render targets
+    pub fn dedicated(mut self, dedicated: bool) -> Self {
+        self.dedicated = dedicated;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let extent = self.extent;
+        if extent.width == 0 || extent.height == 0 || extent.depth == 0 || self.array_layers == 0 {
+            return Err(Error::InvalidImageConfig("Image extent and layer count can't be zero"));
+        }
+        if self.mip_levels == 0 || self.mip_levels > full_mip_level_count(extent) {
+            return Err(Error::InvalidImageConfig("Mip level count exceeds the full mip chain"));
+        }
+        if self.kind == ImageKind::Cube && (extent.width != extent.height || !self.array_layers.is_multiple_of(6)) {
+            return Err(Error::InvalidImageConfig("Cube images have to be square with a multiple of six layers"));
+        }
+        if self.kind == ImageKind::D3 && self.array_layers != 1 {
+            return Err(Error::InvalidImageConfig("3D images can't have array layers"));
+        }
+        if self.samples != vk::SampleCountFlags::TYPE_1 && (self.kind == ImageKind::D3 || self.kind == ImageKind::Cube || self.mip_levels != 1)
+        {
+            return Err(Error::InvalidImageConfig("Multisampled images have to be 2D without mips"));
+        }
+        Ok(())
+    }
+
+    /// View type covering the whole image
+    pub fn view_type(&self) -> vk::ImageViewType {
+        match self.kind {
+            ImageKind::D2 if self.array_layers > 1 => vk::ImageViewType::TYPE_2D_ARRAY,
+            ImageKind::D2 => vk::ImageViewType::TYPE_2D,
+            ImageKind::D2Array => vk::ImageViewType::TYPE_2D_ARRAY,
+            ImageKind::Cube if self.array_layers > 6 => vk::ImageViewType::CUBE_ARRAY,
+            ImageKind::Cube => vk::ImageViewType::CUBE,
+            ImageKind::D3 => vk::ImageViewType::TYPE_3D,
+        }
+    }
+}
+
+/// `vk::Image` with memory from the context allocator. Tracks the layout of every mip level and layer so
+/// `transition` can record the barriers on its own, layouts changed outside of it (render pass final layouts)
+/// have to be reported with `set_layout`.
+pub struct Image {
+    device: Device,
+    image: vk::Image,
+    allocation: Option<Allocation>,
+    config: ImageConfig,
+    aspect_mask: vk::ImageAspectFlags,
+    /// Indexed by `mip_level * array_layers + layer`
+    layouts: Vec<vk::ImageLayout>,
+}
+
+impl Image {
+    pub fn new(context: &Context, config: ImageConfig) -> Result<Self, Error> {
+        config.validate()?;
+        let device = context.device();
+        let mut image = Self {
+            device: device.clone(),
+            image: vk::Image::null(),
+            allocation: None,
+            config,
+            aspect_mask: format_aspect_mask(config.format),
+            layouts: vec![vk::ImageLayout::UNDEFINED; (config.mip_levels * config.array_layers) as usize],
+        };
+
+        let image_create_info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::IMAGE_CREATE_INFO,
+            p_next: null(),
+            flags: if config.kind == ImageKind::Cube {
+                vk::ImageCreateFlags::CUBE_COMPATIBLE
+            } else {
+                vk::ImageCreateFlags::empty()
+            },
+            image_type: if config.kind == ImageKind::D3 {
+                vk::ImageType::TYPE_3D
+            } else {
+                vk::ImageType::TYPE_2D
+            },
+            format: config.format,
+            extent: config.extent,
+            mip_levels: config.mip_levels,
+            array_layers: config.array_layers,
+            samples: config.samples,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: config.usage,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: null(),
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            _marker: Default::default(),
+        };
+        image.image = unsafe { device.create_image(&image_create_info, None)? };
+        image.allocation = Some(
+            image
+                .device
+                .allocator()
+                .allocate_for_image(image.image, config.memory_usage, config.dedicated)?,
+        );
+        Ok(image)
+    }
+
+    pub fn handle(&self) -> vk::Image {
+        self.image
+    }
+
+    pub fn config(&self) -> &ImageConfig {
+        &self.config
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.config.format
+    }
+
+    pub fn extent(&self) -> vk::Extent3D {
+        self.config.extent
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.config.mip_levels
+    }
+
+    pub fn array_layers(&self) -> u32 {
+        self.config.array_layers
+    }
+
+    pub fn aspect_mask(&self) -> vk::ImageAspectFlags {
+        self.aspect_mask
+    }
+
+    pub fn allocation(&self) -> &Allocation {
+        self.allocation.as_ref().unwrap()
+    }
+
+    /// Every mip level and layer
+    pub fn full_range(&self) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask: self.aspect_mask,
+            base_mip_level: 0,
+            level_count: self.config.mip_levels,
+            base_array_layer: 0,
+            layer_count: self.config.array_layers,
+        }
+    }
+
+    pub fn layout(&self, mip_level: u32, layer: u32) -> vk::ImageLayout {
+        self.layouts[(mip_level * self.config.array_layers + layer) as usize]
+    }
+
+    fn resolve_range(&self, range: vk::ImageSubresourceRange) -> (std::ops::Range<u32>, std::ops::Range<u32>) {
+        let level_count = if range.level_count == vk::REMAINING_MIP_LEVELS {
+            self.config.mip_levels - range.base_mip_level
+        } else {
+            range.level_count
+        };
+        let layer_count = if range.layer_count == vk::REMAINING_ARRAY_LAYERS {
+            self.config.array_layers - range.base_array_layer
+        } else {
+            range.layer_count
+        };
+        (
+            range.base_mip_level..range.base_mip_level + level_count,
+            range.base_array_layer..range.base_array_layer + layer_count,
+        )
+    }
+
+    /// Updates the tracked layout after it was changed outside of `transition`
+    pub fn set_layout(&mut self, range: vk::ImageSubresourceRange, layout: vk::ImageLayout) {
+        let (levels, layers) = self.resolve_range(range);
+        for level in levels {
+            for layer in layers.clone() {
+                self.layouts[(level * self.config.array_layers + layer) as usize] = layout;
+            }
+        }
+    }
+
+    /// Records the barriers moving the range to `new_layout`. Subresources already in it still get an execution
+    /// and memory dependency, uploads and blits write to `TRANSFER_DST_OPTIMAL` several times in a row.
+    pub fn transition(&mut self, command_buffer: vk::CommandBuffer, range: vk::ImageSubresourceRange, new_layout: vk::ImageLayout) {
+        let (levels, layers) = self.resolve_range(range);
+        let (dst_access_mask, dst_stage) = layout_access(new_layout, false);
+        let mut barriers = Vec::new();
+        let mut src_stages = vk::PipelineStageFlags::empty();
+
+        // One barrier per run of layers sharing the old layout on each level
+        for level in levels {
+            let mut layer = layers.start;
+            while layer < layers.end {
+                let old_layout = self.layout(level, layer);
+                let run_start = layer;
+                while layer < layers.end && self.layout(level, layer) == old_layout {
+                    layer += 1;
+                }
+                let (src_access_mask, src_stage) = layout_access(old_layout, true);
+                src_stages |= src_stage;
+                barriers.push(vk::ImageMemoryBarrier {
+                    s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+                    p_next: null(),
+                    src_access_mask,
+                    dst_access_mask,
+                    old_layout,
+                    new_layout,
+                    src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                    dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                    image: self.image,
+                    subresource_range: vk::ImageSubresourceRange {
+                        aspect_mask: self.aspect_mask,
+                        base_mip_level: level,
+                        level_count: 1,
+                        base_array_layer: run_start,
+                        layer_count: layer - run_start,
+                    },
+                    _marker: Default::default(),
+                });
+            }
+        }
+        if barriers.is_empty() {
+            return;
+        }
+
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stages,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &barriers,
+            )
+        };
+        self.set_layout(range, new_layout);
+    }
+
+    /// View of the whole image with the view type matching its kind
+    pub fn create_view(&self) -> Result<ImageView, Error> {
+        let config = ImageViewConfig::new(self.config.view_type(), self.config.format, self.full_range());
+        ImageView::new(&self.device, self.image, &config)
+    }
+
+    pub fn create_view_with(&self, config: &ImageViewConfig) -> Result<ImageView, Error> {
+        ImageView::new(&self.device, self.image, config)
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_image(self.image, None) };
+        if let Some(allocation) = self.allocation.take() {
+            self.device.allocator().free(allocation);
+        }
+    }
+}
+
+/// Description of an `ImageView`, identity swizzle unless `components` is set
+#[derive(Clone, Copy, Debug)]
+pub struct ImageViewConfig {
+    pub view_type: vk::ImageViewType,
+    pub format: vk::Format,
+    pub components: vk::ComponentMapping,
+    pub subresource_range: vk::ImageSubresourceRange,
+}
+
+impl ImageViewConfig {
+    pub fn new(view_type: vk::ImageViewType, format: vk::Format, subresource_range: vk::ImageSubresourceRange) -> Self {
+        Self {
+            view_type,
+            format,
+            components: vk::ComponentMapping::default(),
+            subresource_range,
+        }
+    }
+
+    /// Single level and layer 2D color view, what swapchain images and simple render targets need
+    pub fn color_2d(format: vk::Format) -> Self {
+        Self::new(
+            vk::ImageViewType::TYPE_2D,
+            format,
+            vk::ImageSubresourceRange {
+                aspect_mask: format_aspect_mask(format),
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+        )
+    }
+
+    pub fn components(mut self, components: vk::ComponentMapping) -> Self {
+        self.components = components;
+        self
+    }
+
+    pub fn create_info(&self, image: vk::Image) -> vk::ImageViewCreateInfo<'static> {
+        vk::ImageViewCreateInfo {
+            s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+            p_next: null(),
+            flags: Default::default(),
+            image,
+            view_type: self.view_type,
+            format: self.format,
+            components: self.components,
+            subresource_range: self.subresource_range,
+            _marker: Default::default(),
+        }
+    }
+}
+
+/// Owned `vk::ImageView`, can view images it does not own like the swapchain images
+pub struct ImageView {
+    device: Device,
+    view: vk::ImageView,
+    config: ImageViewConfig,
+}
+
+impl ImageView {
+    /// The image has to outlive the view
+    pub fn new(device: &Device, image: vk::Image, config: &ImageViewConfig) -> Result<Self, Error> {
+        let view = unsafe { device.create_image_view(&config.create_info(image), None)? };
+        Ok(Self {
+            device: device.clone(),
+            view,
+            config: *config,
+        })
+    }
+
+    pub fn handle(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn view_type(&self) -> vk::ImageViewType {
+        self.config.view_type
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.config.format
+    }
+
+    pub fn subresource_range(&self) -> vk::ImageSubresourceRange {
+        self.config.subresource_range
+    }
+}
+
+impl Drop for ImageView {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_image_view(self.view, None) };
+    }
+}
+
+/// Description of a `Sampler`, defaults to trilinear filtering with repeat addressing
+#[derive(Clone, Copy, Debug)]
+pub struct SamplerConfig {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode: [vk::SamplerAddressMode; 3],
+    pub max_anisotropy: Option<f32>,
+    pub compare_op: Option<vk::CompareOp>,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub border_color: vk::BorderColor,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SamplerConfig {
+    pub fn new() -> Self {
+        Self {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode: [vk::SamplerAddressMode::REPEAT; 3],
+            max_anisotropy: None,
+            compare_op: None,
+            min_lod: 0.0,
+            max_lod: vk::LOD_CLAMP_NONE,
+            border_color: vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+        }
+    }
+
+    /// Nearest filtering on all axes, for pixel art and lookup tables
+    pub fn nearest() -> Self {
+        Self::new()
+            .filter(vk::Filter::NEAREST, vk::Filter::NEAREST)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+    }
+
+    pub fn filter(mut self, mag_filter: vk::Filter, min_filter: vk::Filter) -> Self {
+        self.mag_filter = mag_filter;
+        self.min_filter = min_filter;
+        self
+    }
+
+    pub fn mipmap_mode(mut self, mipmap_mode: vk::SamplerMipmapMode) -> Self {
+        self.mipmap_mode = mipmap_mode;
+        self
+    }
+
+    /// Same mode for u, v and w
+    pub fn address_mode(mut self, address_mode: vk::SamplerAddressMode) -> Self {
+        self.address_mode = [address_mode; 3];
+        self
+    }
+
+    /// Ignored with a warning if the device was created without the `sampler_anisotropy` feature,
+    /// clamped to the device limit otherwise
+    pub fn anisotropy(mut self, max_anisotropy: f32) -> Self {
+        self.max_anisotropy = Some(max_anisotropy);
+        self
+    }
+
+    /// Depth comparison for shadow map sampling
+    pub fn compare_op(mut self, compare_op: vk::CompareOp) -> Self {
+        self.compare_op = Some(compare_op);
+        self
+    }
+
+    pub fn lod_range(mut self, min_lod: f32, max_lod: f32) -> Self {
+        self.min_lod = min_lod;
+        self.max_lod = max_lod;
+        self
+    }
+
+    pub fn border_color(mut self, border_color: vk::BorderColor) -> Self {
+        self.border_color = border_color;
+        self
+    }
+}
+
+pub struct Sampler {
+    device: Device,
+    sampler: vk::Sampler,
+}
+
+impl Sampler {
+    pub fn new(context: &Context, config: &SamplerConfig) -> Result<Self, Error> {
+        let physical_device = context.physical_device();
+        let max_anisotropy = match config.max_anisotropy {
+            Some(_) if physical_device.features.sampler_anisotropy != vk::TRUE => {
+                warn!("Sampler anisotropy requested but the feature is not enabled, falling back to no anisotropy");
+                None
+            }
+            Some(anisotropy) => Some(anisotropy.clamp(1.0, physical_device.properties.limits.max_sampler_anisotropy)),
+            None => None,
+        };
+
+        let create_info = vk::SamplerCreateInfo {
+            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+            p_next: null(),
+            flags: vk::SamplerCreateFlags::empty(),
+            mag_filter: config.mag_filter,
+            min_filter: config.min_filter,
+            mipmap_mode: config.mipmap_mode,
+            address_mode_u: config.address_mode[0],
+            address_mode_v: config.address_mode[1],
+            address_mode_w: config.address_mode[2],
+            mip_lod_bias: 0.0,
+            anisotropy_enable: max_anisotropy.is_some() as vk::Bool32,
+            max_anisotropy: max_anisotropy.unwrap_or(1.0),
+            compare_enable: config.compare_op.is_some() as vk::Bool32,
+            compare_op: config.compare_op.unwrap_or(vk::CompareOp::ALWAYS),
+            min_lod: config.min_lod,
+            max_lod: config.max_lod,
+            border_color: config.border_color,
+            unnormalized_coordinates: vk::FALSE,
+            _marker: Default::default(),
+        };
+        let sampler = unsafe { context.device().create_sampler(&create_info, None)? };
+        Ok(Self {
+            device: context.device().clone(),
+            sampler,
+        })
+    }
+
+    pub fn handle(&self) -> vk::Sampler {
+        self.sampler
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_sampler(self.sampler, None) };
+    }
+}
@@ -0,0 +1,595 @@
+use crate::backend::vulkan::context::{Context, PhysicalDeviceInfo};
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use ash::vk;
+use log::warn;
+use std::ptr::{null, null_mut};
+use std::sync::Mutex;
+
+/// Index of the first memory type allowed by `type_bits` that has all of the `flags`
+pub fn find_memory_type(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    type_bits: u32,
+    flags: vk::MemoryPropertyFlags,
+) -> Option<u32> {
+    (0..memory_properties.memory_type_count).find(|index| {
+        let allowed = type_bits & (1 << index) != 0;
+        allowed && memory_properties.memory_types[*index as usize].property_flags.contains(flags)
+    })
+}
+
+/// Allocates a single block of the first memory type that fits `requirements` and has all of the `flags`
+pub fn allocate_memory(
+    context: &Context,
+    requirements: vk::MemoryRequirements,
+    flags: vk::MemoryPropertyFlags,
+) -> Result<vk::DeviceMemory, Error> {
+    let memory_type_index = find_memory_type(&context.physical_device().memory_properties, requirements.memory_type_bits, flags)
+        .ok_or(Error::NoSuitableMemoryType)?;
+    let allocate_info = vk::MemoryAllocateInfo {
+        s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
+        p_next: null(),
+        allocation_size: requirements.size,
+        memory_type_index,
+        _marker: Default::default(),
+    };
+    let memory = unsafe { context.device().allocate_memory(&allocate_info, None)? };
+    Ok(memory)
+}
+
+fn align_up(value: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    if alignment <= 1 {
+        return value;
+    }
+    value.div_ceil(alignment) * alignment
+}
+
+/// First fit free-list over a range of `size` bytes. Free ranges are kept sorted by offset and merged with
+/// their neighbours when freed, so fragmentation stays limited to what is actually allocated.
+#[derive(Debug)]
+pub struct FreeList {
+    size: vk::DeviceSize,
+    /// (offset, size) sorted by offset
+    free_ranges: Vec<(vk::DeviceSize, vk::DeviceSize)>,
+}
+
+impl FreeList {
+    pub fn new(size: vk::DeviceSize) -> Self {
+        Self {
+            size,
+            free_ranges: vec![(0, size)],
+        }
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    pub fn free_size(&self) -> vk::DeviceSize {
+        self.free_ranges.iter().map(|(_, size)| size).sum()
+    }
+
+    pub fn used_size(&self) -> vk::DeviceSize {
+        self.size - self.free_size()
+    }
+
+    pub fn largest_free_range(&self) -> vk::DeviceSize {
+        self.free_ranges.iter().map(|(_, size)| *size).max().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free_ranges.len() == 1 && self.free_ranges[0] == (0, self.size)
+    }
+
+    /// Offset of the allocated range or `None` if no free range fits. The bytes skipped for alignment stay free.
+    pub fn allocate(&mut self, size: vk::DeviceSize, alignment: vk::DeviceSize) -> Option<vk::DeviceSize> {
+        for index in 0..self.free_ranges.len() {
+            let (offset, range_size) = self.free_ranges[index];
+            let aligned = align_up(offset, alignment);
+            let padding = aligned - offset;
+            if padding + size > range_size {
+                continue;
+            }
+
+            let remaining = range_size - padding - size;
+            self.free_ranges.remove(index);
+            let mut insert_at = index;
+            if padding > 0 {
+                self.free_ranges.insert(insert_at, (offset, padding));
+                insert_at += 1;
+            }
+            if remaining > 0 {
+                self.free_ranges.insert(insert_at, (aligned + size, remaining));
+            }
+            return Some(aligned);
+        }
+        None
+    }
+
+    pub fn free(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        let index = self.free_ranges.partition_point(|(free_offset, _)| *free_offset < offset);
+        debug_assert!(index == 0 || self.free_ranges[index - 1].0 + self.free_ranges[index - 1].1 <= offset, "Double free");
+        self.free_ranges.insert(index, (offset, size));
+
+        // Merge with the next range, then with the previous one
+        if index + 1 < self.free_ranges.len() && offset + size == self.free_ranges[index + 1].0 {
+            self.free_ranges[index].1 += self.free_ranges[index + 1].1;
+            self.free_ranges.remove(index + 1);
+        }
+        if index > 0 {
+            let (previous_offset, previous_size) = self.free_ranges[index - 1];
+            if previous_offset + previous_size == offset {
+                self.free_ranges[index - 1].1 += self.free_ranges[index].1;
+                self.free_ranges.remove(index);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryUsage {
+    /// Only accessed by the GPU: render targets, textures, static vertex data
+    GpuOnly,
+    /// Written by the CPU, read by the GPU: staging and per frame data. Device local if the device has such host visible memory.
+    CpuToGpu,
+    /// Written by the GPU, read back by the CPU. Cached memory is preferred for fast reads.
+    GpuToCpu,
+}
+
+impl MemoryUsage {
+    /// Host visible usages prefer `HOST_COHERENT` memory over everything else but don't require it, non-coherent
+    /// memory is flushed and invalidated by `Buffer`
+    pub fn required_flags(self) -> vk::MemoryPropertyFlags {
+        match self {
+            MemoryUsage::GpuOnly => vk::MemoryPropertyFlags::empty(),
+            MemoryUsage::CpuToGpu | MemoryUsage::GpuToCpu => vk::MemoryPropertyFlags::HOST_VISIBLE,
+        }
+    }
+
+    pub fn preferred_flags(self) -> vk::MemoryPropertyFlags {
+        match self {
+            MemoryUsage::GpuOnly | MemoryUsage::CpuToGpu => vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryUsage::GpuToCpu => vk::MemoryPropertyFlags::HOST_CACHED,
+        }
+    }
+
+    pub fn is_host_visible(self) -> bool {
+        self != MemoryUsage::GpuOnly
+    }
+}
+
+/// Memory types allowed by `type_bits` that satisfy the usage. Coherent ones come first for host visible usages,
+/// then the ones with the preferred flags.
+pub fn memory_types_for_usage(memory_properties: &vk::PhysicalDeviceMemoryProperties, type_bits: u32, usage: MemoryUsage) -> Vec<u32> {
+    let required = usage.required_flags();
+    let coherent = if usage.is_host_visible() {
+        vk::MemoryPropertyFlags::HOST_COHERENT
+    } else {
+        vk::MemoryPropertyFlags::empty()
+    };
+    let mut candidates: Vec<u32> = (0..memory_properties.memory_type_count)
+        .filter(|index| type_bits & (1 << index) != 0 && memory_properties.memory_types[*index as usize].property_flags.contains(required))
+        .collect();
+    // Stable, types of the same rank stay in index order
+    candidates.sort_by_key(|index| {
+        let flags = memory_properties.memory_types[*index as usize].property_flags;
+        (!flags.contains(coherent), !flags.contains(usage.preferred_flags()))
+    });
+    candidates
+}
+
+pub const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug)]
+pub struct AllocationRequest {
+    pub requirements: vk::MemoryRequirements,
+    pub usage: MemoryUsage,
+    /// Buffers and linear images. Kept in separate blocks from optimal images so `bufferImageGranularity` never applies.
+    pub linear: bool,
+    /// Gives the resource its own `vk::DeviceMemory`. Large resources get one anyway.
+    pub dedicated: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AllocationKind {
+    Block(usize),
+    Dedicated,
+}
+
+/// Memory range handed out by the `Allocator`, has to be returned with `Allocator::free`
+#[derive(Debug)]
+pub struct Allocation {
+    memory: vk::DeviceMemory,
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    memory_type_index: u32,
+    mapped: *mut u8,
+    kind: AllocationKind,
+}
+
+// The mapped pointer refers to persistently mapped device memory, not to thread local data
+unsafe impl Send for Allocation {}
+unsafe impl Sync for Allocation {}
+
+impl Allocation {
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    pub fn offset(&self) -> vk::DeviceSize {
+        self.offset
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    pub fn memory_type_index(&self) -> u32 {
+        self.memory_type_index
+    }
+
+    pub fn is_dedicated(&self) -> bool {
+        self.kind == AllocationKind::Dedicated
+    }
+
+    /// Start of the allocation in persistently mapped memory, `None` if the memory is not host visible
+    pub fn mapped_ptr(&self) -> Option<*mut u8> {
+        if self.mapped.is_null() {
+            None
+        } else {
+            Some(self.mapped)
+        }
+    }
+}
+
+struct Block {
+    memory: vk::DeviceMemory,
+    mapped: *mut u8,
+    linear: bool,
+    free_list: FreeList,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// Device memory objects backing sub-allocations
+    pub block_count: usize,
+    pub dedicated_allocation_count: usize,
+    /// Live allocations, including dedicated ones
+    pub allocation_count: usize,
+    /// Bytes of device memory allocated from the driver
+    pub reserved_bytes: vk::DeviceSize,
+    /// Bytes handed out to allocations
+    pub used_bytes: vk::DeviceSize,
+}
+
+struct AllocatorState {
+    /// Blocks per memory type, freed blocks leave a `None` so block indices stay valid
+    blocks: Vec<Vec<Option<Block>>>,
+    allocation_count: usize,
+    dedicated_allocation_count: usize,
+    dedicated_bytes: vk::DeviceSize,
+}
+
+// Only the mapped pointers are not Send, they point to device memory owned by the allocator
+unsafe impl Send for AllocatorState {}
+
+/// Sub-allocates buffers and images from large `vk::DeviceMemory` blocks, one list of blocks per memory type.
+/// Host visible blocks are mapped persistently. Resources larger than half a block, or requested as dedicated,
+/// get their own memory. All allocations have to be freed before the allocator is dropped.
+pub struct Allocator {
+    device: ash::Device,
+    /// Keeps the device alive for allocators created next to the context one, `None` for the context allocator
+    /// which the device owns itself
+    _owner: Option<Device>,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    /// Granularity of flushes and invalidations, allocations in non-coherent memory are aligned to it
+    non_coherent_atom_size: vk::DeviceSize,
+    block_size: vk::DeviceSize,
+    state: Mutex<AllocatorState>,
+}
+
+impl Allocator {
+    pub fn new(context: &Context) -> Self {
+        Self::with_block_size(context, DEFAULT_BLOCK_SIZE)
+    }
+
+    pub fn with_block_size(context: &Context, block_size: vk::DeviceSize) -> Self {
+        let physical_device = context.physical_device();
+        let mut allocator = Self::from_device(context.device(), physical_device, block_size);
+        allocator._owner = Some(context.device().clone());
+        allocator
+    }
+
+    /// Used by `Context` itself, which creates its allocator before it exists
+    pub(crate) fn from_device(device: &ash::Device, physical_device: &PhysicalDeviceInfo, block_size: vk::DeviceSize) -> Self {
+        let memory_properties = physical_device.memory_properties;
+        Self {
+            device: device.clone(),
+            _owner: None,
+            memory_properties,
+            non_coherent_atom_size: physical_device.properties.limits.non_coherent_atom_size.max(1),
+            block_size,
+            state: Mutex::new(AllocatorState {
+                blocks: (0..memory_properties.memory_type_count).map(|_| Vec::new()).collect(),
+                allocation_count: 0,
+                dedicated_allocation_count: 0,
+                dedicated_bytes: 0,
+            }),
+        }
+    }
+
+    pub fn memory_properties(&self) -> &vk::PhysicalDeviceMemoryProperties {
+        &self.memory_properties
+    }
+
+    pub fn memory_type_flags(&self, memory_type_index: u32) -> vk::MemoryPropertyFlags {
+        self.memory_properties.memory_types[memory_type_index as usize].property_flags
+    }
+
+    /// Small heaps (e.g. 256 MiB of host visible VRAM) get smaller blocks so a single block can't exhaust them
+    fn block_size_for(&self, memory_type_index: u32) -> vk::DeviceSize {
+        let heap_index = self.memory_properties.memory_types[memory_type_index as usize].heap_index;
+        let heap_size = self.memory_properties.memory_heaps[heap_index as usize].size;
+        self.block_size.min(heap_size / 8).max(1)
+    }
+
+    pub fn allocate(&self, request: &AllocationRequest) -> Result<Allocation, Error> {
+        let memory_types = memory_types_for_usage(&self.memory_properties, request.requirements.memory_type_bits, request.usage);
+        if memory_types.is_empty() {
+            return Err(Error::NoSuitableMemoryType);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let mut last_error = Error::NoSuitableMemoryType;
+        // Out of memory in the preferred type falls through to the next one
+        for memory_type_index in memory_types {
+            let request = &self.align_to_atoms(request, memory_type_index);
+            let dedicated = request.dedicated || request.requirements.size > self.block_size_for(memory_type_index) / 2;
+            let result = if dedicated {
+                self.allocate_dedicated(&mut state, request, memory_type_index)
+            } else {
+                self.allocate_from_blocks(&mut state, request, memory_type_index)
+            };
+            match result {
+                Ok(allocation) => {
+                    state.allocation_count += 1;
+                    return Ok(allocation);
+                }
+                Err(Error::Vulkan(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)) | Err(Error::Vulkan(vk::Result::ERROR_OUT_OF_HOST_MEMORY)) => {
+                    last_error = Error::Vulkan(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Non-coherent allocations cover whole atoms, so flushing or invalidating one never touches its neighbors
+    fn align_to_atoms(&self, request: &AllocationRequest, memory_type_index: u32) -> AllocationRequest {
+        let flags = self.memory_type_flags(memory_type_index);
+        let mut requirements = request.requirements;
+        if flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) && !flags.contains(vk::MemoryPropertyFlags::HOST_COHERENT) {
+            let atom = self.non_coherent_atom_size;
+            requirements.alignment = requirements.alignment.max(atom);
+            requirements.size = requirements.size.div_ceil(atom) * atom;
+        }
+        AllocationRequest { requirements, ..*request }
+    }
+
+    fn allocate_device_memory(&self, size: vk::DeviceSize, memory_type_index: u32) -> Result<(vk::DeviceMemory, *mut u8), Error> {
+        let allocate_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
+            p_next: null(),
+            allocation_size: size,
+            memory_type_index,
+            _marker: Default::default(),
+        };
+        let memory = unsafe { self.device.allocate_memory(&allocate_info, None)? };
+        if !self.memory_type_flags(memory_type_index).contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            return Ok((memory, null_mut()));
+        }
+        match unsafe { self.device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) } {
+            Ok(mapped) => Ok((memory, mapped as *mut u8)),
+            Err(result) => {
+                unsafe { self.device.free_memory(memory, None) };
+                Err(result.into())
+            }
+        }
+    }
+
+    fn allocate_dedicated(&self, state: &mut AllocatorState, request: &AllocationRequest, memory_type_index: u32) -> Result<Allocation, Error> {
+        let size = request.requirements.size;
+        let (memory, mapped) = self.allocate_device_memory(size, memory_type_index)?;
+        state.dedicated_allocation_count += 1;
+        state.dedicated_bytes += size;
+        Ok(Allocation {
+            memory,
+            offset: 0,
+            size,
+            memory_type_index,
+            mapped,
+            kind: AllocationKind::Dedicated,
+        })
+    }
+
+    fn allocate_from_blocks(&self, state: &mut AllocatorState, request: &AllocationRequest, memory_type_index: u32) -> Result<Allocation, Error> {
+        let size = request.requirements.size;
+        let alignment = request.requirements.alignment;
+        let blocks = &mut state.blocks[memory_type_index as usize];
+
+        let existing = blocks.iter_mut().enumerate().find_map(|(index, block)| {
+            let block = block.as_mut().filter(|block| block.linear == request.linear)?;
+            block.free_list.allocate(size, alignment).map(|offset| (index, offset))
+        });
+        let (block_index, offset) = match existing {
+            Some(found) => found,
+            None => {
+                let block_size = self.block_size_for(memory_type_index);
+                let (memory, mapped) = self.allocate_device_memory(block_size, memory_type_index)?;
+                let mut free_list = FreeList::new(block_size);
+                let offset = free_list.allocate(size, alignment).expect("Allocation larger than its block");
+                let block = Block {
+                    memory,
+                    mapped,
+                    linear: request.linear,
+                    free_list,
+                };
+                let index = match blocks.iter().position(|block| block.is_none()) {
+                    Some(index) => {
+                        blocks[index] = Some(block);
+                        index
+                    }
+                    None => {
+                        blocks.push(Some(block));
+                        blocks.len() - 1
+                    }
+                };
+                (index, offset)
+            }
+        };
+
+        let block = blocks[block_index].as_ref().unwrap();
+        Ok(Allocation {
+            memory: block.memory,
+            offset,
+            size,
+            memory_type_index,
+            mapped: if block.mapped.is_null() {
+                null_mut()
+            } else {
+                unsafe { block.mapped.add(offset as usize) }
+            },
+            kind: AllocationKind::Block(block_index),
+        })
+    }
+
+    /// Returns the memory, the resource using it has to be destroyed already
+    pub fn free(&self, allocation: Allocation) {
+        let mut state = self.state.lock().unwrap();
+        state.allocation_count -= 1;
+        match allocation.kind {
+            AllocationKind::Dedicated => {
+                state.dedicated_allocation_count -= 1;
+                state.dedicated_bytes -= allocation.size;
+                unsafe { self.device.free_memory(allocation.memory, None) };
+            }
+            AllocationKind::Block(block_index) => {
+                let blocks = &mut state.blocks[allocation.memory_type_index as usize];
+                let block = blocks[block_index].as_mut().expect("Allocation from a freed block");
+                block.free_list.free(allocation.offset, allocation.size);
+                let empty = block.free_list.is_empty();
+
+                // Keep one empty block per memory type around to avoid reallocating on every create/destroy cycle
+                let live_blocks = blocks.iter().filter(|block| block.is_some()).count();
+                if empty && live_blocks > 1 {
+                    let block = blocks[block_index].take().unwrap();
+                    unsafe { self.device.free_memory(block.memory, None) };
+                }
+            }
+        }
+    }
+
+    pub fn is_coherent(&self, allocation: &Allocation) -> bool {
+        self.memory_type_flags(allocation.memory_type_index)
+            .contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+    }
+
+    /// `size` bytes at `offset` into the allocation, widened to whole atoms
+    fn atom_range(&self, allocation: &Allocation, offset: vk::DeviceSize, size: vk::DeviceSize) -> vk::MappedMemoryRange<'static> {
+        let atom = self.non_coherent_atom_size;
+        let memory_size = match allocation.kind {
+            AllocationKind::Dedicated => allocation.size,
+            AllocationKind::Block(_) => self.block_size_for(allocation.memory_type_index),
+        };
+        let start = (allocation.offset + offset) / atom * atom;
+        let end = (allocation.offset + offset + size).div_ceil(atom) * atom;
+        vk::MappedMemoryRange {
+            s_type: vk::StructureType::MAPPED_MEMORY_RANGE,
+            p_next: null(),
+            memory: allocation.memory,
+            offset: start,
+            // The memory may end inside an atom
+            size: if end >= memory_size { vk::WHOLE_SIZE } else { end - start },
+            _marker: Default::default(),
+        }
+    }
+
+    /// Makes CPU writes to the range visible to the device, nothing to do for coherent memory
+    pub fn flush(&self, allocation: &Allocation, offset: vk::DeviceSize, size: vk::DeviceSize) -> Result<(), Error> {
+        if self.is_coherent(allocation) || size == 0 {
+            return Ok(());
+        }
+        unsafe { self.device.flush_mapped_memory_ranges(&[self.atom_range(allocation, offset, size)])? };
+        Ok(())
+    }
+
+    /// Makes device writes to the range visible to the CPU, nothing to do for coherent memory
+    pub fn invalidate(&self, allocation: &Allocation, offset: vk::DeviceSize, size: vk::DeviceSize) -> Result<(), Error> {
+        if self.is_coherent(allocation) || size == 0 {
+            return Ok(());
+        }
+        unsafe { self.device.invalidate_mapped_memory_ranges(&[self.atom_range(allocation, offset, size)])? };
+        Ok(())
+    }
+
+    /// Allocates memory for the buffer and binds it
+    pub fn allocate_for_buffer(&self, buffer: vk::Buffer, usage: MemoryUsage, dedicated: bool) -> Result<Allocation, Error> {
+        let requirements = unsafe { self.device.get_buffer_memory_requirements(buffer) };
+        let allocation = self.allocate(&AllocationRequest {
+            requirements,
+            usage,
+            linear: true,
+            dedicated,
+        })?;
+        if let Err(result) = unsafe { self.device.bind_buffer_memory(buffer, allocation.memory, allocation.offset) } {
+            self.free(allocation);
+            return Err(result.into());
+        }
+        Ok(allocation)
+    }
+
+    /// Allocates memory for an optimally tiled image and binds it
+    pub fn allocate_for_image(&self, image: vk::Image, usage: MemoryUsage, dedicated: bool) -> Result<Allocation, Error> {
+        let requirements = unsafe { self.device.get_image_memory_requirements(image) };
+        let allocation = self.allocate(&AllocationRequest {
+            requirements,
+            usage,
+            linear: false,
+            dedicated,
+        })?;
+        if let Err(result) = unsafe { self.device.bind_image_memory(image, allocation.memory, allocation.offset) } {
+            self.free(allocation);
+            return Err(result.into());
+        }
+        Ok(allocation)
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let state = self.state.lock().unwrap();
+        let mut stats = AllocatorStats {
+            block_count: 0,
+            dedicated_allocation_count: state.dedicated_allocation_count,
+            allocation_count: state.allocation_count,
+            reserved_bytes: state.dedicated_bytes,
+            used_bytes: state.dedicated_bytes,
+        };
+        for block in state.blocks.iter().flatten().flatten() {
+            stats.block_count += 1;
+            stats.reserved_bytes += block.free_list.size();
+            stats.used_bytes += block.free_list.used_size();
+        }
+        stats
+    }
+}
+
+impl Drop for Allocator {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap();
+        if state.allocation_count > 0 {
+            warn!("Allocator dropped with {} live allocations", state.allocation_count);
+        }
+        for block in state.blocks.iter().flatten().flatten() {
+            unsafe { self.device.free_memory(block.memory, None) };
+        }
+    }
+}
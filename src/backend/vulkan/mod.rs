@@ -1,7 +1,30 @@
 pub mod base;
+pub mod bindless;
+pub mod buffer;
 pub mod context;
+pub mod depth;
+pub mod descriptors;
+pub mod device;
 pub mod errors;
+pub mod frames;
+pub mod glslc;
+pub mod hot_reload;
+pub mod image;
+pub mod memory;
+pub mod offscreen;
+pub mod permutations;
+pub mod pipeline;
 pub mod queue;
+pub mod reflection;
 pub mod render_context;
-mod surface;
+pub mod renderer;
+pub mod rendering;
+pub mod shaders;
+pub mod surface;
+pub mod swapchain;
+pub mod texture;
+pub mod upload;
 pub mod utils;
+
+/// The Vulkan bindings the backend is built against, `impl_vertex!` refers to them through this path
+pub use ash;
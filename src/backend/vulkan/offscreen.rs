@@ -0,0 +1,433 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::memory::allocate_memory;
+use crate::backend::vulkan::queue::op_indices::GRAPHICS;
+use crate::image::RgbaImage;
+use crate::utils::{create_color_render_pass, create_pipeline_with_render_pass, PipelineInfo};
+use ash::vk;
+use std::ptr::null;
+
+pub const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+/// Color image with its memory, view and framebuffer plus a host visible buffer the image is copied into for readback.
+pub struct OffscreenTarget {
+    device: Device,
+    extent: vk::Extent2D,
+    format: vk::Format,
+    image: vk::Image,
+    image_memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    framebuffer: vk::Framebuffer,
+    readback_buffer: vk::Buffer,
+    readback_memory: vk::DeviceMemory,
+}
+
+impl OffscreenTarget {
+    /// `format` has to be one of the 8 bit RGBA or BGRA formats so the pixels can be read back as RGBA8. With a null
+    /// `render_pass` there is no framebuffer and the target is rendered to with dynamic rendering.
+    pub fn new(context: &Context, render_pass: vk::RenderPass, extent: vk::Extent2D, format: vk::Format) -> Result<Self, Error> {
+        if !Self::is_readback_format(format) {
+            return Err(Error::UnsupportedReadbackFormat(format));
+        }
+
+        // Handles stay null until created, Drop skips null handles if creation fails midway
+        let mut target = Self {
+            device: context.device().clone(),
+            extent,
+            format,
+            image: vk::Image::null(),
+            image_memory: vk::DeviceMemory::null(),
+            view: vk::ImageView::null(),
+            framebuffer: vk::Framebuffer::null(),
+            readback_buffer: vk::Buffer::null(),
+            readback_memory: vk::DeviceMemory::null(),
+        };
+        let device = context.device();
+
+        let image_create_info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::IMAGE_CREATE_INFO,
+            p_next: null(),
+            flags: Default::default(),
+            image_type: vk::ImageType::TYPE_2D,
+            format,
+            extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: null(),
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            _marker: Default::default(),
+        };
+        target.image = unsafe { device.create_image(&image_create_info, None)? };
+        let image_requirements = unsafe { device.get_image_memory_requirements(target.image) };
+        target.image_memory = allocate_memory(context, image_requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
+        unsafe { device.bind_image_memory(target.image, target.image_memory, 0)? };
+
+        let view_create_info = vk::ImageViewCreateInfo {
+            s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+            p_next: null(),
+            flags: Default::default(),
+            image: target.image,
+            view_type: vk::ImageViewType::TYPE_2D,
+            format,
+            components: vk::ComponentMapping::default(),
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            _marker: Default::default(),
+        };
+        target.view = unsafe { device.create_image_view(&view_create_info, None)? };
+
+        let framebuffer_create_info = vk::FramebufferCreateInfo {
+            s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
+            p_next: null(),
+            flags: Default::default(),
+            render_pass,
+            attachment_count: 1,
+            p_attachments: &target.view as *const vk::ImageView,
+            width: extent.width,
+            height: extent.height,
+            layers: 1,
+            _marker: Default::default(),
+        };
+        if render_pass != vk::RenderPass::null() {
+            target.framebuffer = unsafe { device.create_framebuffer(&framebuffer_create_info, None)? };
+        }
+
+        let buffer_create_info = vk::BufferCreateInfo {
+            s_type: vk::StructureType::BUFFER_CREATE_INFO,
+            p_next: null(),
+            flags: Default::default(),
+            size: target.readback_size(),
+            usage: vk::BufferUsageFlags::TRANSFER_DST,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: null(),
+            _marker: Default::default(),
+        };
+        target.readback_buffer = unsafe { device.create_buffer(&buffer_create_info, None)? };
+        let buffer_requirements = unsafe { device.get_buffer_memory_requirements(target.readback_buffer) };
+        target.readback_memory = allocate_memory(
+            context,
+            buffer_requirements,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        unsafe { device.bind_buffer_memory(target.readback_buffer, target.readback_memory, 0)? };
+        Ok(target)
+    }
+
+    pub fn is_readback_format(format: vk::Format) -> bool {
+        matches!(
+            format,
+            vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB | vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB
+        )
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn framebuffer(&self) -> vk::Framebuffer {
+        self.framebuffer
+    }
+
+    fn readback_size(&self) -> vk::DeviceSize {
+        self.extent.width as vk::DeviceSize * self.extent.height as vk::DeviceSize * 4
+    }
+
+    /// Records the copy of the image into the readback buffer. The image has to be in `TRANSFER_SRC_OPTIMAL`,
+    /// which is the final layout of render passes created with `create_color_render_pass` for offscreen use.
+    pub fn record_readback(&self, command_buffer: vk::CommandBuffer) {
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            },
+        };
+
+        let host_barrier = vk::BufferMemoryBarrier {
+            s_type: vk::StructureType::BUFFER_MEMORY_BARRIER,
+            p_next: null(),
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::HOST_READ,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer: self.readback_buffer,
+            offset: 0,
+            size: vk::WHOLE_SIZE,
+            _marker: Default::default(),
+        };
+
+        unsafe {
+            self.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                self.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.readback_buffer,
+                &[region],
+            );
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[host_barrier],
+                &[],
+            );
+        }
+    }
+
+    /// Reads the readback buffer, the work recorded by `record_readback` has to be finished
+    pub fn read_pixels(&self) -> Result<RgbaImage, Error> {
+        let size = self.readback_size() as usize;
+        let mut pixels = vec![0u8; size];
+        unsafe {
+            let mapped = self
+                .device
+                .map_memory(self.readback_memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())?;
+            std::ptr::copy_nonoverlapping(mapped as *const u8, pixels.as_mut_ptr(), size);
+            self.device.unmap_memory(self.readback_memory);
+        }
+
+        if matches!(self.format, vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        Ok(RgbaImage::from_pixels(self.extent.width, self.extent.height, pixels))
+    }
+}
+
+impl Drop for OffscreenTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_buffer(self.readback_buffer, None);
+            self.device.free_memory(self.readback_memory, None);
+            self.device.destroy_framebuffer(self.framebuffer, None);
+            self.device.destroy_image_view(self.view, None);
+            self.device.destroy_image(self.image, None);
+            self.device.free_memory(self.image_memory, None);
+        }
+    }
+}
+
+/// Renders into an `OffscreenTarget` with the triangle pipeline from `create_pipeline_with_render_pass`
+/// (or a custom one, see `with_pipeline`) and reads the result back, no window or swapchain needed.
+pub struct OffscreenRenderer {
+    device: Device,
+    queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    pipeline_info: PipelineInfo,
+    target: OffscreenTarget,
+}
+
+impl OffscreenRenderer {
+    pub fn new(context: &Context, extent: vk::Extent2D) -> Result<Self, Error> {
+        Self::with_pipeline(context, extent, create_pipeline_with_render_pass)
+    }
+
+    /// `create_pipeline` builds the pipeline for the offscreen render pass and takes ownership of it, its errors are
+    /// returned with the render pass destroyed
+    pub fn with_pipeline(
+        context: &Context,
+        extent: vk::Extent2D,
+        create_pipeline: fn(&Device, vk::RenderPass) -> Result<PipelineInfo, Error>,
+    ) -> Result<Self, Error> {
+        let device = context.device();
+        let queue = context.graphics_queue()?;
+        let queue_family_index = context.queue_family_index(GRAPHICS).ok_or(Error::MissingQueue(GRAPHICS))?;
+        let render_pass = create_color_render_pass(device, OFFSCREEN_FORMAT, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+        let pipeline_info = create_pipeline(device, render_pass).inspect_err(|_| unsafe {
+            device.destroy_render_pass(render_pass, None);
+        })?;
+        let target = match OffscreenTarget::new(context, render_pass, extent, OFFSCREEN_FORMAT) {
+            Ok(target) => target,
+            Err(error) => {
+                unsafe { pipeline_info.destroy(device) };
+                return Err(error);
+            }
+        };
+
+        let mut renderer = Self {
+            device: device.clone(),
+            queue,
+            command_pool: vk::CommandPool::null(),
+            command_buffer: vk::CommandBuffer::null(),
+            fence: vk::Fence::null(),
+            pipeline_info,
+            target,
+        };
+
+        let command_pool_create_info = vk::CommandPoolCreateInfo {
+            s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+            p_next: null(),
+            flags: vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+            queue_family_index,
+            _marker: Default::default(),
+        };
+        renderer.command_pool = unsafe { device.create_command_pool(&command_pool_create_info, None)? };
+
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            p_next: null(),
+            command_pool: renderer.command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+            command_buffer_count: 1,
+            _marker: Default::default(),
+        };
+        renderer.command_buffer = unsafe { device.allocate_command_buffers(&command_buffer_allocate_info)?[0] };
+
+        let fence_create_info = vk::FenceCreateInfo {
+            s_type: vk::StructureType::FENCE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::FenceCreateFlags::empty(),
+            _marker: Default::default(),
+        };
+        renderer.fence = unsafe { device.create_fence(&fence_create_info, None)? };
+        Ok(renderer)
+    }
+
+    pub fn target(&self) -> &OffscreenTarget {
+        &self.target
+    }
+
+    pub fn pipeline_info(&self) -> &PipelineInfo {
+        &self.pipeline_info
+    }
+
+    /// Draws the built-in triangle from `shaders/vshader.vert`
+    pub fn render(&mut self, clear_color: [f32; 4]) -> Result<RgbaImage, Error> {
+        let pipeline = self.pipeline_info.pipeline[0];
+        self.render_with(clear_color, |device, command_buffer| unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        })
+    }
+
+    /// Records the render pass with viewport and scissor covering the target, `record` adds the draw calls.
+    /// Blocks until the frame is rendered and read back.
+    pub fn render_with<F>(&mut self, clear_color: [f32; 4], record: F) -> Result<RgbaImage, Error>
+    where
+        F: FnOnce(&ash::Device, vk::CommandBuffer),
+    {
+        let device = &self.device;
+        let command_buffer = self.command_buffer;
+        let extent = self.target.extent();
+
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next: null(),
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            p_inheritance_info: null(),
+            _marker: Default::default(),
+        };
+        let clear_values = [vk::ClearValue {
+            color: vk::ClearColorValue { float32: clear_color },
+        }];
+        let render_pass_begin_info = vk::RenderPassBeginInfo {
+            s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
+            p_next: null(),
+            render_pass: self.pipeline_info.render_pass,
+            framebuffer: self.target.framebuffer(),
+            render_area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            },
+            clear_value_count: clear_values.len() as u32,
+            p_clear_values: clear_values.as_ptr(),
+            _marker: Default::default(),
+        };
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+
+        unsafe {
+            device.reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+            device.begin_command_buffer(command_buffer, &begin_info)?;
+            device.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, vk::SubpassContents::INLINE);
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+        }
+        record(device, command_buffer);
+        unsafe {
+            device.cmd_end_render_pass(command_buffer);
+        }
+        self.target.record_readback(command_buffer);
+        unsafe {
+            device.end_command_buffer(command_buffer)?;
+        }
+
+        let submit_info = vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            command_buffer_count: 1,
+            p_command_buffers: &command_buffer,
+            ..Default::default()
+        };
+        unsafe {
+            device.reset_fences(&[self.fence])?;
+            device.queue_submit(self.queue, &[submit_info], self.fence)?;
+            device.wait_for_fences(&[self.fence], true, u64::MAX)?;
+        }
+        self.target.read_pixels()
+    }
+}
+
+impl Drop for OffscreenRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            // The last render may still be running. A lost device fails the wait, the resources still have to go.
+            let _ = self.device.device_wait_idle();
+            self.device.destroy_fence(self.fence, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.pipeline_info.destroy(&self.device);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::glslc::permutation_name;
+use crate::backend::vulkan::pipeline::{GraphicsPipeline, GraphicsPipelineBuilder, ShaderStage, SpecializationConstants};
+use crate::backend::vulkan::shaders::{shader_name, ShaderLibrary};
+use ash::vk;
+use log::trace;
+use std::collections::HashMap;
+
+/// Identifies a pipeline variant: defines select shader variants compiled by build.rs (see `@permutation` in
+/// `glslc`), specialization constants are applied when the pipeline is created
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PermutationKey {
+    /// Sorted without duplicates, so the order they are added in doesn't matter
+    defines: Vec<String>,
+    specialization: SpecializationConstants,
+}
+
+impl PermutationKey {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `NAME` or `NAME=VALUE`, as written in the `@permutation` directive
+    pub fn define(mut self, define: &str) -> Self {
+        if let Err(index) = self.defines.binary_search_by(|existing| existing.as_str().cmp(define)) {
+            self.defines.insert(index, define.to_string());
+        }
+        self
+    }
+
+    /// Adds the define if `enabled`, for material features that are toggled
+    pub fn define_if(self, define: &str, enabled: bool) -> Self {
+        if enabled {
+            self.define(define)
+        } else {
+            self
+        }
+    }
+
+    pub fn specialization(mut self, constants: SpecializationConstants) -> Self {
+        self.specialization = constants;
+        self
+    }
+
+    pub fn bool(mut self, id: u32, value: bool) -> Self {
+        self.specialization = self.specialization.bool(id, value);
+        self
+    }
+
+    pub fn u32(mut self, id: u32, value: u32) -> Self {
+        self.specialization = self.specialization.u32(id, value);
+        self
+    }
+
+    pub fn i32(mut self, id: u32, value: i32) -> Self {
+        self.specialization = self.specialization.i32(id, value);
+        self
+    }
+
+    pub fn f32(mut self, id: u32, value: f32) -> Self {
+        self.specialization = self.specialization.f32(id, value);
+        self
+    }
+
+    pub fn defines(&self) -> &[String] {
+        &self.defines
+    }
+
+    pub fn specialization_constants(&self) -> &SpecializationConstants {
+        &self.specialization
+    }
+}
+
+/// Variants of one pipeline, each built on first use and cached by its key.
+///
+/// Every stage uses the library variant `ShaderLibrary::variant` picks for the key's defines, the key's
+/// specialization constants are applied to all stages. Constants no stage declares, or with a size that differs from
+/// the declaration, are an error instead of being ignored silently. Pipelines are destroyed on drop or `clear`, the
+/// GPU must be done with them by then.
+pub struct PipelinePermutations {
+    device: Device,
+    /// Shared by all variants, without the library shaders
+    builder: GraphicsPipelineBuilder,
+    shaders: Vec<(String, vk::ShaderStageFlags)>,
+    variants: HashMap<PermutationKey, GraphicsPipeline>,
+}
+
+impl PipelinePermutations {
+    /// `shaders` are the library names and stages of the shaders, the builder must not have stages of its own
+    pub fn new(context: &Context, builder: GraphicsPipelineBuilder, shaders: &[(&str, vk::ShaderStageFlags)]) -> Self {
+        Self {
+            device: context.device().clone(),
+            builder,
+            shaders: shaders.iter().map(|(name, stage)| (shader_name(name), *stage)).collect(),
+            variants: HashMap::new(),
+        }
+    }
+
+    /// Pipeline of the variant, built if the key wasn't seen before
+    pub fn get(&mut self, library: &ShaderLibrary, key: &PermutationKey) -> Result<&GraphicsPipeline, Error> {
+        if !self.variants.contains_key(key) {
+            let pipeline = self.build(library, key)?;
+            self.variants.insert(key.clone(), pipeline);
+        }
+        Ok(&self.variants[key])
+    }
+
+    pub fn contains(&self, key: &PermutationKey) -> bool {
+        self.variants.contains_key(key)
+    }
+
+    /// Number of variants built so far
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    /// Destroys every variant, e.g. after the library shaders were replaced
+    pub fn clear(&mut self) {
+        self.variants.clear();
+    }
+
+    fn build(&self, library: &ShaderLibrary, key: &PermutationKey) -> Result<GraphicsPipeline, Error> {
+        let mut builder = self.builder.clone();
+        let mut stages = vk::ShaderStageFlags::empty();
+        let mut declared = Vec::new();
+        for (name, stage) in self.shaders.iter() {
+            let shader = library.variant(name, *stage, &key.defines).ok_or_else(|| Error::ShaderNotFound {
+                name: permutation_name(name, &key.defines),
+                stage: *stage,
+            })?;
+            declared.extend(shader.reflection().specialization_constants.iter());
+            builder.stages.push(ShaderStage {
+                stage: *stage,
+                module: shader.module(),
+                entry_point: shader.entry_point().clone(),
+            });
+            stages |= *stage;
+        }
+
+        for (id, value) in key.specialization.values() {
+            match declared.iter().find(|constant| constant.id == id) {
+                None => return Err(Error::InvalidPipelineConfig("Specialization constant is not declared by any stage")),
+                Some(constant) if constant.size as usize != value.len() => {
+                    return Err(Error::InvalidPipelineConfig(
+                        "Specialization constant value doesn't match the declared size",
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        if !key.specialization.is_empty() {
+            builder = builder.specialization(stages, key.specialization.clone());
+        }
+        trace!("Building pipeline permutation {:?}", key);
+        builder.build(&self.device)
+    }
+}
@@ -0,0 +1,858 @@
+use crate::backend::vulkan::buffer::Pod;
+use crate::backend::vulkan::depth::has_stencil;
+use crate::backend::vulkan::descriptors::DescriptorLayoutCache;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::reflection::ReflectedLayout;
+use ash::vk;
+use std::ffi::{c_void, CString};
+use std::mem::size_of;
+use std::ptr::null;
+
+/// Rust type that maps to a vertex attribute format. Matrices take one location per column.
+pub trait VertexFormat {
+    const FORMAT: vk::Format;
+    const COLUMNS: u32 = 1;
+}
+
+macro_rules! vertex_formats {
+    ($($ty:ty => $format:ident),* $(,)?) => {
+        $(impl VertexFormat for $ty {
+            const FORMAT: vk::Format = vk::Format::$format;
+        })*
+    };
+}
+
+vertex_formats!(
+    f32 => R32_SFLOAT,
+    [f32; 2] => R32G32_SFLOAT,
+    [f32; 3] => R32G32B32_SFLOAT,
+    [f32; 4] => R32G32B32A32_SFLOAT,
+    u32 => R32_UINT,
+    [u32; 2] => R32G32_UINT,
+    [u32; 3] => R32G32B32_UINT,
+    [u32; 4] => R32G32B32A32_UINT,
+    i32 => R32_SINT,
+    [i32; 2] => R32G32_SINT,
+    [i32; 3] => R32G32B32_SINT,
+    [i32; 4] => R32G32B32A32_SINT,
+    // Packed colors, normalized to 0..1 in the shader
+    [u8; 4] => R8G8B8A8_UNORM,
+    [u16; 2] => R16G16_UNORM,
+    [u16; 4] => R16G16B16A16_UNORM,
+);
+
+impl VertexFormat for [[f32; 3]; 3] {
+    const FORMAT: vk::Format = vk::Format::R32G32B32_SFLOAT;
+    const COLUMNS: u32 = 3;
+}
+
+impl VertexFormat for [[f32; 4]; 4] {
+    const FORMAT: vk::Format = vk::Format::R32G32B32A32_SFLOAT;
+    const COLUMNS: u32 = 4;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub format: vk::Format,
+    /// Byte offset in the vertex
+    pub offset: u32,
+}
+
+/// Vertex or instance data that knows its attribute layout, implement it with `impl_vertex!`
+pub trait Vertex: Pod {
+    /// Attributes in location order
+    fn attributes() -> Vec<VertexAttribute>;
+}
+
+/// Attributes of one field, used by `impl_vertex!`. `field` only carries the field type.
+pub fn field_attributes<V, T: VertexFormat>(offset: u32, _field: fn(&V) -> &T) -> Vec<VertexAttribute> {
+    let column_size = (size_of::<T>() as u32) / T::COLUMNS;
+    (0..T::COLUMNS)
+        .map(|column| VertexAttribute {
+            format: T::FORMAT,
+            offset: offset + column * column_size,
+        })
+        .collect()
+}
+
+/// Implements `Vertex` for a `#[repr(C)]` struct from its field list. The format comes from the field type
+/// (see `VertexFormat`) unless it is given explicitly:
+/// `impl_vertex!(MyVertex { position, normal, uv: R16G16_SFLOAT });`
+#[macro_export]
+macro_rules! impl_vertex {
+    ($vertex:ty { $($field:ident $(: $format:ident)?),* $(,)? }) => {
+        impl $crate::backend::vulkan::pipeline::Vertex for $vertex {
+            fn attributes() -> Vec<$crate::backend::vulkan::pipeline::VertexAttribute> {
+                let mut attributes = Vec::new();
+                $($crate::impl_vertex!(@field attributes, $vertex, $field $(, $format)?);)*
+                attributes
+            }
+        }
+    };
+    (@field $attributes:ident, $vertex:ty, $field:ident) => {
+        $attributes.extend($crate::backend::vulkan::pipeline::field_attributes(
+            std::mem::offset_of!($vertex, $field) as u32,
+            |vertex: &$vertex| &vertex.$field,
+        ));
+    };
+    (@field $attributes:ident, $vertex:ty, $field:ident, $format:ident) => {
+        $attributes.push($crate::backend::vulkan::pipeline::VertexAttribute {
+            format: $crate::backend::vulkan::ash::vk::Format::$format,
+            offset: std::mem::offset_of!($vertex, $field) as u32,
+        });
+    };
+}
+
+/// Locations a single attribute of the format occupies, 64 bit three and four component vectors take two
+fn format_locations(format: vk::Format) -> u32 {
+    match format {
+        vk::Format::R64G64B64_SFLOAT | vk::Format::R64G64B64A64_SFLOAT | vk::Format::R64G64B64_UINT | vk::Format::R64G64B64A64_UINT => 2,
+        _ => 1,
+    }
+}
+
+/// Vertex buffer bindings and their attributes. Bindings are numbered in the order they are added and the attributes
+/// take consecutive shader locations.
+#[derive(Clone, Debug, Default)]
+pub struct VertexInputLayout {
+    pub bindings: Vec<vk::VertexInputBindingDescription>,
+    pub attributes: Vec<vk::VertexInputAttributeDescription>,
+}
+
+impl VertexInputLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Per vertex data of `V` on the next binding
+    pub fn vertex<V: Vertex>(self) -> Self {
+        self.binding::<V>(vk::VertexInputRate::VERTEX)
+    }
+
+    /// Per instance data of `V` on the next binding
+    pub fn instance<V: Vertex>(self) -> Self {
+        self.binding::<V>(vk::VertexInputRate::INSTANCE)
+    }
+
+    pub fn binding<V: Vertex>(self, input_rate: vk::VertexInputRate) -> Self {
+        self.raw_binding(size_of::<V>() as u32, input_rate, &V::attributes())
+    }
+
+    /// Binding without a `Vertex` type, e.g. for data that is only known at runtime
+    pub fn raw_binding(mut self, stride: u32, input_rate: vk::VertexInputRate, attributes: &[VertexAttribute]) -> Self {
+        let binding = self.bindings.len() as u32;
+        self.bindings.push(vk::VertexInputBindingDescription {
+            binding,
+            stride,
+            input_rate,
+        });
+        let mut location = self.next_location();
+        for attribute in attributes {
+            self.attributes.push(vk::VertexInputAttributeDescription {
+                location,
+                binding,
+                format: attribute.format,
+                offset: attribute.offset,
+            });
+            location += format_locations(attribute.format);
+        }
+        self
+    }
+
+    /// First location not used by any attribute
+    pub fn next_location(&self) -> u32 {
+        self.attributes
+            .iter()
+            .map(|attribute| attribute.location + format_locations(attribute.format))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Blend state presets for a color attachment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Overwrites the attachment
+    Opaque,
+    /// Classic `src * a + dst * (1 - a)` with straight alpha
+    Alpha,
+    /// `src + dst * (1 - a)`, the color is already multiplied with alpha
+    PremultipliedAlpha,
+    Additive,
+}
+
+impl BlendMode {
+    pub fn attachment_state(self) -> vk::PipelineColorBlendAttachmentState {
+        let (blend_enable, src_color_blend_factor, dst_color_blend_factor) = match self {
+            BlendMode::Opaque => (vk::FALSE, vk::BlendFactor::ONE, vk::BlendFactor::ZERO),
+            BlendMode::Alpha => (vk::TRUE, vk::BlendFactor::SRC_ALPHA, vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::PremultipliedAlpha => (vk::TRUE, vk::BlendFactor::ONE, vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive => (vk::TRUE, vk::BlendFactor::ONE, vk::BlendFactor::ONE),
+        };
+        vk::PipelineColorBlendAttachmentState {
+            blend_enable,
+            src_color_blend_factor,
+            dst_color_blend_factor,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: if self == BlendMode::Additive {
+                vk::BlendFactor::ONE
+            } else {
+                vk::BlendFactor::ZERO
+            },
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DepthStencilConfig {
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub compare_op: vk::CompareOp,
+    /// Fragments outside of the range are discarded, needs the `depthBounds` feature
+    pub depth_bounds: Option<(f32, f32)>,
+    /// Front and back face stencil state, `None` disables the stencil test
+    pub stencil: Option<(vk::StencilOpState, vk::StencilOpState)>,
+}
+
+impl DepthStencilConfig {
+    /// No depth or stencil test
+    pub fn disabled() -> Self {
+        Self {
+            depth_test: false,
+            depth_write: false,
+            compare_op: vk::CompareOp::ALWAYS,
+            depth_bounds: None,
+            stencil: None,
+        }
+    }
+
+    /// Depth test with `compare_op`, `write` stores the depth of passing fragments
+    pub fn depth(compare_op: vk::CompareOp, write: bool) -> Self {
+        Self {
+            depth_test: true,
+            depth_write: write,
+            compare_op,
+            ..Self::disabled()
+        }
+    }
+
+    fn create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo<'static> {
+        let (front, back) = self.stencil.unwrap_or_default();
+        let (min_depth_bounds, max_depth_bounds) = self.depth_bounds.unwrap_or((0.0, 1.0));
+        vk::PipelineDepthStencilStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::PipelineDepthStencilStateCreateFlags::empty(),
+            depth_test_enable: self.depth_test as vk::Bool32,
+            depth_write_enable: self.depth_write as vk::Bool32,
+            depth_compare_op: self.compare_op,
+            depth_bounds_test_enable: self.depth_bounds.is_some() as vk::Bool32,
+            stencil_test_enable: self.stencil.is_some() as vk::Bool32,
+            front,
+            back,
+            min_depth_bounds,
+            max_depth_bounds,
+            _marker: Default::default(),
+        }
+    }
+}
+
+/// Attachment formats of a dynamic rendering pass, they take the place of the render pass when creating pipelines
+#[derive(Clone, Debug, Default)]
+pub struct RenderingFormats {
+    pub color_formats: Vec<vk::Format>,
+    /// Also used as the stencil format if it has a stencil aspect
+    pub depth_format: Option<vk::Format>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShaderStage {
+    pub stage: vk::ShaderStageFlags,
+    pub module: vk::ShaderModule,
+    pub entry_point: CString,
+}
+
+/// Values of specialization constants by `constant_id`, applied when the pipeline is created. Ids the shader doesn't
+/// declare are ignored by Vulkan.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SpecializationConstants {
+    /// Sorted by id
+    values: Vec<(u32, Vec<u8>)>,
+}
+
+impl SpecializationConstants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Booleans are 4 byte `VkBool32`s
+    pub fn bool(self, id: u32, value: bool) -> Self {
+        self.raw(id, &(value as vk::Bool32).to_ne_bytes())
+    }
+
+    pub fn u32(self, id: u32, value: u32) -> Self {
+        self.raw(id, &value.to_ne_bytes())
+    }
+
+    pub fn i32(self, id: u32, value: i32) -> Self {
+        self.raw(id, &value.to_ne_bytes())
+    }
+
+    pub fn f32(self, id: u32, value: f32) -> Self {
+        self.raw(id, &value.to_ne_bytes())
+    }
+
+    /// Value in the constant's memory layout, replaces an earlier value for the id
+    pub fn raw(mut self, id: u32, bytes: &[u8]) -> Self {
+        match self.values.binary_search_by_key(&id, |(value_id, _)| *value_id) {
+            Ok(index) => self.values[index].1 = bytes.to_vec(),
+            Err(index) => self.values.insert(index, (id, bytes.to_vec())),
+        }
+        self
+    }
+
+    pub fn get(&self, id: u32) -> Option<&[u8]> {
+        self.values
+            .binary_search_by_key(&id, |(value_id, _)| *value_id)
+            .ok()
+            .map(|index| self.values[index].1.as_slice())
+    }
+
+    /// Ids and values, sorted by id
+    pub fn values(&self) -> impl Iterator<Item = (u32, &[u8])> {
+        self.values.iter().map(|(id, bytes)| (*id, bytes.as_slice()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Map entries and the packed data they point into
+    pub fn map_entries(&self) -> (Vec<vk::SpecializationMapEntry>, Vec<u8>) {
+        let mut entries = Vec::with_capacity(self.values.len());
+        let mut data = Vec::new();
+        for (id, bytes) in self.values.iter() {
+            entries.push(vk::SpecializationMapEntry {
+                constant_id: *id,
+                offset: data.len() as u32,
+                size: bytes.len(),
+            });
+            data.extend_from_slice(bytes);
+        }
+        (entries, data)
+    }
+}
+
+/// Everything needed to create a graphics pipeline, start with `new` and override what differs from the defaults:
+/// triangle lists, filled polygons without culling, counter clockwise front faces, no depth test, a single opaque
+/// color attachment, one sample and dynamic viewport and scissor. Shader modules and the render pass are borrowed,
+/// they have to outlive `build` but not the pipeline.
+#[derive(Clone, Debug)]
+pub struct GraphicsPipelineBuilder {
+    pub stages: Vec<ShaderStage>,
+    pub vertex_input: VertexInputLayout,
+    pub topology: vk::PrimitiveTopology,
+    pub primitive_restart: bool,
+    pub polygon_mode: vk::PolygonMode,
+    pub cull_mode: vk::CullModeFlags,
+    pub front_face: vk::FrontFace,
+    pub line_width: f32,
+    /// Constant factor, clamp and slope factor
+    pub depth_bias: Option<(f32, f32, f32)>,
+    pub depth_clamp: bool,
+    pub samples: vk::SampleCountFlags,
+    /// Minimum fraction of samples shaded individually, needs the `sampleRateShading` feature
+    pub sample_shading: Option<f32>,
+    pub alpha_to_coverage: bool,
+    pub depth_stencil: DepthStencilConfig,
+    /// One entry per color attachment of the subpass
+    pub color_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    pub blend_constants: [f32; 4],
+    pub dynamic_states: Vec<vk::DynamicState>,
+    /// Used when viewport and scissor are not dynamic
+    pub viewport: Option<(vk::Viewport, vk::Rect2D)>,
+    pub set_layouts: Vec<vk::DescriptorSetLayout>,
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+    /// Constants of the stages contained in the flags, the last matching entry applies to a stage
+    pub specializations: Vec<(vk::ShaderStageFlags, SpecializationConstants)>,
+    /// Null for dynamic rendering
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    /// Set for dynamic rendering instead of `render_pass`
+    pub rendering_formats: Option<RenderingFormats>,
+    /// Index given to `color_attachment_state` without a matching attachment, reported by `validate`
+    invalid_attachment_index: Option<usize>,
+}
+
+impl GraphicsPipelineBuilder {
+    pub fn new(render_pass: vk::RenderPass) -> Self {
+        Self {
+            stages: Vec::new(),
+            vertex_input: VertexInputLayout::new(),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart: false,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            line_width: 1.0,
+            depth_bias: None,
+            depth_clamp: false,
+            samples: vk::SampleCountFlags::TYPE_1,
+            sample_shading: None,
+            alpha_to_coverage: false,
+            depth_stencil: DepthStencilConfig::disabled(),
+            color_attachments: vec![BlendMode::Opaque.attachment_state()],
+            blend_constants: [0.0; 4],
+            dynamic_states: vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR],
+            viewport: None,
+            set_layouts: Vec::new(),
+            push_constant_ranges: Vec::new(),
+            specializations: Vec::new(),
+            render_pass,
+            subpass: 0,
+            rendering_formats: None,
+            invalid_attachment_index: None,
+        }
+    }
+
+    /// Pipeline for `vkCmdBeginRendering` passes with these attachments, one opaque blend state per color format.
+    /// The context needs dynamic rendering enabled.
+    pub fn for_dynamic_rendering(color_formats: &[vk::Format], depth_format: Option<vk::Format>) -> Self {
+        let mut builder = Self::new(vk::RenderPass::null());
+        builder.color_attachments = vec![BlendMode::Opaque.attachment_state(); color_formats.len()];
+        builder.rendering_formats = Some(RenderingFormats {
+            color_formats: color_formats.to_vec(),
+            depth_format,
+        });
+        builder
+    }
+
+    /// Shader stage with the `main` entry point, the module only has to live until `build`
+    pub fn stage(self, stage: vk::ShaderStageFlags, module: vk::ShaderModule) -> Self {
+        self.stage_with_entry(stage, module, "main")
+    }
+
+    pub fn stage_with_entry(mut self, stage: vk::ShaderStageFlags, module: vk::ShaderModule, entry_point: &str) -> Self {
+        self.stages.push(ShaderStage {
+            stage,
+            module,
+            entry_point: CString::new(entry_point).expect("Entry point contains a null byte"),
+        });
+        self
+    }
+
+    pub fn vertex_shader(self, module: vk::ShaderModule) -> Self {
+        self.stage(vk::ShaderStageFlags::VERTEX, module)
+    }
+
+    pub fn fragment_shader(self, module: vk::ShaderModule) -> Self {
+        self.stage(vk::ShaderStageFlags::FRAGMENT, module)
+    }
+
+    pub fn vertex_input(mut self, vertex_input: VertexInputLayout) -> Self {
+        self.vertex_input = vertex_input;
+        self
+    }
+
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Restarts strips and fans at the maximum index value
+    pub fn primitive_restart(mut self, primitive_restart: bool) -> Self {
+        self.primitive_restart = primitive_restart;
+        self
+    }
+
+    /// Lines and points need the `fillModeNonSolid` feature
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags, front_face: vk::FrontFace) -> Self {
+        self.cull_mode = cull_mode;
+        self.front_face = front_face;
+        self
+    }
+
+    /// Widths other than 1 need the `wideLines` feature
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    pub fn depth_bias(mut self, constant_factor: f32, clamp: f32, slope_factor: f32) -> Self {
+        self.depth_bias = Some((constant_factor, clamp, slope_factor));
+        self
+    }
+
+    /// Clamps depth instead of clipping, needs the `depthClamp` feature
+    pub fn depth_clamp(mut self, depth_clamp: bool) -> Self {
+        self.depth_clamp = depth_clamp;
+        self
+    }
+
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn sample_shading(mut self, min_sample_shading: f32) -> Self {
+        self.sample_shading = Some(min_sample_shading);
+        self
+    }
+
+    pub fn alpha_to_coverage(mut self, alpha_to_coverage: bool) -> Self {
+        self.alpha_to_coverage = alpha_to_coverage;
+        self
+    }
+
+    pub fn depth_stencil(mut self, depth_stencil: DepthStencilConfig) -> Self {
+        self.depth_stencil = depth_stencil;
+        self
+    }
+
+    /// Depth test with `compare_op`, keeps the stencil state. The subpass needs a depth attachment.
+    pub fn depth(mut self, compare_op: vk::CompareOp, write: bool) -> Self {
+        self.depth_stencil.depth_test = true;
+        self.depth_stencil.depth_write = write;
+        self.depth_stencil.compare_op = compare_op;
+        self
+    }
+
+    /// One blend mode per color attachment of the subpass, replaces the previous attachments
+    pub fn color_attachments(mut self, blend_modes: &[BlendMode]) -> Self {
+        self.color_attachments = blend_modes.iter().map(|mode| mode.attachment_state()).collect();
+        self
+    }
+
+    /// Replaces the blend state of one attachment with a custom one, `build` fails if there is no such attachment
+    pub fn color_attachment_state(mut self, index: usize, state: vk::PipelineColorBlendAttachmentState) -> Self {
+        match self.color_attachments.get_mut(index) {
+            Some(attachment) => *attachment = state,
+            None => self.invalid_attachment_index = Some(index),
+        }
+        self
+    }
+
+    pub fn blend_constants(mut self, blend_constants: [f32; 4]) -> Self {
+        self.blend_constants = blend_constants;
+        self
+    }
+
+    /// Replaces the default dynamic viewport and scissor
+    pub fn dynamic_states(mut self, dynamic_states: &[vk::DynamicState]) -> Self {
+        self.dynamic_states = dynamic_states.to_vec();
+        self
+    }
+
+    /// Baked in viewport and scissor for pipelines without them as dynamic state
+    pub fn viewport(mut self, viewport: vk::Viewport, scissor: vk::Rect2D) -> Self {
+        self.viewport = Some((viewport, scissor));
+        self
+    }
+
+    pub fn set_layouts(mut self, set_layouts: &[vk::DescriptorSetLayout]) -> Self {
+        self.set_layouts = set_layouts.to_vec();
+        self
+    }
+
+    pub fn push_constant_range(mut self, stage_flags: vk::ShaderStageFlags, offset: u32, size: u32) -> Self {
+        self.push_constant_ranges.push(vk::PushConstantRange { stage_flags, offset, size });
+        self
+    }
+
+    /// Specialization constants of the stages in `stages`, replaces constants set for the same flags before
+    pub fn specialization(mut self, stages: vk::ShaderStageFlags, constants: SpecializationConstants) -> Self {
+        self.specializations.retain(|(specialized, _)| *specialized != stages);
+        self.specializations.push((stages, constants));
+        self
+    }
+
+    /// Set layouts and push constants from the reflection of the shaders, see `ReflectedLayout::create_set_layouts`
+    pub fn reflected_layout(
+        mut self,
+        layout: &ReflectedLayout,
+        cache: &mut DescriptorLayoutCache,
+        external: &[(u32, vk::DescriptorSetLayout)],
+    ) -> Result<Self, Error> {
+        self.set_layouts = layout.create_set_layouts(cache, external)?;
+        self.push_constant_ranges = layout.push_constant_ranges();
+        Ok(self)
+    }
+
+    pub fn subpass(mut self, subpass: u32) -> Self {
+        self.subpass = subpass;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if !self.stages.iter().any(|stage| stage.stage == vk::ShaderStageFlags::VERTEX) {
+            return Err(Error::InvalidPipelineConfig("Graphics pipelines need a vertex shader"));
+        }
+        let dynamic_viewport = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]
+            .iter()
+            .all(|state| self.dynamic_states.contains(state));
+        if !dynamic_viewport && self.viewport.is_none() {
+            return Err(Error::InvalidPipelineConfig("Viewport and scissor have to be dynamic or set"));
+        }
+        match &self.rendering_formats {
+            Some(formats) if formats.color_formats.len() != self.color_attachments.len() => {
+                return Err(Error::InvalidPipelineConfig("Every color format needs a blend state"));
+            }
+            None if self.render_pass == vk::RenderPass::null() => {
+                return Err(Error::InvalidPipelineConfig("Pipelines need a render pass or rendering formats"));
+            }
+            _ => {}
+        }
+        if self.invalid_attachment_index.is_some() {
+            return Err(Error::InvalidPipelineConfig("Blend state set for a color attachment that doesn't exist"));
+        }
+        if self.samples != vk::SampleCountFlags::TYPE_1 && self.samples.as_raw().count_ones() != 1 {
+            return Err(Error::InvalidPipelineConfig("Sample count has to be a single power of two"));
+        }
+        Ok(())
+    }
+
+    /// Creates the pipeline layout from the set layouts and push constant ranges, then the pipeline
+    pub fn build(&self, device: &Device) -> Result<GraphicsPipeline, Error> {
+        self.validate()?;
+        let specialization_data: Vec<Option<(Vec<vk::SpecializationMapEntry>, Vec<u8>)>> = self
+            .stages
+            .iter()
+            .map(|stage| {
+                self.specializations
+                    .iter()
+                    .rev()
+                    .find(|(specialized, _)| specialized.contains(stage.stage))
+                    .filter(|(_, constants)| !constants.is_empty())
+                    .map(|(_, constants)| constants.map_entries())
+            })
+            .collect();
+        let specialization_infos: Vec<Option<vk::SpecializationInfo>> = specialization_data
+            .iter()
+            .map(|data| {
+                data.as_ref().map(|(entries, data)| vk::SpecializationInfo {
+                    map_entry_count: entries.len() as u32,
+                    p_map_entries: entries.as_ptr(),
+                    data_size: data.len(),
+                    p_data: data.as_ptr() as *const c_void,
+                    _marker: Default::default(),
+                })
+            })
+            .collect();
+        let stages: Vec<vk::PipelineShaderStageCreateInfo> = self
+            .stages
+            .iter()
+            .zip(specialization_infos.iter())
+            .map(|(stage, specialization_info)| vk::PipelineShaderStageCreateInfo {
+                s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+                p_next: null(),
+                flags: vk::PipelineShaderStageCreateFlags::empty(),
+                stage: stage.stage,
+                module: stage.module,
+                p_name: stage.entry_point.as_ptr(),
+                p_specialization_info: specialization_info
+                    .as_ref()
+                    .map_or(null(), |info| info as *const vk::SpecializationInfo),
+                _marker: Default::default(),
+            })
+            .collect();
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::PipelineVertexInputStateCreateFlags::empty(),
+            vertex_binding_description_count: self.vertex_input.bindings.len() as u32,
+            p_vertex_binding_descriptions: self.vertex_input.bindings.as_ptr(),
+            vertex_attribute_description_count: self.vertex_input.attributes.len() as u32,
+            p_vertex_attribute_descriptions: self.vertex_input.attributes.as_ptr(),
+            _marker: Default::default(),
+        };
+
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::PipelineInputAssemblyStateCreateFlags::empty(),
+            topology: self.topology,
+            primitive_restart_enable: self.primitive_restart as vk::Bool32,
+            _marker: Default::default(),
+        };
+
+        // Pointers are ignored for dynamic viewport and scissor
+        let (viewport, scissor) = self.viewport.unwrap_or_default();
+        let viewport_state = vk::PipelineViewportStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_VIEWPORT_STATE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::PipelineViewportStateCreateFlags::empty(),
+            viewport_count: 1,
+            p_viewports: &viewport,
+            scissor_count: 1,
+            p_scissors: &scissor,
+            _marker: Default::default(),
+        };
+
+        let (depth_bias_constant_factor, depth_bias_clamp, depth_bias_slope_factor) = self.depth_bias.unwrap_or_default();
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::PipelineRasterizationStateCreateFlags::empty(),
+            depth_clamp_enable: self.depth_clamp as vk::Bool32,
+            rasterizer_discard_enable: vk::FALSE,
+            polygon_mode: self.polygon_mode,
+            cull_mode: self.cull_mode,
+            front_face: self.front_face,
+            depth_bias_enable: self.depth_bias.is_some() as vk::Bool32,
+            depth_bias_constant_factor,
+            depth_bias_clamp,
+            depth_bias_slope_factor,
+            line_width: self.line_width,
+            _marker: Default::default(),
+        };
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::PipelineMultisampleStateCreateFlags::empty(),
+            rasterization_samples: self.samples,
+            sample_shading_enable: self.sample_shading.is_some() as vk::Bool32,
+            min_sample_shading: self.sample_shading.unwrap_or(1.0),
+            p_sample_mask: null(),
+            alpha_to_coverage_enable: self.alpha_to_coverage as vk::Bool32,
+            alpha_to_one_enable: vk::FALSE,
+            _marker: Default::default(),
+        };
+
+        let depth_stencil_state = self.depth_stencil.create_info();
+
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::PipelineColorBlendStateCreateFlags::empty(),
+            logic_op_enable: vk::FALSE,
+            logic_op: vk::LogicOp::COPY,
+            attachment_count: self.color_attachments.len() as u32,
+            p_attachments: self.color_attachments.as_ptr(),
+            blend_constants: self.blend_constants,
+            _marker: Default::default(),
+        };
+
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_DYNAMIC_STATE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::PipelineDynamicStateCreateFlags::empty(),
+            dynamic_state_count: self.dynamic_states.len() as u32,
+            p_dynamic_states: self.dynamic_states.as_ptr(),
+            _marker: Default::default(),
+        };
+
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            p_next: null(),
+            flags: vk::PipelineLayoutCreateFlags::empty(),
+            set_layout_count: self.set_layouts.len() as u32,
+            p_set_layouts: self.set_layouts.as_ptr(),
+            push_constant_range_count: self.push_constant_ranges.len() as u32,
+            p_push_constant_ranges: self.push_constant_ranges.as_ptr(),
+            _marker: Default::default(),
+        };
+        let (depth_attachment_format, stencil_attachment_format) = match self.rendering_formats.as_ref().and_then(|formats| formats.depth_format) {
+            Some(format) if has_stencil(format) => (format, format),
+            Some(format) => (format, vk::Format::UNDEFINED),
+            None => (vk::Format::UNDEFINED, vk::Format::UNDEFINED),
+        };
+        let color_formats = self.rendering_formats.as_ref().map_or(&[][..], |formats| &formats.color_formats);
+        let rendering_create_info = vk::PipelineRenderingCreateInfo {
+            s_type: vk::StructureType::PIPELINE_RENDERING_CREATE_INFO,
+            p_next: null(),
+            view_mask: 0,
+            color_attachment_count: color_formats.len() as u32,
+            p_color_attachment_formats: color_formats.as_ptr(),
+            depth_attachment_format,
+            stencil_attachment_format,
+            _marker: Default::default(),
+        };
+
+        let mut pipeline = GraphicsPipeline {
+            device: device.clone(),
+            pipeline: vk::Pipeline::null(),
+            layout: unsafe { device.create_pipeline_layout(&pipeline_layout_create_info, None)? },
+        };
+
+        let pipeline_create_info = vk::GraphicsPipelineCreateInfo {
+            s_type: vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
+            p_next: if self.rendering_formats.is_some() {
+                &rendering_create_info as *const vk::PipelineRenderingCreateInfo as *const c_void
+            } else {
+                null()
+            },
+            flags: vk::PipelineCreateFlags::empty(),
+            stage_count: stages.len() as u32,
+            p_stages: stages.as_ptr(),
+            p_vertex_input_state: &vertex_input_state,
+            p_input_assembly_state: &input_assembly_state,
+            p_tessellation_state: null(),
+            p_viewport_state: &viewport_state,
+            p_rasterization_state: &rasterization_state,
+            p_multisample_state: &multisample_state,
+            p_depth_stencil_state: &depth_stencil_state,
+            p_color_blend_state: &color_blend_state,
+            p_dynamic_state: &dynamic_state,
+            layout: pipeline.layout,
+            render_pass: self.render_pass,
+            subpass: self.subpass,
+            base_pipeline_handle: vk::Pipeline::null(),
+            base_pipeline_index: -1,
+            _marker: Default::default(),
+        };
+        pipeline.pipeline = unsafe {
+            device
+                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_create_info], None)
+                .map_err(|(_, result)| result)?[0]
+        };
+        Ok(pipeline)
+    }
+}
+
+/// Pipeline and its layout, destroyed on drop. The GPU must be done with it by then.
+pub struct GraphicsPipeline {
+    device: Device,
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+}
+
+impl GraphicsPipeline {
+    pub fn handle(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    pub fn layout(&self) -> vk::PipelineLayout {
+        self.layout
+    }
+
+    /// Gives up ownership of the pipeline and layout, e.g. to hand them to a `PipelineInfo`
+    pub fn into_raw(self) -> (vk::Pipeline, vk::PipelineLayout) {
+        let raw = (self.pipeline, self.layout);
+        std::mem::forget(self);
+        raw
+    }
+}
+
+impl Drop for GraphicsPipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device.destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::backend::vulkan::errors::Error;
 use crate::backend::vulkan::queue::op_indices::COUNT;
 use ash::vk;
 
@@ -39,10 +40,20 @@ pub struct QueueHandles {
     pub queues: Vec<Option<vk::Queue>>,
 }
 
+impl Default for QueueHandles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl QueueHandles {
     pub fn new() -> Self {
         Self { queues: vec![None; COUNT] }
     }
+
+    pub fn get(&self, operation: usize) -> Option<vk::Queue> {
+        self.queues[operation]
+    }
 }
 
 #[derive(Clone)]
@@ -53,29 +64,44 @@ pub struct QueueFamilyHandle {
 
 pub struct QueueSelections {
     pub families: Vec<Option<QueueFamily>>,           // Indexed based on family index
-    pub operations: Vec<Option<(QueueFamilyHandle)>>, //Index, Offset --- Index based on operation, returns family index + offset
+    pub operations: Vec<Option<QueueFamilyHandle>>,   //Index, Offset --- Index based on operation, returns family index + offset
+    pub queue_limits: Vec<u32>,                       // Indexed based on family index, max queues the family exposes
 }
 
 impl QueueSelections {
-    pub fn new(family_count: u32) -> Self {
+    pub fn new(queue_limits: &[u32]) -> Self {
         Self {
-            families: vec![None; family_count as usize],
+            families: vec![None; queue_limits.len()],
             operations: vec![None; COUNT],
+            queue_limits: queue_limits.to_vec(),
         }
     }
 
-    pub fn insert_operation(&mut self, operation: u8, family_index: u32) -> Result<(), &str> {
+    pub fn family_index(&self, operation: usize) -> Option<u32> {
+        self.operations[operation].as_ref().map(|handle| handle.index)
+    }
+
+    pub fn insert_operation(&mut self, operation: u8, family_index: u32) -> Result<(), Error> {
         if self.operations[operation as usize].is_some() {
-            return Err("Operation already exists!");
+            return Err(Error::QueueOperationAlreadyMapped(operation as usize));
         }
 
         if let Some(handle) = self.families[family_index as usize].as_mut() {
+            // The family is out of queues, the operation shares the last one
+            if handle.count >= self.queue_limits[family_index as usize] {
+                self.operations[operation as usize] = Some(QueueFamilyHandle {
+                    index: family_index,
+                    offset: handle.count - 1,
+                });
+                return Ok(());
+            }
+
             self.operations[operation as usize] = Some(QueueFamilyHandle {
                 index: family_index,
                 offset: handle.count,
             });
             handle.count += 1;
-            /// TODO Parameters for priorities
+            // TODO Parameters for priorities
             handle.priorities.push(1.0);
             return Ok(());
         }
@@ -89,7 +115,7 @@ impl QueueSelections {
         Ok(())
     }
 
-    pub fn to_vk_creation_info(&self) -> Vec<vk::DeviceQueueCreateInfo> {
+    pub fn to_vk_creation_info(&self) -> Vec<vk::DeviceQueueCreateInfo<'_>> {
         let mut queues = Vec::with_capacity(self.families.len());
         for (index, queue_family) in self.families.iter().enumerate() {
             if let Some(queue_family) = queue_family {
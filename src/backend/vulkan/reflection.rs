@@ -0,0 +1,819 @@
+use crate::backend::vulkan::descriptors::{DescriptorLayoutCache, LayoutBinding};
+use crate::backend::vulkan::errors::Error;
+use ash::vk;
+use std::collections::{BTreeMap, HashMap};
+use std::ptr::null;
+
+const MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+/// Deeper type nesting only happens in malformed modules with self-referencing types
+const MAX_TYPE_DEPTH: u32 = 64;
+/// Vertex inputs beyond this can't be bound by any device, stops huge arrays from expanding
+const MAX_INPUT_LOCATIONS: usize = 256;
+
+/// Opcodes the reflection looks at, everything else is skipped
+mod op {
+    pub const NAME: u32 = 5;
+    pub const ENTRY_POINT: u32 = 15;
+    pub const TYPE_BOOL: u32 = 20;
+    pub const TYPE_INT: u32 = 21;
+    pub const TYPE_FLOAT: u32 = 22;
+    pub const TYPE_VECTOR: u32 = 23;
+    pub const TYPE_MATRIX: u32 = 24;
+    pub const TYPE_IMAGE: u32 = 25;
+    pub const TYPE_SAMPLER: u32 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u32 = 27;
+    pub const TYPE_ARRAY: u32 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u32 = 29;
+    pub const TYPE_STRUCT: u32 = 30;
+    pub const TYPE_POINTER: u32 = 32;
+    pub const CONSTANT_TRUE: u32 = 41;
+    pub const CONSTANT_FALSE: u32 = 42;
+    pub const CONSTANT: u32 = 43;
+    pub const SPEC_CONSTANT_TRUE: u32 = 48;
+    pub const SPEC_CONSTANT_FALSE: u32 = 49;
+    pub const SPEC_CONSTANT: u32 = 50;
+    pub const VARIABLE: u32 = 59;
+    pub const DECORATE: u32 = 71;
+    pub const MEMBER_DECORATE: u32 = 72;
+    pub const TYPE_ACCELERATION_STRUCTURE: u32 = 5341;
+}
+
+mod decoration {
+    pub const SPEC_ID: u32 = 1;
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ROW_MAJOR: u32 = 4;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const BUILT_IN: u32 = 11;
+    pub const LOCATION: u32 = 30;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+}
+
+mod storage_class {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const INPUT: u32 = 1;
+    pub const UNIFORM: u32 = 2;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+/// `Dim` operand of image types
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+fn execution_model_stage(execution_model: u32) -> vk::ShaderStageFlags {
+    match execution_model {
+        0 => vk::ShaderStageFlags::VERTEX,
+        1 => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => vk::ShaderStageFlags::GEOMETRY,
+        4 => vk::ShaderStageFlags::FRAGMENT,
+        5 => vk::ShaderStageFlags::COMPUTE,
+        5267 | 5364 => vk::ShaderStageFlags::TASK_EXT,
+        5268 | 5365 => vk::ShaderStageFlags::MESH_EXT,
+        5313 => vk::ShaderStageFlags::RAYGEN_KHR,
+        5314 => vk::ShaderStageFlags::INTERSECTION_KHR,
+        5315 => vk::ShaderStageFlags::ANY_HIT_KHR,
+        5316 => vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+        5317 => vk::ShaderStageFlags::MISS_KHR,
+        5318 => vk::ShaderStageFlags::CALLABLE_KHR,
+        _ => vk::ShaderStageFlags::empty(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryPoint {
+    pub name: String,
+    pub stage: vk::ShaderStageFlags,
+}
+
+/// Vertex shader input, matrices and arrays are split into one input per location
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexInput {
+    pub location: u32,
+    pub format: vk::Format,
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// Array size, 0 for runtime sized arrays
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalarKind {
+    Bool,
+    Int,
+    UInt,
+    Float,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecializationConstant {
+    /// `constant_id` of the constant, the key of `vk::SpecializationMapEntry`
+    pub id: u32,
+    pub kind: ScalarKind,
+    /// Size in bytes, booleans are 4 byte `VkBool32`s
+    pub size: u32,
+    /// Bits of the default value, zero extended
+    pub default_value: u64,
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct,
+    Pointer { pointee: u32 },
+    AccelerationStructure,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Decorations {
+    spec_id: Option<u32>,
+    buffer_block: bool,
+    array_stride: Option<u32>,
+    built_in: bool,
+    location: Option<u32>,
+    binding: Option<u32>,
+    set: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct MemberDecorations {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>,
+    row_major: bool,
+    built_in: bool,
+}
+
+/// Raw module contents gathered in a single pass over the instructions
+#[derive(Default)]
+struct Module {
+    entry_points: Vec<EntryPoint>,
+    names: HashMap<u32, String>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), MemberDecorations>,
+    types: HashMap<u32, Type>,
+    struct_members: HashMap<u32, Vec<u32>>,
+    /// Value of scalar constants, used for array lengths
+    constants: HashMap<u32, u64>,
+    /// Id, type and default value
+    spec_constants: Vec<(u32, u32, u64)>,
+    /// Id, pointer type and storage class
+    variables: Vec<(u32, u32, u32)>,
+}
+
+fn operands<const N: usize>(words: &[u32]) -> Result<[u32; N], Error> {
+    words
+        .get(..N)
+        .and_then(|words| words.try_into().ok())
+        .ok_or(Error::InvalidSpirv("Instruction is missing operands"))
+}
+
+/// Nul terminated UTF-8 packed into words
+fn literal_string(words: &[u32]) -> Result<String, Error> {
+    let mut bytes = Vec::new();
+    for word in words {
+        for byte in word.to_le_bytes() {
+            if byte == 0 {
+                return String::from_utf8(bytes).map_err(|_| Error::InvalidSpirv("String is not UTF-8"));
+            }
+            bytes.push(byte);
+        }
+    }
+    Err(Error::InvalidSpirv("String is not terminated"))
+}
+
+/// Value of a 32 or 64 bit literal following the result id
+fn literal_value(words: &[u32]) -> u64 {
+    match words {
+        [low] => *low as u64,
+        [low, high, ..] => *low as u64 | (*high as u64) << 32,
+        [] => 0,
+    }
+}
+
+impl Module {
+    fn instruction(&mut self, opcode: u32, words: &[u32]) -> Result<(), Error> {
+        match opcode {
+            op::NAME => {
+                let [target] = operands(words)?;
+                let name = literal_string(&words[1..])?;
+                self.names.insert(target, name);
+            }
+            op::ENTRY_POINT => {
+                let [execution_model, _function] = operands(words)?;
+                let name = literal_string(&words[2..])?;
+                let stage = execution_model_stage(execution_model);
+                self.entry_points.push(EntryPoint { name, stage });
+            }
+            op::TYPE_BOOL => {
+                let [id] = operands(words)?;
+                self.types.insert(id, Type::Bool);
+            }
+            op::TYPE_INT => {
+                let [id, width, signed] = operands(words)?;
+                self.types.insert(
+                    id,
+                    Type::Int {
+                        width,
+                        signed: signed == 1,
+                    },
+                );
+            }
+            op::TYPE_FLOAT => {
+                let [id, width] = operands(words)?;
+                self.types.insert(id, Type::Float { width });
+            }
+            op::TYPE_VECTOR => {
+                let [id, component, count] = operands(words)?;
+                self.types.insert(id, Type::Vector { component, count });
+            }
+            op::TYPE_MATRIX => {
+                let [id, column, count] = operands(words)?;
+                self.types.insert(id, Type::Matrix { column, count });
+            }
+            op::TYPE_IMAGE => {
+                let [id, _sampled_type, dim, _depth, _arrayed, _multisampled, sampled] = operands(words)?;
+                self.types.insert(id, Type::Image { dim, sampled });
+            }
+            op::TYPE_SAMPLER => {
+                let [id] = operands(words)?;
+                self.types.insert(id, Type::Sampler);
+            }
+            op::TYPE_SAMPLED_IMAGE => {
+                let [id, _image] = operands(words)?;
+                self.types.insert(id, Type::SampledImage);
+            }
+            op::TYPE_ARRAY => {
+                let [id, element, length] = operands(words)?;
+                self.types.insert(id, Type::Array { element, length });
+            }
+            op::TYPE_RUNTIME_ARRAY => {
+                let [id, element] = operands(words)?;
+                self.types.insert(id, Type::RuntimeArray { element });
+            }
+            op::TYPE_STRUCT => {
+                let [id] = operands(words)?;
+                self.types.insert(id, Type::Struct);
+                self.struct_members.insert(id, words[1..].to_vec());
+            }
+            op::TYPE_POINTER => {
+                let [id, _storage_class, pointee] = operands(words)?;
+                self.types.insert(id, Type::Pointer { pointee });
+            }
+            op::TYPE_ACCELERATION_STRUCTURE => {
+                let [id] = operands(words)?;
+                self.types.insert(id, Type::AccelerationStructure);
+            }
+            op::CONSTANT => {
+                let [_type, id] = operands(words)?;
+                self.constants.insert(id, literal_value(&words[2..]));
+            }
+            op::CONSTANT_TRUE | op::CONSTANT_FALSE => {
+                let [_type, id] = operands(words)?;
+                self.constants.insert(id, (opcode == op::CONSTANT_TRUE) as u64);
+            }
+            op::SPEC_CONSTANT => {
+                let [ty, id] = operands(words)?;
+                let value = literal_value(&words[2..]);
+                self.constants.insert(id, value);
+                self.spec_constants.push((id, ty, value));
+            }
+            op::SPEC_CONSTANT_TRUE | op::SPEC_CONSTANT_FALSE => {
+                let [ty, id] = operands(words)?;
+                let value = (opcode == op::SPEC_CONSTANT_TRUE) as u64;
+                self.constants.insert(id, value);
+                self.spec_constants.push((id, ty, value));
+            }
+            op::VARIABLE => {
+                let [ty, id, storage_class] = operands(words)?;
+                self.variables.push((id, ty, storage_class));
+            }
+            op::DECORATE => {
+                let [target, kind] = operands(words)?;
+                let value = words.get(2).copied();
+                let decorations = self.decorations.entry(target).or_default();
+                match kind {
+                    decoration::SPEC_ID => decorations.spec_id = value,
+                    decoration::BUFFER_BLOCK => decorations.buffer_block = true,
+                    decoration::ARRAY_STRIDE => decorations.array_stride = value,
+                    decoration::BUILT_IN => decorations.built_in = true,
+                    decoration::LOCATION => decorations.location = value,
+                    decoration::BINDING => decorations.binding = value,
+                    decoration::DESCRIPTOR_SET => decorations.set = value,
+                    _ => {}
+                }
+            }
+            op::MEMBER_DECORATE => {
+                let [target, member, kind] = operands(words)?;
+                let value = words.get(3).copied();
+                let decorations = self.member_decorations.entry((target, member)).or_default();
+                match kind {
+                    decoration::OFFSET => decorations.offset = value,
+                    decoration::MATRIX_STRIDE => decorations.matrix_stride = value,
+                    decoration::ROW_MAJOR => decorations.row_major = true,
+                    decoration::BUILT_IN => decorations.built_in = true,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn ty(&self, id: u32) -> Result<Type, Error> {
+        self.types
+            .get(&id)
+            .copied()
+            .ok_or(Error::InvalidSpirv("Reference to an undefined type"))
+    }
+
+    fn decorations(&self, id: u32) -> Decorations {
+        self.decorations.get(&id).copied().unwrap_or_default()
+    }
+
+    fn name(&self, id: u32) -> Option<String> {
+        self.names.get(&id).filter(|name| !name.is_empty()).cloned()
+    }
+
+    fn array_length(&self, length: u32) -> Result<u32, Error> {
+        self.constants
+            .get(&length)
+            .map(|length| *length as u32)
+            .ok_or(Error::InvalidSpirv("Array length is not a constant"))
+    }
+
+    /// Element type and element count of arrays, nested arrays multiply
+    fn unwrap_arrays(&self, mut id: u32) -> Result<(u32, u32), Error> {
+        let mut count: u32 = 1;
+        for _ in 0..MAX_TYPE_DEPTH {
+            match self.ty(id)? {
+                Type::Array { element, length } => {
+                    count = count
+                        .checked_mul(self.array_length(length)?)
+                        .ok_or(Error::InvalidSpirv("Array element count overflows"))?;
+                    id = element;
+                }
+                Type::RuntimeArray { element } => {
+                    count = 0;
+                    id = element;
+                }
+                _ => return Ok((id, count)),
+            }
+        }
+        Err(Error::InvalidSpirv("Arrays nested too deep"))
+    }
+
+    fn descriptor_type(&self, storage_class: u32, id: u32) -> Result<Option<vk::DescriptorType>, Error> {
+        let descriptor_type = match (storage_class, self.ty(id)?) {
+            (storage_class::UNIFORM_CONSTANT, Type::SampledImage) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            (storage_class::UNIFORM_CONSTANT, Type::Sampler) => vk::DescriptorType::SAMPLER,
+            (storage_class::UNIFORM_CONSTANT, Type::AccelerationStructure) => vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+            (storage_class::UNIFORM_CONSTANT, Type::Image { dim, sampled }) => match (dim, sampled) {
+                (DIM_SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                (DIM_BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                (DIM_BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                _ => vk::DescriptorType::SAMPLED_IMAGE,
+            },
+            // Before SPIR-V 1.3 storage buffers are uniform blocks decorated as `BufferBlock`
+            (storage_class::UNIFORM, Type::Struct) if self.decorations(id).buffer_block => vk::DescriptorType::STORAGE_BUFFER,
+            (storage_class::UNIFORM, Type::Struct) => vk::DescriptorType::UNIFORM_BUFFER,
+            (storage_class::STORAGE_BUFFER, Type::Struct) => vk::DescriptorType::STORAGE_BUFFER,
+            _ => return Ok(None),
+        };
+        Ok(Some(descriptor_type))
+    }
+
+    fn scalar(&self, id: u32) -> Result<(ScalarKind, u32), Error> {
+        match self.ty(id)? {
+            Type::Bool => Ok((ScalarKind::Bool, 32)),
+            Type::Int { width, signed: true } => Ok((ScalarKind::Int, width)),
+            Type::Int { width, signed: false } => Ok((ScalarKind::UInt, width)),
+            Type::Float { width } => Ok((ScalarKind::Float, width)),
+            _ => Err(Error::InvalidSpirv("Expected a scalar type")),
+        }
+    }
+
+    /// Formats of the locations an input of the type occupies
+    fn input_formats(&self, id: u32, formats: &mut Vec<(u32, vk::Format)>, depth: u32) -> Result<(), Error> {
+        let depth = nested(depth)?;
+        let (kind, width, count) = match self.ty(id)? {
+            Type::Vector { component, count } => {
+                let (kind, width) = self.scalar(component)?;
+                (kind, width, count)
+            }
+            Type::Matrix { column, count } => {
+                for _ in 0..count {
+                    self.input_formats(column, formats, depth)?;
+                }
+                return Ok(());
+            }
+            Type::Array { element, length } => {
+                for _ in 0..self.array_length(length)? {
+                    self.input_formats(element, formats, depth)?;
+                }
+                return Ok(());
+            }
+            _ => {
+                let (kind, width) = self.scalar(id)?;
+                (kind, width, 1)
+            }
+        };
+        let format = input_format(kind, width, count).ok_or(Error::InvalidSpirv("Vertex input type has no matching format"))?;
+        // 64 bit vectors with more than two components take two locations
+        let locations = if width == 64 && count > 2 { 2 } else { 1 };
+        if formats.len() >= MAX_INPUT_LOCATIONS {
+            return Err(Error::InvalidSpirv("Vertex input occupies too many locations"));
+        }
+        formats.push((locations, format));
+        Ok(())
+    }
+
+    /// Size in bytes of a type inside an explicitly laid out block
+    fn block_size(&self, id: u32, depth: u32) -> Result<u32, Error> {
+        let depth = nested(depth)?;
+        let size = match self.ty(id)? {
+            Type::Bool => Some(4),
+            Type::Int { width, .. } | Type::Float { width } => Some(width / 8),
+            Type::Vector { component, count } => self.block_size(component, depth)?.checked_mul(count),
+            Type::Matrix { column, count } => self.block_size(column, depth)?.checked_mul(count),
+            Type::Array { element, length } => {
+                let stride = match self.decorations(id).array_stride {
+                    Some(stride) => stride,
+                    None => self.block_size(element, depth)?,
+                };
+                stride.checked_mul(self.array_length(length)?)
+            }
+            Type::RuntimeArray { .. } => Some(0),
+            // Member offsets are relative to the start of the struct
+            Type::Struct => Some(self.struct_range(id, depth)?.1),
+            _ => return Err(Error::InvalidSpirv("Opaque type inside a block")),
+        };
+        size.ok_or(Error::InvalidSpirv("Block size overflows"))
+    }
+
+    /// Offset of the first member and end of the last member of a block
+    fn struct_range(&self, id: u32, depth: u32) -> Result<(u32, u32), Error> {
+        let members = self
+            .struct_members
+            .get(&id)
+            .ok_or(Error::InvalidSpirv("Reference to an undefined type"))?;
+        let mut start = u32::MAX;
+        let mut end = 0;
+        for (index, member) in members.iter().enumerate() {
+            let decorations = self.member_decorations.get(&(id, index as u32)).copied().unwrap_or_default();
+            let offset = decorations.offset.ok_or(Error::InvalidSpirv("Block member without an offset"))?;
+            let size = match (self.ty(*member)?, decorations.matrix_stride) {
+                (Type::Matrix { column, count }, Some(stride)) => {
+                    let vectors = match (decorations.row_major, self.ty(column)?) {
+                        (true, Type::Vector { count: rows, .. }) => rows,
+                        _ => count,
+                    };
+                    stride.checked_mul(vectors).ok_or(Error::InvalidSpirv("Block size overflows"))?
+                }
+                _ => self.block_size(*member, depth)?,
+            };
+            start = start.min(offset);
+            end = end.max(offset.checked_add(size).ok_or(Error::InvalidSpirv("Block size overflows"))?);
+        }
+        Ok((start.min(end), end))
+    }
+
+    fn is_built_in(&self, variable: u32, pointee: u32) -> bool {
+        if self.decorations(variable).built_in {
+            return true;
+        }
+        // Blocks like `gl_PerVertex` decorate their members
+        self.member_decorations
+            .iter()
+            .any(|((target, _), decorations)| *target == pointee && decorations.built_in)
+    }
+
+    fn reflect(self) -> Result<ShaderReflection, Error> {
+        let stages = self
+            .entry_points
+            .iter()
+            .fold(vk::ShaderStageFlags::empty(), |stages, entry_point| stages | entry_point.stage);
+        let mut reflection = ShaderReflection {
+            entry_points: self.entry_points.clone(),
+            stages,
+            vertex_inputs: Vec::new(),
+            bindings: Vec::new(),
+            push_constant_range: None,
+            specialization_constants: Vec::new(),
+        };
+
+        for (id, ty, storage_class) in self.variables.iter().copied() {
+            let pointee = match self.ty(ty)? {
+                Type::Pointer { pointee, .. } => pointee,
+                _ => return Err(Error::InvalidSpirv("Variable type is not a pointer")),
+            };
+            let decorations = self.decorations(id);
+            match storage_class {
+                storage_class::INPUT if stages.contains(vk::ShaderStageFlags::VERTEX) && !self.is_built_in(id, pointee) => {
+                    let Some(mut location) = decorations.location else {
+                        continue;
+                    };
+                    let mut formats = Vec::new();
+                    self.input_formats(pointee, &mut formats, 0)?;
+                    for (locations, format) in formats {
+                        reflection.vertex_inputs.push(VertexInput {
+                            location,
+                            format,
+                            name: self.name(id),
+                        });
+                        location += locations;
+                    }
+                }
+                storage_class::PUSH_CONSTANT => {
+                    let (offset, end) = self.struct_range(pointee, 0)?;
+                    reflection.push_constant_range = Some(vk::PushConstantRange {
+                        stage_flags: stages,
+                        offset,
+                        size: end - offset,
+                    });
+                }
+                storage_class::UNIFORM_CONSTANT | storage_class::UNIFORM | storage_class::STORAGE_BUFFER => {
+                    let (element, count) = self.unwrap_arrays(pointee)?;
+                    let Some(descriptor_type) = self.descriptor_type(storage_class, element)? else {
+                        continue;
+                    };
+                    let (Some(set), Some(binding)) = (decorations.set, decorations.binding) else {
+                        return Err(Error::InvalidSpirv("Resource without descriptor set or binding"));
+                    };
+                    reflection.bindings.push(DescriptorBinding {
+                        set,
+                        binding,
+                        descriptor_type,
+                        count,
+                        stages,
+                        // Uniform blocks without an instance name have an empty variable name
+                        name: self.name(id).or_else(|| self.name(element)),
+                    });
+                }
+                _ => {}
+            }
+        }
+        reflection.vertex_inputs.sort_by_key(|input| input.location);
+        reflection.bindings.sort_by_key(|binding| (binding.set, binding.binding));
+
+        for (id, ty, default_value) in self.spec_constants.iter().copied() {
+            let Some(spec_id) = self.decorations(id).spec_id else {
+                continue;
+            };
+            let (kind, width) = self.scalar(ty)?;
+            reflection.specialization_constants.push(SpecializationConstant {
+                id: spec_id,
+                kind,
+                size: width / 8,
+                default_value,
+                name: self.name(id),
+            });
+        }
+        reflection.specialization_constants.sort_by_key(|constant| constant.id);
+        Ok(reflection)
+    }
+}
+
+/// Depth of the types nested in one at `depth`, fails instead of recursing forever on self-referencing types
+fn nested(depth: u32) -> Result<u32, Error> {
+    if depth >= MAX_TYPE_DEPTH {
+        return Err(Error::InvalidSpirv("Types nested too deep"));
+    }
+    Ok(depth + 1)
+}
+
+fn input_format(kind: ScalarKind, width: u32, count: u32) -> Option<vk::Format> {
+    use vk::Format as F;
+    let formats = match (kind, width) {
+        (ScalarKind::Float, 16) => [F::R16_SFLOAT, F::R16G16_SFLOAT, F::R16G16B16_SFLOAT, F::R16G16B16A16_SFLOAT],
+        (ScalarKind::Int, 16) => [F::R16_SINT, F::R16G16_SINT, F::R16G16B16_SINT, F::R16G16B16A16_SINT],
+        (ScalarKind::UInt, 16) => [F::R16_UINT, F::R16G16_UINT, F::R16G16B16_UINT, F::R16G16B16A16_UINT],
+        (ScalarKind::Float, 32) => [F::R32_SFLOAT, F::R32G32_SFLOAT, F::R32G32B32_SFLOAT, F::R32G32B32A32_SFLOAT],
+        (ScalarKind::Int, 32) => [F::R32_SINT, F::R32G32_SINT, F::R32G32B32_SINT, F::R32G32B32A32_SINT],
+        (ScalarKind::UInt, 32) => [F::R32_UINT, F::R32G32_UINT, F::R32G32B32_UINT, F::R32G32B32A32_UINT],
+        (ScalarKind::Float, 64) => [F::R64_SFLOAT, F::R64G64_SFLOAT, F::R64G64B64_SFLOAT, F::R64G64B64A64_SFLOAT],
+        (ScalarKind::Int, 64) => [F::R64_SINT, F::R64G64_SINT, F::R64G64B64_SINT, F::R64G64B64A64_SINT],
+        (ScalarKind::UInt, 64) => [F::R64_UINT, F::R64G64_UINT, F::R64G64B64_UINT, F::R64G64B64A64_UINT],
+        _ => return None,
+    };
+    formats.get(count.checked_sub(1)? as usize).copied()
+}
+
+/// Words of a module stored as little endian bytes. Copies, so the bytes don't need to be 4 byte aligned.
+pub fn spirv_words(bytes: &[u8]) -> Result<Vec<u32>, Error> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(Error::InvalidSpirv("Size is not a multiple of 4 bytes"));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect())
+}
+
+/// Interface of a SPIR-V module: entry points, vertex inputs, descriptor bindings, push constants and specialization
+/// constants. Resources are attributed to every stage of the module, modules with several entry points of different
+/// stages report the union.
+#[derive(Clone, Debug)]
+pub struct ShaderReflection {
+    pub entry_points: Vec<EntryPoint>,
+    pub stages: vk::ShaderStageFlags,
+    /// Sorted by location, only filled for vertex shaders
+    pub vertex_inputs: Vec<VertexInput>,
+    /// Sorted by set and binding
+    pub bindings: Vec<DescriptorBinding>,
+    /// Covers the members the module declares, the offset is the one of the first member
+    pub push_constant_range: Option<vk::PushConstantRange>,
+    /// Sorted by constant id
+    pub specialization_constants: Vec<SpecializationConstant>,
+}
+
+impl ShaderReflection {
+    /// Parses a module from its file contents, e.g. a `.spv` file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Self::parse(&spirv_words(bytes)?)
+    }
+
+    pub fn parse(words: &[u32]) -> Result<Self, Error> {
+        if words.len() < HEADER_WORDS {
+            return Err(Error::InvalidSpirv("Module is shorter than the header"));
+        }
+        // Modules written on a machine with the other endianness
+        let swapped: Vec<u32>;
+        let words = match words[0] {
+            MAGIC => words,
+            magic if magic.swap_bytes() == MAGIC => {
+                swapped = words.iter().map(|word| word.swap_bytes()).collect();
+                &swapped
+            }
+            _ => return Err(Error::InvalidSpirv("Missing magic number")),
+        };
+
+        let mut module = Module::default();
+        let mut offset = HEADER_WORDS;
+        while offset < words.len() {
+            let word_count = (words[offset] >> 16) as usize;
+            let opcode = words[offset] & 0xffff;
+            if word_count == 0 || offset + word_count > words.len() {
+                return Err(Error::InvalidSpirv("Truncated instruction"));
+            }
+            module.instruction(opcode, &words[offset + 1..offset + word_count])?;
+            offset += word_count;
+        }
+        module.reflect()
+    }
+
+    pub fn entry_point(&self, stage: vk::ShaderStageFlags) -> Option<&EntryPoint> {
+        self.entry_points.iter().find(|entry_point| entry_point.stage == stage)
+    }
+}
+
+/// Descriptor bindings and push constants of all stages of a pipeline, bindings used by several stages are merged
+#[derive(Clone, Debug, Default)]
+pub struct ReflectedLayout {
+    /// Sorted by set and binding
+    pub bindings: Vec<DescriptorBinding>,
+    /// A single range covering the push constants of every stage, push with all of its stages
+    pub push_constant_range: Option<vk::PushConstantRange>,
+}
+
+fn push_constant_end(range: &vk::PushConstantRange) -> Result<u32, Error> {
+    range
+        .offset
+        .checked_add(range.size)
+        .ok_or(Error::InvalidSpirv("Push constant range overflows"))
+}
+
+impl ReflectedLayout {
+    /// Fails if stages declare different types for the same binding. Array sizes are merged to the largest.
+    pub fn merge(shaders: &[&ShaderReflection]) -> Result<Self, Error> {
+        let mut bindings: BTreeMap<(u32, u32), DescriptorBinding> = BTreeMap::new();
+        let mut push_constant_range: Option<vk::PushConstantRange> = None;
+        for shader in shaders {
+            for binding in &shader.bindings {
+                match bindings.get_mut(&(binding.set, binding.binding)) {
+                    Some(merged) if merged.descriptor_type != binding.descriptor_type => {
+                        return Err(Error::IncompatibleShaderBindings {
+                            set: binding.set,
+                            binding: binding.binding,
+                        });
+                    }
+                    Some(merged) => {
+                        merged.stages |= binding.stages;
+                        merged.count = if merged.count == 0 || binding.count == 0 {
+                            0
+                        } else {
+                            merged.count.max(binding.count)
+                        };
+                        merged.name = merged.name.take().or_else(|| binding.name.clone());
+                    }
+                    None => {
+                        bindings.insert((binding.set, binding.binding), binding.clone());
+                    }
+                }
+            }
+            if let Some(range) = shader.push_constant_range {
+                push_constant_range = Some(match push_constant_range {
+                    Some(merged) => {
+                        let offset = merged.offset.min(range.offset);
+                        let end = push_constant_end(&merged)?.max(push_constant_end(&range)?);
+                        vk::PushConstantRange {
+                            stage_flags: merged.stage_flags | range.stage_flags,
+                            offset,
+                            size: end - offset,
+                        }
+                    }
+                    None => range,
+                });
+            }
+        }
+        Ok(Self {
+            bindings: bindings.into_values().collect(),
+            push_constant_range,
+        })
+    }
+
+    /// Highest set index plus one, sets in between without bindings get empty layouts
+    pub fn set_count(&self) -> u32 {
+        self.bindings.iter().map(|binding| binding.set + 1).max().unwrap_or(0)
+    }
+
+    pub fn set_bindings(&self, set: u32) -> Vec<LayoutBinding> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.set == set)
+            .map(|binding| LayoutBinding::new(binding.binding, binding.descriptor_type, binding.stages).count(binding.count))
+            .collect()
+    }
+
+    /// Layouts of every set from the cache. Sets listed in `external` use the given layout instead, e.g. the one of a
+    /// `BindlessTable`, runtime sized arrays are only allowed in those.
+    pub fn create_set_layouts(
+        &self,
+        cache: &mut DescriptorLayoutCache,
+        external: &[(u32, vk::DescriptorSetLayout)],
+    ) -> Result<Vec<vk::DescriptorSetLayout>, Error> {
+        let set_count = external.iter().map(|(set, _)| set + 1).fold(self.set_count(), u32::max);
+        (0..set_count)
+            .map(|set| match external.iter().find(|(external_set, _)| *external_set == set) {
+                Some((_, layout)) => Ok(*layout),
+                None => {
+                    let bindings = self.set_bindings(set);
+                    if bindings.iter().any(|binding| binding.count == 0) {
+                        return Err(Error::InvalidPipelineConfig(
+                            "Runtime sized descriptor array outside an external set layout",
+                        ));
+                    }
+                    cache.layout(&bindings)
+                }
+            })
+            .collect()
+    }
+
+    pub fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        self.push_constant_range.into_iter().collect()
+    }
+
+    /// Pipeline layout owned by the caller, set layouts are owned by the cache
+    pub fn create_pipeline_layout(
+        &self,
+        device: &ash::Device,
+        cache: &mut DescriptorLayoutCache,
+        external: &[(u32, vk::DescriptorSetLayout)],
+    ) -> Result<vk::PipelineLayout, Error> {
+        let set_layouts = self.create_set_layouts(cache, external)?;
+        let push_constant_ranges = self.push_constant_ranges();
+        let create_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            p_next: null(),
+            flags: vk::PipelineLayoutCreateFlags::empty(),
+            set_layout_count: set_layouts.len() as u32,
+            p_set_layouts: set_layouts.as_ptr(),
+            push_constant_range_count: push_constant_ranges.len() as u32,
+            p_push_constant_ranges: push_constant_ranges.as_ptr(),
+            _marker: Default::default(),
+        };
+        Ok(unsafe { device.create_pipeline_layout(&create_info, None)? })
+    }
+}
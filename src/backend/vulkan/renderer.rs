@@ -0,0 +1,305 @@
+use crate::backend::renderer::{validate_draw, Backend, BufferHandle, ColorVertex, Command, CommandList, Error, Renderer, ResourceSlots};
+use crate::backend::vulkan::base::{Base, BaseConfigBuilder};
+use crate::backend::vulkan::context::{Context, ContextConfigurator};
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors;
+use crate::backend::vulkan::buffer::Buffer;
+use crate::backend::vulkan::frames::{FramesInFlight, DEFAULT_FRAMES_IN_FLIGHT};
+use crate::backend::vulkan::memory::MemoryUsage;
+use crate::backend::vulkan::offscreen::{OffscreenRenderer, OffscreenTarget};
+use crate::backend::vulkan::pipeline::{BlendMode, DepthStencilConfig, GraphicsPipeline, GraphicsPipelineBuilder, VertexInputLayout};
+use crate::backend::vulkan::queue::op_indices::PRESENT;
+use crate::backend::vulkan::shaders::create_embedded_module;
+use crate::backend::vulkan::swapchain::{select_surface_format, Swapchain, SwapchainConfig};
+use crate::image::RgbaImage;
+use crate::utils::{create_pipeline_with_vertex_input, PipelineInfo};
+use ash::vk;
+use std::ptr::null;
+use std::sync::Arc;
+use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use winit::window::Window;
+
+/// Pipeline drawing `ColorVertex` triangle lists from vertex binding 0
+pub fn create_color_vertex_pipeline(device: &Device, render_pass: vk::RenderPass) -> Result<PipelineInfo, errors::Error> {
+    create_color_vertex_pipeline_with_depth(device, render_pass, DepthStencilConfig::disabled())
+}
+
+/// `create_color_vertex_pipeline` for render passes with a depth attachment
+pub fn create_color_vertex_pipeline_with_depth(
+    device: &Device,
+    render_pass: vk::RenderPass,
+    depth_stencil: DepthStencilConfig,
+) -> Result<PipelineInfo, errors::Error> {
+    let vertex_input = VertexInputLayout::new().vertex::<ColorVertex>();
+    create_pipeline_with_vertex_input(device, render_pass, "color_vshader", "color_fshader", vertex_input, depth_stencil)
+}
+
+/// `create_color_vertex_pipeline` for dynamic rendering, depth tested with `LESS` if there is a depth attachment
+pub fn create_color_vertex_rendering_pipeline(
+    device: &Device,
+    color_format: vk::Format,
+    depth_format: Option<vk::Format>,
+) -> Result<GraphicsPipeline, errors::Error> {
+    let vertex_shader = create_embedded_module(device, "color_vshader")?;
+    let fragment_shader = match create_embedded_module(device, "color_fshader") {
+        Ok(fragment_shader) => fragment_shader,
+        Err(error) => {
+            unsafe { device.destroy_shader_module(vertex_shader, None) };
+            return Err(error);
+        }
+    };
+    let mut builder = GraphicsPipelineBuilder::for_dynamic_rendering(&[color_format], depth_format)
+        .vertex_shader(vertex_shader)
+        .fragment_shader(fragment_shader)
+        .vertex_input(VertexInputLayout::new().vertex::<ColorVertex>())
+        .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::CLOCKWISE)
+        .color_attachments(&[BlendMode::Alpha]);
+    if depth_format.is_some() {
+        builder = builder.depth(vk::CompareOp::LESS, true);
+    }
+    let pipeline = builder.build(device);
+    // The pipeline doesn't need the modules once it is created
+    unsafe {
+        device.destroy_shader_module(vertex_shader, None);
+        device.destroy_shader_module(fragment_shader, None);
+    }
+    pipeline
+}
+
+/// Host visible vertex buffer, written once at creation
+struct VertexBuffer {
+    buffer: Buffer,
+    length: usize,
+}
+
+/// Blits the offscreen target into the swapchain images
+struct WindowPresenter {
+    swapchain: Swapchain,
+    frames: FramesInFlight,
+}
+
+impl WindowPresenter {
+    /// The target has to hold a finished frame in `TRANSFER_SRC_OPTIMAL`
+    fn present(&mut self, context: &Context, target: &OffscreenTarget) -> Result<(), errors::Error> {
+        let image_available = self.frames.begin_frame()?.image_available();
+        // Nothing to present to while minimized, the frame is reused on the next attempt
+        let Some(image_index) = self.swapchain.acquire_next_image(context, image_available)? else {
+            return Ok(());
+        };
+        let render_finished = self.frames.image_acquired(image_index, self.swapchain.image_count())?;
+
+        let device = context.device();
+        let command_buffer = self.frames.current().command_buffer();
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next: null(),
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            p_inheritance_info: null(),
+            _marker: Default::default(),
+        };
+        let subresource = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let corner = |extent: vk::Extent2D| vk::Offset3D {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: 1,
+        };
+        // Scales if the swapchain extent was clamped to something other than the window size
+        let blit = vk::ImageBlit {
+            src_subresource: subresource,
+            src_offsets: [vk::Offset3D::default(), corner(target.extent())],
+            dst_subresource: subresource,
+            dst_offsets: [vk::Offset3D::default(), corner(self.swapchain.extent())],
+        };
+        unsafe {
+            device.begin_command_buffer(command_buffer, &begin_info)?;
+            self.swapchain.record_transfer_barrier(command_buffer, image_index);
+            device.cmd_blit_image(
+                command_buffer,
+                target.image(),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.swapchain.image(image_index),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[blit],
+                vk::Filter::LINEAR,
+            );
+            self.swapchain.record_transfer_present_barrier(command_buffer, image_index);
+            device.end_command_buffer(command_buffer)?;
+        }
+        self.frames
+            .submit(context.graphics_queue()?, image_index, vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)?;
+
+        let present_queue = context.present_queue().ok_or(errors::Error::MissingQueue(PRESENT))?;
+        self.swapchain.present(present_queue, image_index, render_finished)
+    }
+}
+
+/// `Renderer` rendering into an offscreen target on a Vulkan context. Windowed renderers blit the target into
+/// a swapchain image to present it.
+pub struct VulkanRenderer {
+    buffers: ResourceSlots<VertexBuffer>,
+    renderer: OffscreenRenderer,
+    presenter: Option<WindowPresenter>,
+    last_frame: Option<RgbaImage>,
+    context: Context,
+    // Dropped after the surface, which the context's device owns
+    window: Option<Arc<Window>>,
+}
+
+impl VulkanRenderer {
+    /// Creates its own instance and headless context, see `create_renderer`
+    pub fn headless(width: u32, height: u32, validation: bool) -> Result<Self, errors::Error> {
+        let mut base_config_builder = BaseConfigBuilder::new().use_headless_vulkan_extensions();
+        if validation {
+            base_config_builder = base_config_builder.use_khronos_validation();
+        }
+        let base = Base::new(base_config_builder.build("Eikon", "Eikon", "1.3.0", "1.0.0", "1.0.0"))?;
+        let context = Context::new(base, ContextConfigurator::headless(&[]))?;
+        Self::new(context, width, height)
+    }
+
+    /// Creates its own instance and a context presenting to the window, see `create_renderer`
+    pub fn windowed(window: Arc<Window>, width: u32, height: u32, validation: bool) -> Result<Self, errors::Error> {
+        let mut base_config_builder = BaseConfigBuilder::new().use_core_vulkan_extensions();
+        if validation {
+            base_config_builder = base_config_builder.use_khronos_validation();
+        }
+        let base = Base::new(base_config_builder.build("Eikon", "Eikon", "1.3.0", "1.0.0", "1.0.0"))?;
+        let configurator = ContextConfigurator::new(
+            window.window_handle().map_err(|_| errors::Error::UnsupportedWindowHandle)?.as_raw(),
+            window.display_handle().map_err(|_| errors::Error::UnsupportedDisplayHandle)?.as_raw(),
+            &["VK_KHR_swapchain"],
+        );
+        let context = Context::new(base, configurator)?;
+
+        // UNORM like the target, so the blit copies the values without an sRGB conversion
+        let swapchain_config = SwapchainConfig::new()
+            .surface_formats(&[vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_UNORM])
+            .image_usage(vk::ImageUsageFlags::TRANSFER_DST);
+        let surface_format = select_surface_format(&context, &swapchain_config)?;
+        let window_extent = vk::Extent2D { width, height };
+        let swapchain = Swapchain::new(&context, vk::RenderPass::null(), surface_format, window_extent, swapchain_config)?;
+        let frames = FramesInFlight::new(&context, DEFAULT_FRAMES_IN_FLIGHT)?;
+
+        let mut renderer = Self::new(context, width, height)?;
+        renderer.presenter = Some(WindowPresenter { swapchain, frames });
+        renderer.window = Some(window);
+        Ok(renderer)
+    }
+
+    /// Renders offscreen only, `present` fails with `Error::NoWindow`
+    pub fn new(context: Context, width: u32, height: u32) -> Result<Self, errors::Error> {
+        let renderer = OffscreenRenderer::with_pipeline(&context, vk::Extent2D { width, height }, create_color_vertex_pipeline)?;
+        Ok(Self {
+            buffers: ResourceSlots::new(),
+            renderer,
+            presenter: None,
+            last_frame: None,
+            context,
+            window: None,
+        })
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+}
+
+impl Renderer for VulkanRenderer {
+    fn backend(&self) -> Backend {
+        Backend::Vulkan
+    }
+
+    fn extent(&self) -> (u32, u32) {
+        let extent = self.renderer.target().extent();
+        (extent.width, extent.height)
+    }
+
+    fn create_vertex_buffer(&mut self, vertices: &[ColorVertex]) -> Result<BufferHandle, Error> {
+        let buffer = Buffer::with_data(&self.context, vertices, vk::BufferUsageFlags::VERTEX_BUFFER, MemoryUsage::CpuToGpu)?;
+        Ok(self.buffers.insert(VertexBuffer {
+            buffer,
+            length: vertices.len(),
+        }))
+    }
+
+    fn destroy_buffer(&mut self, buffer: BufferHandle) -> Result<(), Error> {
+        // Submits are synchronous, the buffer can't be in use anymore
+        self.buffers.remove(buffer).map(|_| ())
+    }
+
+    fn submit(&mut self, commands: &CommandList) -> Result<(), Error> {
+        let mut draws = Vec::with_capacity(commands.commands().len());
+        for command in commands.commands() {
+            match *command {
+                Command::Draw {
+                    buffer,
+                    first_vertex,
+                    vertex_count,
+                } => {
+                    let vertex_buffer = self.buffers.get(buffer)?;
+                    validate_draw(buffer, vertex_buffer.length, first_vertex, vertex_count)?;
+                    draws.push((vertex_buffer.buffer.handle(), first_vertex, vertex_count));
+                }
+            }
+        }
+
+        if self.presenter.is_some() {
+            // The last present may still be blitting from the target
+            self.context.wait_idle()?;
+        }
+        let pipeline = self.renderer.pipeline_info().pipeline[0];
+        let image = self.renderer.render_with(commands.clear_color(), |device, command_buffer| unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            for (buffer, first_vertex, vertex_count) in draws {
+                device.cmd_bind_vertex_buffers(command_buffer, 0, &[buffer], &[0]);
+                device.cmd_draw(command_buffer, vertex_count, 1, first_vertex, 0);
+            }
+        })?;
+        self.last_frame = Some(image);
+        Ok(())
+    }
+
+    fn read_pixels(&mut self) -> Result<RgbaImage, Error> {
+        match &self.last_frame {
+            Some(image) => Ok(image.clone()),
+            None => {
+                let (width, height) = self.extent();
+                Ok(RgbaImage::new(width, height))
+            }
+        }
+    }
+
+    fn present(&mut self) -> Result<(), Error> {
+        let presenter = self.presenter.as_mut().ok_or(Error::NoWindow)?;
+        // The target has no contents before the first submit
+        if self.last_frame.is_none() {
+            return Ok(());
+        }
+        presenter.present(&self.context, self.renderer.target())?;
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        let extent = vk::Extent2D { width, height };
+        if let Some(presenter) = self.presenter.as_mut() {
+            presenter.swapchain.resize(extent);
+        }
+        if width == 0 || height == 0 || extent == self.renderer.target().extent() {
+            return Ok(());
+        }
+        self.renderer = OffscreenRenderer::with_pipeline(&self.context, extent, create_color_vertex_pipeline)?;
+        self.last_frame = None;
+        Ok(())
+    }
+
+    fn set_vsync(&mut self, vsync: bool) {
+        if let Some(presenter) = self.presenter.as_mut() {
+            presenter.swapchain.set_vsync(vsync);
+        }
+    }
+}
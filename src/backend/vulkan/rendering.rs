@@ -0,0 +1,194 @@
+use crate::backend::vulkan::depth::has_stencil;
+use crate::backend::vulkan::image::layout_access;
+use ash::vk;
+use std::ptr::null;
+
+/// One attachment of a dynamic rendering pass. Loads and stores by default, `clear` replaces the load.
+#[derive(Clone, Copy)]
+pub struct AttachmentConfig {
+    pub view: vk::ImageView,
+    /// Layout the image is in during rendering, the transition is up to the caller
+    pub layout: vk::ImageLayout,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    pub clear_value: vk::ClearValue,
+    /// Single sample view the attachment is resolved into at the end of rendering
+    pub resolve: Option<(vk::ImageView, vk::ResolveModeFlags)>,
+}
+
+impl AttachmentConfig {
+    pub fn new(view: vk::ImageView, layout: vk::ImageLayout) -> Self {
+        Self {
+            view,
+            layout,
+            load_op: vk::AttachmentLoadOp::LOAD,
+            store_op: vk::AttachmentStoreOp::STORE,
+            clear_value: vk::ClearValue::default(),
+            resolve: None,
+        }
+    }
+
+    pub fn color(view: vk::ImageView) -> Self {
+        Self::new(view, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+    }
+
+    /// Depth and stencil buffers are usually cleared and not needed afterwards, use `store` to keep them
+    pub fn depth(view: vk::ImageView) -> Self {
+        Self::new(view, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .clear_depth(1.0, 0)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+    }
+
+    pub fn clear_color(mut self, color: [f32; 4]) -> Self {
+        self.load_op = vk::AttachmentLoadOp::CLEAR;
+        self.clear_value = vk::ClearValue {
+            color: vk::ClearColorValue { float32: color },
+        };
+        self
+    }
+
+    pub fn clear_depth(mut self, depth: f32, stencil: u32) -> Self {
+        self.load_op = vk::AttachmentLoadOp::CLEAR;
+        self.clear_value = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue { depth, stencil },
+        };
+        self
+    }
+
+    /// Previous contents are undefined, cheaper than loading when everything is overwritten anyway
+    pub fn discard(mut self) -> Self {
+        self.load_op = vk::AttachmentLoadOp::DONT_CARE;
+        self
+    }
+
+    pub fn store_op(mut self, store_op: vk::AttachmentStoreOp) -> Self {
+        self.store_op = store_op;
+        self
+    }
+
+    pub fn resolve(mut self, view: vk::ImageView, mode: vk::ResolveModeFlags) -> Self {
+        self.resolve = Some((view, mode));
+        self
+    }
+
+    pub fn info(&self) -> vk::RenderingAttachmentInfo<'static> {
+        let (resolve_image_view, resolve_mode) = self.resolve.unwrap_or((vk::ImageView::null(), vk::ResolveModeFlags::NONE));
+        vk::RenderingAttachmentInfo {
+            s_type: vk::StructureType::RENDERING_ATTACHMENT_INFO,
+            p_next: null(),
+            image_view: self.view,
+            image_layout: self.layout,
+            resolve_mode,
+            resolve_image_view,
+            resolve_image_layout: self.layout,
+            load_op: self.load_op,
+            store_op: self.store_op,
+            clear_value: self.clear_value,
+            _marker: Default::default(),
+        }
+    }
+}
+
+/// Attachments and area of a `vkCmdBeginRendering` pass, the replacement for render pass and framebuffer objects.
+/// Pipelines used inside have to be built with matching formats, see `GraphicsPipelineBuilder::for_dynamic_rendering`.
+#[derive(Clone)]
+pub struct RenderingConfig {
+    pub render_area: vk::Rect2D,
+    pub layer_count: u32,
+    pub color_attachments: Vec<AttachmentConfig>,
+    pub depth_attachment: Option<AttachmentConfig>,
+    pub stencil_attachment: Option<AttachmentConfig>,
+}
+
+impl RenderingConfig {
+    /// Renders to the whole `extent`
+    pub fn new(extent: vk::Extent2D) -> Self {
+        Self {
+            render_area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            },
+            layer_count: 1,
+            color_attachments: Vec::new(),
+            depth_attachment: None,
+            stencil_attachment: None,
+        }
+    }
+
+    pub fn color_attachment(mut self, attachment: AttachmentConfig) -> Self {
+        self.color_attachments.push(attachment);
+        self
+    }
+
+    /// Uses the attachment for the stencil aspect too if `format` has one
+    pub fn depth_attachment(mut self, attachment: AttachmentConfig, format: vk::Format) -> Self {
+        self.depth_attachment = Some(attachment);
+        if has_stencil(format) {
+            self.stencil_attachment = Some(attachment);
+        }
+        self
+    }
+
+    pub fn begin(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        let color_attachments: Vec<vk::RenderingAttachmentInfo> = self.color_attachments.iter().map(|attachment| attachment.info()).collect();
+        let depth_attachment = self.depth_attachment.map(|attachment| attachment.info());
+        let stencil_attachment = self.stencil_attachment.map(|attachment| attachment.info());
+        let rendering_info = vk::RenderingInfo {
+            s_type: vk::StructureType::RENDERING_INFO,
+            p_next: null(),
+            flags: vk::RenderingFlags::empty(),
+            render_area: self.render_area,
+            layer_count: self.layer_count,
+            view_mask: 0,
+            color_attachment_count: color_attachments.len() as u32,
+            p_color_attachments: color_attachments.as_ptr(),
+            p_depth_attachment: depth_attachment.as_ref().map_or(null(), |attachment| attachment as *const _),
+            p_stencil_attachment: stencil_attachment.as_ref().map_or(null(), |attachment| attachment as *const _),
+            _marker: Default::default(),
+        };
+        unsafe { device.cmd_begin_rendering(command_buffer, &rendering_info) };
+    }
+
+    pub fn end(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        unsafe { device.cmd_end_rendering(command_buffer) };
+    }
+}
+
+/// Layout transition for images without layout tracking, e.g. swapchain images. Access and stages come from
+/// `layout_access` of `old_layout`, with `discard` the previous contents are dropped by transitioning from `UNDEFINED`.
+pub fn record_layout_transition(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    range: vk::ImageSubresourceRange,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    discard: bool,
+) {
+    let (src_access_mask, src_stage) = layout_access(old_layout, true);
+    let (dst_access_mask, dst_stage) = layout_access(new_layout, false);
+    let barrier = vk::ImageMemoryBarrier {
+        s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+        p_next: null(),
+        src_access_mask,
+        dst_access_mask,
+        old_layout: if discard { vk::ImageLayout::UNDEFINED } else { old_layout },
+        new_layout,
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range: range,
+        _marker: Default::default(),
+    };
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage,
+            dst_stage,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier],
+        )
+    };
+}
@@ -0,0 +1,262 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::reflection::{spirv_words, ShaderReflection};
+use ash::vk;
+use log::{info, trace};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr::null;
+
+/// Module compiled by build.rs from `shaders/`
+pub struct EmbeddedShader {
+    /// Source path relative to `shaders/` without extension, permutations append their defines, see `glslc::permutation_name`
+    pub name: &'static str,
+    /// From the source extension, empty if the module has to be reflected to know it
+    pub stage: vk::ShaderStageFlags,
+    /// Permutation defines, `NAME` or `NAME=VALUE`
+    pub defines: &'static [&'static str],
+    pub code: &'static [u8],
+}
+
+/// Every shader build.rs compiled, part of the binary
+pub static EMBEDDED_SHADERS: &[EmbeddedShader] = include!(concat!(env!("OUT_DIR"), "/embedded_shaders.rs"));
+
+pub fn embedded_shader(name: &str) -> Option<&'static EmbeddedShader> {
+    EMBEDDED_SHADERS.iter().find(|shader| shader.name == name)
+}
+
+/// Names are paths relative to the shader directory without extension, separated by `/` on every platform.
+/// A trailing `.spv` is ignored, so file names resolve too.
+pub fn shader_name(path: &str) -> String {
+    let name = path.replace('\\', "/");
+    match name.strip_suffix(".spv") {
+        Some(name) => name.to_string(),
+        None => name,
+    }
+}
+
+pub fn create_shader_module(device: &ash::Device, code: &[u32]) -> Result<vk::ShaderModule, Error> {
+    let create_info = vk::ShaderModuleCreateInfo {
+        s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
+        p_next: null(),
+        flags: vk::ShaderModuleCreateFlags::empty(),
+        code_size: code.len() * 4,
+        p_code: code.as_ptr(),
+        _marker: Default::default(),
+    };
+    Ok(unsafe { device.create_shader_module(&create_info, None)? })
+}
+
+/// Splits a variant name from `glslc::permutation_name` into the shader name and its defines
+pub fn split_permutation_name(name: &str) -> (&str, Vec<&str>) {
+    match name.strip_suffix(']').and_then(|name| name.split_once('[')) {
+        Some((name, defines)) => (name, defines.split(',').collect()),
+        None => (name, Vec::new()),
+    }
+}
+
+/// Module of an embedded shader owned by the caller
+pub fn create_embedded_module(device: &ash::Device, name: &str) -> Result<vk::ShaderModule, Error> {
+    let shader = embedded_shader(name).ok_or_else(|| Error::ShaderNotFound {
+        name: name.to_string(),
+        stage: vk::ShaderStageFlags::empty(),
+    })?;
+    create_shader_module(device, &spirv_words(shader.code)?)
+}
+
+/// Where a library shader was loaded from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShaderOrigin {
+    Embedded,
+    File(PathBuf),
+    /// Added from memory with `ShaderLibrary::add`
+    Memory,
+}
+
+/// A module of the library with its reflection, one per entry point stage
+pub struct LibraryShader {
+    module: vk::ShaderModule,
+    entry_point: CString,
+    reflection: ShaderReflection,
+    origin: ShaderOrigin,
+}
+
+impl LibraryShader {
+    pub fn module(&self) -> vk::ShaderModule {
+        self.module
+    }
+
+    pub fn entry_point(&self) -> &CString {
+        &self.entry_point
+    }
+
+    pub fn reflection(&self) -> &ShaderReflection {
+        &self.reflection
+    }
+
+    pub fn origin(&self) -> &ShaderOrigin {
+        &self.origin
+    }
+}
+
+/// Owns shader modules and resolves them by name and stage, see `shader_name`. Modules come from the SPIR-V embedded
+/// by build.rs, compiled modules found in a directory or memory. Adding a module under an existing name and stage
+/// replaces and destroys the old one, pipelines already created from it are unaffected.
+pub struct ShaderLibrary {
+    device: Device,
+    shaders: HashMap<(String, vk::ShaderStageFlags), LibraryShader>,
+}
+
+impl ShaderLibrary {
+    pub fn new(context: &Context) -> Self {
+        Self {
+            device: context.device().clone(),
+            shaders: HashMap::new(),
+        }
+    }
+
+    /// Library with every embedded shader
+    pub fn with_embedded(context: &Context) -> Result<Self, Error> {
+        let mut library = Self::new(context);
+        library.add_embedded()?;
+        Ok(library)
+    }
+
+    pub fn add_embedded(&mut self) -> Result<(), Error> {
+        for shader in EMBEDDED_SHADERS {
+            self.add_with_origin(shader.name, shader.stage, shader.code, ShaderOrigin::Embedded)?;
+        }
+        Ok(())
+    }
+
+    /// Adds every `.spv` file below `dir`, returns how many modules were found
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<usize, Error> {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+        find_modules(dir, &mut files)?;
+        for path in files.iter() {
+            let relative = path.strip_prefix(dir).unwrap_or(path);
+            let name = shader_name(&relative.with_extension("").to_string_lossy());
+            let code = fs::read(path)?;
+            self.add_with_origin(&name, vk::ShaderStageFlags::empty(), &code, ShaderOrigin::File(path.clone()))?;
+        }
+        info!("Loaded {} shader modules from {}", files.len(), dir.display());
+        Ok(files.len())
+    }
+
+    /// Adds the module under `name` for every stage it has an entry point for. `stage` limits it to one stage, empty
+    /// takes the stages from the module.
+    pub fn add(&mut self, name: &str, stage: vk::ShaderStageFlags, code: &[u8]) -> Result<(), Error> {
+        self.add_with_origin(name, stage, code, ShaderOrigin::Memory)
+    }
+
+    pub fn add_with_origin(&mut self, name: &str, stage: vk::ShaderStageFlags, code: &[u8], origin: ShaderOrigin) -> Result<(), Error> {
+        let name = shader_name(name);
+        let words = spirv_words(code)?;
+        let reflection = ShaderReflection::parse(&words)?;
+        let entry_points: Vec<_> = reflection
+            .entry_points
+            .iter()
+            .filter(|entry_point| stage.is_empty() || entry_point.stage == stage)
+            .collect();
+        if entry_points.is_empty() {
+            return Err(Error::ShaderNotFound { name, stage });
+        }
+
+        for entry_point in entry_points {
+            let shader = LibraryShader {
+                module: create_shader_module(&self.device, &words)?,
+                entry_point: CString::new(entry_point.name.as_str()).map_err(|_| Error::InvalidSpirv("Entry point name contains a nul"))?,
+                reflection: reflection.clone(),
+                origin: origin.clone(),
+            };
+            trace!("Added shader {} ({:?})", name, entry_point.stage);
+            if let Some(replaced) = self.shaders.insert((name.clone(), entry_point.stage), shader) {
+                unsafe { self.device.destroy_shader_module(replaced.module, None) };
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str, stage: vk::ShaderStageFlags) -> Option<&LibraryShader> {
+        self.shaders.get(&(shader_name(name), stage))
+    }
+
+    /// Module of the shader, the library keeps owning it
+    pub fn module(&self, name: &str, stage: vk::ShaderStageFlags) -> Result<vk::ShaderModule, Error> {
+        self.get(name, stage)
+            .map(|shader| shader.module)
+            .ok_or_else(|| Error::ShaderNotFound {
+                name: shader_name(name),
+                stage,
+            })
+    }
+
+    /// Variant of the shader compiled with the most of `defines` and no others. Defines the shader has no permutation
+    /// for are ignored, so one set of defines can select the variants of every stage of a pipeline. Variants with the
+    /// same number of matching defines are picked by name.
+    pub fn variant(&self, name: &str, stage: vk::ShaderStageFlags, defines: &[String]) -> Option<&LibraryShader> {
+        let name = shader_name(name);
+        self.shaders
+            .iter()
+            .filter(|((_, shader_stage), _)| *shader_stage == stage)
+            .filter_map(|((variant_name, _), shader)| {
+                let (base_name, variant_defines) = split_permutation_name(variant_name);
+                let matches = base_name == name && variant_defines.iter().all(|define| defines.iter().any(|key| key == define));
+                matches.then_some((variant_defines.len(), variant_name, shader))
+            })
+            .max_by(|(count, name, _), (other_count, other_name, _)| count.cmp(other_count).then(other_name.cmp(name)))
+            .map(|(_, _, shader)| shader)
+    }
+
+    pub fn contains(&self, name: &str, stage: vk::ShaderStageFlags) -> bool {
+        self.get(name, stage).is_some()
+    }
+
+    /// Destroys the module, returns false if there is none
+    pub fn remove(&mut self, name: &str, stage: vk::ShaderStageFlags) -> bool {
+        match self.shaders.remove(&(shader_name(name), stage)) {
+            Some(shader) => {
+                unsafe { self.device.destroy_shader_module(shader.module, None) };
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Names and stages of all shaders, in no particular order
+    pub fn shaders(&self) -> impl Iterator<Item = (&str, vk::ShaderStageFlags)> {
+        self.shaders.keys().map(|(name, stage)| (name.as_str(), *stage))
+    }
+
+    pub fn len(&self) -> usize {
+        self.shaders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shaders.is_empty()
+    }
+}
+
+impl Drop for ShaderLibrary {
+    fn drop(&mut self) {
+        for shader in self.shaders.values() {
+            unsafe { self.device.destroy_shader_module(shader.module, None) };
+        }
+    }
+}
+
+fn find_modules(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_modules(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "spv") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -1,59 +1,52 @@
 use crate::backend::vulkan::base::Base;
-use crate::{fatal_assert, fatal_unwrap, fatal_unwrap_e};
+use crate::backend::vulkan::errors::Error;
 use ash::vk::PhysicalDevice;
 use ash::{khr, vk};
-use log::{error, info, trace, warn};
 use std::ptr::null;
 use winit::raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 
 pub struct Surface {
-    raw_window_handle: RawWindowHandle,
-    raw_display_handle: RawDisplayHandle,
     surface_instance: khr::surface::Instance,
     pub surface: vk::SurfaceKHR,
 }
 
 impl Surface {
-    pub fn new(base: &Base, raw_window_handle: RawWindowHandle, raw_display_handle: RawDisplayHandle) -> Self {
+    pub fn new(base: &Base, raw_window_handle: RawWindowHandle, raw_display_handle: RawDisplayHandle) -> Result<Self, Error> {
         let surface_instance = khr::surface::Instance::new(&base.ash_instance, &base.vulkan_instance);
-        let surface = Self::create_surface(base, raw_window_handle, raw_display_handle);
-        Self {
+        let surface = Self::create_surface(base, raw_window_handle, raw_display_handle)?;
+        Ok(Self {
             surface_instance,
             surface,
-            raw_window_handle,
-            raw_display_handle,
-        }
+        })
     }
-    fn create_surface(base: &Base, raw_window_handle: RawWindowHandle, raw_display_handle: RawDisplayHandle) -> vk::SurfaceKHR {
-        let raw_window_handle = raw_window_handle;
+    fn create_surface(
+        base: &Base,
+        raw_window_handle: RawWindowHandle,
+        raw_display_handle: RawDisplayHandle,
+    ) -> Result<vk::SurfaceKHR, Error> {
         match raw_window_handle {
-            /// Win32
+            // Win32
             RawWindowHandle::Win32(raw_handle) => {
                 let win32_surface_loader = khr::win32_surface::Instance::new(&base.ash_instance, &base.vulkan_instance);
                 let surface_info = vk::Win32SurfaceCreateInfoKHR {
                     s_type: vk::StructureType::WIN32_SURFACE_CREATE_INFO_KHR,
                     p_next: null(),
                     flags: Default::default(),
-                    hinstance: raw_handle.hinstance.unwrap().get(),
+                    hinstance: raw_handle.hinstance.map_or(0, |hinstance| hinstance.get()),
                     hwnd: raw_handle.hwnd.get(),
                     _marker: Default::default(),
                 };
 
-                let platform_surface = unsafe {
-                    fatal_unwrap_e!(
-                        win32_surface_loader.create_win32_surface(&surface_info, None),
-                        "Failed to create surface! {}"
-                    )
-                };
-                platform_surface
+                let platform_surface = unsafe { win32_surface_loader.create_win32_surface(&surface_info, None)? };
+                Ok(platform_surface)
             }
 
-            /// Linux
+            // Linux
             RawWindowHandle::Wayland(raw_handle) => {
                 let wayland_surface_loader = khr::wayland_surface::Instance::new(&base.ash_instance, &base.vulkan_instance);
                 let display = match raw_display_handle {
                     RawDisplayHandle::Wayland(display) => display.display,
-                    _ => fatal_assert!("Wayland surfaces must be created with a Wayland display handle!"),
+                    _ => return Err(Error::UnsupportedDisplayHandle),
                 };
 
                 let surface_info = vk::WaylandSurfaceCreateInfoKHR {
@@ -64,105 +57,71 @@ impl Surface {
                     surface: raw_handle.surface.as_ptr(),
                     _marker: Default::default(),
                 };
-                let platform_surface = unsafe {
-                    fatal_unwrap_e!(
-                        wayland_surface_loader.create_wayland_surface(&surface_info, None),
-                        "Failed to create surface! {}"
-                    )
-                };
-                platform_surface
+                let platform_surface = unsafe { wayland_surface_loader.create_wayland_surface(&surface_info, None)? };
+                Ok(platform_surface)
             }
             RawWindowHandle::Xcb(raw_handle) => {
                 let xcb_surface_loader = khr::xcb_surface::Instance::new(&base.ash_instance, &base.vulkan_instance);
-                let mut display = match raw_display_handle {
+                let display = match raw_display_handle {
                     RawDisplayHandle::Xcb(display) => display,
-                    _ => fatal_assert!("XCB surfaces must be created with a XCB display handle!"),
+                    _ => return Err(Error::UnsupportedDisplayHandle),
                 };
 
                 let surface_info = vk::XcbSurfaceCreateInfoKHR {
                     s_type: vk::StructureType::XCB_SURFACE_CREATE_INFO_KHR,
                     p_next: null(),
                     flags: Default::default(),
-                    connection: fatal_unwrap!(display.connection, "Failed to get XCB connection!").as_ptr(),
+                    connection: display.connection.ok_or(Error::UnsupportedDisplayHandle)?.as_ptr(),
                     window: raw_handle.window.get(),
                     _marker: Default::default(),
                 };
-                let platform_surface = unsafe {
-                    fatal_unwrap_e!(
-                        xcb_surface_loader.create_xcb_surface(&surface_info, None),
-                        "Failed to create surface! {}"
-                    )
-                };
-                platform_surface
+                let platform_surface = unsafe { xcb_surface_loader.create_xcb_surface(&surface_info, None)? };
+                Ok(platform_surface)
             }
             RawWindowHandle::Xlib(raw_handle) => {
                 let xlib_surface_loader = khr::xlib_surface::Instance::new(&base.ash_instance, &base.vulkan_instance);
                 let display = match raw_display_handle {
                     RawDisplayHandle::Xlib(display) => display,
-                    _ => fatal_assert!("Xlib surfaces must be created with a Xlib display handle!"),
+                    _ => return Err(Error::UnsupportedDisplayHandle),
                 };
 
                 let surface_info = vk::XlibSurfaceCreateInfoKHR {
                     s_type: vk::StructureType::XLIB_SURFACE_CREATE_INFO_KHR,
                     p_next: null(),
                     flags: Default::default(),
-                    dpy: fatal_unwrap!(display.display, "Failed to get Xlib display!").as_ptr(),
+                    dpy: display.display.ok_or(Error::UnsupportedDisplayHandle)?.as_ptr(),
                     window: raw_handle.window,
                     _marker: Default::default(),
                 };
-                let platform_surface = unsafe {
-                    fatal_unwrap_e!(
-                        xlib_surface_loader.create_xlib_surface(&surface_info, None),
-                        "Failed to create surface! {}"
-                    )
-                };
-                platform_surface
+                let platform_surface = unsafe { xlib_surface_loader.create_xlib_surface(&surface_info, None)? };
+                Ok(platform_surface)
             }
 
-            (_) => {
-                fatal_assert!("Unsupported window handle type!");
-            }
+            _ => Err(Error::UnsupportedWindowHandle),
         }
     }
 
-    pub fn get_physical_device_surface_capabilities(&self, physical_device: &PhysicalDevice) -> vk::SurfaceCapabilitiesKHR {
-        unsafe {
-            fatal_unwrap_e!(
-                self.surface_instance
-                    .get_physical_device_surface_capabilities(*physical_device, self.surface),
-                "Failed to get surface capabilities! {}"
-            )
-        }
+    pub fn get_physical_device_surface_capabilities(&self, physical_device: &PhysicalDevice) -> Result<vk::SurfaceCapabilitiesKHR, Error> {
+        let result = unsafe { self.surface_instance.get_physical_device_surface_capabilities(*physical_device, self.surface)? };
+        Ok(result)
     }
 
-    pub fn get_physical_device_surface_formats(&self, physical_device: &PhysicalDevice) -> Vec<vk::SurfaceFormatKHR> {
-        unsafe {
-            fatal_unwrap_e!(
-                self.surface_instance
-                    .get_physical_device_surface_formats(*physical_device, self.surface),
-                "Failed to get surface formats! {}"
-            )
-        }
+    pub fn get_physical_device_surface_formats(&self, physical_device: &PhysicalDevice) -> Result<Vec<vk::SurfaceFormatKHR>, Error> {
+        let result = unsafe { self.surface_instance.get_physical_device_surface_formats(*physical_device, self.surface)? };
+        Ok(result)
     }
 
-    pub fn get_physical_device_surface_present_modes(&self, physical_device: &PhysicalDevice) -> Vec<vk::PresentModeKHR> {
-        unsafe {
-            fatal_unwrap_e!(
-                self.surface_instance
-                    .get_physical_device_surface_present_modes(*physical_device, self.surface),
-                "Failed to get surface present modes! {}"
-            )
-        }
+    pub fn get_physical_device_surface_present_modes(&self, physical_device: &PhysicalDevice) -> Result<Vec<vk::PresentModeKHR>, Error> {
+        let result = unsafe { self.surface_instance.get_physical_device_surface_present_modes(*physical_device, self.surface)? };
+        Ok(result)
     }
 
-    pub fn get_physical_device_surface_support(&self, physical_device: PhysicalDevice, queue_family_index: u32) -> bool {
-        unsafe {
-            fatal_unwrap_e!(
-                self.surface_instance
-                    .get_physical_device_surface_support(physical_device, queue_family_index, self.surface),
-                "Failed to get surface support! {}"
-            )
-        }
+    pub fn get_physical_device_surface_support(&self, physical_device: PhysicalDevice, queue_family_index: u32) -> Result<bool, Error> {
+        let supported = unsafe {
+            self.surface_instance
+                .get_physical_device_surface_support(physical_device, queue_family_index, self.surface)?
+        };
+        Ok(supported)
     }
 }
 
@@ -0,0 +1,500 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::depth::DepthBuffer;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::{ImageView, ImageViewConfig};
+use crate::backend::vulkan::queue::op_indices::{GRAPHICS, PRESENT};
+use crate::backend::vulkan::rendering::record_layout_transition;
+use ash::{khr, vk};
+use log::{info, trace, warn};
+use std::ptr::null;
+
+/// Ordered preferences for the swapchain, the first supported entry wins
+#[derive(Clone, Debug)]
+pub struct SwapchainConfig {
+    /// Used when vsync is off. FIFO is the fallback since every implementation supports it.
+    pub present_modes: Vec<vk::PresentModeKHR>,
+    pub surface_formats: Vec<vk::Format>,
+    pub color_spaces: Vec<vk::ColorSpaceKHR>,
+    /// Forces FIFO: no tearing and frames are limited to the display refresh rate
+    pub vsync: bool,
+    /// Depth attachment created along with the images, the render pass has to use the same format (see `select_depth_format`)
+    pub depth_format: Option<vk::Format>,
+    /// Usage of the images besides `COLOR_ATTACHMENT`, e.g. `TRANSFER_DST` to blit into them
+    pub image_usage: vk::ImageUsageFlags,
+}
+
+impl SwapchainConfig {
+    pub fn new() -> Self {
+        Self {
+            present_modes: vec![vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::FIFO_RELAXED],
+            surface_formats: vec![
+                vk::Format::B8G8R8A8_SRGB,
+                vk::Format::R8G8B8A8_SRGB,
+                vk::Format::B8G8R8A8_UNORM,
+                vk::Format::R8G8B8A8_UNORM,
+            ],
+            color_spaces: vec![vk::ColorSpaceKHR::SRGB_NONLINEAR],
+            vsync: true,
+            depth_format: None,
+            image_usage: vk::ImageUsageFlags::empty(),
+        }
+    }
+
+    pub fn present_modes(mut self, present_modes: &[vk::PresentModeKHR]) -> Self {
+        self.present_modes = present_modes.to_vec();
+        self
+    }
+
+    pub fn surface_formats(mut self, surface_formats: &[vk::Format]) -> Self {
+        self.surface_formats = surface_formats.to_vec();
+        self
+    }
+
+    pub fn color_spaces(mut self, color_spaces: &[vk::ColorSpaceKHR]) -> Self {
+        self.color_spaces = color_spaces.to_vec();
+        self
+    }
+
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    pub fn depth_format(mut self, depth_format: vk::Format) -> Self {
+        self.depth_format = Some(depth_format);
+        self
+    }
+
+    pub fn image_usage(mut self, image_usage: vk::ImageUsageFlags) -> Self {
+        self.image_usage = image_usage;
+        self
+    }
+}
+
+impl Default for SwapchainConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// First supported format in preference order, color spaces are preferred in order for each format.
+/// Falls back to the first available format with a warning if none of the preferences is supported.
+pub fn choose_surface_format(available: &[vk::SurfaceFormatKHR], config: &SwapchainConfig) -> Option<vk::SurfaceFormatKHR> {
+    for format in config.surface_formats.iter() {
+        for color_space in config.color_spaces.iter() {
+            let preferred = available
+                .iter()
+                .find(|available| available.format == *format && available.color_space == *color_space);
+            if let Some(surface_format) = preferred {
+                return Some(*surface_format);
+            }
+        }
+    }
+    let fallback = available.first().copied();
+    if let Some(surface_format) = fallback {
+        warn!(
+            "None of the preferred surface formats is supported, using {:?} {:?}",
+            surface_format.format, surface_format.color_space
+        );
+    }
+    fallback
+}
+
+/// FIFO with vsync, otherwise the first supported preference and FIFO if none is supported
+pub fn choose_present_mode(available: &[vk::PresentModeKHR], config: &SwapchainConfig) -> vk::PresentModeKHR {
+    if config.vsync {
+        return vk::PresentModeKHR::FIFO;
+    }
+    config
+        .present_modes
+        .iter()
+        .find(|present_mode| available.contains(present_mode))
+        .copied()
+        .unwrap_or(vk::PresentModeKHR::FIFO)
+}
+
+/// Resolves the format preferences of `config` against the surface. Done before creating the swapchain
+/// because the render pass has to be created with the same format.
+pub fn select_surface_format(context: &Context, config: &SwapchainConfig) -> Result<vk::SurfaceFormatKHR, Error> {
+    let surface = context.surface().ok_or(Error::HeadlessContext)?;
+    let formats = surface.get_physical_device_surface_formats(&context.physical_device().device)?;
+    choose_surface_format(&formats, config).ok_or(Error::NoSurfaceFormats)
+}
+
+/// Surfaces with a fixed size report it as the current extent, otherwise the window size is clamped to the allowed range.
+/// A zero sized extent means the window is minimized.
+pub fn select_extent(capabilities: &vk::SurfaceCapabilitiesKHR, window_extent: vk::Extent2D) -> vk::Extent2D {
+    if capabilities.current_extent.width != u32::MAX {
+        return capabilities.current_extent;
+    }
+    vk::Extent2D {
+        width: window_extent
+            .width
+            .clamp(capabilities.min_image_extent.width, capabilities.max_image_extent.width),
+        height: window_extent
+            .height
+            .clamp(capabilities.min_image_extent.height, capabilities.max_image_extent.height),
+    }
+}
+
+/// One more than the minimum so the driver never blocks us, limited by the maximum (0 means no limit)
+pub fn select_image_count(capabilities: &vk::SurfaceCapabilitiesKHR) -> u32 {
+    let image_count = capabilities.min_image_count + 1;
+    match capabilities.max_image_count {
+        0 => image_count,
+        max_image_count => image_count.min(max_image_count),
+    }
+}
+
+/// Owns the swapchain with its images, image views, depth buffer and framebuffers for `render_pass`.
+/// Everything is recreated when the surface changes (resize, out of date, suboptimal), the previous swapchain
+/// is passed as `oldSwapchain` so presentation continues smoothly. While the window is minimized there is no
+/// image to acquire and frames are skipped.
+pub struct Swapchain {
+    device: Device,
+    loader: khr::swapchain::Device,
+    swapchain: vk::SwapchainKHR,
+    render_pass: vk::RenderPass,
+    config: SwapchainConfig,
+    surface_format: vk::SurfaceFormatKHR,
+    present_mode: vk::PresentModeKHR,
+    extent: vk::Extent2D,
+    window_extent: vk::Extent2D,
+    images: Vec<vk::Image>,
+    image_views: Vec<ImageView>,
+    /// Shared by all images, the render pass dependency keeps frames from writing it at the same time
+    depth_buffer: Option<DepthBuffer>,
+    framebuffers: Vec<vk::Framebuffer>,
+    needs_recreation: bool,
+}
+
+impl Swapchain {
+    /// `surface_format` comes from `select_surface_format` with the same `config`, `render_pass` has to be
+    /// compatible with it and `config.depth_format` and outlive the swapchain. With a null `render_pass` no
+    /// framebuffers are created, rendering goes through `rendering::RenderingConfig` instead.
+    pub fn new(
+        context: &Context,
+        render_pass: vk::RenderPass,
+        surface_format: vk::SurfaceFormatKHR,
+        window_extent: vk::Extent2D,
+        config: SwapchainConfig,
+    ) -> Result<Self, Error> {
+        if context.is_headless() {
+            return Err(Error::HeadlessContext);
+        }
+        let mut swapchain = Self {
+            device: context.device().clone(),
+            loader: khr::swapchain::Device::new(&context.base().vulkan_instance, context.device()),
+            swapchain: vk::SwapchainKHR::null(),
+            render_pass,
+            config,
+            surface_format,
+            present_mode: vk::PresentModeKHR::FIFO,
+            extent: vk::Extent2D { width: 0, height: 0 },
+            window_extent,
+            images: Vec::new(),
+            image_views: Vec::new(),
+            depth_buffer: None,
+            framebuffers: Vec::new(),
+            needs_recreation: true,
+        };
+        swapchain.recreate(context)?;
+        Ok(swapchain)
+    }
+
+    pub fn handle(&self) -> vk::SwapchainKHR {
+        self.swapchain
+    }
+
+    pub fn surface_format(&self) -> vk::SurfaceFormatKHR {
+        self.surface_format
+    }
+
+    /// Present mode selected from the configuration for the current swapchain
+    pub fn present_mode(&self) -> vk::PresentModeKHR {
+        self.present_mode
+    }
+
+    pub fn config(&self) -> &SwapchainConfig {
+        &self.config
+    }
+
+    pub fn vsync(&self) -> bool {
+        self.config.vsync
+    }
+
+    /// Switches between FIFO and the present mode preferences, the swapchain is recreated before the next acquire
+    pub fn set_vsync(&mut self, vsync: bool) {
+        if vsync != self.config.vsync {
+            self.config.vsync = vsync;
+            self.needs_recreation = true;
+        }
+    }
+
+    /// Size of the swapchain images, zero while minimized
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.extent.width == 0 || self.extent.height == 0
+    }
+
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn image(&self, image_index: u32) -> vk::Image {
+        self.images[image_index as usize]
+    }
+
+    pub fn image_view(&self, image_index: u32) -> vk::ImageView {
+        self.image_views[image_index as usize].handle()
+    }
+
+    /// Depth buffer matching the current extent if the config has a depth format
+    pub fn depth_buffer(&self) -> Option<&DepthBuffer> {
+        self.depth_buffer.as_ref()
+    }
+
+    pub fn depth_buffer_mut(&mut self) -> Option<&mut DepthBuffer> {
+        self.depth_buffer.as_mut()
+    }
+
+    /// Only available if the swapchain was created with a render pass
+    pub fn framebuffer(&self, image_index: u32) -> vk::Framebuffer {
+        self.framebuffers[image_index as usize]
+    }
+
+    /// Call on window resize events, the swapchain is recreated before the next acquire
+    pub fn resize(&mut self, window_extent: vk::Extent2D) {
+        if window_extent != self.window_extent {
+            self.window_extent = window_extent;
+            self.needs_recreation = true;
+        }
+    }
+
+    /// Acquires the next image, recreating the swapchain first if needed. `semaphore` is signaled once the image
+    /// is ready. Returns `None` (and leaves `semaphore` unsignaled) if there is nothing to render to, e.g. while minimized.
+    pub fn acquire_next_image(&mut self, context: &Context, semaphore: vk::Semaphore) -> Result<Option<u32>, Error> {
+        // A second attempt right after recreating covers surfaces that changed between the two calls
+        for _ in 0..2 {
+            if self.needs_recreation {
+                self.recreate(context)?;
+            }
+            if self.is_minimized() {
+                return Ok(None);
+            }
+
+            match unsafe { self.loader.acquire_next_image(self.swapchain, u64::MAX, semaphore, vk::Fence::null()) } {
+                Ok((image_index, suboptimal)) => {
+                    // The semaphore is signaled, the image has to be presented before recreating
+                    if suboptimal {
+                        self.needs_recreation = true;
+                    }
+                    return Ok(Some(image_index));
+                }
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.needs_recreation = true,
+                Err(result) => return Err(result.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Presents the image after `wait_semaphore` is signaled. Out of date and suboptimal swapchains
+    /// are recreated on the next acquire.
+    pub fn present(&mut self, queue: vk::Queue, image_index: u32, wait_semaphore: vk::Semaphore) -> Result<(), Error> {
+        let present_info = vk::PresentInfoKHR {
+            s_type: vk::StructureType::PRESENT_INFO_KHR,
+            p_next: null(),
+            wait_semaphore_count: 1,
+            p_wait_semaphores: &wait_semaphore,
+            swapchain_count: 1,
+            p_swapchains: &self.swapchain,
+            p_image_indices: &image_index,
+            p_results: std::ptr::null_mut(),
+            _marker: Default::default(),
+        };
+        match unsafe { self.loader.queue_present(queue, &present_info) } {
+            Ok(false) => Ok(()),
+            Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.needs_recreation = true;
+                Ok(())
+            }
+            Err(result) => Err(result.into()),
+        }
+    }
+
+    /// Recreates the swapchain for the current surface size. Waits for the device to be idle so the
+    /// old images are no longer in use. While minimized the old swapchain is kept and nothing is recreated.
+    pub fn recreate(&mut self, context: &Context) -> Result<(), Error> {
+        let surface = context.surface().ok_or(Error::HeadlessContext)?;
+        let physical_device = &context.physical_device().device;
+        let capabilities = surface.get_physical_device_surface_capabilities(physical_device)?;
+        let extent = select_extent(&capabilities, self.window_extent);
+        if extent.width == 0 || extent.height == 0 {
+            trace!("Surface is zero sized, postponing swapchain recreation");
+            self.extent = extent;
+            self.needs_recreation = true;
+            return Ok(());
+        }
+
+        let image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT | self.config.image_usage;
+        if !capabilities.supported_usage_flags.contains(image_usage) {
+            return Err(Error::UnsupportedSwapchainUsage(image_usage));
+        }
+
+        context.wait_idle()?;
+        let present_modes = surface.get_physical_device_surface_present_modes(physical_device)?;
+        self.present_mode = choose_present_mode(&present_modes, &self.config);
+
+        let queue_family_indices = [
+            context.queue_family_index(GRAPHICS).ok_or(Error::MissingQueue(GRAPHICS))?,
+            context.queue_family_index(PRESENT).ok_or(Error::MissingQueue(PRESENT))?,
+        ];
+        let (image_sharing_mode, queue_family_index_count) = if queue_family_indices[0] != queue_family_indices[1] {
+            (vk::SharingMode::CONCURRENT, 2)
+        } else {
+            (vk::SharingMode::EXCLUSIVE, 0)
+        };
+
+        let old_swapchain = self.swapchain;
+        let swapchain_create_info = vk::SwapchainCreateInfoKHR {
+            s_type: vk::StructureType::SWAPCHAIN_CREATE_INFO_KHR,
+            p_next: null(),
+            flags: Default::default(),
+            surface: surface.surface,
+            min_image_count: select_image_count(&capabilities),
+            image_format: self.surface_format.format,
+            image_color_space: self.surface_format.color_space,
+            image_extent: extent,
+            image_array_layers: 1,
+            image_usage,
+            image_sharing_mode,
+            queue_family_index_count,
+            p_queue_family_indices: queue_family_indices.as_ptr(),
+            pre_transform: capabilities.current_transform,
+            composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
+            present_mode: self.present_mode,
+            clipped: vk::TRUE,
+            old_swapchain,
+            _marker: Default::default(),
+        };
+        let swapchain = unsafe { self.loader.create_swapchain(&swapchain_create_info, None) };
+
+        // The old swapchain is retired either way, it can't be used anymore
+        self.destroy_image_resources();
+        unsafe { self.loader.destroy_swapchain(old_swapchain, None) };
+        match swapchain {
+            Ok(swapchain) => self.swapchain = swapchain,
+            Err(result) => {
+                self.swapchain = vk::SwapchainKHR::null();
+                return Err(result.into());
+            }
+        }
+        self.extent = extent;
+
+        self.images = unsafe { self.loader.get_swapchain_images(self.swapchain)? };
+        if let Some(depth_format) = self.config.depth_format {
+            self.depth_buffer = Some(DepthBuffer::new(context, depth_format, extent, vk::SampleCountFlags::TYPE_1)?);
+        }
+        for image in self.images.iter() {
+            let view = ImageView::new(&self.device, *image, &ImageViewConfig::color_2d(self.surface_format.format))?;
+            if self.render_pass != vk::RenderPass::null() {
+                let framebuffer = self.create_framebuffer(view.handle())?;
+                self.framebuffers.push(framebuffer);
+            }
+            self.image_views.push(view);
+        }
+        self.needs_recreation = false;
+        info!(
+            "Created swapchain with {} images of {}x{}, {:?} {:?}, {:?}",
+            self.images.len(),
+            extent.width,
+            extent.height,
+            self.surface_format.format,
+            self.surface_format.color_space,
+            self.present_mode
+        );
+        Ok(())
+    }
+
+    /// Dynamic rendering: moves the image to `COLOR_ATTACHMENT_OPTIMAL` after the acquire semaphore wait at the color
+    /// output stage, the previous contents are discarded
+    pub fn record_rendering_barrier(&self, command_buffer: vk::CommandBuffer, image_index: u32) {
+        let image = self.image(image_index);
+        let range = ImageViewConfig::color_2d(self.surface_format.format).subresource_range;
+        let (old_layout, new_layout) = (vk::ImageLayout::PRESENT_SRC_KHR, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        record_layout_transition(&self.device, command_buffer, image, range, old_layout, new_layout, true);
+    }
+
+    /// Dynamic rendering: moves the image to `PRESENT_SRC_KHR` once rendering is done
+    pub fn record_present_barrier(&self, command_buffer: vk::CommandBuffer, image_index: u32) {
+        let image = self.image(image_index);
+        let range = ImageViewConfig::color_2d(self.surface_format.format).subresource_range;
+        let (old_layout, new_layout) = (vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::ImageLayout::PRESENT_SRC_KHR);
+        record_layout_transition(&self.device, command_buffer, image, range, old_layout, new_layout, false);
+    }
+
+    /// Blits and copies: moves the image to `TRANSFER_DST_OPTIMAL` after the acquire semaphore wait at the color output
+    /// stage, the previous contents are discarded. Needs `TRANSFER_DST` in `SwapchainConfig::image_usage`.
+    pub fn record_transfer_barrier(&self, command_buffer: vk::CommandBuffer, image_index: u32) {
+        let image = self.image(image_index);
+        let range = ImageViewConfig::color_2d(self.surface_format.format).subresource_range;
+        let (old_layout, new_layout) = (vk::ImageLayout::PRESENT_SRC_KHR, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+        record_layout_transition(&self.device, command_buffer, image, range, old_layout, new_layout, true);
+    }
+
+    /// Blits and copies: moves the image to `PRESENT_SRC_KHR` once the transfer is done
+    pub fn record_transfer_present_barrier(&self, command_buffer: vk::CommandBuffer, image_index: u32) {
+        let image = self.image(image_index);
+        let range = ImageViewConfig::color_2d(self.surface_format.format).subresource_range;
+        let (old_layout, new_layout) = (vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::PRESENT_SRC_KHR);
+        record_layout_transition(&self.device, command_buffer, image, range, old_layout, new_layout, false);
+    }
+
+    fn create_framebuffer(&self, view: vk::ImageView) -> Result<vk::Framebuffer, Error> {
+        let mut attachments = vec![view];
+        if let Some(depth_buffer) = &self.depth_buffer {
+            attachments.push(depth_buffer.view().handle());
+        }
+        let create_info = vk::FramebufferCreateInfo {
+            s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
+            p_next: null(),
+            flags: Default::default(),
+            render_pass: self.render_pass,
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            width: self.extent.width,
+            height: self.extent.height,
+            layers: 1,
+            _marker: Default::default(),
+        };
+        let framebuffer = unsafe { self.device.create_framebuffer(&create_info, None)? };
+        Ok(framebuffer)
+    }
+
+    fn destroy_image_resources(&mut self) {
+        unsafe {
+            for framebuffer in self.framebuffers.drain(..) {
+                self.device.destroy_framebuffer(framebuffer, None);
+            }
+        }
+        self.image_views.clear();
+        self.depth_buffer = None;
+        // Images belong to the swapchain
+        self.images.clear();
+    }
+}
+
+impl Drop for Swapchain {
+    fn drop(&mut self) {
+        unsafe {
+            // Presentation may still read the images, they are destroyed even if the wait fails
+            let _ = self.device.device_wait_idle();
+        }
+        self.destroy_image_resources();
+        unsafe { self.loader.destroy_swapchain(self.swapchain, None) };
+    }
+}
@@ -0,0 +1,282 @@
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::{full_mip_level_count, mip_extent, Image, ImageConfig, ImageKind, ImageView};
+use crate::backend::vulkan::queue::op_indices::GRAPHICS;
+use crate::backend::vulkan::upload::UploadManager;
+use crate::texture::{bc, is_block_compressed, level_size, TextureData};
+use ash::vk;
+use log::{info, warn};
+use std::borrow::Cow;
+use std::ptr::null;
+
+/// Sampled image with a view of all its levels and layers, in `SHADER_READ_ONLY_OPTIMAL` once loaded
+pub struct Texture {
+    // Declared before the image so it is destroyed first
+    view: ImageView,
+    image: Image,
+}
+
+impl Texture {
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    pub fn view(&self) -> &ImageView {
+        &self.view
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.image.format()
+    }
+
+    pub fn extent(&self) -> vk::Extent3D {
+        self.image.extent()
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.image.mip_levels()
+    }
+}
+
+/// Format a texture is uploaded in. Its own format if the device can sample it with optimal tiling, otherwise the
+/// format the CPU decoder produces. The second value tells if the data has to be decompressed first. Formats without a
+/// CPU decoder, ETC2, EAC and the SNORM BC formats, fail with `Error::UnsupportedTextureFormat` on devices that can't
+/// sample them.
+pub fn select_texture_format(format: vk::Format, properties: vk::FormatProperties) -> Result<(vk::Format, bool), Error> {
+    if properties.optimal_tiling_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE) {
+        return Ok((format, false));
+    }
+    match bc::decompressed_format(format) {
+        Some(decompressed) => Ok((decompressed, true)),
+        None => Err(Error::UnsupportedTextureFormat(format)),
+    }
+}
+
+/// `TextureData` decoded to the format `bc::decompressed_format` reports
+fn decompress_texture(data: &TextureData) -> Result<TextureData, Error> {
+    let format = bc::decompressed_format(data.format).ok_or(Error::UnsupportedTextureFormat(data.format))?;
+    let mut levels = Vec::with_capacity(data.levels.len());
+    for (level, level_data) in data.levels.iter().enumerate() {
+        let extent = data.level_extent(level as u32);
+        let layer_size = level_size(data.format, extent).unwrap();
+        let mut pixels = Vec::with_capacity(level_size(format, extent).unwrap() * data.array_layers as usize);
+        for layer in level_data.chunks_exact(layer_size) {
+            pixels.extend_from_slice(&bc::decompress(data.format, extent, layer)?);
+        }
+        levels.push(pixels);
+    }
+    Ok(TextureData {
+        format,
+        levels,
+        ..data.clone()
+    })
+}
+
+/// Turns `TextureData` into `Texture`s. The levels are uploaded through an `UploadManager`, missing mip levels are
+/// generated with `vkCmdBlitImage` on the graphics queue which also acquires the uploaded levels. Loading blocks
+/// until the texture is ready to be sampled.
+pub struct TextureLoader {
+    device: Device,
+    queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    generate_mips: bool,
+}
+
+impl TextureLoader {
+    pub fn new(context: &Context) -> Result<Self, Error> {
+        let device = context.device();
+        let queue = context.graphics_queue()?;
+        let mut loader = Self {
+            device: device.clone(),
+            queue,
+            command_pool: vk::CommandPool::null(),
+            command_buffer: vk::CommandBuffer::null(),
+            fence: vk::Fence::null(),
+            generate_mips: true,
+        };
+
+        let command_pool_create_info = vk::CommandPoolCreateInfo {
+            s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+            p_next: null(),
+            flags: vk::CommandPoolCreateFlags::TRANSIENT | vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+            queue_family_index: context.queue_family_index(GRAPHICS).ok_or(Error::MissingQueue(GRAPHICS))?,
+            _marker: Default::default(),
+        };
+        loader.command_pool = unsafe { device.create_command_pool(&command_pool_create_info, None)? };
+
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            p_next: null(),
+            command_pool: loader.command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+            command_buffer_count: 1,
+            _marker: Default::default(),
+        };
+        loader.command_buffer = unsafe { device.allocate_command_buffers(&command_buffer_allocate_info)?[0] };
+
+        let fence_create_info = vk::FenceCreateInfo {
+            s_type: vk::StructureType::FENCE_CREATE_INFO,
+            p_next: null(),
+            flags: vk::FenceCreateFlags::empty(),
+            _marker: Default::default(),
+        };
+        loader.fence = unsafe { device.create_fence(&fence_create_info, None)? };
+        Ok(loader)
+    }
+
+    /// Generate the mip chain of textures that only come with their base level, on by default
+    pub fn generate_mips(mut self, generate_mips: bool) -> Self {
+        self.generate_mips = generate_mips;
+        self
+    }
+
+    pub fn load(&mut self, context: &Context, uploads: &mut UploadManager, data: &TextureData) -> Result<Texture, Error> {
+        data.validate()?;
+        let (format, decompress) = select_texture_format(data.format, context.format_properties(data.format))?;
+        let data = if decompress {
+            info!("Device can't sample {:?}, decompressing to {:?} on the CPU", data.format, format);
+            Cow::Owned(decompress_texture(data)?)
+        } else {
+            Cow::Borrowed(data)
+        };
+
+        let features = context.format_properties(format).optimal_tiling_features;
+        let full_chain = full_mip_level_count(data.extent);
+        let provided_levels = data.levels.len() as u32;
+        let mut mip_levels = provided_levels;
+        let mut blit_filter = None;
+        if self.generate_mips && provided_levels < full_chain {
+            let blittable = features.contains(vk::FormatFeatureFlags::BLIT_SRC | vk::FormatFeatureFlags::BLIT_DST);
+            if blittable && !is_block_compressed(format) {
+                mip_levels = full_chain;
+                blit_filter = Some(if features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR) {
+                    vk::Filter::LINEAR
+                } else {
+                    vk::Filter::NEAREST
+                });
+            } else {
+                warn!("Can't generate mips for {:?}, the texture keeps its {} levels", format, provided_levels);
+            }
+        }
+
+        let extent = data.extent;
+        let config = match data.kind {
+            ImageKind::D2 => ImageConfig::new_2d(format, extent.width, extent.height).array_layers(data.array_layers),
+            ImageKind::D2Array => ImageConfig::new_2d_array(format, extent.width, extent.height, data.array_layers),
+            ImageKind::Cube => ImageConfig::new_cube(format, extent.width).array_layers(data.array_layers),
+            ImageKind::D3 => ImageConfig::new_3d(format, extent.width, extent.height, extent.depth),
+        };
+        let mut usage = vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST;
+        if blit_filter.is_some() {
+            usage |= vk::ImageUsageFlags::TRANSFER_SRC;
+        }
+        let mut image = Image::new(context, config.mip_levels(mip_levels).usage(usage))?;
+
+        let mut ticket = None;
+        for (level, level_data) in data.levels.iter().enumerate() {
+            ticket = Some(uploads.upload_image(&mut image, level as u32, level_data)?);
+        }
+        if let Some(ticket) = ticket {
+            uploads.wait(ticket)?;
+        }
+
+        let command_buffer = self.command_buffer;
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next: null(),
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            p_inheritance_info: null(),
+            _marker: Default::default(),
+        };
+        unsafe { self.device.begin_command_buffer(command_buffer, &begin_info)? };
+        uploads.record_acquire_barriers(command_buffer)?;
+        if let Some(filter) = blit_filter {
+            self.record_mip_generation(command_buffer, &mut image, provided_levels, filter);
+        }
+        let range = image.full_range();
+        image.transition(command_buffer, range, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        unsafe { self.device.end_command_buffer(command_buffer)? };
+
+        let submit_info = vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            p_next: null(),
+            wait_semaphore_count: 0,
+            p_wait_semaphores: null(),
+            p_wait_dst_stage_mask: null(),
+            command_buffer_count: 1,
+            p_command_buffers: &command_buffer,
+            signal_semaphore_count: 0,
+            p_signal_semaphores: null(),
+            _marker: Default::default(),
+        };
+        unsafe {
+            self.device.reset_fences(&[self.fence])?;
+            self.device.queue_submit(self.queue, &[submit_info], self.fence)?;
+            self.device.wait_for_fences(&[self.fence], true, u64::MAX)?;
+        }
+
+        let view = image.create_view()?;
+        Ok(Texture { view, image })
+    }
+
+    /// Blits every level from `first_level` on from the one above it, leaves the source levels in `TRANSFER_SRC_OPTIMAL`
+    fn record_mip_generation(&self, command_buffer: vk::CommandBuffer, image: &mut Image, first_level: u32, filter: vk::Filter) {
+        let (aspect_mask, layer_count) = (image.aspect_mask(), image.array_layers());
+        let level_range = |level| vk::ImageSubresourceRange {
+            aspect_mask,
+            base_mip_level: level,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count,
+        };
+        let level_layers = |level| vk::ImageSubresourceLayers {
+            aspect_mask,
+            mip_level: level,
+            base_array_layer: 0,
+            layer_count,
+        };
+        let far_corner = |extent: vk::Extent3D| vk::Offset3D {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: extent.depth as i32,
+        };
+
+        for level in first_level..image.mip_levels() {
+            let (source, destination) = (level_range(level - 1), level_range(level));
+            let (source_layers, destination_layers) = (level_layers(level - 1), level_layers(level));
+            image.transition(command_buffer, source, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+            image.transition(command_buffer, destination, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+            let blit = vk::ImageBlit {
+                src_subresource: source_layers,
+                src_offsets: [vk::Offset3D::default(), far_corner(mip_extent(image.extent(), level - 1))],
+                dst_subresource: destination_layers,
+                dst_offsets: [vk::Offset3D::default(), far_corner(mip_extent(image.extent(), level))],
+            };
+            unsafe {
+                self.device.cmd_blit_image(
+                    command_buffer,
+                    image.handle(),
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    image.handle(),
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[blit],
+                    filter,
+                )
+            };
+        }
+    }
+}
+
+impl Drop for TextureLoader {
+    fn drop(&mut self) {
+        unsafe {
+            // Mip generation may still be running, the pool goes even if the wait fails
+            let _ = self.device.queue_wait_idle(self.queue);
+            self.device.destroy_fence(self.fence, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
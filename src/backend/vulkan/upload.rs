@@ -0,0 +1,859 @@
+use crate::backend::vulkan::buffer::{Buffer, Pod};
+use crate::backend::vulkan::context::Context;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::{layout_access, mip_extent, Image};
+use crate::backend::vulkan::memory::MemoryUsage;
+use crate::backend::vulkan::queue::op_indices::{GRAPHICS, TRANSFER};
+use crate::texture::format_block;
+use ash::vk;
+use std::collections::VecDeque;
+use std::ptr::null;
+
+pub const DEFAULT_STAGING_SIZE: vk::DeviceSize = 32 * 1024 * 1024;
+/// Staging offsets are aligned to this, enough for every texel block size
+const STAGING_ALIGNMENT: vk::DeviceSize = 16;
+
+/// Ring allocator over the staging buffer. Space is handed out at the head and released at the tail in the
+/// order it was allocated, a batch releases everything it allocated once the GPU is done with it.
+#[derive(Debug)]
+pub struct StagingRing {
+    capacity: vk::DeviceSize,
+    head: vk::DeviceSize,
+    tail: vk::DeviceSize,
+    /// Bytes between tail and head, including the bytes skipped when wrapping around
+    used: vk::DeviceSize,
+}
+
+impl StagingRing {
+    pub fn new(capacity: vk::DeviceSize) -> Self {
+        Self {
+            capacity,
+            head: 0,
+            tail: 0,
+            used: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> vk::DeviceSize {
+        self.capacity
+    }
+
+    pub fn used(&self) -> vk::DeviceSize {
+        self.used
+    }
+
+    /// Returns the offset and the bytes consumed from the ring, which includes alignment and wrap around padding
+    pub fn allocate(&mut self, size: vk::DeviceSize, alignment: vk::DeviceSize) -> Option<(vk::DeviceSize, vk::DeviceSize)> {
+        if size > self.capacity {
+            return None;
+        }
+        if self.used == 0 {
+            self.head = 0;
+            self.tail = 0;
+        } else if self.head == self.tail {
+            // Full
+            return None;
+        }
+
+        let aligned = self.head.div_ceil(alignment.max(1)) * alignment.max(1);
+        let (offset, end) = if self.head >= self.tail {
+            if aligned + size <= self.capacity {
+                (aligned, aligned + size)
+            } else if size <= self.tail {
+                // Wraps around, the rest of the ring is skipped
+                (0, size)
+            } else {
+                return None;
+            }
+        } else if aligned + size <= self.tail {
+            (aligned, aligned + size)
+        } else {
+            return None;
+        };
+
+        let consumed = if end > self.head {
+            end - self.head
+        } else {
+            self.capacity - self.head + end
+        };
+        self.head = end % self.capacity;
+        self.used += consumed;
+        Some((offset, consumed))
+    }
+
+    /// Releases the oldest `size` bytes, the sum of what one batch consumed
+    pub fn release(&mut self, size: vk::DeviceSize) {
+        debug_assert!(size <= self.used, "Released more than was allocated");
+        self.tail = (self.tail + size) % self.capacity;
+        self.used -= size;
+    }
+}
+
+/// Identifies a submitted batch of uploads, later batches have larger tickets
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UploadTicket(u64);
+
+struct Batch {
+    ticket: u64,
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    ring_bytes: vk::DeviceSize,
+    copies: usize,
+    /// Image ranges the graphics family has to release before the copies run
+    releases: Vec<ImageRelease>,
+    /// Graphics command buffer with the releases and the semaphore the copies wait on, once submitted
+    release_submit: Option<(vk::CommandBuffer, vk::Semaphore)>,
+}
+
+/// Image range owned by the graphics family that is moved to the transfer family for a re-upload
+struct ImageRelease {
+    image: vk::Image,
+    range: vk::ImageSubresourceRange,
+    old_layout: vk::ImageLayout,
+}
+
+enum AcquireResource {
+    Buffer {
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    },
+    /// Stays in the layout it was uploaded in
+    Image {
+        image: vk::Image,
+        range: vk::ImageSubresourceRange,
+        layout: vk::ImageLayout,
+    },
+}
+
+/// Resource waiting for its acquire barrier on the graphics queue
+struct PendingAcquire {
+    ticket: u64,
+    resource: AcquireResource,
+}
+
+/// Streams data into GPU only resources without blocking the render thread.
+///
+/// Data is copied into a persistently mapped staging ring and the copies are recorded on the transfer queue.
+/// `flush` submits them as one batch with its own fence and returns its ticket, `is_complete` polls it.
+/// The render thread only waits if the ring is full. With a dedicated transfer queue family the buffers are released
+/// to the graphics family after the copy, `record_acquire_barriers` records the matching acquire on a graphics command
+/// buffer once the batch completed. Destination buffers and images have to stay alive until then.
+/// Images that were already in use on the graphics queue are released by a command buffer `flush` submits to the
+/// graphics queue, the copies wait for it with a semaphore.
+pub struct UploadManager {
+    device: Device,
+    staging: Buffer,
+    ring: StagingRing,
+    command_pool: vk::CommandPool,
+    queue: vk::Queue,
+    /// Only used with a dedicated transfer queue, `graphics_pool` is null otherwise
+    graphics_queue: vk::Queue,
+    graphics_pool: vk::CommandPool,
+    transfer_family: u32,
+    graphics_family: u32,
+    recording: Option<Batch>,
+    in_flight: VecDeque<Batch>,
+    /// Finished command buffers and fences for reuse
+    free_batches: Vec<(vk::CommandBuffer, vk::Fence)>,
+    free_release_submits: Vec<(vk::CommandBuffer, vk::Semaphore)>,
+    pending_acquires: Vec<PendingAcquire>,
+    next_ticket: u64,
+    completed_ticket: u64,
+}
+
+impl UploadManager {
+    pub fn new(context: &Context) -> Result<Self, Error> {
+        Self::with_staging_size(context, DEFAULT_STAGING_SIZE)
+    }
+
+    pub fn with_staging_size(context: &Context, staging_size: vk::DeviceSize) -> Result<Self, Error> {
+        let device = context.device();
+        let graphics_family = context.queue_family_index(GRAPHICS).ok_or(Error::MissingQueue(GRAPHICS))?;
+        let graphics_queue = context.graphics_queue()?;
+        // Graphics queues always support transfers
+        let (queue, transfer_family) = match (context.transfer_queue(), context.queue_family_index(TRANSFER)) {
+            (Some(queue), Some(family)) => (queue, family),
+            _ => (graphics_queue, graphics_family),
+        };
+        let staging = Buffer::new(context, staging_size, vk::BufferUsageFlags::TRANSFER_SRC, MemoryUsage::CpuToGpu)?;
+
+        let command_pool_create_info = vk::CommandPoolCreateInfo {
+            s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+            p_next: null(),
+            flags: vk::CommandPoolCreateFlags::TRANSIENT | vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+            queue_family_index: transfer_family,
+            _marker: Default::default(),
+        };
+        let command_pool = unsafe { device.create_command_pool(&command_pool_create_info, None)? };
+        let graphics_pool = if transfer_family != graphics_family {
+            let graphics_pool_create_info = vk::CommandPoolCreateInfo {
+                queue_family_index: graphics_family,
+                ..command_pool_create_info
+            };
+            match unsafe { device.create_command_pool(&graphics_pool_create_info, None) } {
+                Ok(pool) => pool,
+                Err(result) => {
+                    unsafe { device.destroy_command_pool(command_pool, None) };
+                    return Err(result.into());
+                }
+            }
+        } else {
+            vk::CommandPool::null()
+        };
+        Ok(Self {
+            device: device.clone(),
+            staging,
+            ring: StagingRing::new(staging_size),
+            command_pool,
+            queue,
+            graphics_queue,
+            graphics_pool,
+            transfer_family,
+            graphics_family,
+            recording: None,
+            in_flight: VecDeque::new(),
+            free_batches: Vec::new(),
+            free_release_submits: Vec::new(),
+            pending_acquires: Vec::new(),
+            next_ticket: 1,
+            completed_ticket: 0,
+        })
+    }
+
+    /// True if copies run on a different queue family than graphics and need ownership transfers
+    pub fn uses_dedicated_transfer_queue(&self) -> bool {
+        self.transfer_family != self.graphics_family
+    }
+
+    pub fn transfer_family_index(&self) -> u32 {
+        self.transfer_family
+    }
+
+    pub fn staging_size(&self) -> vk::DeviceSize {
+        self.ring.capacity()
+    }
+
+    /// Ticket of the newest batch known to be complete
+    pub fn completed(&self) -> UploadTicket {
+        UploadTicket(self.completed_ticket)
+    }
+
+    fn begin_batch(&mut self) -> Result<&mut Batch, Error> {
+        if self.recording.is_none() {
+            let (command_buffer, fence) = match self.free_batches.pop() {
+                Some(batch) => batch,
+                None => {
+                    let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+                        s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+                        p_next: null(),
+                        command_pool: self.command_pool,
+                        level: vk::CommandBufferLevel::PRIMARY,
+                        command_buffer_count: 1,
+                        _marker: Default::default(),
+                    };
+                    let fence_create_info = vk::FenceCreateInfo {
+                        s_type: vk::StructureType::FENCE_CREATE_INFO,
+                        p_next: null(),
+                        flags: vk::FenceCreateFlags::empty(),
+                        _marker: Default::default(),
+                    };
+                    let command_buffer = unsafe { self.device.allocate_command_buffers(&command_buffer_allocate_info)?[0] };
+                    let fence = match unsafe { self.device.create_fence(&fence_create_info, None) } {
+                        Ok(fence) => fence,
+                        Err(result) => {
+                            unsafe { self.device.free_command_buffers(self.command_pool, &[command_buffer]) };
+                            return Err(result.into());
+                        }
+                    };
+                    (command_buffer, fence)
+                }
+            };
+
+            let begin_info = vk::CommandBufferBeginInfo {
+                s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+                p_next: null(),
+                flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                p_inheritance_info: null(),
+                _marker: Default::default(),
+            };
+            if let Err(result) = unsafe { self.device.begin_command_buffer(command_buffer, &begin_info) } {
+                self.free_batches.push((command_buffer, fence));
+                return Err(result.into());
+            }
+            self.recording = Some(Batch {
+                ticket: self.next_ticket,
+                command_buffer,
+                fence,
+                ring_bytes: 0,
+                copies: 0,
+                releases: Vec::new(),
+                release_submit: None,
+            });
+            self.next_ticket += 1;
+        }
+        Ok(self.recording.as_mut().unwrap())
+    }
+
+    /// Staging space for `size` bytes. Submits the recorded copies and waits for the oldest batch if the ring is full.
+    fn allocate_staging(&mut self, size: vk::DeviceSize) -> Result<vk::DeviceSize, Error> {
+        let capacity = self.ring.capacity();
+        if size > capacity {
+            return Err(Error::StagingTooSmall { size, capacity });
+        }
+        loop {
+            self.begin_batch()?;
+            if let Some((offset, consumed)) = self.ring.allocate(size, STAGING_ALIGNMENT) {
+                self.recording.as_mut().unwrap().ring_bytes += consumed;
+                return Ok(offset);
+            }
+            if self.recording.as_ref().is_some_and(|batch| batch.copies > 0) {
+                self.flush()?;
+            }
+            let oldest = self
+                .in_flight
+                .front()
+                .map(|batch| batch.ticket)
+                .ok_or(Error::StagingTooSmall { size, capacity })?;
+            self.wait(UploadTicket(oldest))?;
+        }
+    }
+
+    /// Records a copy of `data` to `destination` at the byte `offset`. The destination needs `TRANSFER_DST` usage.
+    /// Data larger than the staging ring is split into several copies. Returns the ticket of the batch that
+    /// completes the upload, it is submitted by the next `flush`.
+    pub fn upload_buffer<T: Pod>(&mut self, destination: &Buffer, offset: vk::DeviceSize, data: &[T]) -> Result<UploadTicket, Error> {
+        let size = size_of_val(data) as vk::DeviceSize;
+        if offset.checked_add(size).is_none_or(|end| end > destination.size()) {
+            return Err(Error::BufferOutOfRange {
+                offset,
+                size,
+                buffer_size: destination.size(),
+            });
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, size as usize) };
+        // Half the ring per copy so the next chunk can be staged while the previous one is in flight
+        let chunk_size = (self.ring.capacity() / 2).max(1) as usize;
+
+        let mut ticket = UploadTicket(self.next_ticket);
+        for (index, chunk) in bytes.chunks(chunk_size).enumerate() {
+            let staging_offset = self.allocate_staging(chunk.len() as vk::DeviceSize)?;
+            self.staging.write_slice(staging_offset, chunk)?;
+
+            let batch = self.recording.as_mut().unwrap();
+            let region = vk::BufferCopy {
+                src_offset: staging_offset,
+                dst_offset: offset + (index * chunk_size) as vk::DeviceSize,
+                size: chunk.len() as vk::DeviceSize,
+            };
+            unsafe {
+                self.device
+                    .cmd_copy_buffer(batch.command_buffer, self.staging.handle(), destination.handle(), &[region])
+            };
+            batch.copies += 1;
+            ticket = UploadTicket(batch.ticket);
+        }
+
+        if size > 0 {
+            self.release_buffer(destination.handle(), offset, size)?;
+        }
+        Ok(ticket)
+    }
+
+    /// Records copies of one mip level of every layer. `data` holds the layers back to back, rows of texel blocks
+    /// tightly packed like `TextureData` levels. The level is transitioned to and left in `TRANSFER_DST_OPTIMAL`,
+    /// the image needs `TRANSFER_DST` usage. Levels larger than the staging ring are copied in bands of rows, a single
+    /// row larger than the ring fails with `Error::StagingTooSmall`.
+    /// Re-uploading a level that is already in use on the graphics queue moves it back to the transfer family, its
+    /// previous upload has to be acquired with `record_acquire_barriers` before.
+    pub fn upload_image(&mut self, image: &mut Image, mip_level: u32, data: &[u8]) -> Result<UploadTicket, Error> {
+        let format = image.format();
+        let block = format_block(format).ok_or(Error::UnsupportedTextureFormat(format))?;
+        let extent = mip_extent(image.extent(), mip_level);
+        let blocks_x = extent.width.div_ceil(block.width) as usize;
+        let blocks_y = extent.height.div_ceil(block.height) as usize;
+        let row_size = blocks_x * block.bytes as usize;
+        let slice_size = row_size * blocks_y;
+        let layers = image.array_layers();
+        if mip_level >= image.mip_levels() || data.len() != slice_size * extent.depth as usize * layers as usize {
+            return Err(crate::texture::Error::InvalidData("Image data does not match the mip level size").into());
+        }
+        // Checked before anything is recorded, rows are never split
+        if row_size as vk::DeviceSize > self.ring.capacity() {
+            return Err(Error::StagingTooSmall {
+                size: row_size as vk::DeviceSize,
+                capacity: self.ring.capacity(),
+            });
+        }
+
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: image.aspect_mask(),
+            base_mip_level: mip_level,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: layers,
+        };
+        let command_buffer = self.begin_batch()?.command_buffer;
+        if self.uses_dedicated_transfer_queue() {
+            self.acquire_for_transfer(command_buffer, image, range);
+        } else {
+            image.transition(command_buffer, range, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+        }
+
+        let rows_per_copy = ((self.ring.capacity() / 2) as usize / row_size).clamp(1, blocks_y);
+        let mut ticket = UploadTicket(self.next_ticket);
+        for (index, slice) in data.chunks_exact(slice_size).enumerate() {
+            let (layer, z) = (index as u32 / extent.depth, index as u32 % extent.depth);
+            for first_row in (0..blocks_y).step_by(rows_per_copy) {
+                let rows = rows_per_copy.min(blocks_y - first_row);
+                let chunk = &slice[first_row * row_size..(first_row + rows) * row_size];
+                let staging_offset = self.allocate_staging(chunk.len() as vk::DeviceSize)?;
+                self.staging.write_slice(staging_offset, chunk)?;
+
+                let y = first_row as u32 * block.height;
+                let region = vk::BufferImageCopy {
+                    buffer_offset: staging_offset,
+                    buffer_row_length: 0,
+                    buffer_image_height: 0,
+                    image_subresource: vk::ImageSubresourceLayers {
+                        aspect_mask: image.aspect_mask(),
+                        mip_level,
+                        base_array_layer: layer,
+                        layer_count: 1,
+                    },
+                    image_offset: vk::Offset3D { x: 0, y: y as i32, z: z as i32 },
+                    // The last band may end in partial blocks at the bottom edge
+                    image_extent: vk::Extent3D {
+                        width: extent.width,
+                        height: (rows as u32 * block.height).min(extent.height - y),
+                        depth: 1,
+                    },
+                };
+                let batch = self.recording.as_mut().unwrap();
+                unsafe {
+                    self.device.cmd_copy_buffer_to_image(
+                        batch.command_buffer,
+                        self.staging.handle(),
+                        image.handle(),
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        &[region],
+                    )
+                };
+                batch.copies += 1;
+                ticket = UploadTicket(batch.ticket);
+            }
+        }
+
+        self.release_image(image.handle(), range, vk::ImageLayout::TRANSFER_DST_OPTIMAL)?;
+        Ok(ticket)
+    }
+
+    /// Moves the level to `TRANSFER_DST_OPTIMAL` on the transfer queue, only transfer stages are valid there.
+    /// Layers with contents are owned by the graphics family and get the acquire half of an ownership transfer,
+    /// the release half is submitted to the graphics queue by `flush`.
+    fn acquire_for_transfer(&mut self, command_buffer: vk::CommandBuffer, image: &mut Image, range: vk::ImageSubresourceRange) {
+        let batch = self.recording.as_mut().unwrap();
+        let mut barriers = Vec::new();
+        let mut layer = 0;
+        // One barrier per run of layers sharing the old layout
+        while layer < range.layer_count {
+            let old_layout = image.layout(range.base_mip_level, layer);
+            let run_start = layer;
+            while layer < range.layer_count && image.layout(range.base_mip_level, layer) == old_layout {
+                layer += 1;
+            }
+            let run = vk::ImageSubresourceRange {
+                base_array_layer: run_start,
+                layer_count: layer - run_start,
+                ..range
+            };
+            let (src_queue_family_index, dst_queue_family_index) = if old_layout == vk::ImageLayout::UNDEFINED {
+                (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+            } else {
+                batch.releases.push(ImageRelease {
+                    image: image.handle(),
+                    range: run,
+                    old_layout,
+                });
+                (self.graphics_family, self.transfer_family)
+            };
+            barriers.push(vk::ImageMemoryBarrier {
+                s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+                p_next: null(),
+                src_access_mask: vk::AccessFlags::empty(),
+                dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                old_layout,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                src_queue_family_index,
+                dst_queue_family_index,
+                image: image.handle(),
+                subresource_range: run,
+                _marker: Default::default(),
+            });
+        }
+        // The source stage matches the stage the copies wait on the release semaphore at
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &barriers,
+            )
+        };
+        image.set_layout(range, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+    }
+
+    /// Releases the range to the graphics family, or makes the transfer write visible if there is only one family
+    fn release_buffer(&mut self, buffer: vk::Buffer, offset: vk::DeviceSize, size: vk::DeviceSize) -> Result<(), Error> {
+        let dedicated = self.uses_dedicated_transfer_queue();
+        let (command_buffer, ticket) = {
+            let batch = self.begin_batch()?;
+            (batch.command_buffer, batch.ticket)
+        };
+        if dedicated {
+            let barrier = vk::BufferMemoryBarrier {
+                s_type: vk::StructureType::BUFFER_MEMORY_BARRIER,
+                p_next: null(),
+                src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags::empty(),
+                src_queue_family_index: self.transfer_family,
+                dst_queue_family_index: self.graphics_family,
+                buffer,
+                offset,
+                size,
+                _marker: Default::default(),
+            };
+            unsafe {
+                self.device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[barrier],
+                    &[],
+                )
+            };
+        }
+        // The acquire side is needed in both cases, without an ownership transfer it is a plain memory barrier
+        self.pending_acquires.push(PendingAcquire {
+            ticket,
+            resource: AcquireResource::Buffer { buffer, offset, size },
+        });
+        Ok(())
+    }
+
+    /// `release_buffer` for an image range, the layout is kept
+    fn release_image(&mut self, image: vk::Image, range: vk::ImageSubresourceRange, layout: vk::ImageLayout) -> Result<(), Error> {
+        let dedicated = self.uses_dedicated_transfer_queue();
+        let (command_buffer, ticket) = {
+            let batch = self.begin_batch()?;
+            (batch.command_buffer, batch.ticket)
+        };
+        if dedicated {
+            let barrier = vk::ImageMemoryBarrier {
+                s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+                p_next: null(),
+                src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags::empty(),
+                old_layout: layout,
+                new_layout: layout,
+                src_queue_family_index: self.transfer_family,
+                dst_queue_family_index: self.graphics_family,
+                image,
+                subresource_range: range,
+                _marker: Default::default(),
+            };
+            unsafe {
+                self.device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[barrier],
+                )
+            };
+        }
+        self.pending_acquires.push(PendingAcquire {
+            ticket,
+            resource: AcquireResource::Image { image, range, layout },
+        });
+        Ok(())
+    }
+
+    /// Submits the recorded copies, `None` if nothing was recorded
+    pub fn flush(&mut self) -> Result<Option<UploadTicket>, Error> {
+        let Some(batch) = self.recording.take() else {
+            return Ok(None);
+        };
+        let ticket = batch.ticket;
+        unsafe { self.device.end_command_buffer(batch.command_buffer)? };
+        // Queued before submitting to keep the ring space in order. A failed submit means a lost device, the batch never retires.
+        self.in_flight.push_back(batch);
+        let wait_semaphore = self.submit_releases()?;
+        let batch = self.in_flight.back().unwrap();
+        let wait_stage = vk::PipelineStageFlags::TRANSFER;
+        let submit_info = vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            p_next: null(),
+            wait_semaphore_count: wait_semaphore.is_some() as u32,
+            p_wait_semaphores: wait_semaphore.as_ref().map_or(null(), |semaphore| semaphore),
+            p_wait_dst_stage_mask: &wait_stage,
+            command_buffer_count: 1,
+            p_command_buffers: &batch.command_buffer,
+            signal_semaphore_count: 0,
+            p_signal_semaphores: null(),
+            _marker: Default::default(),
+        };
+        unsafe { self.device.queue_submit(self.queue, &[submit_info], batch.fence)? };
+        Ok(Some(UploadTicket(ticket)))
+    }
+
+    /// Submits the release half of the ownership transfers of the newest batch to the graphics queue,
+    /// returns the semaphore its copies have to wait on
+    fn submit_releases(&mut self) -> Result<Option<vk::Semaphore>, Error> {
+        if self.in_flight.back().unwrap().releases.is_empty() {
+            return Ok(None);
+        }
+        let (command_buffer, semaphore) = match self.free_release_submits.pop() {
+            Some(submit) => submit,
+            None => {
+                let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+                    s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+                    p_next: null(),
+                    command_pool: self.graphics_pool,
+                    level: vk::CommandBufferLevel::PRIMARY,
+                    command_buffer_count: 1,
+                    _marker: Default::default(),
+                };
+                let semaphore_create_info = vk::SemaphoreCreateInfo {
+                    s_type: vk::StructureType::SEMAPHORE_CREATE_INFO,
+                    p_next: null(),
+                    flags: vk::SemaphoreCreateFlags::empty(),
+                    _marker: Default::default(),
+                };
+                let command_buffer = unsafe { self.device.allocate_command_buffers(&command_buffer_allocate_info)?[0] };
+                let semaphore = match unsafe { self.device.create_semaphore(&semaphore_create_info, None) } {
+                    Ok(semaphore) => semaphore,
+                    Err(result) => {
+                        unsafe { self.device.free_command_buffers(self.graphics_pool, &[command_buffer]) };
+                        return Err(result.into());
+                    }
+                };
+                (command_buffer, semaphore)
+            }
+        };
+        let batch = self.in_flight.back_mut().unwrap();
+        // Kept with the batch from here on, it is reused once the batch retires
+        batch.release_submit = Some((command_buffer, semaphore));
+
+        let mut src_stages = vk::PipelineStageFlags::empty();
+        let barriers: Vec<_> = batch
+            .releases
+            .iter()
+            .map(|release| {
+                let (src_access_mask, src_stage) = layout_access(release.old_layout, true);
+                src_stages |= src_stage;
+                vk::ImageMemoryBarrier {
+                    s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+                    p_next: null(),
+                    src_access_mask,
+                    dst_access_mask: vk::AccessFlags::empty(),
+                    old_layout: release.old_layout,
+                    new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    src_queue_family_index: self.graphics_family,
+                    dst_queue_family_index: self.transfer_family,
+                    image: release.image,
+                    subresource_range: release.range,
+                    _marker: Default::default(),
+                }
+            })
+            .collect();
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next: null(),
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            p_inheritance_info: null(),
+            _marker: Default::default(),
+        };
+        let submit_info = vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            p_next: null(),
+            wait_semaphore_count: 0,
+            p_wait_semaphores: null(),
+            p_wait_dst_stage_mask: null(),
+            command_buffer_count: 1,
+            p_command_buffers: &command_buffer,
+            signal_semaphore_count: 1,
+            p_signal_semaphores: &semaphore,
+            _marker: Default::default(),
+        };
+        unsafe {
+            self.device.begin_command_buffer(command_buffer, &begin_info)?;
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stages,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &barriers,
+            );
+            self.device.end_command_buffer(command_buffer)?;
+            self.device.queue_submit(self.graphics_queue, &[submit_info], vk::Fence::null())?;
+        }
+        Ok(Some(semaphore))
+    }
+
+    fn retire_oldest(&mut self) -> Result<(), Error> {
+        let batch = self.in_flight.pop_front().unwrap();
+        self.ring.release(batch.ring_bytes);
+        self.completed_ticket = batch.ticket;
+        unsafe {
+            self.device.reset_fences(&[batch.fence])?;
+            self.device
+                .reset_command_buffer(batch.command_buffer, vk::CommandBufferResetFlags::empty())?;
+            // The copies waited on the release semaphore, its command buffer finished before them
+            if let Some((command_buffer, semaphore)) = batch.release_submit {
+                self.device.reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+                self.free_release_submits.push((command_buffer, semaphore));
+            }
+        }
+        self.free_batches.push((batch.command_buffer, batch.fence));
+        Ok(())
+    }
+
+    /// Retires finished batches without blocking and returns the newest completed ticket
+    pub fn poll(&mut self) -> Result<UploadTicket, Error> {
+        while let Some(batch) = self.in_flight.front() {
+            if !unsafe { self.device.get_fence_status(batch.fence)? } {
+                break;
+            }
+            self.retire_oldest()?;
+        }
+        Ok(self.completed())
+    }
+
+    pub fn is_complete(&mut self, ticket: UploadTicket) -> Result<bool, Error> {
+        Ok(self.poll()? >= ticket)
+    }
+
+    /// Blocks until the batch finished, submits it first if it is still being recorded
+    pub fn wait(&mut self, ticket: UploadTicket) -> Result<(), Error> {
+        if self.recording.as_ref().is_some_and(|batch| batch.ticket <= ticket.0) {
+            self.flush()?;
+        }
+        while let Some(batch) = self.in_flight.front() {
+            if batch.ticket > ticket.0 {
+                break;
+            }
+            unsafe { self.device.wait_for_fences(&[batch.fence], true, u64::MAX)? };
+            self.retire_oldest()?;
+        }
+        Ok(())
+    }
+
+    /// Records the acquire barriers of all completed uploads into a command buffer of the graphics family.
+    /// Call before the uploaded buffers and images are used on the graphics queue. Returns the number of barriers recorded.
+    pub fn record_acquire_barriers(&mut self, command_buffer: vk::CommandBuffer) -> Result<usize, Error> {
+        self.poll()?;
+        let (source, destination) = if self.uses_dedicated_transfer_queue() {
+            (self.transfer_family, self.graphics_family)
+        } else {
+            (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+        };
+        let completed = self.completed_ticket;
+        // Ownership transfers already made the writes available, only a single family needs the source access
+        let src_access_mask = if source == vk::QUEUE_FAMILY_IGNORED {
+            vk::AccessFlags::TRANSFER_WRITE
+        } else {
+            vk::AccessFlags::empty()
+        };
+        let mut buffer_barriers = Vec::new();
+        let mut image_barriers = Vec::new();
+        for acquire in self.pending_acquires.iter().filter(|acquire| acquire.ticket <= completed) {
+            match acquire.resource {
+                AcquireResource::Buffer { buffer, offset, size } => buffer_barriers.push(vk::BufferMemoryBarrier {
+                    s_type: vk::StructureType::BUFFER_MEMORY_BARRIER,
+                    p_next: null(),
+                    src_access_mask,
+                    dst_access_mask: vk::AccessFlags::MEMORY_READ,
+                    src_queue_family_index: source,
+                    dst_queue_family_index: destination,
+                    buffer,
+                    offset,
+                    size,
+                    _marker: Default::default(),
+                }),
+                AcquireResource::Image { image, range, layout } => image_barriers.push(vk::ImageMemoryBarrier {
+                    s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+                    p_next: null(),
+                    src_access_mask,
+                    dst_access_mask: vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+                    old_layout: layout,
+                    new_layout: layout,
+                    src_queue_family_index: source,
+                    dst_queue_family_index: destination,
+                    image,
+                    subresource_range: range,
+                    _marker: Default::default(),
+                }),
+            }
+        }
+        let count = buffer_barriers.len() + image_barriers.len();
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let source_stage = if source == vk::QUEUE_FAMILY_IGNORED {
+            vk::PipelineStageFlags::TRANSFER
+        } else {
+            vk::PipelineStageFlags::TOP_OF_PIPE
+        };
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                source_stage,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &buffer_barriers,
+                &image_barriers,
+            )
+        };
+        self.pending_acquires.retain(|acquire| acquire.ticket > completed);
+        Ok(count)
+    }
+}
+
+impl Drop for UploadManager {
+    fn drop(&mut self) {
+        unsafe {
+            // Copies still pending when the wait fails are abandoned, their batches are freed regardless
+            let _ = self.device.queue_wait_idle(self.queue);
+            let batches: Vec<_> = self.recording.take().into_iter().chain(self.in_flight.drain(..)).collect();
+            let release_submits = batches.iter().filter_map(|batch| batch.release_submit);
+            let release_submits: Vec<_> = release_submits.chain(self.free_release_submits.drain(..)).collect();
+            if !release_submits.is_empty() {
+                let _ = self.device.queue_wait_idle(self.graphics_queue);
+            }
+            for (_, semaphore) in release_submits {
+                self.device.destroy_semaphore(semaphore, None);
+            }
+            for (_, fence) in batches.iter().map(|batch| (batch.command_buffer, batch.fence)).chain(self.free_batches.drain(..)) {
+                self.device.destroy_fence(fence, None);
+            }
+            // Frees the command buffers as well
+            self.device.destroy_command_pool(self.command_pool, None);
+            if self.graphics_pool != vk::CommandPool::null() {
+                self.device.destroy_command_pool(self.graphics_pool, None);
+            }
+        }
+    }
+}
@@ -17,11 +17,11 @@ macro_rules! platform_surface_extension {
             {
                 khr::wayland_surface::NAME.as_ptr()
             }
-            #[cfg(feature = "xlib")]
+            #[cfg(all(feature = "xlib", not(feature = "wayland")))]
             {
                 khr::xlib_surface::NAME.as_ptr()
             }
-            #[cfg(feature = "xcb")]
+            #[cfg(all(feature = "xcb", not(feature = "wayland"), not(feature = "xlib")))]
             {
                 khr::xcb_surface::NAME.as_ptr()
             }
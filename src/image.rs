@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Write};
+use std::path::Path;
+
+/// Tightly packed 8 bit RGBA pixels, rows go from top to bottom
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), (width * height * 4) as usize, "Pixel buffer does not match the image size");
+        Self { width, height, pixels }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[offset],
+            self.pixels[offset + 1],
+            self.pixels[offset + 2],
+            self.pixels[offset + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: [u8; 4]) {
+        let offset = ((y * self.width + x) * 4) as usize;
+        self.pixels[offset..offset + 4].copy_from_slice(&pixel);
+    }
+
+    pub fn write_png<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Binary PPM (P6), the alpha channel is dropped
+    pub fn write_ppm<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in self.pixels.chunks_exact(4) {
+            writer.write_all(&pixel[..3])?;
+        }
+        writer.flush()
+    }
+
+    /// Reads an 8 bit PNG, grayscale and RGB images are expanded to RGBA
+    pub fn read_png<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::decode_png(BufReader::new(File::open(path)?))
+    }
+
+    /// `read_png` for PNG data that is already in memory or comes from another source
+    pub fn decode_png<R: Read>(reader: R) -> Result<Self, Error> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buffer)
+            .map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+        buffer.truncate(info.buffer_size());
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]]).collect(),
+            png::ColorType::Grayscale => buffer.iter().flat_map(|g| [*g, *g, *g, 255]).collect(),
+            png::ColorType::Indexed => return Err(Error::new(ErrorKind::InvalidData, "Unexpanded indexed PNG")),
+        };
+        Ok(Self::from_pixels(info.width, info.height, pixels))
+    }
+}
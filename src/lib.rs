@@ -1,4 +1,6 @@
 pub mod backend;
+pub mod image;
+pub mod texture;
 pub mod utils;
 
 pub mod log;
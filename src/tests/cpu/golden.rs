@@ -0,0 +1,17 @@
+use crate::backend::cpu::framebuffer::Framebuffer;
+use crate::backend::cpu::rasterizer::{draw_triangles, RasterState, Vertex};
+use crate::tests::golden::{assert_golden, GoldenConfig};
+
+/// Same triangle as `shaders/vshader.vert`, the Vulkan golden test compares against the same reference
+#[test]
+fn cpu_golden_triangle_test() {
+    let vertices = [
+        Vertex::new([0.0, -0.5, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        Vertex::new([0.5, 0.5, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        Vertex::new([-0.5, 0.5, 0.0, 1.0], [0.0, 0.0, 1.0]),
+    ];
+    let mut framebuffer = Framebuffer::new(64, 64);
+    framebuffer.clear([0.0, 0.0, 0.0, 1.0], 1.0);
+    draw_triangles(&mut framebuffer, &RasterState::default(), &vertices, |color| [color[0], color[1], color[2], 1.0]);
+    assert_golden("triangle", framebuffer.color(), &GoldenConfig::default());
+}
@@ -0,0 +1,4 @@
+#[cfg(test)]
+mod golden;
+#[cfg(test)]
+mod rasterizer;
@@ -0,0 +1,151 @@
+use crate::backend::cpu::framebuffer::{to_unorm8, Framebuffer};
+use crate::backend::cpu::rasterizer::{draw_triangles, BlendMode, CompareOp, CullMode, RasterState, Vertex};
+
+fn vertex(x: f32, y: f32, z: f32) -> Vertex<0> {
+    Vertex::new([x, y, z, 1.0], [])
+}
+
+fn quad(z: f32) -> Vec<Vertex<0>> {
+    vec![
+        vertex(-1.0, -1.0, z),
+        vertex(1.0, -1.0, z),
+        vertex(1.0, 1.0, z),
+        vertex(-1.0, -1.0, z),
+        vertex(1.0, 1.0, z),
+        vertex(-1.0, 1.0, z),
+    ]
+}
+
+#[test]
+fn rasterizer_shared_edge_test() {
+    // The diagonal passes exactly through pixel centers, each of them must be shaded once
+    let mut framebuffer = Framebuffer::new(4, 4);
+    let mut invocations = 0;
+    draw_triangles(&mut framebuffer, &RasterState::default(), &quad(0.5), |_| {
+        invocations += 1;
+        [1.0, 1.0, 1.0, 1.0]
+    });
+    assert_eq!(invocations, 16);
+
+    // Fan around the center, all edges meet in a pixel corner
+    let mut framebuffer = Framebuffer::new(4, 4);
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    let mut fan = Vec::new();
+    for index in 0..corners.len() {
+        let (next_x, next_y) = corners[(index + 1) % corners.len()];
+        fan.push(vertex(0.0, 0.0, 0.5));
+        fan.push(vertex(corners[index].0, corners[index].1, 0.5));
+        fan.push(vertex(next_x, next_y, 0.5));
+    }
+    let mut invocations = 0;
+    draw_triangles(&mut framebuffer, &RasterState::default(), &fan, |_| {
+        invocations += 1;
+        [1.0, 1.0, 1.0, 1.0]
+    });
+    assert_eq!(invocations, 16);
+}
+
+#[test]
+fn rasterizer_depth_test() {
+    let mut framebuffer = Framebuffer::new(8, 8);
+    framebuffer.clear([0.0, 0.0, 0.0, 1.0], 1.0);
+    let state = RasterState {
+        depth_test: Some(CompareOp::Less),
+        depth_write: true,
+        ..RasterState::default()
+    };
+    draw_triangles(&mut framebuffer, &state, &quad(0.25), |_| [1.0, 0.0, 0.0, 1.0]);
+    draw_triangles(&mut framebuffer, &state, &quad(0.75), |_| [0.0, 0.0, 1.0, 1.0]);
+    assert_eq!(framebuffer.color().pixel(3, 3), [255, 0, 0, 255]);
+    assert_eq!(framebuffer.depth(3, 3), 0.25);
+
+    draw_triangles(&mut framebuffer, &state, &quad(0.1), |_| [0.0, 1.0, 0.0, 1.0]);
+    assert_eq!(framebuffer.color().pixel(3, 3), [0, 255, 0, 255]);
+    assert_eq!(framebuffer.depth(3, 3), 0.1);
+}
+
+#[test]
+fn rasterizer_alpha_blend_test() {
+    let mut framebuffer = Framebuffer::new(2, 2);
+    framebuffer.clear([0.0, 0.0, 1.0, 1.0], 1.0);
+    draw_triangles(&mut framebuffer, &RasterState::default(), &quad(0.5), |_| [1.0, 0.0, 0.0, 0.5]);
+    assert_eq!(framebuffer.color().pixel(0, 0), [128, 0, 128, 128]);
+
+    let replace = RasterState {
+        blend: BlendMode::Replace,
+        ..RasterState::default()
+    };
+    draw_triangles(&mut framebuffer, &replace, &quad(0.5), |_| [0.0, 1.0, 0.0, 0.5]);
+    assert_eq!(framebuffer.color().pixel(1, 1), [0, 255, 0, 128]);
+}
+
+#[test]
+fn rasterizer_perspective_interpolation_test() {
+    // The right side is twice as far away, the attribute must not be interpolated linearly in screen space
+    const WIDTH: u32 = 16;
+    let left_top = Vertex::new([-1.0, -1.0, 0.5, 1.0], [0.0]);
+    let left_bottom = Vertex::new([-1.0, 1.0, 0.5, 1.0], [0.0]);
+    let right_top = Vertex::new([2.0, -2.0, 1.0, 2.0], [1.0]);
+    let right_bottom = Vertex::new([2.0, 2.0, 1.0, 2.0], [1.0]);
+    let mut framebuffer = Framebuffer::new(WIDTH, 1);
+    draw_triangles(
+        &mut framebuffer,
+        &RasterState::default(),
+        &[left_top, right_top, right_bottom, left_top, right_bottom, left_bottom],
+        |attributes| [attributes[0], 0.0, 0.0, 1.0],
+    );
+
+    for x in 0..WIDTH {
+        let s = (x as f32 + 0.5) / WIDTH as f32;
+        let expected = (s / 2.0) / ((1.0 - s) + s / 2.0);
+        let actual = framebuffer.color().pixel(x, 0)[0];
+        assert!(
+            actual.abs_diff(to_unorm8(expected)) <= 1,
+            "Pixel {} has {} instead of {}",
+            x,
+            actual,
+            to_unorm8(expected)
+        );
+    }
+}
+
+#[test]
+fn rasterizer_cull_test() {
+    let clockwise = [vertex(0.0, -1.0, 0.5), vertex(1.0, 1.0, 0.5), vertex(-1.0, 1.0, 0.5)];
+    let counter_clockwise = [clockwise[0], clockwise[2], clockwise[1]];
+    let state = RasterState {
+        cull_mode: CullMode::Back,
+        ..RasterState::default()
+    };
+
+    let mut invocations = 0;
+    draw_triangles(&mut Framebuffer::new(8, 8), &state, &counter_clockwise, |_| {
+        invocations += 1;
+        [1.0; 4]
+    });
+    assert_eq!(invocations, 0);
+    draw_triangles(&mut Framebuffer::new(8, 8), &state, &clockwise, |_| {
+        invocations += 1;
+        [1.0; 4]
+    });
+    assert!(invocations > 0);
+}
+
+#[test]
+fn rasterizer_clip_test() {
+    let mut invocations = 0;
+    let behind = [vertex(-1.0, -1.0, -0.5), vertex(1.0, -1.0, -0.5), vertex(0.0, 1.0, -0.5)];
+    draw_triangles(&mut Framebuffer::new(8, 8), &RasterState::default(), &behind, |_| {
+        invocations += 1;
+        [1.0; 4]
+    });
+    assert_eq!(invocations, 0);
+
+    // Crosses the near plane at y = 0 and leaves the viewport on every side, only the lower half is visible
+    let crossing = [vertex(0.0, -4.0, -1.0), vertex(4.0, 4.0, 1.0), vertex(-4.0, 4.0, 1.0)];
+    draw_triangles(&mut Framebuffer::new(8, 8), &RasterState::default(), &crossing, |_| {
+        invocations += 1;
+        [1.0; 4]
+    });
+    assert_eq!(invocations, 32);
+}
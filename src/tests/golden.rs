@@ -0,0 +1,155 @@
+use crate::image::RgbaImage;
+use log::warn;
+use std::fs;
+use std::path::PathBuf;
+
+/// Set to `1` to regenerate the reference images instead of comparing against them
+pub const UPDATE_ENV: &str = "EIKON_UPDATE_GOLDEN";
+
+pub fn reference_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/tests/references")
+}
+
+/// Actual and diff images of failed comparisons are written here
+pub fn failure_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+pub struct GoldenConfig {
+    /// Largest per channel difference that still counts as a match
+    pub channel_tolerance: u8,
+    /// Largest perceptual difference (0 - 1, see `perceptual_difference`) that still counts as a match
+    pub perceptual_threshold: f32,
+    /// Number of mismatching pixels the image may have and still pass. Covers rasterization differences on edges.
+    pub allowed_failing_pixels: usize,
+}
+
+impl Default for GoldenConfig {
+    fn default() -> Self {
+        Self {
+            channel_tolerance: 2,
+            perceptual_threshold: 0.05,
+            allowed_failing_pixels: 0,
+        }
+    }
+}
+
+pub struct ImageDiff {
+    pub failing_pixels: usize,
+    pub max_channel_difference: u8,
+    pub max_perceptual_difference: f32,
+    /// Failing pixels are red, matching pixels are a faded grayscale of the reference
+    pub diff_image: RgbaImage,
+}
+
+impl ImageDiff {
+    pub fn passes(&self, config: &GoldenConfig) -> bool {
+        self.failing_pixels <= config.allowed_failing_pixels
+    }
+}
+
+fn blend_on_white(pixel: [u8; 4]) -> [f32; 3] {
+    let alpha = pixel[3] as f32 / 255.0;
+    let blend = |channel: u8| 255.0 + (channel as f32 - 255.0) * alpha;
+    [blend(pixel[0]), blend(pixel[1]), blend(pixel[2])]
+}
+
+fn to_yiq(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    [
+        r * 0.298895 + g * 0.586622 + b * 0.114482,
+        r * 0.595978 - g * 0.274176 - b * 0.321802,
+        r * 0.21147 - g * 0.522617 + b * 0.311147,
+    ]
+}
+
+/// Weighted YIQ distance (Kotsarenko and Ramos), alpha is blended against white. 0 is identical, 1 is the largest possible difference.
+pub fn perceptual_difference(actual: [u8; 4], reference: [u8; 4]) -> f32 {
+    // Largest possible weighted distance over all color pairs
+    const MAX_DELTA: f32 = 35215.0;
+    let [y1, i1, q1] = to_yiq(blend_on_white(actual));
+    let [y2, i2, q2] = to_yiq(blend_on_white(reference));
+    let (y, i, q) = (y1 - y2, i1 - i2, q1 - q2);
+    (0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / MAX_DELTA
+}
+
+pub fn compare_images(actual: &RgbaImage, reference: &RgbaImage, config: &GoldenConfig) -> ImageDiff {
+    assert_eq!(
+        (actual.width, actual.height),
+        (reference.width, reference.height),
+        "Image size does not match the reference"
+    );
+
+    let mut diff = ImageDiff {
+        failing_pixels: 0,
+        max_channel_difference: 0,
+        max_perceptual_difference: 0.0,
+        diff_image: RgbaImage::new(reference.width, reference.height),
+    };
+
+    for y in 0..reference.height {
+        for x in 0..reference.width {
+            let actual_pixel = actual.pixel(x, y);
+            let reference_pixel = reference.pixel(x, y);
+            let channel_difference = actual_pixel
+                .iter()
+                .zip(reference_pixel.iter())
+                .map(|(a, r)| a.abs_diff(*r))
+                .max()
+                .unwrap_or(0);
+            let perceptual = perceptual_difference(actual_pixel, reference_pixel);
+            diff.max_channel_difference = diff.max_channel_difference.max(channel_difference);
+            diff.max_perceptual_difference = diff.max_perceptual_difference.max(perceptual);
+
+            if channel_difference > config.channel_tolerance && perceptual > config.perceptual_threshold {
+                diff.failing_pixels += 1;
+                diff.diff_image.set_pixel(x, y, [255, 0, 0, 255]);
+                continue;
+            }
+            let [luma, _, _] = to_yiq(blend_on_white(reference_pixel));
+            let faded = (255.0 - (255.0 - luma) * 0.1) as u8;
+            diff.diff_image.set_pixel(x, y, [faded, faded, faded, 255]);
+        }
+    }
+    diff
+}
+
+/// Compares `actual` against `src/tests/references/<name>.png`. On failure the actual and diff images are written
+/// into `target/golden`. A missing reference fails, with `EIKON_UPDATE_GOLDEN=1` `actual` is written as the new reference.
+pub fn assert_golden(name: &str, actual: &RgbaImage, config: &GoldenConfig) {
+    let reference_path = reference_dir().join(format!("{}.png", name));
+    if std::env::var_os(UPDATE_ENV).is_some_and(|value| value == "1") {
+        fs::create_dir_all(reference_dir()).expect("Failed to create reference directory");
+        actual.write_png(&reference_path).expect("Failed to write reference image");
+        warn!("Reference image {} written, commit it to the repository", reference_path.display());
+        return;
+    }
+    if !reference_path.exists() {
+        panic!(
+            "Reference image {} is missing, run with {}=1 to create it",
+            reference_path.display(),
+            UPDATE_ENV
+        );
+    }
+
+    let reference = RgbaImage::read_png(&reference_path).expect("Failed to read reference image");
+    let diff = compare_images(actual, &reference, config);
+    if diff.passes(config) {
+        return;
+    }
+
+    fs::create_dir_all(failure_dir()).expect("Failed to create golden failure directory");
+    let actual_path = failure_dir().join(format!("{}.actual.png", name));
+    let diff_path = failure_dir().join(format!("{}.diff.png", name));
+    actual.write_png(&actual_path).expect("Failed to write actual image");
+    diff.diff_image.write_png(&diff_path).expect("Failed to write diff image");
+    panic!(
+        "Golden image {} mismatch: {} failing pixels (allowed {}), max channel difference {}, max perceptual difference {:.4}. See {}",
+        name,
+        diff.failing_pixels,
+        config.allowed_failing_pixels,
+        diff.max_channel_difference,
+        diff.max_perceptual_difference,
+        diff_path.display()
+    );
+}
@@ -0,0 +1,74 @@
+use crate::image::RgbaImage;
+use crate::tests::golden::{compare_images, perceptual_difference, GoldenConfig};
+use std::fs;
+
+fn test_image() -> RgbaImage {
+    let mut image = RgbaImage::new(3, 2);
+    image.set_pixel(0, 0, [255, 0, 0, 255]);
+    image.set_pixel(2, 1, [10, 20, 30, 40]);
+    image
+}
+
+#[test]
+fn image_png_round_trip_test() {
+    let image = test_image();
+    let path = std::env::temp_dir().join("eikon_image_round_trip.png");
+    image.write_png(&path).expect("Failed to write PNG");
+    let loaded = RgbaImage::read_png(&path).expect("Failed to read PNG");
+    assert_eq!(loaded, image);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn image_ppm_test() {
+    let image = test_image();
+    let path = std::env::temp_dir().join("eikon_image.ppm");
+    image.write_ppm(&path).expect("Failed to write PPM");
+    let bytes = fs::read(&path).unwrap();
+    let header = b"P6\n3 2\n255\n";
+    assert_eq!(&bytes[..header.len()], header);
+    assert_eq!(bytes.len(), header.len() + 3 * 2 * 3);
+    assert_eq!(&bytes[header.len()..header.len() + 3], &[255, 0, 0]);
+    assert_eq!(&bytes[bytes.len() - 3..], &[10, 20, 30]);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn golden_compare_identical_test() {
+    let image = test_image();
+    let diff = compare_images(&image, &image, &GoldenConfig::default());
+    assert_eq!(diff.failing_pixels, 0);
+    assert_eq!(diff.max_channel_difference, 0);
+    assert!(diff.passes(&GoldenConfig::default()));
+}
+
+#[test]
+fn golden_compare_tolerance_test() {
+    let reference = test_image();
+    let mut actual = test_image();
+    actual.set_pixel(1, 0, [2, 1, 0, 0]);
+    let diff = compare_images(&actual, &reference, &GoldenConfig::default());
+    assert_eq!(diff.max_channel_difference, 2);
+    assert!(diff.passes(&GoldenConfig::default()));
+
+    actual.set_pixel(0, 0, [0, 0, 255, 255]);
+    let diff = compare_images(&actual, &reference, &GoldenConfig::default());
+    assert_eq!(diff.failing_pixels, 1);
+    assert_eq!(diff.diff_image.pixel(0, 0), [255, 0, 0, 255]);
+    assert!(!diff.passes(&GoldenConfig::default()));
+
+    let lenient = GoldenConfig {
+        allowed_failing_pixels: 1,
+        ..GoldenConfig::default()
+    };
+    assert!(diff.passes(&lenient));
+}
+
+#[test]
+fn golden_perceptual_difference_test() {
+    assert_eq!(perceptual_difference([10, 20, 30, 255], [10, 20, 30, 255]), 0.0);
+    let black_white = perceptual_difference([0, 0, 0, 255], [255, 255, 255, 255]);
+    assert!(black_white > 0.9 && black_white <= 1.0);
+    // Fully transparent pixels look the same regardless of color
+    assert!(perceptual_difference([255, 0, 0, 0], [0, 0, 255, 0]) < 1e-6);
+}
@@ -1 +1,10 @@
+#[cfg(test)]
+mod cpu;
+pub mod golden;
+#[cfg(test)]
+mod image;
+#[cfg(test)]
+pub mod renderer;
+#[cfg(test)]
+mod texture;
 pub mod vulkan;
@@ -0,0 +1,87 @@
+use crate::backend::renderer::{create_renderer, Backend, BackendPreference, ColorVertex, CommandList, Error, Renderer, RendererConfig};
+use crate::tests::golden::{assert_golden, GoldenConfig};
+use crate::tests::vulkan::log::Logger;
+
+/// Same triangle as `shaders/vshader.vert`
+pub fn triangle_vertices() -> [ColorVertex; 3] {
+    [
+        ColorVertex::new([0.0, -0.5, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]),
+        ColorVertex::new([0.5, 0.5, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0]),
+        ColorVertex::new([-0.5, 0.5, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]),
+    ]
+}
+
+/// Application side code, knows nothing about the active backend
+pub fn render_triangle(renderer: &mut dyn Renderer) -> Result<(), Error> {
+    let buffer = renderer.create_vertex_buffer(&triangle_vertices())?;
+    let mut commands = CommandList::new([0.0, 0.0, 0.0, 1.0]);
+    commands.draw(buffer, 0, 3);
+    renderer.submit(&commands)?;
+    renderer.destroy_buffer(buffer)
+}
+
+#[test]
+fn renderer_cpu_test() {
+    Logger::init(log::LevelFilter::Trace);
+    let config = RendererConfig::new(64, 64).preference(BackendPreference::Cpu);
+    let mut renderer = create_renderer(&config).expect("Failed to create renderer");
+    assert_eq!(renderer.backend(), Backend::Cpu);
+    assert_eq!(renderer.extent(), (64, 64));
+
+    render_triangle(renderer.as_mut()).expect("Failed to render");
+    let image = renderer.read_pixels().expect("Failed to read pixels");
+    assert_golden("triangle", &image, &GoldenConfig::default());
+}
+
+#[test]
+fn renderer_auto_test() {
+    // Vulkan where available, the CPU backend otherwise. Both have to produce the same image.
+    Logger::init(log::LevelFilter::Trace);
+    let mut renderer = create_renderer(&RendererConfig::new(64, 64)).expect("Failed to create renderer");
+    render_triangle(renderer.as_mut()).expect("Failed to render");
+    let image = renderer.read_pixels().expect("Failed to read pixels");
+    let config = GoldenConfig {
+        allowed_failing_pixels: 64,
+        ..GoldenConfig::default()
+    };
+    assert_golden("triangle", &image, &config);
+}
+
+#[test]
+fn renderer_invalid_draw_test() {
+    Logger::init(log::LevelFilter::Trace);
+    let mut renderer = create_renderer(&RendererConfig::new(8, 8).preference(BackendPreference::Cpu)).expect("Failed to create renderer");
+    let buffer = renderer.create_vertex_buffer(&triangle_vertices()).unwrap();
+
+    let mut commands = CommandList::new([0.0; 4]);
+    commands.draw(buffer, 1, 3);
+    match renderer.submit(&commands) {
+        Err(Error::DrawOutOfBounds { vertex_count: 3, .. }) => {}
+        result => panic!("Unexpected result {:?}", result),
+    }
+
+    renderer.destroy_buffer(buffer).unwrap();
+    let mut commands = CommandList::new([0.0; 4]);
+    commands.draw(buffer, 0, 3);
+    match renderer.submit(&commands) {
+        Err(Error::InvalidBuffer(invalid)) => assert_eq!(invalid, buffer),
+        result => panic!("Unexpected result {:?}", result),
+    }
+    assert!(renderer.destroy_buffer(buffer).is_err());
+}
+
+#[test]
+fn renderer_resize_test() {
+    Logger::init(log::LevelFilter::Trace);
+    let mut renderer = create_renderer(&RendererConfig::new(8, 8).preference(BackendPreference::Cpu)).expect("Failed to create renderer");
+    assert!(matches!(renderer.present(), Err(Error::NoWindow)));
+
+    renderer.resize(16, 4).expect("Failed to resize");
+    assert_eq!(renderer.extent(), (16, 4));
+    // Minimized windows keep the target
+    renderer.resize(0, 0).expect("Failed to resize");
+    assert_eq!(renderer.extent(), (16, 4));
+    render_triangle(renderer.as_mut()).expect("Failed to render");
+    let image = renderer.read_pixels().expect("Failed to read pixels");
+    assert_eq!((image.width, image.height), (16, 4));
+}
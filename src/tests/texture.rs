@@ -0,0 +1,458 @@
+use crate::backend::vulkan::image::ImageKind;
+use crate::image::RgbaImage;
+use crate::tests::golden::reference_dir;
+use crate::texture::bc::{decompress, decompressed_format};
+use crate::texture::dds::parse_dds;
+use crate::texture::jpeg::decode_jpeg;
+use crate::texture::ktx2::{parse_ktx2, IDENTIFIER};
+use crate::texture::{format_block, level_size, Error, TextureData};
+use ash::vk;
+use std::fs;
+
+/// Pixels `gradient_420.jpg` was encoded from
+fn gradient_source(x: u32, y: u32) -> [u8; 3] {
+    [(x * 12).min(255) as u8, (y * 20).min(255) as u8, ((x * 7 + y * 11) % 256) as u8]
+}
+
+/// Packs fields into a 128 bit block starting at the least significant bit
+struct BlockWriter {
+    bits: u128,
+    position: u32,
+}
+
+impl BlockWriter {
+    fn new() -> Self {
+        Self { bits: 0, position: 0 }
+    }
+
+    fn write(&mut self, value: u32, count: u32) -> &mut Self {
+        self.bits |= (value as u128) << self.position;
+        self.position += count;
+        self
+    }
+
+    fn finish(&self) -> [u8; 16] {
+        assert_eq!(self.position, 128, "Block has {} bits", self.position);
+        self.bits.to_le_bytes()
+    }
+}
+
+fn extent(width: u32, height: u32) -> vk::Extent3D {
+    vk::Extent3D { width, height, depth: 1 }
+}
+
+#[test]
+fn jpeg_decode_test() {
+    let bytes = fs::read(reference_dir().join("gradient_420.jpg")).expect("Failed to read JPEG");
+    let image = decode_jpeg(&bytes).expect("Failed to decode JPEG");
+    assert_eq!((image.width, image.height), (20, 12));
+
+    // Chroma is subsampled 2x2, sharp color steps blur but luma has to stay close
+    let mut total_error = 0u32;
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let pixel = image.pixel(x, y);
+            let expected = gradient_source(x, y);
+            assert_eq!(pixel[3], 255);
+            for channel in 0..3 {
+                let error = (pixel[channel] as i32 - expected[channel] as i32).unsigned_abs();
+                assert!(error <= 40, "Pixel {},{} channel {} is {} instead of {}", x, y, channel, pixel[channel], expected[channel]);
+                total_error += error;
+            }
+        }
+    }
+    let mean_error = total_error as f32 / (20.0 * 12.0 * 3.0);
+    assert!(mean_error < 6.0, "Mean error {}", mean_error);
+}
+
+#[test]
+fn jpeg_progressive_rejected_test() {
+    // SOI followed by a progressive frame header
+    let bytes = [0xff, 0xd8, 0xff, 0xc2, 0x00, 0x0b, 0x08, 0x00, 0x01, 0x00, 0x01, 0x01, 0x01, 0x11, 0x00];
+    assert!(matches!(decode_jpeg(&bytes), Err(Error::Unsupported(_))));
+    assert!(matches!(decode_jpeg(&[0xff, 0xd8]), Err(Error::InvalidData(_))));
+}
+
+#[test]
+fn jpeg_malformed_test() {
+    // 8x8 grayscale frame header
+    let frame = [0xff, 0xd8, 0xff, 0xc0, 0x00, 0x0b, 0x08, 0x00, 0x08, 0x00, 0x08, 0x01, 0x01, 0x11, 0x00];
+    let scan = [0xff, 0xda, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3f, 0x00];
+    let empty_scan = [frame.as_slice(), &[0xff, 0xda, 0x00, 0x02]].concat();
+    assert!(matches!(decode_jpeg(&empty_scan), Err(Error::InvalidData(_))));
+    let undefined_tables = [frame.as_slice(), &scan, &[0x00, 0xff, 0xd9]].concat();
+    assert!(matches!(decode_jpeg(&undefined_tables), Err(Error::InvalidData(_))));
+
+    // Tables with a single one bit code, the DC one decodes to a difference category of 16 bits
+    let huffman = |class: u8, value: u8| {
+        let mut table = vec![0xff, 0xc4, 0x00, 0x14, class << 4, 0x01];
+        table.extend_from_slice(&[0; 15]);
+        table.push(value);
+        table
+    };
+    let oversized_dc = [frame.as_slice(), &huffman(0, 16), &huffman(1, 0), &scan, &[0x00, 0xff, 0xd9]].concat();
+    assert!(matches!(decode_jpeg(&oversized_dc), Err(Error::InvalidData(_))));
+}
+
+#[test]
+fn bc1_decode_test() {
+    // Red and blue endpoints, every palette entry once in the first row
+    let mut block = vec![0x00, 0xf8, 0x1f, 0x00, 0b11100100, 0, 0, 0];
+    let pixels = decompress(vk::Format::BC1_RGBA_UNORM_BLOCK, extent(4, 4), &block).unwrap();
+    assert_eq!(&pixels[..16], &[255, 0, 0, 255, 0, 0, 255, 255, 170, 0, 85, 255, 85, 0, 170, 255]);
+
+    // Swapped endpoints select the three color mode with transparent black
+    block.swap(0, 2);
+    block.swap(1, 3);
+    let pixels = decompress(vk::Format::BC1_RGBA_UNORM_BLOCK, extent(4, 4), &block).unwrap();
+    assert_eq!(&pixels[8..16], &[127, 0, 127, 255, 0, 0, 0, 0]);
+    let pixels = decompress(vk::Format::BC1_RGB_UNORM_BLOCK, extent(4, 4), &block).unwrap();
+    assert_eq!(&pixels[12..16], &[0, 0, 0, 255]);
+}
+
+#[test]
+fn bc_partial_block_test() {
+    // 6x5 image covers 2x2 blocks, the overhanging texels are dropped
+    let white = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+    let data: Vec<u8> = white.iter().copied().cycle().take(4 * 8).collect();
+    let pixels = decompress(vk::Format::BC1_RGB_SRGB_BLOCK, extent(6, 5), &data).unwrap();
+    assert_eq!(pixels.len(), 6 * 5 * 4);
+    assert!(pixels.iter().all(|value| *value == 255));
+    assert!(matches!(decompress(vk::Format::BC1_RGB_SRGB_BLOCK, extent(6, 5), &data[..24]), Err(Error::InvalidData(_))));
+    assert_eq!(decompressed_format(vk::Format::BC1_RGB_SRGB_BLOCK), Some(vk::Format::R8G8B8A8_SRGB));
+    assert_eq!(decompressed_format(vk::Format::BC6H_UFLOAT_BLOCK), Some(vk::Format::R16G16B16A16_SFLOAT));
+    assert_eq!(decompressed_format(vk::Format::ASTC_10X6_SRGB_BLOCK), Some(vk::Format::R8G8B8A8_SRGB));
+    assert_eq!(decompressed_format(vk::Format::ETC2_R8G8B8_UNORM_BLOCK), None);
+}
+
+#[test]
+fn bc3_bc4_bc5_decode_test() {
+    // Alpha 255 to 0 in eight steps, pixel i uses index i % 8
+    let alpha_indices: u64 = (0..16).map(|pixel| ((pixel % 8) as u64) << (3 * pixel)).sum();
+    let mut alpha = vec![255, 0];
+    alpha.extend_from_slice(&alpha_indices.to_le_bytes()[..6]);
+    let color = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+
+    let bc3: Vec<u8> = alpha.iter().chain(color.iter()).copied().collect();
+    let pixels = decompress(vk::Format::BC3_UNORM_BLOCK, extent(4, 4), &bc3).unwrap();
+    let alphas: Vec<u8> = pixels.chunks_exact(4).take(8).map(|pixel| pixel[3]).collect();
+    assert_eq!(alphas, vec![255, 0, 218, 182, 145, 109, 72, 36]);
+
+    let pixels = decompress(vk::Format::BC4_UNORM_BLOCK, extent(4, 4), &alpha).unwrap();
+    assert_eq!(&pixels[8..12], &[218, 0, 0, 255]);
+
+    // Six value mode has explicit 0 and 255
+    let mut six = alpha.clone();
+    six.swap(0, 1);
+    let bc5: Vec<u8> = alpha.iter().chain(six.iter()).copied().collect();
+    let pixels = decompress(vk::Format::BC5_UNORM_BLOCK, extent(4, 4), &bc5).unwrap();
+    assert_eq!(&pixels[24..32], &[72, 0, 0, 255, 36, 255, 0, 255]);
+}
+
+#[test]
+fn bc7_mode6_decode_test() {
+    // Mode 6: one subset, 7 bit RGBA endpoints with a p-bit each and 4 bit indices
+    let mut writer = BlockWriter::new();
+    writer.write(1 << 6, 7);
+    for (e0, e1) in [(127, 0), (0, 127), (64, 64), (127, 0)] {
+        writer.write(e0, 7).write(e1, 7);
+    }
+    writer.write(1, 1).write(0, 1);
+    // Anchor pixel 0 has 3 index bits, pixel 1 uses the second endpoint
+    writer.write(0, 3).write(15, 4);
+    for _ in 2..16 {
+        writer.write(0, 4);
+    }
+    let pixels = decompress(vk::Format::BC7_UNORM_BLOCK, extent(4, 4), &writer.finish()).unwrap();
+    assert_eq!(&pixels[..4], &[255, 1, 129, 255]);
+    assert_eq!(&pixels[4..8], &[0, 254, 128, 0]);
+
+    // Reserved mode 8 decodes to transparent black
+    let pixels = decompress(vk::Format::BC7_SRGB_BLOCK, extent(4, 4), &[0; 16]).unwrap();
+    assert!(pixels.iter().all(|value| *value == 0));
+}
+
+#[test]
+fn bc7_mode1_partition_test() {
+    // Mode 1 with partition 13, the top two rows are subset 0 and the bottom two subset 1
+    let mut writer = BlockWriter::new();
+    writer.write(0b10, 2).write(13, 6);
+    let endpoints = [[63, 63, 0, 0], [0, 0, 0, 0], [0, 0, 63, 63]];
+    for channel in endpoints {
+        for value in channel {
+            writer.write(value, 6);
+        }
+    }
+    // Shared p-bits of both subsets
+    writer.write(1, 1).write(1, 1);
+    // Anchors are pixel 0 and 15
+    for pixel in 0..16 {
+        let bits = if pixel == 0 || pixel == 15 { 2 } else { 3 };
+        writer.write(0, bits);
+    }
+    let pixels = decompress(vk::Format::BC7_UNORM_BLOCK, extent(4, 4), &writer.finish()).unwrap();
+    for pixel in pixels.chunks_exact(4).take(8) {
+        assert_eq!(pixel, &[255, 2, 2, 255]);
+    }
+    for pixel in pixels.chunks_exact(4).skip(8) {
+        assert_eq!(pixel, &[2, 2, 255, 255]);
+    }
+}
+
+/// Half float texels of a decompressed BC6H block
+fn halves(pixels: &[u8]) -> Vec<u16> {
+    pixels.chunks_exact(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])).collect()
+}
+
+#[test]
+fn bc6h_decode_test() {
+    // Mode 11: one region with untransformed 10 bit endpoints, pixel 1 uses the second endpoint
+    let mode11 = |w: [u32; 3], x: [u32; 3]| {
+        let mut writer = BlockWriter::new();
+        writer.write(0b00011, 5);
+        w.iter().chain(x.iter()).for_each(|&value| {
+            writer.write(value, 10);
+        });
+        writer.write(0, 3).write(15, 4).write(8, 4);
+        for _ in 3..16 {
+            writer.write(0, 4);
+        }
+        writer.finish()
+    };
+    let block = mode11([0; 3], [1023; 3]);
+    let pixels = decompress(vk::Format::BC6H_UFLOAT_BLOCK, extent(4, 4), &block).unwrap();
+    assert_eq!(pixels.len(), 4 * 4 * 8);
+    assert_eq!(&halves(&pixels)[..12], &[0, 0, 0, 0x3c00, 0x7bff, 0x7bff, 0x7bff, 0x3c00, 0x41df, 0x41df, 0x41df, 0x3c00]);
+
+    // Signed endpoints are sign extended, -511 is the most negative value
+    let block = mode11([0; 3], [0x201, 0x1ff, 0]);
+    let pixels = decompress(vk::Format::BC6H_SFLOAT_BLOCK, extent(4, 4), &block).unwrap();
+    assert_eq!(&halves(&pixels)[4..8], &[0xfbff, 0x7bff, 0, 0x3c00]);
+
+    // Mode 12: 11 bit base with the top bit stored apart from the rest and a 9 bit delta of -1
+    let mut writer = BlockWriter::new();
+    writer.write(0b00111, 5).write(0, 30).write(0x1ff, 9).write(1, 1);
+    writer.write(0, 20).write(0, 3).write(15, 4);
+    for _ in 2..16 {
+        writer.write(0, 4);
+    }
+    let pixels = decompress(vk::Format::BC6H_UFLOAT_BLOCK, extent(4, 4), &writer.finish()).unwrap();
+    assert_eq!(&halves(&pixels)[..8], &[0x3e07, 0, 0, 0x3c00, 0x3df8, 0, 0, 0x3c00]);
+
+    // Reserved mode 10011 decodes to black
+    let pixels = decompress(vk::Format::BC6H_UFLOAT_BLOCK, extent(4, 4), &[0b10011; 16]).unwrap();
+    assert!(halves(&pixels).chunks_exact(4).all(|texel| texel == [0, 0, 0, 0x3c00]));
+}
+
+/// ASTC block with the fields below the weights written by `writer` and the weight stream `weights` in the top bits
+fn astc_block(writer: &mut BlockWriter, weights: u32) -> [u8; 16] {
+    writer.write(0, 96 - writer.position).write(weights.reverse_bits(), 32);
+    writer.finish()
+}
+
+#[test]
+fn astc_decode_test() {
+    // 4x4 grid of 2 bit weights, one partition with luminance endpoints 0 and 255
+    let mut writer = BlockWriter::new();
+    writer.write(0x42, 11).write(0, 2).write(0, 4).write(0, 8).write(255, 8);
+    let weights: u32 = (0..16).map(|texel| (texel % 4) << (2 * texel)).sum();
+    let pixels = decompress(vk::Format::ASTC_4X4_UNORM_BLOCK, extent(4, 4), &astc_block(&mut writer, weights)).unwrap();
+    for row in pixels.chunks_exact(16) {
+        assert_eq!(row, &[0, 0, 0, 255, 84, 84, 84, 255, 171, 171, 171, 255, 255, 255, 255, 255]);
+    }
+
+    // Three level weights are trits, five of them packed into eight bits: 181 are all ones, the last one is alone
+    let mut writer = BlockWriter::new();
+    writer.write(0x51, 11).write(0, 2).write(0, 4).write(0, 8).write(255, 8);
+    let weights = 181 | (181 << 8) | (181 << 16) | (1 << 24);
+    let pixels = decompress(vk::Format::ASTC_4X4_SRGB_BLOCK, extent(4, 4), &astc_block(&mut writer, weights)).unwrap();
+    assert!(pixels.chunks_exact(4).all(|pixel| pixel == [128, 128, 128, 255]));
+
+    // Void extent blocks hold one 16 bit color, the 6x5 blocks of a 13x5 image hang over the right edge
+    let mut writer = BlockWriter::new();
+    writer.write(0x1fc, 9).write(0, 1).write(3, 2);
+    for _ in 0..4 {
+        writer.write(0x1fff, 13);
+    }
+    for value in [0x1234, 0xff00, 0x00ff, 0xffff] {
+        writer.write(value, 16);
+    }
+    let data = writer.finish().repeat(3);
+    let pixels = decompress(vk::Format::ASTC_6X5_UNORM_BLOCK, extent(13, 5), &data).unwrap();
+    assert_eq!(pixels.len(), 13 * 5 * 4);
+    assert!(pixels.chunks_exact(4).all(|pixel| pixel == [0x12, 0xff, 0x00, 0xff]));
+
+    // Reserved block modes decode to the error color
+    let pixels = decompress(vk::Format::ASTC_8X8_UNORM_BLOCK, extent(8, 8), &[0; 16]).unwrap();
+    assert!(pixels.chunks_exact(4).all(|pixel| pixel == [255, 0, 255, 255]));
+}
+
+#[test]
+fn compressed_arbitrary_blocks_test() {
+    // Every bit pattern is a block the decoders have to handle, most of them illegal or HDR ASTC
+    let mut state = 0x2545_f491u32;
+    let blocks: Vec<u8> = (0..16 * 64 * 64)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    let formats = [
+        vk::Format::BC6H_UFLOAT_BLOCK,
+        vk::Format::BC6H_SFLOAT_BLOCK,
+        vk::Format::ASTC_4X4_UNORM_BLOCK,
+        vk::Format::ASTC_5X4_SRGB_BLOCK,
+        vk::Format::ASTC_6X6_UNORM_BLOCK,
+        vk::Format::ASTC_8X5_SRGB_BLOCK,
+        vk::Format::ASTC_10X8_UNORM_BLOCK,
+        vk::Format::ASTC_12X12_SRGB_BLOCK,
+    ];
+    for format in formats {
+        let block = format_block(format).unwrap();
+        decompress(format, extent(block.width * 64, block.height * 64), &blocks).unwrap();
+    }
+}
+
+/// Runs `parse` on every truncation of `bytes` and on copies with every byte flipped, none of which may panic
+fn parse_damaged<T>(bytes: &[u8], parse: impl Fn(&[u8]) -> Result<T, Error>) {
+    for length in 0..bytes.len() {
+        let _ = parse(&bytes[..length]);
+    }
+    let mut damaged = bytes.to_vec();
+    for offset in 0..bytes.len() {
+        for flip in [0x01, 0x80, 0xff] {
+            damaged[offset] ^= flip;
+            let _ = parse(&damaged);
+            damaged[offset] ^= flip;
+        }
+    }
+}
+
+#[test]
+fn parsers_damaged_input_test() {
+    let jpeg = fs::read(reference_dir().join("gradient_420.jpg")).expect("Failed to read JPEG");
+    parse_damaged(&jpeg, decode_jpeg);
+
+    let levels = vec![vec![1; 4 * 4 * 4 * 2], vec![2; 2 * 2 * 4 * 2], vec![3; 4 * 2]];
+    parse_damaged(&ktx2_file(vk::Format::R8G8B8A8_UNORM, 4, 4, 2, 1, &levels), parse_ktx2);
+    parse_damaged(&ktx2_file(vk::Format::BC7_SRGB_BLOCK, 8, 8, 0, 6, &[vec![4; 4 * 16 * 6]]), parse_ktx2);
+
+    let mut dds = dds_header(8, 4, 2, b"DX10");
+    for value in [98u32, 3, 0, 1, 0] {
+        dds.extend_from_slice(&value.to_le_bytes());
+    }
+    dds.extend_from_slice(&[5; 2 * 16 + 16]);
+    parse_damaged(&dds, parse_dds);
+    let mut legacy = dds_header(4, 4, 1, b"DXT1");
+    legacy.extend_from_slice(&[6; 8]);
+    parse_damaged(&legacy, parse_dds);
+}
+
+fn ktx2_file(format: vk::Format, width: u32, height: u32, layers: u32, faces: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = IDENTIFIER.to_vec();
+    for value in [format.as_raw() as u32, 1, width, height, 0, layers, faces, levels.len() as u32, 0] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    // Empty data format descriptor, key/value data and supercompression global data
+    bytes.extend_from_slice(&[0; 4 * 4 + 2 * 8]);
+    let mut offset = bytes.len() + levels.len() * 24;
+    let mut index = Vec::new();
+    for level in levels {
+        for value in [offset, level.len(), level.len()] {
+            index.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+        offset += level.len();
+    }
+    bytes.extend(index);
+    levels.iter().for_each(|level| bytes.extend_from_slice(level));
+    bytes
+}
+
+#[test]
+fn ktx2_parse_test() {
+    let levels = vec![(0..4 * 2 * 4 * 3).map(|value| value as u8).collect(), vec![7; 2 * 4 * 3]];
+    let bytes = ktx2_file(vk::Format::R8G8B8A8_SRGB, 4, 2, 3, 1, &levels);
+    let data = TextureData::from_bytes(&bytes, false).expect("Failed to parse KTX2");
+    assert_eq!(data.format, vk::Format::R8G8B8A8_SRGB);
+    assert_eq!(data.kind, ImageKind::D2Array);
+    assert_eq!((data.extent.width, data.extent.height, data.extent.depth), (4, 2, 1));
+    assert_eq!(data.array_layers, 3);
+    assert_eq!(data.levels, levels);
+    data.validate().unwrap();
+
+    let cube = ktx2_file(vk::Format::BC1_RGBA_UNORM_BLOCK, 8, 8, 0, 6, &[vec![0; 4 * 8 * 6]]);
+    let data = parse_ktx2(&cube).expect("Failed to parse KTX2 cube");
+    assert_eq!((data.kind, data.array_layers), (ImageKind::Cube, 6));
+
+    // Level sizes have to match the header
+    let truncated = ktx2_file(vk::Format::R8G8B8A8_UNORM, 4, 4, 0, 1, &[vec![0; 60]]);
+    assert!(matches!(parse_ktx2(&truncated), Err(Error::InvalidData(_))));
+    let mut supercompressed = ktx2_file(vk::Format::R8G8B8A8_UNORM, 1, 1, 0, 1, &[vec![0; 4]]);
+    supercompressed[44] = 2;
+    assert!(matches!(parse_ktx2(&supercompressed), Err(Error::Unsupported(_))));
+}
+
+fn dds_header(width: u32, height: u32, mip_count: u32, four_cc: &[u8; 4]) -> Vec<u8> {
+    let mut header = [0u32; 31];
+    header[0] = 124;
+    header[1] = 0x1 | 0x2 | 0x4 | 0x1000 | 0x20000;
+    header[2] = height;
+    header[3] = width;
+    header[6] = mip_count;
+    header[18] = 32;
+    header[19] = 0x4;
+    header[20] = u32::from_le_bytes(*four_cc);
+    let mut bytes = b"DDS ".to_vec();
+    header.iter().for_each(|value| bytes.extend_from_slice(&value.to_le_bytes()));
+    bytes
+}
+
+#[test]
+fn dds_parse_test() {
+    // Two BC1 layers with two levels each, stored layer by layer
+    let mut bytes = dds_header(8, 8, 2, b"DX10");
+    for value in [71u32, 3, 0, 2, 0] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    for layer in 0..2u8 {
+        bytes.extend_from_slice(&[layer; 4 * 8]);
+        bytes.extend_from_slice(&[layer + 10; 8]);
+    }
+    let data = parse_dds(&bytes).expect("Failed to parse DDS");
+    assert_eq!(data.format, vk::Format::BC1_RGBA_UNORM_BLOCK);
+    assert_eq!((data.kind, data.array_layers), (ImageKind::D2Array, 2));
+    assert_eq!(data.levels.len(), 2);
+    assert_eq!(data.levels[0], [vec![0; 32], vec![1; 32]].concat());
+    assert_eq!(data.levels[1], [vec![10; 8], vec![11; 8]].concat());
+    data.validate().unwrap();
+
+    let mut legacy = dds_header(4, 4, 1, b"DXT5");
+    legacy.extend_from_slice(&[0; 16]);
+    let data = TextureData::from_bytes(&legacy, true).expect("Failed to parse DDS");
+    assert_eq!((data.format, data.kind), (vk::Format::BC3_UNORM_BLOCK, ImageKind::D2));
+
+    assert!(matches!(parse_dds(&legacy[..100]), Err(Error::InvalidData(_))));
+    let mut too_many_levels = dds_header(4, 4, 4, b"DXT5");
+    too_many_levels.extend_from_slice(&[0; 64]);
+    assert!(matches!(parse_dds(&too_many_levels), Err(Error::InvalidData(_))));
+    let unknown = dds_header(4, 4, 1, b"ETC1");
+    assert!(matches!(parse_dds(&unknown), Err(Error::Unsupported(_))));
+}
+
+#[test]
+fn texture_from_png_test() {
+    let mut image = RgbaImage::new(5, 3);
+    image.set_pixel(4, 2, [1, 2, 3, 4]);
+    let path = std::env::temp_dir().join("eikon_texture.png");
+    image.write_png(&path).expect("Failed to write PNG");
+    let data = TextureData::load(&path, true).expect("Failed to load PNG");
+    fs::remove_file(path).unwrap();
+
+    assert_eq!(data.format, vk::Format::R8G8B8A8_SRGB);
+    assert_eq!(data.levels, vec![image.pixels]);
+    assert_eq!(level_size(data.format, data.extent), Some(5 * 3 * 4));
+    assert_eq!(level_size(vk::Format::ASTC_6X5_UNORM_BLOCK, extent(13, 5)), Some(3 * 16));
+    assert!(matches!(TextureData::from_bytes(b"GIF89a", true), Err(Error::Unsupported(_))));
+}
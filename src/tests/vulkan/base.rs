@@ -1,5 +1,5 @@
 use crate::backend::vulkan::base::{Base, BaseConfigBuilder};
-use crate::backend::vulkan::errors::Error::ValidationLayerNotSupported;
+use crate::backend::vulkan::errors::Error::{ExtensionNotSupported, ValidationLayerNotSupported};
 use crate::backend::vulkan::utils::to_version;
 use ash::vk;
 use log::{LevelFilter, Metadata, Record};
@@ -31,6 +31,7 @@ fn test_base_config_fail() {
                 println!("Layer error {}", index);
                 return;
             }
+            error => assert!(false, "Unexpected error {}", error),
         }
     }
     assert!(false, "Layer found!");
@@ -49,11 +50,25 @@ fn test_base_config_fail_multiple() {
                 println!("Layer error {}", index);
                 return;
             }
+            error => assert!(false, "Unexpected error {}", error),
         }
     }
     assert!(false, "Layer found!");
 }
 
+#[test]
+fn test_base_config_missing_extension() {
+    let base_cfg = BaseConfigBuilder::new()
+        .vulkan_extensions(&["VK_EXT_debug_utils", "FAIL_EXTENSION"])
+        .build("Test", "Test", "1.0.0", "1.0.0", "1.0.0");
+
+    match Base::new(base_cfg) {
+        Err(ExtensionNotSupported(extension)) => assert_eq!(extension, CString::new("FAIL_EXTENSION").unwrap()),
+        Err(error) => assert!(false, "Unexpected error {}", error),
+        Ok(_) => assert!(false, "Extension found!"),
+    }
+}
+
 #[test]
 fn test_base_config_values() {
     let validation = "VK_LAYER_KHRONOS_validation";
@@ -0,0 +1,87 @@
+use crate::backend::vulkan::bindless::{BindlessConfig, BindlessTable, SlotAllocator};
+use crate::backend::vulkan::buffer::Buffer;
+use crate::backend::vulkan::context::{Context, ContextConfigurator};
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::{Image, ImageConfig, Sampler, SamplerConfig};
+use crate::backend::vulkan::memory::MemoryUsage;
+use crate::tests::vulkan::test_utils::{create_headless_test_base, create_headless_test_context};
+use ash::vk;
+
+#[test]
+fn slot_allocator_test() {
+    let mut slots = SlotAllocator::new(3, 2);
+    assert_eq!((slots.allocate(), slots.allocate(), slots.allocate()), (Some(0), Some(1), Some(2)));
+    assert_eq!(slots.allocate(), None);
+
+    assert!(slots.free(1));
+    assert!(!slots.free(1));
+    assert!(!slots.free(7));
+    assert!(!slots.is_allocated(1));
+    // Retired for two frames before it can be handed out again
+    assert_eq!(slots.allocate(), None);
+    slots.next_frame();
+    assert_eq!(slots.allocate(), None);
+    slots.next_frame();
+    assert_eq!(slots.allocate(), Some(1));
+    assert_eq!(slots.len(), 3);
+
+    let limits = vk::PhysicalDeviceVulkan12Properties {
+        max_per_stage_descriptor_update_after_bind_samplers: 1000,
+        max_per_stage_descriptor_update_after_bind_sampled_images: 500,
+        max_descriptor_set_update_after_bind_samplers: 1000,
+        max_descriptor_set_update_after_bind_sampled_images: 1000,
+        max_per_stage_descriptor_update_after_bind_storage_buffers: 64,
+        max_descriptor_set_update_after_bind_storage_buffers: 64,
+        max_per_stage_descriptor_update_after_bind_storage_images: 8192,
+        max_descriptor_set_update_after_bind_storage_images: 8192,
+        ..Default::default()
+    };
+    let config = BindlessConfig::default().clamp_to(&limits);
+    assert_eq!((config.textures, config.storage_buffers, config.storage_images), (500, 64, 1024));
+}
+
+#[test]
+fn bindless_table_test() {
+    let context = create_headless_test_context();
+    assert!(matches!(
+        BindlessTable::new(&context, BindlessConfig::default()),
+        Err(Error::DeviceFeatureNotEnabled(_))
+    ));
+    drop(context);
+
+    let configurator = ContextConfigurator::headless(&[]).descriptor_indexing(true);
+    let context = Context::new(create_headless_test_base(), configurator).expect("Failed to create context");
+    assert!(context.device_features().descriptor_indexing);
+    let config = BindlessConfig::new(1).textures(2).storage_buffers(4).storage_images(1);
+    let mut table = BindlessTable::new(&context, config).expect("Failed to create bindless table");
+    assert_ne!(table.set(), vk::DescriptorSet::null());
+
+    let image = Image::new(
+        &context,
+        ImageConfig::new_2d(vk::Format::R8G8B8A8_UNORM, 4, 4).usage(vk::ImageUsageFlags::SAMPLED),
+    )
+    .expect("Failed to create image");
+    let view = image.create_view().expect("Failed to create view");
+    let sampler = Sampler::new(&context, &SamplerConfig::new()).expect("Failed to create sampler");
+    let first = table.add_texture(view.handle(), sampler.handle()).expect("Failed to add texture");
+    let second = table.add_texture(view.handle(), sampler.handle()).expect("Failed to add texture");
+    assert_eq!((first.index(), second.index()), (0, 1));
+    assert!(matches!(
+        table.add_texture(view.handle(), sampler.handle()),
+        Err(Error::BindlessSlotsExhausted(_))
+    ));
+
+    assert!(table.remove_texture(first));
+    table.next_frame();
+    let reused = table
+        .add_texture(view.handle(), sampler.handle())
+        .expect("Failed to reuse texture slot");
+    assert_eq!(reused.index(), 0);
+
+    let buffer = Buffer::new(&context, 256, vk::BufferUsageFlags::STORAGE_BUFFER, MemoryUsage::GpuOnly).expect("Failed to create buffer");
+    let handle = table
+        .add_storage_buffer(buffer.handle(), 0, vk::WHOLE_SIZE)
+        .expect("Failed to add storage buffer");
+    assert_eq!(table.storage_buffer_count(), 1);
+    assert!(table.remove_storage_buffer(handle));
+}
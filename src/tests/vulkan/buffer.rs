@@ -0,0 +1,58 @@
+use crate::backend::renderer::ColorVertex;
+use crate::backend::vulkan::buffer::Buffer;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::memory::MemoryUsage;
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use ash::vk;
+
+#[test]
+fn buffer_write_read_test() {
+    let context = create_headless_test_context();
+    let vertices = [
+        ColorVertex::new([0.0, 1.0, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]),
+        ColorVertex::new([1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0]),
+    ];
+    let mut buffer =
+        Buffer::with_data(&context, &vertices, vk::BufferUsageFlags::VERTEX_BUFFER, MemoryUsage::CpuToGpu).expect("Failed to create buffer");
+    assert_eq!(buffer.size(), 64);
+    assert!(buffer.is_mapped());
+    assert_eq!(buffer.read_slice::<ColorVertex>(0, 2).expect("Failed to read"), vertices);
+
+    // Unaligned offsets are fine for byte copies
+    buffer.write_slice(3, &[7u32, 9u32]).expect("Failed to write");
+    assert_eq!(buffer.read_slice::<u32>(3, 2).expect("Failed to read"), vec![7, 9]);
+    // Widened to whole atoms for non-coherent memory, a no-op otherwise
+    buffer.flush_range(3, 8).expect("Failed to flush");
+    buffer.invalidate_range(0, 64).expect("Failed to invalidate");
+    assert!(matches!(buffer.flush_range(60, 8), Err(Error::BufferOutOfRange { .. })));
+
+    match buffer.write_slice(60, &[0u64]) {
+        Err(Error::BufferOutOfRange { offset, size, buffer_size }) => assert_eq!((offset, size, buffer_size), (60, 8, 64)),
+        _ => panic!("Write past the end was accepted"),
+    }
+    assert!(matches!(buffer.read_slice::<u64>(0, usize::MAX), Err(Error::BufferOutOfRange { .. })));
+    assert_eq!(context.allocator().stats().allocation_count, 1);
+    drop(buffer);
+    assert_eq!(context.allocator().stats().allocation_count, 0);
+}
+
+#[test]
+fn buffer_gpu_only_test() {
+    let context = create_headless_test_context();
+    let buffer = Buffer::new(&context, 256, vk::BufferUsageFlags::STORAGE_BUFFER, MemoryUsage::GpuOnly).expect("Failed to create buffer");
+    assert_eq!(buffer.memory_usage(), MemoryUsage::GpuOnly);
+    let flags = context.allocator().memory_type_flags(buffer.allocation().memory_type_index());
+    if !flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+        assert!(matches!(buffer.read_slice::<u8>(0, 1), Err(Error::BufferNotMapped)));
+    }
+}
+
+#[test]
+fn buffer_outlives_context_test() {
+    let context = create_headless_test_context();
+    let mut buffer = Buffer::new(&context, 16, vk::BufferUsageFlags::VERTEX_BUFFER, MemoryUsage::CpuToGpu).expect("Failed to create buffer");
+    // The buffer keeps the device and its allocator alive
+    drop(context);
+    buffer.write_slice(0, &[1u32, 2, 3, 4]).expect("Failed to write");
+    assert_eq!(buffer.read_slice::<u32>(0, 4).expect("Failed to read"), vec![1, 2, 3, 4]);
+}
@@ -1,6 +1,7 @@
-use crate::backend::vulkan::context::{obtain_queues, ContextConfigurator};
+use crate::backend::vulkan::context::{obtain_queues, Context, ContextConfigurator};
+use crate::backend::vulkan::queue::op_indices::{COMPUTE, GRAPHICS, PRESENT};
 use crate::tests::vulkan::log::Logger;
-use crate::tests::vulkan::test_utils::{create_test_base, TestApp};
+use crate::tests::vulkan::test_utils::{create_headless_test_base, create_test_base, TestApp};
 use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 use winit::window::Window;
 
@@ -30,12 +31,18 @@ fn context_configurator_test() {
             window.display_handle().expect("Failed to get raw display handle").as_raw(),
             &["VK_KHR_swapchain"],
         );
-        let surface = context_config.create_surface(&base);
-        let physical_devices = context_config.obtain_physical_devices(&base, &surface);
+        let surface = context_config.create_surface(&base).expect("Failed to create surface");
+        let physical_devices = context_config
+            .obtain_physical_devices(&base, surface.as_ref())
+            .expect("Failed to obtain physical devices");
         assert!(physical_devices.len() > 0);
-        let queue_selections = context_config.obtain_queue_families(&base, &physical_devices[0].device, &surface);
+        let queue_selections = context_config
+            .obtain_queue_families(&base, &physical_devices[0].device, surface.as_ref())
+            .expect("Failed to obtain queue families");
         assert!(queue_selections.families.len() > 0);
-        let logical_device = context_config.select_logical_device(&base, &queue_selections, &physical_devices[0]);
+        let logical_device = context_config
+            .select_logical_device(&base, &queue_selections, &physical_devices[0])
+            .expect("Failed to create logical device");
         let queue_handles = obtain_queues(&logical_device, &queue_selections);
         assert!(queue_handles.queues.len() > 0);
         unsafe { logical_device.destroy_device(None) };
@@ -44,3 +51,36 @@ fn context_configurator_test() {
     let mut app = TestApp::new(testfn);
     app.run();
 }
+
+#[test]
+fn context_test() {
+    Logger::init(log::LevelFilter::Trace);
+    let testfn = |window: &Window| {
+        let base = create_test_base();
+        let context_config = ContextConfigurator::new(
+            window.window_handle().expect("Failed to get raw window handle").as_raw(),
+            window.display_handle().expect("Failed to get raw display handle").as_raw(),
+            &["VK_KHR_swapchain"],
+        );
+        let context = Context::new(base, context_config).expect("Failed to create context");
+        assert!(context.present_queue().is_some());
+        assert!(context.queue_family_index(GRAPHICS).is_some());
+        assert!(context.graphics_queue().is_ok_and(|queue| queue != ash::vk::Queue::null()));
+    };
+    let mut app = TestApp::new(testfn);
+    app.run();
+}
+
+#[test]
+fn headless_context_test() {
+    Logger::init(log::LevelFilter::Trace);
+    let base = create_headless_test_base();
+    let context_config = ContextConfigurator::headless(&[]);
+    assert!(context_config.is_headless());
+    let context = Context::new(base, context_config).expect("Failed to create headless context");
+    assert!(context.is_headless());
+    assert!(context.present_queue().is_none());
+    assert!(context.queue_family_index(PRESENT).is_none());
+    assert!(context.queue_family_index(COMPUTE).is_some());
+    assert!(context.physical_device().surface_properties.is_none());
+}
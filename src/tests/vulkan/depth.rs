@@ -0,0 +1,94 @@
+use crate::backend::renderer::ColorVertex;
+use crate::backend::vulkan::depth::{choose_depth_format, has_stencil, select_depth_format, DepthBuffer, DEPTH_FORMATS, DEPTH_STENCIL_FORMATS};
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::{Image, ImageConfig};
+use crate::backend::vulkan::pipeline::{DepthStencilConfig, GraphicsPipelineBuilder, VertexInputLayout};
+use crate::backend::vulkan::shaders::ShaderLibrary;
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use crate::utils::create_render_pass_with_depth;
+use ash::vk;
+use std::ptr::null;
+
+fn attachment_support(supported: &'static [vk::Format]) -> impl Fn(vk::Format) -> vk::FormatProperties {
+    move |format| vk::FormatProperties {
+        optimal_tiling_features: if supported.contains(&format) {
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            vk::FormatFeatureFlags::empty()
+        },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn depth_format_selection_test() {
+    let all = attachment_support(&[vk::Format::D32_SFLOAT, vk::Format::D24_UNORM_S8_UINT, vk::Format::D32_SFLOAT_S8_UINT]);
+    assert_eq!(choose_depth_format(&DEPTH_FORMATS, &all), Some(vk::Format::D32_SFLOAT));
+    assert_eq!(choose_depth_format(&DEPTH_STENCIL_FORMATS, &all), Some(vk::Format::D24_UNORM_S8_UINT));
+
+    // AMD style: no D24S8
+    let no_d24 = attachment_support(&[vk::Format::D32_SFLOAT, vk::Format::D32_SFLOAT_S8_UINT]);
+    assert_eq!(choose_depth_format(&DEPTH_STENCIL_FORMATS, &no_d24), Some(vk::Format::D32_SFLOAT_S8_UINT));
+
+    let stencil_only = attachment_support(&[vk::Format::D24_UNORM_S8_UINT]);
+    assert_eq!(choose_depth_format(&DEPTH_FORMATS, &stencil_only), Some(vk::Format::D24_UNORM_S8_UINT));
+    assert_eq!(choose_depth_format(&DEPTH_FORMATS, attachment_support(&[])), None);
+
+    assert!(has_stencil(vk::Format::D24_UNORM_S8_UINT));
+    assert!(!has_stencil(vk::Format::D32_SFLOAT));
+}
+
+#[test]
+fn depth_buffer_test() {
+    let context = create_headless_test_context();
+    let device = context.device();
+    let extent = vk::Extent2D { width: 64, height: 32 };
+    let format = select_depth_format(&context, true).expect("Failed to select depth format");
+    assert!(has_stencil(format));
+
+    let depth_buffer = DepthBuffer::new(&context, format, extent, vk::SampleCountFlags::TYPE_1).expect("Failed to create depth buffer");
+    assert_eq!(depth_buffer.extent(), extent);
+    assert!(depth_buffer.has_stencil());
+    assert!(matches!(
+        DepthBuffer::new(&context, vk::Format::R8G8B8A8_UNORM, extent, vk::SampleCountFlags::TYPE_1),
+        Err(Error::InvalidImageConfig(_))
+    ));
+
+    // Color target plus depth render pass, framebuffer and depth tested pipeline
+    let render_pass = create_render_pass_with_depth(device, vk::Format::R8G8B8A8_UNORM, Some(format), vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+    let color = Image::new(
+        &context,
+        ImageConfig::new_2d(vk::Format::R8G8B8A8_UNORM, extent.width, extent.height)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC),
+    )
+    .expect("Failed to create color image");
+    let color_view = color.create_view().expect("Failed to create color view");
+    let attachments = [color_view.handle(), depth_buffer.view().handle()];
+    let framebuffer_create_info = vk::FramebufferCreateInfo {
+        s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
+        p_next: null(),
+        render_pass,
+        attachment_count: attachments.len() as u32,
+        p_attachments: attachments.as_ptr(),
+        width: extent.width,
+        height: extent.height,
+        layers: 1,
+        ..Default::default()
+    };
+    let framebuffer = unsafe { device.create_framebuffer(&framebuffer_create_info, None) }.expect("Failed to create framebuffer");
+
+    let shaders = ShaderLibrary::with_embedded(&context).expect("Failed to load shaders");
+    let pipeline = GraphicsPipelineBuilder::new(render_pass)
+        .vertex_shader(shaders.module("color_vshader", vk::ShaderStageFlags::VERTEX).expect("Missing vertex shader"))
+        .fragment_shader(shaders.module("color_fshader", vk::ShaderStageFlags::FRAGMENT).expect("Missing fragment shader"))
+        .vertex_input(VertexInputLayout::new().vertex::<ColorVertex>())
+        .depth_stencil(DepthStencilConfig::depth(vk::CompareOp::LESS, true))
+        .build(device)
+        .expect("Failed to build depth tested pipeline");
+    drop(pipeline);
+
+    unsafe {
+        device.destroy_framebuffer(framebuffer, None);
+        device.destroy_render_pass(render_pass, None);
+    }
+}
@@ -0,0 +1,91 @@
+use crate::backend::vulkan::buffer::Buffer;
+use crate::backend::vulkan::descriptors::{
+    DescriptorAllocator, DescriptorLayoutCache, DescriptorWriter, FrameDescriptorAllocators, LayoutBinding, PoolSizes,
+};
+use crate::backend::vulkan::image::{Image, ImageConfig, Sampler, SamplerConfig};
+use crate::backend::vulkan::memory::MemoryUsage;
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use ash::vk;
+
+#[test]
+fn pool_sizes_test() {
+    let sizes = PoolSizes::new(10).ratios(&[(vk::DescriptorType::UNIFORM_BUFFER, 1.5), (vk::DescriptorType::SAMPLER, 0.01)]);
+    let pool_sizes = sizes.pool_sizes(10);
+    assert_eq!(pool_sizes.len(), 2);
+    assert_eq!(pool_sizes[0].descriptor_count, 15);
+    // Every type gets at least one descriptor
+    assert_eq!(pool_sizes[1].descriptor_count, 1);
+
+    let mut writer = DescriptorWriter::new()
+        .buffer(0, 0, vk::DescriptorType::UNIFORM_BUFFER, vk::Buffer::null(), 0, 64)
+        .image(
+            1,
+            2,
+            vk::DescriptorType::SAMPLED_IMAGE,
+            vk::ImageView::null(),
+            vk::ImageLayout::GENERAL,
+            vk::Sampler::null(),
+        );
+    assert_eq!(writer.len(), 2);
+    writer.clear();
+    assert!(writer.is_empty());
+}
+
+#[test]
+fn descriptors_test() {
+    let context = create_headless_test_context();
+    let mut cache = DescriptorLayoutCache::new(&context);
+    let stages = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT;
+    let uniform = LayoutBinding::new(0, vk::DescriptorType::UNIFORM_BUFFER, stages);
+    let texture = LayoutBinding::new(1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, vk::ShaderStageFlags::FRAGMENT);
+    let layout = cache.layout(&[uniform, texture]).expect("Failed to create layout");
+    // Binding order doesn't matter for the key
+    assert_eq!(cache.layout(&[texture, uniform]).expect("Failed to get layout"), layout);
+    assert_ne!(cache.layout(&[uniform]).expect("Failed to create layout"), layout);
+    assert_eq!(cache.len(), 2);
+
+    // Two sets per pool, the third allocation needs a new pool
+    let mut allocator = DescriptorAllocator::new(&context, PoolSizes::new(2));
+    let sets: Vec<vk::DescriptorSet> = (0..3).map(|_| allocator.allocate(layout).expect("Failed to allocate")).collect();
+    assert_eq!(allocator.pool_count(), 2);
+    assert!(sets.iter().all(|set| *set != vk::DescriptorSet::null()));
+
+    let buffer = Buffer::new(&context, 256, vk::BufferUsageFlags::UNIFORM_BUFFER, MemoryUsage::CpuToGpu).expect("Failed to create buffer");
+    let image = Image::new(
+        &context,
+        ImageConfig::new_2d(vk::Format::R8G8B8A8_UNORM, 4, 4).usage(vk::ImageUsageFlags::SAMPLED),
+    )
+    .expect("Failed to create image");
+    let view = image.create_view().expect("Failed to create view");
+    let sampler = Sampler::new(&context, &SamplerConfig::new()).expect("Failed to create sampler");
+    let image_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+    let writer = DescriptorWriter::new().uniform_buffer(0, &buffer).image(
+        1,
+        0,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        view.handle(),
+        image_layout,
+        sampler.handle(),
+    );
+    for set in &sets {
+        writer.update(context.device(), *set);
+    }
+
+    // Reset pools are reused instead of creating new ones
+    allocator.reset().expect("Failed to reset");
+    for _ in 0..3 {
+        allocator.allocate(layout).expect("Failed to allocate after reset");
+    }
+    assert_eq!(allocator.pool_count(), 2);
+
+    let mut frames = FrameDescriptorAllocators::new(&context, 2, PoolSizes::default());
+    for frame in [0, 1, 0] {
+        let set = frames
+            .begin_frame(frame)
+            .expect("Failed to begin frame")
+            .allocate(layout)
+            .expect("Failed to allocate");
+        writer.update(context.device(), set);
+        assert_eq!(frames.current().pool_count(), 1);
+    }
+}
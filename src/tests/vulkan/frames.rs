@@ -0,0 +1,79 @@
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::frames::{FramesInFlight, MAX_FRAMES_IN_FLIGHT};
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use ash::vk;
+
+#[test]
+fn frames_in_flight_count_test() {
+    let context = create_headless_test_context();
+    for frame_count in [0, MAX_FRAMES_IN_FLIGHT + 1] {
+        match FramesInFlight::new(&context, frame_count) {
+            Err(Error::InvalidFramesInFlight(count)) => assert_eq!(count, frame_count),
+            Err(error) => panic!("Unexpected error {}", error),
+            Ok(_) => panic!("{} frames in flight were accepted", frame_count),
+        }
+    }
+}
+
+#[test]
+fn frames_in_flight_cycle_test() {
+    let context = create_headless_test_context();
+    let device = context.device();
+    let queue = context.graphics_queue().expect("Missing graphics queue");
+    let mut frames = FramesInFlight::new(&context, 3).expect("Failed to create frames in flight");
+    assert_eq!(frames.frame_count(), 3);
+
+    // Images are acquired out of order like a real swapchain may do
+    let image_count = 2;
+    let image_indices = [0, 1, 1, 0, 0, 1, 0];
+    let mut render_finished_semaphores = Vec::new();
+    for (frame_number, image_index) in image_indices.into_iter().enumerate() {
+        assert_eq!(frames.current_index(), frame_number % 3);
+        let image_available = frames.begin_frame().expect("Failed to begin frame").image_available();
+
+        // Stands in for the acquire, signals the semaphore the submit waits on
+        let acquire_submit = vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            signal_semaphore_count: 1,
+            p_signal_semaphores: &image_available,
+            ..Default::default()
+        };
+        unsafe { device.queue_submit(queue, &[acquire_submit], vk::Fence::null()) }.expect("Failed to signal semaphore");
+
+        let render_finished = frames.image_acquired(image_index, image_count).expect("Failed to track image");
+        render_finished_semaphores.push((image_index, render_finished));
+
+        let command_buffer = frames.current().command_buffer();
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            ..Default::default()
+        };
+        unsafe {
+            device.begin_command_buffer(command_buffer, &begin_info).expect("Failed to begin command buffer");
+            device.end_command_buffer(command_buffer).expect("Failed to end command buffer");
+        }
+        frames
+            .submit(queue, image_index, vk::PipelineStageFlags::ALL_COMMANDS)
+            .expect("Failed to submit frame");
+
+        // Nobody presents, consume the render finished semaphore so it can be signaled again
+        let wait_stage = vk::PipelineStageFlags::ALL_COMMANDS;
+        let present_submit = vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            wait_semaphore_count: 1,
+            p_wait_semaphores: &render_finished,
+            p_wait_dst_stage_mask: &wait_stage,
+            ..Default::default()
+        };
+        unsafe { device.queue_submit(queue, &[present_submit], vk::Fence::null()) }.expect("Failed to wait for semaphore");
+    }
+
+    // One semaphore per image, independent of the frame
+    for (image_index, semaphore) in render_finished_semaphores.iter() {
+        for (other_index, other) in render_finished_semaphores.iter() {
+            assert_eq!(image_index == other_index, semaphore == other);
+        }
+    }
+    context.wait_idle().expect("Failed to wait for device");
+}
@@ -0,0 +1,76 @@
+use crate::backend::vulkan::glslc::{
+    glslc_command, is_shader_source, output_path, parse_depfile, permutation_name, stage_flag_name, CompileOptions, Optimization,
+};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn compile_options_test() {
+    let source = "#version 450\n// @target-env vulkan1.3\n//@optimize size\n// @define MAX_LIGHTS=16\n\
+                  // @permutation SKINNED\n// @permutation SHADOWS=2 SKINNED\n// plain comment\nvoid main() {}\n";
+    let options = CompileOptions::parse(source).expect("Failed to parse directives");
+    assert_eq!(options.target_env.as_deref(), Some("vulkan1.3"));
+    assert_eq!(options.optimization, Optimization::Size);
+    assert_eq!(options.defines, vec!["MAX_LIGHTS=16"]);
+    assert_eq!(options.variants().len(), 3);
+    assert!(options.variants()[0].is_empty());
+    assert!(CompileOptions::parse("// @optimize fast").is_err());
+    assert!(CompileOptions::parse("// @permutation").is_err());
+    assert!(CompileOptions::parse("// @unknown").is_err());
+
+    let permutation = &options.permutations[1];
+    assert_eq!(permutation_name("post/blur", permutation), "post/blur[SHADOWS=2,SKINNED]");
+    assert_eq!(permutation_name("post/blur", &[]), "post/blur");
+    assert_eq!(
+        output_path(Path::new("out"), Path::new("blur.frag"), 0),
+        Path::new("out").join("blur.frag.spv")
+    );
+    assert_eq!(
+        output_path(Path::new("out"), Path::new("blur.frag"), 2),
+        Path::new("out").join("blur.frag.2.spv")
+    );
+
+    let command = glslc_command(
+        Path::new("shaders/blur.frag"),
+        Path::new("out/blur.frag.2.spv"),
+        &options,
+        permutation,
+    );
+    let arguments: Vec<_> = command.get_args().map(|argument| argument.to_string_lossy().into_owned()).collect();
+    assert_eq!(
+        arguments,
+        vec![
+            "-I",
+            "shaders",
+            "--target-env=vulkan1.3",
+            "-Os",
+            "-DMAX_LIGHTS=16",
+            "-DSHADOWS=2",
+            "-DSKINNED",
+            "shaders/blur.frag",
+            "-o",
+            "out/blur.frag.2.spv"
+        ]
+    );
+    let command = glslc_command(Path::new("rt.rgen"), Path::new("rt.rgen.spv"), &CompileOptions::default(), &[]);
+    assert!(command.get_args().any(|argument| argument == "--target-env=vulkan1.2"));
+}
+
+#[test]
+fn shader_sources_test() {
+    assert!(is_shader_source(Path::new("a.comp")));
+    assert!(is_shader_source(Path::new("a.glsl")));
+    assert!(!is_shader_source(Path::new("common.glsli")));
+    assert_eq!(stage_flag_name(Path::new("a.mesh")), Some("MESH_EXT"));
+    assert_eq!(stage_flag_name(Path::new("a.rchit")), Some("CLOSEST_HIT_KHR"));
+    assert_eq!(stage_flag_name(Path::new("a.glsl")), None);
+
+    let depfile = "C:\\out\\blur.frag.spv: C:\\shaders\\blur.frag \\\n  shaders/common\\ lighting.glsli\n";
+    assert_eq!(
+        parse_depfile(depfile),
+        vec![
+            PathBuf::from("C:\\shaders\\blur.frag"),
+            PathBuf::from("shaders/common lighting.glsli")
+        ]
+    );
+    assert!(parse_depfile("").is_empty());
+}
@@ -0,0 +1,18 @@
+use crate::backend::vulkan::offscreen::OffscreenRenderer;
+use crate::tests::golden::{assert_golden, GoldenConfig};
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use ash::vk;
+
+#[test]
+fn golden_triangle_test() {
+    let context = create_headless_test_context();
+    let mut renderer = OffscreenRenderer::new(&context, vk::Extent2D { width: 64, height: 64 }).expect("Failed to create renderer");
+    let image = renderer.render([0.0, 0.0, 0.0, 1.0]).expect("Failed to render");
+
+    // Implementations may disagree on edge coverage and interpolation rounding
+    let config = GoldenConfig {
+        allowed_failing_pixels: 64,
+        ..GoldenConfig::default()
+    };
+    assert_golden("triangle", &image, &config);
+}
@@ -0,0 +1,75 @@
+use crate::backend::vulkan::hot_reload::{HotReloadConfig, ShaderHotReload, ShaderWatcher};
+use crate::backend::vulkan::pipeline::GraphicsPipelineBuilder;
+use crate::backend::vulkan::shaders::ShaderLibrary;
+use crate::tests::vulkan::reflection::vertex_module;
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use crate::utils::create_color_render_pass;
+use ash::vk;
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn shader_watcher_test() {
+    let dir = std::env::temp_dir().join("eikon_shader_watcher");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("post")).expect("Failed to create shader directory");
+    fs::write(dir.join("mesh.vert"), "#version 450").expect("Failed to write shader");
+    fs::write(dir.join("notes.txt"), "not a shader").expect("Failed to write file");
+
+    let mut watcher = ShaderWatcher::new(&dir);
+    assert!(watcher.changed().is_empty());
+
+    fs::write(dir.join("post").join("blur.frag"), "#version 450").expect("Failed to write shader");
+    let later = SystemTime::now() + Duration::from_secs(10);
+    fs::File::options()
+        .write(true)
+        .open(dir.join("mesh.vert"))
+        .and_then(|file| file.set_modified(later))
+        .expect("Failed to touch shader");
+    assert_eq!(watcher.changed(), vec![dir.join("mesh.vert"), dir.join("post").join("blur.frag")]);
+    assert!(watcher.changed().is_empty());
+
+    fs::remove_file(dir.join("mesh.vert")).expect("Failed to remove shader");
+    assert!(watcher.changed().is_empty());
+    fs::remove_dir_all(&dir).expect("Failed to remove shader directory");
+}
+
+#[test]
+fn pipeline_rebuild_test() {
+    let context = create_headless_test_context();
+    let device = context.device();
+    let render_pass = create_color_render_pass(device, vk::Format::R8G8B8A8_UNORM, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+    let code: Vec<u8> = vertex_module().iter().flat_map(|word| word.to_le_bytes()).collect();
+    let mut library = ShaderLibrary::new(&context);
+    library
+        .add("mesh", vk::ShaderStageFlags::VERTEX, &code)
+        .expect("Failed to add shader");
+
+    let config = HotReloadConfig::new(std::env::temp_dir().join("eikon_missing_shaders"), std::env::temp_dir()).retire_frames(2);
+    let mut hot_reload = ShaderHotReload::new(&context, config);
+    let id = hot_reload
+        .add_pipeline(
+            &library,
+            GraphicsPipelineBuilder::new(render_pass),
+            &[("mesh", vk::ShaderStageFlags::VERTEX)],
+        )
+        .expect("Failed to build pipeline");
+    let first = hot_reload.pipeline(id).handle();
+    assert_eq!(hot_reload.update(&mut library), 0);
+
+    library
+        .add("mesh", vk::ShaderStageFlags::VERTEX, &code)
+        .expect("Failed to replace shader");
+    assert_eq!(hot_reload.invalidate("mesh"), 1);
+    assert_eq!(hot_reload.invalidate("other"), 0);
+    assert_eq!(hot_reload.update(&mut library), 1);
+    assert_ne!(hot_reload.pipeline(id).handle(), first);
+    assert_eq!(hot_reload.retired_count(), 1);
+    hot_reload.update(&mut library);
+    assert_eq!(hot_reload.retired_count(), 1);
+    hot_reload.update(&mut library);
+    assert_eq!(hot_reload.retired_count(), 0);
+
+    drop(hot_reload);
+    unsafe { device.destroy_render_pass(render_pass, None) };
+}
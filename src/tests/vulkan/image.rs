@@ -0,0 +1,124 @@
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::{full_mip_level_count, mip_extent, Image, ImageConfig, Sampler, SamplerConfig};
+use crate::backend::vulkan::queue::op_indices::GRAPHICS;
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use ash::vk;
+
+#[test]
+fn image_mip_chain_test() {
+    let extent = vk::Extent3D {
+        width: 256,
+        height: 64,
+        depth: 1,
+    };
+    assert_eq!(full_mip_level_count(extent), 9);
+    assert_eq!(full_mip_level_count(vk::Extent3D { width: 1, height: 1, depth: 1 }), 1);
+    assert_eq!(full_mip_level_count(vk::Extent3D { width: 5, height: 3, depth: 17 }), 5);
+    let last = mip_extent(extent, 8);
+    assert_eq!((last.width, last.height, last.depth), (1, 1, 1));
+    let second = mip_extent(extent, 1);
+    assert_eq!((second.width, second.height, second.depth), (128, 32, 1));
+}
+
+#[test]
+fn image_config_validation_test() {
+    let format = vk::Format::R8G8B8A8_UNORM;
+    let valid = [
+        ImageConfig::new_2d(format, 64, 32).full_mip_chain(),
+        ImageConfig::new_2d_array(format, 64, 64, 4),
+        ImageConfig::new_cube(format, 32).array_layers(12),
+        ImageConfig::new_3d(format, 16, 16, 16).mip_levels(5),
+        ImageConfig::new_2d(format, 64, 64).samples(vk::SampleCountFlags::TYPE_4),
+    ];
+    for config in valid {
+        assert!(config.validate().is_ok(), "{:?} was rejected", config);
+    }
+    let views: Vec<_> = valid.iter().map(|config| config.view_type()).collect();
+    assert_eq!(
+        views,
+        vec![
+            vk::ImageViewType::TYPE_2D,
+            vk::ImageViewType::TYPE_2D_ARRAY,
+            vk::ImageViewType::CUBE_ARRAY,
+            vk::ImageViewType::TYPE_3D,
+            vk::ImageViewType::TYPE_2D,
+        ]
+    );
+
+    let invalid = [
+        ImageConfig::new_2d(format, 0, 32),
+        ImageConfig::new_2d(format, 64, 32).mip_levels(8),
+        ImageConfig::new_cube(format, 32).array_layers(7),
+        ImageConfig::new_3d(format, 16, 16, 16).array_layers(2),
+        ImageConfig::new_2d(format, 64, 64).full_mip_chain().samples(vk::SampleCountFlags::TYPE_4),
+    ];
+    for config in invalid {
+        assert!(matches!(config.validate(), Err(Error::InvalidImageConfig(_))), "{:?} was accepted", config);
+    }
+}
+
+#[test]
+fn image_layout_tracking_test() {
+    let context = create_headless_test_context();
+    let device = context.device();
+
+    let mut cube = Image::new(&context, ImageConfig::new_cube(vk::Format::R8G8B8A8_UNORM, 64).full_mip_chain()).expect("Failed to create image");
+    let view = cube.create_view().expect("Failed to create view");
+    assert_eq!(view.view_type(), vk::ImageViewType::CUBE);
+    let volume = Image::new(&context, ImageConfig::new_3d(vk::Format::R8_UNORM, 8, 8, 8)).expect("Failed to create image");
+    assert_eq!(volume.create_view().expect("Failed to create view").view_type(), vk::ImageViewType::TYPE_3D);
+    let depth = Image::new(
+        &context,
+        ImageConfig::new_2d(vk::Format::D32_SFLOAT, 64, 64).usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT),
+    )
+    .expect("Failed to create image");
+    assert_eq!(depth.aspect_mask(), vk::ImageAspectFlags::DEPTH);
+    let _sampler = Sampler::new(&context, &SamplerConfig::new().anisotropy(16.0)).expect("Failed to create sampler");
+
+    let command_pool_create_info = vk::CommandPoolCreateInfo {
+        s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+        queue_family_index: context.queue_family_index(GRAPHICS).unwrap(),
+        ..Default::default()
+    };
+    unsafe {
+        let command_pool = device.create_command_pool(&command_pool_create_info, None).expect("Failed to create pool");
+        let allocate_info = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+            command_buffer_count: 1,
+            ..Default::default()
+        };
+        let command_buffer = device.allocate_command_buffers(&allocate_info).expect("Failed to allocate")[0];
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            ..Default::default()
+        };
+        device.begin_command_buffer(command_buffer, &begin_info).expect("Failed to begin");
+
+        cube.transition(command_buffer, cube.full_range(), vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+        // Only the first mip of face 2 moves on
+        let face = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 2,
+            layer_count: 1,
+        };
+        cube.transition(command_buffer, face, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+        assert_eq!(cube.layout(0, 2), vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+        assert_eq!(cube.layout(0, 1), vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+        assert_eq!(cube.layout(1, 2), vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+
+        // Mixed old layouts in one call
+        cube.transition(command_buffer, cube.full_range(), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        for level in 0..cube.mip_levels() {
+            for layer in 0..6 {
+                assert_eq!(cube.layout(level, layer), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+            }
+        }
+
+        device.end_command_buffer(command_buffer).expect("Failed to end");
+        device.destroy_command_pool(command_pool, None);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::backend::vulkan::memory::{memory_types_for_usage, AllocationRequest, Allocator, FreeList, MemoryUsage};
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use ash::vk;
+
+#[test]
+fn free_list_alignment_test() {
+    let mut free_list = FreeList::new(1024);
+    assert_eq!(free_list.allocate(10, 1), Some(0));
+    // The padding in front of an aligned allocation stays free
+    assert_eq!(free_list.allocate(100, 256), Some(256));
+    assert_eq!(free_list.allocate(6, 1), Some(10));
+    assert_eq!(free_list.used_size(), 116);
+    assert_eq!(free_list.allocate(1024, 1), None);
+}
+
+#[test]
+fn free_list_merge_test() {
+    let mut free_list = FreeList::new(300);
+    let offsets: Vec<_> = (0..3).map(|_| free_list.allocate(100, 1).expect("Failed to allocate")).collect();
+    assert_eq!(offsets, vec![0, 100, 200]);
+    assert_eq!(free_list.largest_free_range(), 0);
+
+    free_list.free(0, 100);
+    free_list.free(200, 100);
+    assert_eq!(free_list.largest_free_range(), 100);
+    assert_eq!(free_list.allocate(200, 1), None);
+
+    // Freeing the middle merges all three ranges
+    free_list.free(100, 100);
+    assert!(free_list.is_empty());
+    assert_eq!(free_list.allocate(300, 1), Some(0));
+}
+
+#[test]
+fn memory_types_for_usage_test() {
+    let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+        memory_type_count: 4,
+        ..Default::default()
+    };
+    memory_properties.memory_types[0].property_flags = vk::MemoryPropertyFlags::DEVICE_LOCAL;
+    memory_properties.memory_types[1].property_flags = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+    memory_properties.memory_types[2].property_flags =
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_CACHED;
+    // Non-coherent memory is accepted, but only after every coherent type
+    memory_properties.memory_types[3].property_flags = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_CACHED;
+
+    assert_eq!(memory_types_for_usage(&memory_properties, 0b111, MemoryUsage::GpuOnly), vec![0, 1, 2]);
+    assert_eq!(memory_types_for_usage(&memory_properties, 0b111, MemoryUsage::CpuToGpu), vec![1, 2]);
+    assert_eq!(memory_types_for_usage(&memory_properties, 0b111, MemoryUsage::GpuToCpu), vec![2, 1]);
+    assert_eq!(memory_types_for_usage(&memory_properties, 0b1111, MemoryUsage::GpuToCpu), vec![2, 1, 3]);
+    assert_eq!(memory_types_for_usage(&memory_properties, 0b1000, MemoryUsage::CpuToGpu), vec![3]);
+    assert_eq!(memory_types_for_usage(&memory_properties, 0b001, MemoryUsage::GpuToCpu), Vec::<u32>::new());
+}
+
+#[test]
+fn allocator_test() {
+    let context = create_headless_test_context();
+    let allocator = Allocator::with_block_size(&context, 1024 * 1024);
+    let request = |size, usage, dedicated| AllocationRequest {
+        requirements: vk::MemoryRequirements {
+            size,
+            alignment: 256,
+            memory_type_bits: u32::MAX,
+        },
+        usage,
+        linear: true,
+        dedicated,
+    };
+
+    let first = allocator.allocate(&request(1000, MemoryUsage::CpuToGpu, false)).expect("Failed to allocate");
+    let second = allocator.allocate(&request(1000, MemoryUsage::CpuToGpu, false)).expect("Failed to allocate");
+    assert_eq!(first.memory(), second.memory(), "Small allocations should share a block");
+    assert_eq!(second.offset() % 256, 0);
+    assert!(first.offset() + first.size() <= second.offset() || second.offset() + second.size() <= first.offset());
+    let mapped = first.mapped_ptr().expect("Host visible allocation is not mapped");
+    unsafe { mapped.write_bytes(0xff, first.size() as usize) };
+
+    let large = allocator.allocate(&request(768 * 1024, MemoryUsage::GpuOnly, false)).expect("Failed to allocate");
+    assert!(large.is_dedicated());
+    let dedicated = allocator.allocate(&request(1000, MemoryUsage::GpuToCpu, true)).expect("Failed to allocate");
+    assert!(dedicated.is_dedicated());
+
+    let stats = allocator.stats();
+    assert_eq!(stats.allocation_count, 4);
+    assert_eq!(stats.dedicated_allocation_count, 2);
+    assert_eq!(stats.used_bytes, 2000 + 768 * 1024 + 1000);
+    assert!(stats.block_count >= 1);
+    assert!(stats.reserved_bytes >= stats.used_bytes);
+
+    for allocation in [first, second, large, dedicated] {
+        allocator.free(allocation);
+    }
+    let stats = allocator.stats();
+    assert_eq!(stats.allocation_count, 0);
+    assert_eq!(stats.dedicated_allocation_count, 0);
+    assert_eq!(stats.used_bytes, 0);
+}
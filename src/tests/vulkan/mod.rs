@@ -1,8 +1,50 @@
 #[cfg(test)]
 mod base;
 #[cfg(test)]
+mod bindless;
+#[cfg(test)]
+mod buffer;
+#[cfg(test)]
 mod context;
+#[cfg(test)]
+mod depth;
+#[cfg(test)]
+mod descriptors;
+#[cfg(test)]
+mod frames;
+#[cfg(test)]
+mod glslc;
+#[cfg(test)]
+mod golden;
+#[cfg(test)]
+mod hot_reload;
+#[cfg(test)]
+mod image;
 pub mod log;
+#[cfg(test)]
+mod memory;
+#[cfg(test)]
+mod offscreen;
+#[cfg(test)]
+mod permutations;
+#[cfg(test)]
+mod pipeline;
+#[cfg(test)]
+mod queue;
+#[cfg(test)]
+mod reflection;
+#[cfg(test)]
+mod renderer;
+#[cfg(test)]
+mod rendering;
+#[cfg(test)]
+mod shaders;
+#[cfg(test)]
+mod swapchain;
 pub mod test_utils;
 #[cfg(test)]
+mod texture;
+#[cfg(test)]
+mod upload;
+#[cfg(test)]
 pub mod utils;
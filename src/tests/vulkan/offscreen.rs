@@ -0,0 +1,21 @@
+use crate::backend::vulkan::offscreen::OffscreenRenderer;
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use ash::vk;
+
+#[test]
+fn offscreen_triangle_test() {
+    let context = create_headless_test_context();
+    let mut renderer = OffscreenRenderer::new(&context, vk::Extent2D { width: 64, height: 64 }).expect("Failed to create renderer");
+    let image = renderer.render([0.0, 0.0, 0.0, 1.0]).expect("Failed to render");
+
+    assert_eq!(image.width, 64);
+    assert_eq!(image.height, 64);
+    assert_eq!(image.pixel(0, 0), [0, 0, 0, 255]);
+    let center = image.pixel(32, 40);
+    assert!(center[0] > 0 || center[1] > 0 || center[2] > 0, "Triangle was not rendered");
+
+    image
+        .write_png(std::env::temp_dir().join("eikon_offscreen_triangle.png"))
+        .expect("Failed to write PNG");
+    drop(renderer);
+}
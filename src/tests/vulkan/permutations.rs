@@ -0,0 +1,115 @@
+use crate::backend::vulkan::descriptors::DescriptorLayoutCache;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::permutations::{PermutationKey, PipelinePermutations};
+use crate::backend::vulkan::pipeline::{GraphicsPipelineBuilder, SpecializationConstants};
+use crate::backend::vulkan::reflection::{ReflectedLayout, ShaderReflection};
+use crate::backend::vulkan::shaders::{split_permutation_name, ShaderLibrary};
+use crate::tests::vulkan::reflection::{fragment_module, vertex_module};
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use crate::utils::create_color_render_pass;
+use ash::vk;
+
+fn bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+#[test]
+fn permutation_key_test() {
+    let constants = SpecializationConstants::new().f32(3, 2.0).bool(0, true).u32(3, 7);
+    assert_eq!(constants.len(), 2);
+    assert_eq!(constants.get(3), Some(&7u32.to_ne_bytes()[..]));
+    let (entries, data) = constants.map_entries();
+    assert_eq!(entries.len(), 2);
+    assert_eq!((entries[0].constant_id, entries[0].offset, entries[0].size), (0, 0, 4));
+    assert_eq!((entries[1].constant_id, entries[1].offset, entries[1].size), (3, 4, 4));
+    assert_eq!(data.len(), 8);
+
+    let key = PermutationKey::new()
+        .define("SKINNED")
+        .define("ALPHA_TEST")
+        .define("SKINNED")
+        .u32(1, 4);
+    let same = PermutationKey::new()
+        .u32(1, 4)
+        .define("ALPHA_TEST")
+        .define_if("SKINNED", true)
+        .define_if("NORMAL_MAP", false);
+    assert_eq!(key, same);
+    assert_eq!(key.defines(), ["ALPHA_TEST", "SKINNED"]);
+    assert_ne!(key, same.u32(1, 5));
+
+    assert_eq!(
+        split_permutation_name("mesh[ALPHA_TEST,TAPS=9]"),
+        ("mesh", vec!["ALPHA_TEST", "TAPS=9"])
+    );
+    assert_eq!(split_permutation_name("post/blur"), ("post/blur", vec![]));
+}
+
+#[test]
+fn pipeline_permutations_test() {
+    let context = create_headless_test_context();
+    let device = context.device();
+    let render_pass = create_color_render_pass(device, vk::Format::R8G8B8A8_UNORM, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+    let mut library = ShaderLibrary::new(&context);
+    for name in ["mesh", "mesh[SKINNED]"] {
+        library
+            .add(name, vk::ShaderStageFlags::VERTEX, &bytes(&vertex_module()))
+            .expect("Failed to add vertex shader");
+    }
+    library
+        .add("surface", vk::ShaderStageFlags::FRAGMENT, &bytes(&fragment_module(false)))
+        .expect("Failed to add fragment shader");
+    let skinned = library
+        .variant(
+            "mesh",
+            vk::ShaderStageFlags::VERTEX,
+            &["ALPHA_TEST".to_string(), "SKINNED".to_string()],
+        )
+        .expect("Missing skinned variant");
+    assert_eq!(
+        skinned.module(),
+        library.module("mesh[SKINNED]", vk::ShaderStageFlags::VERTEX).unwrap()
+    );
+
+    let mut cache = DescriptorLayoutCache::new(&context);
+    let vertex = ShaderReflection::parse(&vertex_module()).expect("Failed to reflect vertex module");
+    let fragment = ShaderReflection::parse(&fragment_module(false)).expect("Failed to reflect fragment module");
+    let layout = ReflectedLayout::merge(&[&vertex, &fragment]).expect("Failed to merge");
+    let builder = GraphicsPipelineBuilder::new(render_pass)
+        .reflected_layout(&layout, &mut cache, &[])
+        .expect("Failed to create layouts");
+    let mut permutations = PipelinePermutations::new(
+        &context,
+        builder,
+        &[("mesh", vk::ShaderStageFlags::VERTEX), ("surface", vk::ShaderStageFlags::FRAGMENT)],
+    );
+
+    let key = PermutationKey::new()
+        .define("SKINNED")
+        .define("ALPHA_TEST")
+        .bool(0, false)
+        .f32(3, 2.0);
+    let pipeline = permutations.get(&library, &key).expect("Failed to build variant").handle();
+    assert_ne!(pipeline, vk::Pipeline::null());
+    assert_eq!(permutations.get(&library, &key).expect("Missing cached variant").handle(), pipeline);
+    permutations
+        .get(&library, &PermutationKey::new())
+        .expect("Failed to build base variant");
+    assert_eq!(permutations.len(), 2);
+
+    assert!(matches!(
+        permutations.get(&library, &PermutationKey::new().u32(7, 1)),
+        Err(Error::InvalidPipelineConfig(_))
+    ));
+    let wide = SpecializationConstants::new().raw(3, &2.0f64.to_ne_bytes());
+    assert!(matches!(
+        permutations.get(&library, &PermutationKey::new().specialization(wide)),
+        Err(Error::InvalidPipelineConfig(_))
+    ));
+    assert_eq!(permutations.len(), 2);
+
+    permutations.clear();
+    assert!(!permutations.contains(&key));
+    drop(permutations);
+    unsafe { device.destroy_render_pass(render_pass, None) };
+}
@@ -0,0 +1,91 @@
+use crate::backend::renderer::ColorVertex;
+use crate::backend::vulkan::buffer::Pod;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::pipeline::{BlendMode, DepthStencilConfig, GraphicsPipelineBuilder, Vertex, VertexAttribute, VertexInputLayout};
+use crate::backend::vulkan::shaders::ShaderLibrary;
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use crate::utils::create_color_render_pass;
+use ash::vk;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InstanceData {
+    transform: [[f32; 4]; 4],
+    tint: [u8; 4],
+    uv: [u16; 2],
+}
+
+unsafe impl Pod for InstanceData {}
+
+crate::impl_vertex!(InstanceData { transform, tint, uv: R16G16_SFLOAT });
+
+#[test]
+fn vertex_attributes_test() {
+    assert_eq!(
+        ColorVertex::attributes(),
+        vec![
+            VertexAttribute {
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: 0
+            },
+            VertexAttribute {
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: 16
+            },
+        ]
+    );
+
+    let layout = VertexInputLayout::new().vertex::<ColorVertex>().instance::<InstanceData>();
+    assert_eq!(layout.bindings.len(), 2);
+    assert_eq!(layout.bindings[0].stride, 32);
+    assert_eq!(layout.bindings[1].stride, 72);
+    assert_eq!(layout.bindings[1].input_rate, vk::VertexInputRate::INSTANCE);
+
+    // The matrix takes one location per column
+    let instance: Vec<_> = layout.attributes.iter().filter(|attribute| attribute.binding == 1).collect();
+    assert_eq!(instance.len(), 6);
+    assert_eq!(instance.iter().map(|attribute| attribute.location).collect::<Vec<_>>(), [2, 3, 4, 5, 6, 7]);
+    assert_eq!(instance.iter().map(|attribute| attribute.offset).collect::<Vec<_>>(), [0, 16, 32, 48, 64, 68]);
+    assert_eq!(instance[4].format, vk::Format::R8G8B8A8_UNORM);
+    assert_eq!(instance[5].format, vk::Format::R16G16_SFLOAT);
+    assert_eq!(layout.next_location(), 8);
+}
+
+#[test]
+fn pipeline_builder_test() {
+    let context = create_headless_test_context();
+    let device = context.device();
+    let render_pass = create_color_render_pass(device, vk::Format::R8G8B8A8_UNORM, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+    let shaders = ShaderLibrary::with_embedded(&context).expect("Failed to load shaders");
+
+    let builder = GraphicsPipelineBuilder::new(render_pass)
+        .vertex_shader(shaders.module("color_vshader", vk::ShaderStageFlags::VERTEX).expect("Missing vertex shader"))
+        .fragment_shader(shaders.module("color_fshader", vk::ShaderStageFlags::FRAGMENT).expect("Missing fragment shader"))
+        .vertex_input(VertexInputLayout::new().vertex::<ColorVertex>());
+    let pipeline = builder.build(device).expect("Failed to build default pipeline");
+    assert_ne!(pipeline.handle(), vk::Pipeline::null());
+
+    let pipeline = builder
+        .clone()
+        .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+        .primitive_restart(true)
+        .cull_mode(vk::CullModeFlags::BACK, vk::FrontFace::CLOCKWISE)
+        .depth_bias(1.0, 0.0, 1.5)
+        .depth_stencil(DepthStencilConfig::depth(vk::CompareOp::LESS_OR_EQUAL, false))
+        .color_attachments(&[BlendMode::PremultipliedAlpha])
+        .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR, vk::DynamicState::BLEND_CONSTANTS])
+        .push_constant_range(vk::ShaderStageFlags::VERTEX, 0, 64)
+        .build(device)
+        .expect("Failed to build configured pipeline");
+    assert_ne!(pipeline.layout(), vk::PipelineLayout::null());
+    drop(pipeline);
+
+    assert!(matches!(builder.clone().dynamic_states(&[]).build(device), Err(Error::InvalidPipelineConfig(_))));
+    let mut fragment_only = builder.clone();
+    fragment_only.stages.remove(0);
+    assert!(matches!(fragment_only.build(device), Err(Error::InvalidPipelineConfig(_))));
+    let missing_attachment = builder.clone().color_attachment_state(1, BlendMode::Alpha.attachment_state());
+    assert!(matches!(missing_attachment.build(device), Err(Error::InvalidPipelineConfig(_))));
+
+    unsafe { device.destroy_render_pass(render_pass, None) };
+}
@@ -0,0 +1,41 @@
+use crate::backend::vulkan::errors::Error::QueueOperationAlreadyMapped;
+use crate::backend::vulkan::queue::op_indices::{COMPUTE, GRAPHICS, PRESENT, TRANSFER};
+use crate::backend::vulkan::queue::QueueSelections;
+
+#[test]
+fn queue_selections_insert_test() {
+    let mut queue_selections = QueueSelections::new(&[2, 1]);
+    queue_selections.insert_operation(GRAPHICS as u8, 0).unwrap();
+    queue_selections.insert_operation(PRESENT as u8, 0).unwrap();
+    queue_selections.insert_operation(TRANSFER as u8, 1).unwrap();
+
+    assert_eq!(queue_selections.families[0].as_ref().unwrap().count, 2);
+    assert_eq!(queue_selections.families[1].as_ref().unwrap().count, 1);
+    assert_eq!(queue_selections.operations[PRESENT].as_ref().unwrap().offset, 1);
+    assert_eq!(queue_selections.family_index(TRANSFER), Some(1));
+    assert_eq!(queue_selections.family_index(COMPUTE), None);
+}
+
+#[test]
+fn queue_selections_share_exhausted_family_test() {
+    let mut queue_selections = QueueSelections::new(&[1]);
+    queue_selections.insert_operation(GRAPHICS as u8, 0).unwrap();
+    queue_selections.insert_operation(COMPUTE as u8, 0).unwrap();
+    queue_selections.insert_operation(TRANSFER as u8, 0).unwrap();
+
+    let family = queue_selections.families[0].as_ref().unwrap();
+    assert_eq!(family.count, 1);
+    assert_eq!(family.priorities.len(), 1);
+    assert_eq!(queue_selections.operations[TRANSFER].as_ref().unwrap().offset, 0);
+    assert_eq!(queue_selections.to_vk_creation_info()[0].queue_count, 1);
+}
+
+#[test]
+fn queue_selections_duplicate_operation_test() {
+    let mut queue_selections = QueueSelections::new(&[4]);
+    queue_selections.insert_operation(GRAPHICS as u8, 0).unwrap();
+    match queue_selections.insert_operation(GRAPHICS as u8, 0) {
+        Err(QueueOperationAlreadyMapped(operation)) => assert_eq!(operation, GRAPHICS),
+        _ => panic!("Duplicate operation was accepted!"),
+    }
+}
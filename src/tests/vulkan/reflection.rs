@@ -0,0 +1,301 @@
+use crate::backend::vulkan::descriptors::DescriptorLayoutCache;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::reflection::{ReflectedLayout, ScalarKind, ShaderReflection};
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use ash::vk;
+
+const CAPABILITY: u32 = 17;
+const MEMORY_MODEL: u32 = 14;
+const ENTRY_POINT: u32 = 15;
+const NAME: u32 = 5;
+const DECORATE: u32 = 71;
+const MEMBER_DECORATE: u32 = 72;
+const TYPE_VOID: u32 = 19;
+const TYPE_BOOL: u32 = 20;
+const TYPE_INT: u32 = 21;
+const TYPE_FLOAT: u32 = 22;
+const TYPE_VECTOR: u32 = 23;
+const TYPE_MATRIX: u32 = 24;
+const TYPE_IMAGE: u32 = 25;
+const TYPE_SAMPLED_IMAGE: u32 = 27;
+const TYPE_ARRAY: u32 = 28;
+const TYPE_RUNTIME_ARRAY: u32 = 29;
+const TYPE_STRUCT: u32 = 30;
+const TYPE_POINTER: u32 = 32;
+const TYPE_FUNCTION: u32 = 33;
+const CONSTANT: u32 = 43;
+const SPEC_CONSTANT_TRUE: u32 = 48;
+const SPEC_CONSTANT: u32 = 50;
+const FUNCTION: u32 = 54;
+const FUNCTION_END: u32 = 56;
+const VARIABLE: u32 = 59;
+const LABEL: u32 = 248;
+const RETURN: u32 = 253;
+
+const INPUT: u32 = 1;
+const UNIFORM: u32 = 2;
+const UNIFORM_CONSTANT: u32 = 0;
+const PUSH_CONSTANT: u32 = 9;
+const STORAGE_BUFFER: u32 = 12;
+
+/// Just enough of an assembler to write modules by hand, there is no shader compiler in the test environment
+struct Assembler {
+    words: Vec<u32>,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        let mut assembler = Self {
+            words: vec![0x0723_0203, 0x0001_0000, 0, 64, 0],
+        };
+        assembler.op(CAPABILITY, &[1]).op(MEMORY_MODEL, &[0, 1]);
+        assembler
+    }
+
+    fn op(&mut self, opcode: u32, operands: &[u32]) -> &mut Self {
+        self.words.push((operands.len() as u32 + 1) << 16 | opcode);
+        self.words.extend_from_slice(operands);
+        self
+    }
+
+    fn op_string(&mut self, opcode: u32, before: &[u32], string: &str, after: &[u32]) -> &mut Self {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.resize(bytes.len() / 4 * 4 + 4, 0);
+        let mut operands = before.to_vec();
+        operands.extend(bytes.chunks(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])));
+        operands.extend_from_slice(after);
+        self.op(opcode, &operands)
+    }
+
+    /// `void main() {}` with id 3, the types up to id 2 are declared
+    fn main(&mut self) -> &mut Self {
+        self.op(TYPE_VOID, &[1])
+            .op(TYPE_FUNCTION, &[2, 1])
+            .op(FUNCTION, &[1, 3, 0, 2])
+            .op(LABEL, &[63])
+            .op(RETURN, &[])
+            .op(FUNCTION_END, &[])
+    }
+}
+
+/// Camera uniform, texture array, matrix vertex input and push constants with specialization constants
+pub(super) fn vertex_module() -> Vec<u32> {
+    let mut assembler = Assembler::new();
+    assembler
+        .op_string(ENTRY_POINT, &[0, 3], "main", &[9, 10, 33])
+        .op_string(NAME, &[9], "in_position", &[])
+        .op_string(NAME, &[10], "in_transform", &[])
+        .op_string(NAME, &[13], "camera", &[])
+        .op_string(NAME, &[20], "textures", &[])
+        .op_string(NAME, &[24], "exposure", &[])
+        .op(DECORATE, &[9, 30, 0])
+        .op(DECORATE, &[10, 30, 1])
+        .op(DECORATE, &[33, 11, 42])
+        .op(DECORATE, &[11, 2])
+        .op(MEMBER_DECORATE, &[11, 0, 35, 0])
+        .op(MEMBER_DECORATE, &[11, 0, 7, 16])
+        .op(DECORATE, &[13, 34, 0])
+        .op(DECORATE, &[13, 33, 0])
+        .op(DECORATE, &[20, 34, 1])
+        .op(DECORATE, &[20, 33, 2])
+        .op(DECORATE, &[21, 2])
+        .op(MEMBER_DECORATE, &[21, 0, 35, 0])
+        .op(MEMBER_DECORATE, &[21, 0, 7, 16])
+        .op(MEMBER_DECORATE, &[21, 1, 35, 64])
+        .op(DECORATE, &[24, 1, 3])
+        .op(DECORATE, &[27, 1, 0])
+        .op(TYPE_FLOAT, &[4, 32])
+        .op(TYPE_VECTOR, &[5, 4, 4])
+        .op(TYPE_MATRIX, &[6, 5, 4])
+        .op(TYPE_POINTER, &[7, INPUT, 5])
+        .op(TYPE_POINTER, &[8, INPUT, 6])
+        .op(VARIABLE, &[7, 9, INPUT])
+        .op(VARIABLE, &[8, 10, INPUT])
+        .op(TYPE_STRUCT, &[11, 6])
+        .op(TYPE_POINTER, &[12, UNIFORM, 11])
+        .op(VARIABLE, &[12, 13, UNIFORM])
+        .op(TYPE_IMAGE, &[14, 4, 1, 0, 0, 0, 1, 0])
+        .op(TYPE_SAMPLED_IMAGE, &[15, 14])
+        .op(TYPE_INT, &[16, 32, 0])
+        .op(CONSTANT, &[16, 17, 4])
+        .op(TYPE_ARRAY, &[18, 15, 17])
+        .op(TYPE_POINTER, &[19, UNIFORM_CONSTANT, 18])
+        .op(VARIABLE, &[19, 20, UNIFORM_CONSTANT])
+        .op(TYPE_STRUCT, &[21, 6, 5])
+        .op(TYPE_POINTER, &[22, PUSH_CONSTANT, 21])
+        .op(VARIABLE, &[22, 23, PUSH_CONSTANT])
+        .op(SPEC_CONSTANT, &[4, 24, 1.5f32.to_bits()])
+        .op(TYPE_BOOL, &[26])
+        .op(SPEC_CONSTANT_TRUE, &[26, 27])
+        .op(TYPE_INT, &[31, 32, 1])
+        .op(TYPE_POINTER, &[32, INPUT, 31])
+        .op(VARIABLE, &[32, 33, INPUT])
+        .main();
+    assembler.words
+}
+
+/// Shares the camera binding, adds a storage buffer with a runtime sized array and push constants after the vertex
+/// ones. `conflicting` declares a combined image sampler at the camera binding instead.
+pub(super) fn fragment_module(conflicting: bool) -> Vec<u32> {
+    let mut assembler = Assembler::new();
+    assembler
+        .op_string(ENTRY_POINT, &[4, 3], "main", &[])
+        .op(DECORATE, &[11, 2])
+        .op(MEMBER_DECORATE, &[11, 0, 35, 0])
+        .op(DECORATE, &[13, 34, 0])
+        .op(DECORATE, &[13, 33, 0])
+        .op(DECORATE, &[18, 6, 16])
+        .op(DECORATE, &[19, 2])
+        .op(MEMBER_DECORATE, &[19, 0, 35, 0])
+        .op(DECORATE, &[21, 34, 2])
+        .op(DECORATE, &[21, 33, 0])
+        .op(DECORATE, &[22, 2])
+        .op(MEMBER_DECORATE, &[22, 0, 35, 80])
+        .op(TYPE_FLOAT, &[4, 32])
+        .op(TYPE_VECTOR, &[5, 4, 4])
+        .op(TYPE_IMAGE, &[14, 4, 1, 0, 0, 0, 1, 0]);
+    if conflicting {
+        assembler
+            .op(TYPE_SAMPLED_IMAGE, &[11, 14])
+            .op(TYPE_POINTER, &[12, UNIFORM_CONSTANT, 11])
+            .op(VARIABLE, &[12, 13, UNIFORM_CONSTANT]);
+    } else {
+        assembler
+            .op(TYPE_STRUCT, &[11, 5])
+            .op(TYPE_POINTER, &[12, UNIFORM, 11])
+            .op(VARIABLE, &[12, 13, UNIFORM]);
+    }
+    assembler
+        .op(TYPE_RUNTIME_ARRAY, &[18, 5])
+        .op(TYPE_STRUCT, &[19, 18])
+        .op(TYPE_POINTER, &[20, STORAGE_BUFFER, 19])
+        .op(VARIABLE, &[20, 21, STORAGE_BUFFER])
+        .op(TYPE_STRUCT, &[22, 5])
+        .op(TYPE_POINTER, &[23, PUSH_CONSTANT, 22])
+        .op(VARIABLE, &[23, 24, PUSH_CONSTANT])
+        .op_string(NAME, &[19], "Lights", &[])
+        .main();
+    assembler.words
+}
+
+#[test]
+fn reflection_test() {
+    let vertex = ShaderReflection::parse(&vertex_module()).expect("Failed to reflect vertex module");
+    assert_eq!(vertex.entry_points.len(), 1);
+    assert_eq!(vertex.entry_points[0].name, "main");
+    assert_eq!(vertex.stages, vk::ShaderStageFlags::VERTEX);
+
+    // The matrix takes 4 locations, gl_VertexIndex is skipped
+    let inputs: Vec<(u32, vk::Format)> = vertex.vertex_inputs.iter().map(|input| (input.location, input.format)).collect();
+    assert_eq!(inputs.len(), 5);
+    assert!(inputs.iter().all(|(_, format)| *format == vk::Format::R32G32B32A32_SFLOAT));
+    assert_eq!(inputs.iter().map(|(location, _)| *location).collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+    assert_eq!(vertex.vertex_inputs[1].name.as_deref(), Some("in_transform"));
+
+    assert_eq!(vertex.bindings.len(), 2);
+    assert_eq!(vertex.bindings[0].descriptor_type, vk::DescriptorType::UNIFORM_BUFFER);
+    assert_eq!(vertex.bindings[0].name.as_deref(), Some("camera"));
+    assert_eq!((vertex.bindings[1].set, vertex.bindings[1].binding), (1, 2));
+    assert_eq!(vertex.bindings[1].descriptor_type, vk::DescriptorType::COMBINED_IMAGE_SAMPLER);
+    assert_eq!(vertex.bindings[1].count, 4);
+
+    let push_constants = vertex.push_constant_range.expect("Missing push constants");
+    assert_eq!((push_constants.offset, push_constants.size), (0, 80));
+
+    assert_eq!(vertex.specialization_constants.len(), 2);
+    assert_eq!(vertex.specialization_constants[0].id, 0);
+    assert_eq!(vertex.specialization_constants[0].kind, ScalarKind::Bool);
+    assert_eq!(vertex.specialization_constants[0].default_value, 1);
+    assert_eq!(vertex.specialization_constants[1].id, 3);
+    assert_eq!(vertex.specialization_constants[1].kind, ScalarKind::Float);
+    assert_eq!(vertex.specialization_constants[1].default_value, 1.5f32.to_bits() as u64);
+    assert_eq!(vertex.specialization_constants[1].name.as_deref(), Some("exposure"));
+
+    let fragment = ShaderReflection::parse(&fragment_module(false)).expect("Failed to reflect fragment module");
+    assert!(fragment.vertex_inputs.is_empty());
+    assert_eq!(fragment.bindings[1].descriptor_type, vk::DescriptorType::STORAGE_BUFFER);
+    assert_eq!(fragment.bindings[1].name.as_deref(), Some("Lights"));
+
+    let layout = ReflectedLayout::merge(&[&vertex, &fragment]).expect("Failed to merge");
+    assert_eq!(layout.set_count(), 3);
+    assert_eq!(layout.bindings.len(), 3);
+    assert_eq!(
+        layout.bindings[0].stages,
+        vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT
+    );
+    assert_eq!(layout.bindings[1].stages, vk::ShaderStageFlags::VERTEX);
+    let push_constants = layout.push_constant_range.expect("Missing push constants");
+    assert_eq!((push_constants.offset, push_constants.size), (0, 96));
+    assert_eq!(
+        push_constants.stage_flags,
+        vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT
+    );
+
+    let conflicting = ShaderReflection::parse(&fragment_module(true)).expect("Failed to reflect fragment module");
+    assert!(matches!(
+        ReflectedLayout::merge(&[&vertex, &conflicting]),
+        Err(Error::IncompatibleShaderBindings { set: 0, binding: 0 })
+    ));
+
+    // Byte swapped modules are accepted, truncated ones are not
+    let swapped: Vec<u32> = vertex_module().iter().map(|word| word.swap_bytes()).collect();
+    assert_eq!(
+        ShaderReflection::parse(&swapped)
+            .expect("Failed to reflect swapped module")
+            .bindings
+            .len(),
+        2
+    );
+    let bytes: Vec<u8> = vertex_module().iter().flat_map(|word| word.to_le_bytes()).collect();
+    assert!(ShaderReflection::from_bytes(&bytes).is_ok());
+    assert!(matches!(ShaderReflection::from_bytes(&bytes[1..]), Err(Error::InvalidSpirv(_))));
+    let mut truncated = vertex_module();
+    truncated.push(3 << 16 | NAME);
+    assert!(matches!(ShaderReflection::parse(&truncated), Err(Error::InvalidSpirv(_))));
+
+    // An array of itself must not recurse forever, a block larger than 4 GiB must not wrap around
+    let mut cyclic = Assembler::new();
+    cyclic
+        .op_string(ENTRY_POINT, &[0, 3], "main", &[9])
+        .op(DECORATE, &[9, 30, 0])
+        .op(TYPE_INT, &[4, 32, 0])
+        .op(CONSTANT, &[4, 5, 2])
+        .op(TYPE_ARRAY, &[6, 6, 5])
+        .op(TYPE_POINTER, &[7, INPUT, 6])
+        .op(VARIABLE, &[7, 9, INPUT])
+        .main();
+    assert!(matches!(ShaderReflection::parse(&cyclic.words), Err(Error::InvalidSpirv(_))));
+    let mut oversized = Assembler::new();
+    oversized
+        .op_string(ENTRY_POINT, &[0, 3], "main", &[])
+        .op(DECORATE, &[8, 2])
+        .op(MEMBER_DECORATE, &[8, 0, 35, 0])
+        .op(TYPE_INT, &[4, 32, 0])
+        .op(CONSTANT, &[4, 5, 0x8000_0000])
+        .op(TYPE_FLOAT, &[6, 32])
+        .op(TYPE_ARRAY, &[7, 6, 5])
+        .op(TYPE_STRUCT, &[8, 7])
+        .op(TYPE_POINTER, &[9, PUSH_CONSTANT, 8])
+        .op(VARIABLE, &[9, 10, PUSH_CONSTANT])
+        .main();
+    assert!(matches!(ShaderReflection::parse(&oversized.words), Err(Error::InvalidSpirv(_))));
+}
+
+#[test]
+fn reflected_pipeline_layout_test() {
+    let context = create_headless_test_context();
+    let mut cache = DescriptorLayoutCache::new(&context);
+    let vertex = ShaderReflection::parse(&vertex_module()).expect("Failed to reflect vertex module");
+    let fragment = ShaderReflection::parse(&fragment_module(false)).expect("Failed to reflect fragment module");
+    let layout = ReflectedLayout::merge(&[&vertex, &fragment]).expect("Failed to merge");
+
+    let set_layouts = layout.create_set_layouts(&mut cache, &[]).expect("Failed to create set layouts");
+    assert_eq!(set_layouts.len(), 3);
+    assert_eq!(cache.len(), 3);
+    let pipeline_layout = layout
+        .create_pipeline_layout(context.device(), &mut cache, &[])
+        .expect("Failed to create pipeline layout");
+    // Set layouts come from the cache the second time
+    assert_eq!(cache.len(), 3);
+    unsafe { context.device().destroy_pipeline_layout(pipeline_layout, None) };
+}
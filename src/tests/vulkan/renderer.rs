@@ -0,0 +1,20 @@
+use crate::backend::renderer::{create_renderer, Backend, BackendPreference, RendererConfig};
+use crate::tests::golden::{assert_golden, GoldenConfig};
+use crate::tests::renderer::render_triangle;
+use crate::tests::vulkan::log::Logger;
+
+#[test]
+fn vulkan_renderer_test() {
+    Logger::init(log::LevelFilter::Trace);
+    let config = RendererConfig::new(64, 64).preference(BackendPreference::Vulkan).validation(true);
+    let mut renderer = create_renderer(&config).expect("Failed to create renderer");
+    assert_eq!(renderer.backend(), Backend::Vulkan);
+
+    render_triangle(renderer.as_mut()).expect("Failed to render");
+    let image = renderer.read_pixels().expect("Failed to read pixels");
+    let config = GoldenConfig {
+        allowed_failing_pixels: 64,
+        ..GoldenConfig::default()
+    };
+    assert_golden("triangle", &image, &config);
+}
@@ -0,0 +1,141 @@
+use crate::backend::renderer::ColorVertex;
+use crate::backend::vulkan::buffer::Buffer;
+use crate::backend::vulkan::context::{Context, ContextConfigurator, DeviceFeatures};
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::memory::MemoryUsage;
+use crate::backend::vulkan::offscreen::{OffscreenTarget, OFFSCREEN_FORMAT};
+use crate::backend::vulkan::pipeline::GraphicsPipelineBuilder;
+use crate::backend::vulkan::queue::op_indices::GRAPHICS;
+use crate::backend::vulkan::renderer::create_color_vertex_rendering_pipeline;
+use crate::backend::vulkan::rendering::{record_layout_transition, AttachmentConfig, RenderingConfig};
+use crate::tests::vulkan::log::Logger;
+use crate::tests::vulkan::test_utils::create_headless_test_base;
+use ash::vk;
+
+#[test]
+fn rendering_config_test() {
+    let color_view = vk::ImageView::null();
+    let color = AttachmentConfig::color(color_view).clear_color([0.0, 0.5, 1.0, 1.0]);
+    let info = color.info();
+    assert_eq!(info.load_op, vk::AttachmentLoadOp::CLEAR);
+    assert_eq!(info.store_op, vk::AttachmentStoreOp::STORE);
+    assert_eq!(info.image_layout, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+    assert_eq!(info.resolve_mode, vk::ResolveModeFlags::NONE);
+    assert_eq!(unsafe { info.clear_value.color.float32 }, [0.0, 0.5, 1.0, 1.0]);
+
+    let depth = AttachmentConfig::depth(vk::ImageView::null());
+    assert_eq!(depth.load_op, vk::AttachmentLoadOp::CLEAR);
+    assert_eq!(depth.store_op, vk::AttachmentStoreOp::DONT_CARE);
+
+    let extent = vk::Extent2D { width: 16, height: 8 };
+    let depth_only = RenderingConfig::new(extent).color_attachment(color).depth_attachment(depth, vk::Format::D32_SFLOAT);
+    assert!(depth_only.depth_attachment.is_some());
+    assert!(depth_only.stencil_attachment.is_none());
+    assert_eq!(depth_only.render_area.extent, extent);
+    let depth_stencil = RenderingConfig::new(extent).depth_attachment(depth, vk::Format::D24_UNORM_S8_UINT);
+    assert!(depth_stencil.stencil_attachment.is_some());
+
+    let required = DeviceFeatures {
+        dynamic_rendering: true,
+        ..Default::default()
+    };
+    assert!(required.supported_by(&required));
+    assert!(!required.supported_by(&DeviceFeatures::default()));
+    assert!(DeviceFeatures::default().supported_by(&DeviceFeatures::default()));
+
+    // Neither a render pass nor rendering formats
+    let builder = GraphicsPipelineBuilder::new(vk::RenderPass::null()).vertex_shader(vk::ShaderModule::null());
+    assert!(matches!(builder.validate(), Err(Error::InvalidPipelineConfig(_))));
+    let builder = GraphicsPipelineBuilder::for_dynamic_rendering(&[OFFSCREEN_FORMAT], None).vertex_shader(vk::ShaderModule::null());
+    assert!(builder.validate().is_ok());
+}
+
+#[test]
+fn dynamic_rendering_test() {
+    Logger::init(log::LevelFilter::Trace);
+    let configurator = ContextConfigurator::headless(&[]).dynamic_rendering(true);
+    let context = Context::new(create_headless_test_base(), configurator).expect("Failed to create context");
+    assert!(context.device_features().dynamic_rendering);
+    let device = context.device();
+
+    let extent = vk::Extent2D { width: 64, height: 64 };
+    let target = OffscreenTarget::new(&context, vk::RenderPass::null(), extent, OFFSCREEN_FORMAT).expect("Failed to create target");
+    assert_eq!(target.framebuffer(), vk::Framebuffer::null());
+    let pipeline = create_color_vertex_rendering_pipeline(device, OFFSCREEN_FORMAT, None).expect("Failed to create pipeline");
+    let triangle = [
+        ColorVertex::new([0.0, -0.5, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]),
+        ColorVertex::new([0.5, 0.5, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]),
+        ColorVertex::new([-0.5, 0.5, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]),
+    ];
+    let vertices = Buffer::with_data(&context, &triangle, vk::BufferUsageFlags::VERTEX_BUFFER, MemoryUsage::CpuToGpu)
+        .expect("Failed to create vertex buffer");
+
+    let command_pool_create_info = vk::CommandPoolCreateInfo {
+        s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+        queue_family_index: context.queue_family_index(GRAPHICS).unwrap(),
+        ..Default::default()
+    };
+    let command_pool = unsafe { device.create_command_pool(&command_pool_create_info, None) }.expect("Failed to create command pool");
+    let allocate_info = vk::CommandBufferAllocateInfo {
+        s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+        command_pool,
+        level: vk::CommandBufferLevel::PRIMARY,
+        command_buffer_count: 1,
+        ..Default::default()
+    };
+    let command_buffer = unsafe { device.allocate_command_buffers(&allocate_info) }.expect("Failed to allocate command buffer")[0];
+
+    let range = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    };
+    let rendering = RenderingConfig::new(extent).color_attachment(AttachmentConfig::color(target.view()).clear_color([0.0, 0.0, 0.0, 1.0]));
+    let viewport = vk::Viewport {
+        width: extent.width as f32,
+        height: extent.height as f32,
+        max_depth: 1.0,
+        ..Default::default()
+    };
+    unsafe {
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            ..Default::default()
+        };
+        device.begin_command_buffer(command_buffer, &begin_info).expect("Failed to begin command buffer");
+        let (undefined, attachment) = (vk::ImageLayout::UNDEFINED, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        record_layout_transition(device, command_buffer, target.image(), range, undefined, attachment, true);
+        rendering.begin(device, command_buffer);
+        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline.handle());
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        device.cmd_set_scissor(command_buffer, 0, &[rendering.render_area]);
+        device.cmd_bind_vertex_buffers(command_buffer, 0, &[vertices.handle()], &[0]);
+        device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        rendering.end(device, command_buffer);
+        let transfer = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+        record_layout_transition(device, command_buffer, target.image(), range, attachment, transfer, false);
+        target.record_readback(command_buffer);
+        device.end_command_buffer(command_buffer).expect("Failed to end command buffer");
+
+        let queue = context.graphics_queue().expect("Missing graphics queue");
+        let submit_info = vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            command_buffer_count: 1,
+            p_command_buffers: &command_buffer,
+            ..Default::default()
+        };
+        device
+            .queue_submit(queue, &[submit_info], vk::Fence::null())
+            .expect("Failed to submit");
+        device.queue_wait_idle(queue).expect("Failed to wait for the queue");
+    }
+
+    let image = target.read_pixels().expect("Failed to read pixels");
+    assert_eq!(image.pixel(0, 0), [0, 0, 0, 255]);
+    assert_eq!(image.pixel(32, 40), [255, 0, 0, 255]);
+
+    unsafe { device.destroy_command_pool(command_pool, None) };
+}
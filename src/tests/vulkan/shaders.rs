@@ -0,0 +1,65 @@
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::shaders::{embedded_shader, shader_name, ShaderLibrary, ShaderOrigin};
+use crate::tests::vulkan::reflection::{fragment_module, vertex_module};
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use ash::vk;
+
+fn bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+#[test]
+fn embedded_shaders_test() {
+    assert_eq!(
+        embedded_shader("color_vshader").map(|shader| shader.stage),
+        Some(vk::ShaderStageFlags::VERTEX)
+    );
+    assert_eq!(
+        embedded_shader("color_fshader").map(|shader| shader.stage),
+        Some(vk::ShaderStageFlags::FRAGMENT)
+    );
+    assert!(embedded_shader("missing").is_none());
+
+    assert_eq!(shader_name("post\\blur.spv"), "post/blur");
+    assert_eq!(shader_name("post/blur"), "post/blur");
+}
+
+#[test]
+fn shader_library_test() {
+    let context = create_headless_test_context();
+    let mut library = ShaderLibrary::new(&context);
+    assert!(library.is_empty());
+
+    library
+        .add("mesh/vertex.spv", vk::ShaderStageFlags::empty(), &bytes(&vertex_module()))
+        .expect("Failed to add vertex shader");
+    library
+        .add("mesh/fragment", vk::ShaderStageFlags::FRAGMENT, &bytes(&fragment_module(false)))
+        .expect("Failed to add fragment shader");
+    assert_eq!(library.len(), 2);
+
+    let vertex = library
+        .get("mesh/vertex", vk::ShaderStageFlags::VERTEX)
+        .expect("Vertex shader is missing");
+    assert_ne!(vertex.module(), vk::ShaderModule::null());
+    assert_eq!(vertex.entry_point().to_str(), Ok("main"));
+    assert_eq!(vertex.origin(), &ShaderOrigin::Memory);
+    assert!(library.module("mesh/vertex", vk::ShaderStageFlags::FRAGMENT).is_err());
+
+    assert!(matches!(
+        library.add("mesh/other", vk::ShaderStageFlags::COMPUTE, &bytes(&vertex_module())),
+        Err(Error::ShaderNotFound { .. })
+    ));
+    assert!(matches!(
+        library.add("broken", vk::ShaderStageFlags::empty(), &[1, 2, 3]),
+        Err(Error::InvalidSpirv(_))
+    ));
+
+    // Replacing destroys the old module
+    library
+        .add("mesh/vertex", vk::ShaderStageFlags::VERTEX, &bytes(&vertex_module()))
+        .expect("Failed to replace vertex shader");
+    assert_eq!(library.len(), 2);
+    assert!(library.remove("mesh/fragment", vk::ShaderStageFlags::FRAGMENT));
+    assert!(!library.contains("mesh/fragment", vk::ShaderStageFlags::FRAGMENT));
+}
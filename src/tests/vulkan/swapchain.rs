@@ -0,0 +1,177 @@
+use crate::backend::vulkan::context::{Context, ContextConfigurator};
+use crate::backend::vulkan::depth::select_depth_format;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::swapchain::{
+    choose_present_mode, choose_surface_format, select_extent, select_image_count, select_surface_format, Swapchain, SwapchainConfig,
+};
+use crate::tests::vulkan::log::Logger;
+use crate::tests::vulkan::test_utils::{create_headless_test_context, create_test_base, TestApp};
+use crate::utils::{create_color_render_pass, create_render_pass_with_depth};
+use ash::vk;
+use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use winit::window::Window;
+
+fn capabilities(current_extent: vk::Extent2D, min_image_count: u32, max_image_count: u32) -> vk::SurfaceCapabilitiesKHR {
+    vk::SurfaceCapabilitiesKHR {
+        min_image_count,
+        max_image_count,
+        current_extent,
+        min_image_extent: vk::Extent2D { width: 1, height: 1 },
+        max_image_extent: vk::Extent2D { width: 4096, height: 4096 },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn swapchain_select_extent_test() {
+    let window_extent = vk::Extent2D { width: 800, height: 600 };
+    let fixed = capabilities(vk::Extent2D { width: 640, height: 480 }, 2, 0);
+    assert_eq!(select_extent(&fixed, window_extent), vk::Extent2D { width: 640, height: 480 });
+
+    let free = capabilities(vk::Extent2D { width: u32::MAX, height: u32::MAX }, 2, 0);
+    assert_eq!(select_extent(&free, window_extent), window_extent);
+    let huge = vk::Extent2D { width: 10000, height: 10000 };
+    assert_eq!(select_extent(&free, huge), vk::Extent2D { width: 4096, height: 4096 });
+
+    // Minimized windows report a zero sized surface
+    let minimized = capabilities(vk::Extent2D { width: 0, height: 0 }, 2, 0);
+    assert_eq!(select_extent(&minimized, window_extent), vk::Extent2D { width: 0, height: 0 });
+}
+
+#[test]
+fn swapchain_select_image_count_test() {
+    let extent = vk::Extent2D { width: 1, height: 1 };
+    assert_eq!(select_image_count(&capabilities(extent, 2, 0)), 3);
+    assert_eq!(select_image_count(&capabilities(extent, 2, 8)), 3);
+    assert_eq!(select_image_count(&capabilities(extent, 3, 3)), 3);
+}
+
+fn surface_format(format: vk::Format, color_space: vk::ColorSpaceKHR) -> vk::SurfaceFormatKHR {
+    vk::SurfaceFormatKHR { format, color_space }
+}
+
+#[test]
+fn swapchain_choose_surface_format_test() {
+    let available = [
+        surface_format(vk::Format::B8G8R8A8_UNORM, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+        surface_format(vk::Format::R8G8B8A8_SRGB, vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT),
+        surface_format(vk::Format::R8G8B8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+    ];
+    let config = SwapchainConfig::default();
+    assert_eq!(choose_surface_format(&available, &config), Some(available[2]));
+
+    // Color spaces are preferred in order for each format
+    let config = config.color_spaces(&[vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT, vk::ColorSpaceKHR::SRGB_NONLINEAR]);
+    assert_eq!(choose_surface_format(&available, &config), Some(available[1]));
+
+    let config = SwapchainConfig::new().surface_formats(&[vk::Format::R16G16B16A16_SFLOAT]);
+    assert_eq!(choose_surface_format(&available, &config), Some(available[0]));
+    assert_eq!(choose_surface_format(&[], &config), None);
+}
+
+#[test]
+fn swapchain_choose_present_mode_test() {
+    let available = [vk::PresentModeKHR::FIFO, vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::MAILBOX];
+    let config = SwapchainConfig::new();
+    assert_eq!(choose_present_mode(&available, &config), vk::PresentModeKHR::FIFO);
+
+    let config = config.vsync(false);
+    assert_eq!(choose_present_mode(&available, &config), vk::PresentModeKHR::MAILBOX);
+    let config = config.present_modes(&[vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::IMMEDIATE]);
+    assert_eq!(choose_present_mode(&available, &config), vk::PresentModeKHR::IMMEDIATE);
+    let config = config.present_modes(&[vk::PresentModeKHR::FIFO_RELAXED]);
+    assert_eq!(choose_present_mode(&available, &config), vk::PresentModeKHR::FIFO);
+}
+
+#[test]
+fn swapchain_headless_test() {
+    let context = create_headless_test_context();
+    match select_surface_format(&context, &SwapchainConfig::default()) {
+        Err(Error::HeadlessContext) => {}
+        result => panic!("Unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn swapchain_recreate_test() {
+    Logger::init(log::LevelFilter::Trace);
+    let testfn = |window: &Window| {
+        let context_config = ContextConfigurator::new(
+            window.window_handle().expect("Failed to get raw window handle").as_raw(),
+            window.display_handle().expect("Failed to get raw display handle").as_raw(),
+            &["VK_KHR_swapchain"],
+        );
+        let context = Context::new(create_test_base(), context_config).expect("Failed to create context");
+        let config = SwapchainConfig::new().vsync(false);
+        let surface_format = select_surface_format(&context, &config).expect("Failed to select surface format");
+        let device = context.device();
+        let render_pass = create_color_render_pass(device, surface_format.format, vk::ImageLayout::PRESENT_SRC_KHR);
+
+        let size = window.inner_size();
+        let window_extent = vk::Extent2D {
+            width: size.width,
+            height: size.height,
+        };
+        let mut swapchain = Swapchain::new(&context, render_pass, surface_format, window_extent, config).expect("Failed to create swapchain");
+        assert!(swapchain.image_count() > 0);
+        assert!(!swapchain.is_minimized());
+        let old_handle = swapchain.handle();
+
+        assert_eq!(swapchain.surface_format(), surface_format);
+        assert!(!swapchain.vsync());
+
+        swapchain.set_vsync(true);
+        swapchain.recreate(&context).expect("Failed to recreate swapchain");
+        assert_eq!(swapchain.present_mode(), vk::PresentModeKHR::FIFO);
+        assert_ne!(swapchain.handle(), vk::SwapchainKHR::null());
+        assert_ne!(swapchain.handle(), old_handle);
+        assert_ne!(swapchain.framebuffer(0), vk::Framebuffer::null());
+
+        drop(swapchain);
+        unsafe { device.destroy_render_pass(render_pass, None) };
+    };
+    let mut app = TestApp::new(testfn);
+    app.run();
+}
+
+#[test]
+fn swapchain_depth_test() {
+    Logger::init(log::LevelFilter::Trace);
+    let testfn = |window: &Window| {
+        let context_config = ContextConfigurator::new(
+            window.window_handle().expect("Failed to get raw window handle").as_raw(),
+            window.display_handle().expect("Failed to get raw display handle").as_raw(),
+            &["VK_KHR_swapchain"],
+        );
+        let context = Context::new(create_test_base(), context_config).expect("Failed to create context");
+        let depth_format = select_depth_format(&context, false).expect("Failed to select depth format");
+        let config = SwapchainConfig::new().depth_format(depth_format);
+        let surface_format = select_surface_format(&context, &config).expect("Failed to select surface format");
+        let device = context.device();
+        let render_pass = create_render_pass_with_depth(device, surface_format.format, Some(depth_format), vk::ImageLayout::PRESENT_SRC_KHR);
+
+        let size = window.inner_size();
+        let window_extent = vk::Extent2D {
+            width: size.width,
+            height: size.height,
+        };
+        let mut swapchain = Swapchain::new(&context, render_pass, surface_format, window_extent, config).expect("Failed to create swapchain");
+        let depth_buffer = swapchain.depth_buffer().expect("Swapchain without a depth buffer");
+        assert_eq!(depth_buffer.format(), depth_format);
+        assert_eq!(depth_buffer.extent(), swapchain.extent());
+
+        // The depth buffer follows the swapchain size
+        swapchain.resize(vk::Extent2D {
+            width: size.width / 2,
+            height: size.height / 2,
+        });
+        swapchain.recreate(&context).expect("Failed to recreate swapchain");
+        assert_eq!(swapchain.depth_buffer().unwrap().extent(), swapchain.extent());
+        assert_ne!(swapchain.framebuffer(0), vk::Framebuffer::null());
+
+        drop(swapchain);
+        unsafe { device.destroy_render_pass(render_pass, None) };
+    };
+    let mut app = TestApp::new(testfn);
+    app.run();
+}
@@ -1,9 +1,14 @@
 use crate::backend::vulkan::base::{Base, BaseConfigBuilder};
+use crate::backend::vulkan::context::{Context, ContextConfigurator};
+use crate::tests::vulkan::log::Logger;
 use winit::application::ApplicationHandler;
 use winit::event::WindowEvent;
 use winit::event::WindowEvent::CloseRequested;
 use winit::event_loop::{ActiveEventLoop, EventLoop};
+#[cfg(target_os = "windows")]
 use winit::platform::windows::EventLoopBuilderExtWindows;
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+use winit::platform::x11::EventLoopBuilderExtX11;
 use winit::window::{Window, WindowId};
 
 pub struct TestApp<T> {
@@ -68,3 +73,18 @@ pub fn create_test_base() -> Base {
         .build("Test", "Test", "1.0.0", "1.0.0", "1.0.0");
     Base::new(base_config).expect("Failed to create base!")
 }
+
+/// Base for tests that don't need a window, works with software implementations such as lavapipe
+pub fn create_headless_test_base() -> Base {
+    let base_config = BaseConfigBuilder::new()
+        .use_khronos_validation()
+        .use_headless_vulkan_extensions()
+        .build("Test", "Test", "1.3.0", "1.0.0", "1.0.0");
+    Base::new(base_config).expect("Failed to create base!")
+}
+
+/// Headless context with trace logging, the setup shared by most Vulkan tests
+pub fn create_headless_test_context() -> Context {
+    Logger::init(log::LevelFilter::Trace);
+    Context::new(create_headless_test_base(), ContextConfigurator::headless(&[])).expect("Failed to create context")
+}
@@ -0,0 +1,70 @@
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::ImageKind;
+use crate::backend::vulkan::texture::{select_texture_format, TextureLoader};
+use crate::backend::vulkan::upload::UploadManager;
+use crate::image::RgbaImage;
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use crate::texture::TextureData;
+use ash::vk;
+
+#[test]
+fn texture_format_selection_test() {
+    let sampled = vk::FormatProperties {
+        optimal_tiling_features: vk::FormatFeatureFlags::SAMPLED_IMAGE,
+        ..Default::default()
+    };
+    let unsupported = vk::FormatProperties::default();
+    let bc7 = vk::Format::BC7_SRGB_BLOCK;
+    assert_eq!(select_texture_format(bc7, sampled).unwrap(), (bc7, false));
+    assert_eq!(select_texture_format(bc7, unsupported).unwrap(), (vk::Format::R8G8B8A8_SRGB, true));
+    let astc = vk::Format::ASTC_8X8_UNORM_BLOCK;
+    assert_eq!(select_texture_format(astc, unsupported).unwrap(), (vk::Format::R8G8B8A8_UNORM, true));
+    let bc6h = vk::Format::BC6H_SFLOAT_BLOCK;
+    assert_eq!(select_texture_format(bc6h, unsupported).unwrap(), (vk::Format::R16G16B16A16_SFLOAT, true));
+    assert!(matches!(
+        select_texture_format(vk::Format::ETC2_R8G8B8_UNORM_BLOCK, unsupported),
+        Err(Error::UnsupportedTextureFormat(vk::Format::ETC2_R8G8B8_UNORM_BLOCK))
+    ));
+}
+
+#[test]
+fn texture_load_test() {
+    let context = create_headless_test_context();
+    // Small ring so the base level is copied in several bands
+    let mut uploads = UploadManager::with_staging_size(&context, 4096).expect("Failed to create upload manager");
+    let mut loader = TextureLoader::new(&context).expect("Failed to create loader");
+
+    let image = RgbaImage::from_pixels(64, 32, (0..64 * 32 * 4).map(|value| value as u8).collect());
+    let texture = loader
+        .load(&context, &mut uploads, &TextureData::from_rgba(image, true))
+        .expect("Failed to load texture");
+    assert_eq!(texture.format(), vk::Format::R8G8B8A8_SRGB);
+    assert_eq!(texture.mip_levels(), 7);
+    for level in 0..texture.mip_levels() {
+        assert_eq!(texture.image().layout(level, 0), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+    }
+    assert_eq!(texture.view().view_type(), vk::ImageViewType::TYPE_2D);
+
+    // BC1 cube map, sampled directly or decompressed depending on the device
+    let cube = TextureData {
+        format: vk::Format::BC1_RGBA_UNORM_BLOCK,
+        kind: ImageKind::Cube,
+        extent: vk::Extent3D {
+            width: 8,
+            height: 8,
+            depth: 1,
+        },
+        array_layers: 6,
+        levels: vec![vec![0xff; 4 * 8 * 6]],
+    };
+    let texture = loader.load(&context, &mut uploads, &cube).expect("Failed to load cube texture");
+    assert!(texture.format() == vk::Format::BC1_RGBA_UNORM_BLOCK || texture.format() == vk::Format::R8G8B8A8_UNORM);
+    assert_eq!(texture.view().view_type(), vk::ImageViewType::CUBE);
+    assert_eq!(texture.image().layout(0, 5), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+    let broken = TextureData {
+        levels: vec![vec![0; 10]],
+        ..cube
+    };
+    assert!(matches!(loader.load(&context, &mut uploads, &broken), Err(Error::Texture(_))));
+}
@@ -0,0 +1,146 @@
+use crate::backend::vulkan::buffer::Buffer;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::{Image, ImageConfig};
+use crate::backend::vulkan::memory::MemoryUsage;
+use crate::backend::vulkan::queue::op_indices::GRAPHICS;
+use crate::backend::vulkan::upload::{StagingRing, UploadManager};
+use crate::tests::vulkan::test_utils::create_headless_test_context;
+use ash::vk;
+
+#[test]
+fn staging_ring_wrap_test() {
+    let mut ring = StagingRing::new(100);
+    assert_eq!(ring.allocate(40, 16), Some((0, 40)));
+    // Alignment padding counts as consumed
+    assert_eq!(ring.allocate(40, 16), Some((48, 48)));
+    assert_eq!(ring.allocate(20, 16), None);
+
+    ring.release(40);
+    // Wraps to the start, the 12 bytes at the end are skipped
+    assert_eq!(ring.allocate(30, 16), Some((0, 42)));
+    assert_eq!(ring.used(), 90);
+    assert_eq!(ring.allocate(20, 1), None);
+
+    ring.release(48);
+    ring.release(42);
+    assert_eq!(ring.used(), 0);
+    assert_eq!(ring.allocate(100, 16), Some((0, 100)));
+    assert_eq!(ring.allocate(1, 1), None);
+    assert_eq!(ring.allocate(101, 1), None);
+}
+
+#[test]
+fn upload_buffer_test() {
+    let context = create_headless_test_context();
+    let mut uploads = UploadManager::with_staging_size(&context, 1024).expect("Failed to create upload manager");
+
+    // Larger than the staging ring, has to be split and to wait for earlier chunks
+    let data: Vec<u32> = (0..1000).collect();
+    let usage = vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER;
+    let destination = Buffer::new(&context, 4000, usage, MemoryUsage::GpuToCpu).expect("Failed to create buffer");
+    let small = Buffer::new(&context, 16, usage, MemoryUsage::GpuToCpu).expect("Failed to create buffer");
+
+    let ticket = uploads.upload_buffer(&destination, 0, &data).expect("Failed to upload");
+    let small_ticket = uploads.upload_buffer(&small, 4, &[1.5f32, 2.5f32]).expect("Failed to upload");
+    assert_eq!(ticket, small_ticket, "Uploads after the last split should share the batch");
+    assert!(uploads.upload_buffer(&small, 12, &[0u64]).is_err());
+    assert!(uploads.flush().expect("Failed to flush").is_some());
+    uploads.wait(ticket).expect("Failed to wait");
+    assert!(uploads.is_complete(ticket).expect("Failed to poll"));
+
+    assert_eq!(destination.read_slice::<u32>(0, 1000).expect("Failed to read"), data);
+    assert_eq!(small.read_slice::<f32>(4, 2).expect("Failed to read"), vec![1.5, 2.5]);
+
+    // Acquire barriers are recorded on the graphics family
+    let device = context.device();
+    let command_pool_create_info = vk::CommandPoolCreateInfo {
+        s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+        queue_family_index: context.queue_family_index(GRAPHICS).unwrap(),
+        ..Default::default()
+    };
+    unsafe {
+        let command_pool = device.create_command_pool(&command_pool_create_info, None).expect("Failed to create pool");
+        let allocate_info = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+            command_buffer_count: 1,
+            ..Default::default()
+        };
+        let command_buffer = device.allocate_command_buffers(&allocate_info).expect("Failed to allocate")[0];
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            ..Default::default()
+        };
+        device.begin_command_buffer(command_buffer, &begin_info).expect("Failed to begin");
+        assert_eq!(uploads.record_acquire_barriers(command_buffer).expect("Failed to record"), 2);
+        assert_eq!(uploads.record_acquire_barriers(command_buffer).expect("Failed to record"), 0);
+        device.end_command_buffer(command_buffer).expect("Failed to end");
+        device.destroy_command_pool(command_pool, None);
+    }
+}
+
+#[test]
+fn upload_image_row_too_large_test() {
+    let context = create_headless_test_context();
+    let mut uploads = UploadManager::with_staging_size(&context, 1024).expect("Failed to create upload manager");
+    // A row of 512 RGBA8 texels needs 2048 bytes
+    let config = ImageConfig::new_2d(vk::Format::R8G8B8A8_UNORM, 512, 2).usage(vk::ImageUsageFlags::TRANSFER_DST);
+    let mut image = Image::new(&context, config).expect("Failed to create image");
+    assert!(matches!(
+        uploads.upload_image(&mut image, 0, &[0; 512 * 2 * 4]),
+        Err(Error::StagingTooSmall { size: 2048, capacity: 1024 })
+    ));
+}
+
+#[test]
+fn upload_image_reupload_test() {
+    let context = create_headless_test_context();
+    let mut uploads = UploadManager::new(&context).expect("Failed to create upload manager");
+    let config = ImageConfig::new_2d(vk::Format::R8G8B8A8_UNORM, 4, 4);
+    let mut image = Image::new(&context, config).expect("Failed to create image");
+    let ticket = uploads.upload_image(&mut image, 0, &[255; 4 * 4 * 4]).expect("Failed to upload");
+    uploads.wait(ticket).expect("Failed to wait");
+
+    // The acquire moves the image to the graphics family, the second upload has to release it from there again
+    let device = context.device();
+    let queue = context.graphics_queue().expect("Missing graphics queue");
+    let command_pool_create_info = vk::CommandPoolCreateInfo {
+        s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+        queue_family_index: context.queue_family_index(GRAPHICS).unwrap(),
+        ..Default::default()
+    };
+    unsafe {
+        let command_pool = device.create_command_pool(&command_pool_create_info, None).expect("Failed to create pool");
+        let allocate_info = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+            command_buffer_count: 1,
+            ..Default::default()
+        };
+        let command_buffer = device.allocate_command_buffers(&allocate_info).expect("Failed to allocate")[0];
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            ..Default::default()
+        };
+        device.begin_command_buffer(command_buffer, &begin_info).expect("Failed to begin");
+        assert_eq!(uploads.record_acquire_barriers(command_buffer).expect("Failed to record"), 1);
+        image.transition(command_buffer, image.full_range(), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        device.end_command_buffer(command_buffer).expect("Failed to end");
+        let submit_info = vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            command_buffer_count: 1,
+            p_command_buffers: &command_buffer,
+            ..Default::default()
+        };
+        device.queue_submit(queue, &[submit_info], vk::Fence::null()).expect("Failed to submit");
+        device.queue_wait_idle(queue).expect("Failed to wait");
+        device.destroy_command_pool(command_pool, None);
+    }
+
+    let ticket = uploads.upload_image(&mut image, 0, &[0; 4 * 4 * 4]).expect("Failed to upload");
+    assert_eq!(image.layout(0, 0), vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+    uploads.wait(ticket).expect("Failed to wait");
+    assert!(uploads.is_complete(ticket).expect("Failed to poll"));
+}
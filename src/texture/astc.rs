@@ -0,0 +1,547 @@
+//! CPU decoder for 2D ASTC blocks in the LDR profile, used when the device can't sample ASTC directly.
+//! Follows the decoding process of the Khronos Data Format Specification. HDR endpoint modes and HDR void extent
+//! blocks are not part of the LDR profile and decode to the error color like every other illegal encoding.
+
+use ash::vk;
+
+/// Texel color of illegal blocks
+const ERROR_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+/// Color endpoint ranges ordered by precision, the decoder picks the largest one that fits the block
+const COLOR_LEVELS: [u32; 21] = [2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32, 40, 48, 64, 80, 96, 128, 160, 192, 256];
+
+/// Weight ranges indexed by the block mode's precision bit and range field
+const WEIGHT_LEVELS: [u32; 12] = [2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32];
+
+/// `Some(true)` for the sRGB ASTC formats, `Some(false)` for the UNORM ones and `None` for everything else
+pub fn is_srgb(format: vk::Format) -> Option<bool> {
+    // ASTC formats are numbered from 4x4 to 12x12 with the UNORM and sRGB variant of every size next to each other
+    let raw = format.as_raw();
+    let first = vk::Format::ASTC_4X4_UNORM_BLOCK.as_raw();
+    (first..=vk::Format::ASTC_12X12_SRGB_BLOCK.as_raw())
+        .contains(&raw)
+        .then_some((raw - first) % 2 == 1)
+}
+
+/// How the values of an integer sequence are packed: plain bits, or a trit or quint plus plain bits
+#[derive(Clone, Copy)]
+enum Packing {
+    Bits,
+    Trits,
+    Quints,
+}
+
+/// Range of an integer sequence, `levels` values with the number of plain bits each value stores
+#[derive(Clone, Copy)]
+struct Range {
+    packing: Packing,
+    bits: u32,
+}
+
+impl Range {
+    fn new(levels: u32) -> Self {
+        let (packing, base) = if levels.is_multiple_of(3) {
+            (Packing::Trits, levels / 3)
+        } else if levels.is_multiple_of(5) {
+            (Packing::Quints, levels / 5)
+        } else {
+            (Packing::Bits, levels)
+        };
+        Self {
+            packing,
+            bits: base.trailing_zeros(),
+        }
+    }
+
+    /// Bits a sequence of `count` values takes
+    fn sequence_bits(self, count: u32) -> u32 {
+        count * self.bits
+            + match self.packing {
+                Packing::Bits => 0,
+                Packing::Trits => (8 * count).div_ceil(5),
+                Packing::Quints => (7 * count).div_ceil(3),
+            }
+    }
+}
+
+/// Reads fields of a 128 bit block from the least significant bit up, bits at or past `end` read as zero
+struct BitReader {
+    bits: u128,
+    position: u32,
+    end: u32,
+}
+
+impl BitReader {
+    fn new(bits: u128, position: u32, end: u32) -> Self {
+        Self { bits, position, end }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let available = self.end.saturating_sub(self.position).min(count);
+        self.position += count;
+        if available == 0 {
+            return 0;
+        }
+        ((self.bits >> (self.position - count)) & ((1u128 << available) - 1)) as u32
+    }
+}
+
+fn field(bits: u128, start: u32, count: u32) -> u32 {
+    BitReader::new(bits, start, 128).read(count)
+}
+
+/// Five trits packed into eight bits
+fn decode_trits(packed: u32) -> [u32; 5] {
+    let bit = |value: u32, index: u32| (value >> index) & 1;
+    let (c, t3, t4) = if (packed >> 2) & 7 == 7 {
+        ((((packed >> 5) & 7) << 2) | (packed & 3), 2, 2)
+    } else if (packed >> 5) & 3 == 3 {
+        (packed & 0x1f, bit(packed, 7), 2)
+    } else {
+        (packed & 0x1f, (packed >> 5) & 3, bit(packed, 7))
+    };
+    let (t0, t1, t2) = if c & 3 == 3 {
+        ((bit(c, 3) << 1) | (bit(c, 2) & !bit(c, 3) & 1), bit(c, 4), 2)
+    } else if (c >> 2) & 3 == 3 {
+        (c & 3, 2, 2)
+    } else {
+        ((bit(c, 1) << 1) | (bit(c, 0) & !bit(c, 1) & 1), (c >> 2) & 3, bit(c, 4))
+    };
+    [t0, t1, t2, t3, t4]
+}
+
+/// Three quints packed into seven bits
+fn decode_quints(packed: u32) -> [u32; 3] {
+    let bit = |value: u32, index: u32| (value >> index) & 1;
+    if (packed >> 1) & 3 == 3 && (packed >> 5) & 3 == 0 {
+        let q2 = (bit(packed, 0) << 2) | ((bit(packed, 4) & !bit(packed, 0) & 1) << 1) | (bit(packed, 3) & !bit(packed, 0) & 1);
+        return [4, 4, q2];
+    }
+    let (c, q2) = if (packed >> 1) & 3 == 3 {
+        ((((packed >> 3) & 3) << 3) | ((!(packed >> 5) & 3) << 1) | (packed & 1), 4)
+    } else {
+        (packed & 0x1f, (packed >> 5) & 3)
+    };
+    if c & 7 == 5 {
+        [(c >> 3) & 3, 4, q2]
+    } else {
+        [c & 7, (c >> 3) & 3, q2]
+    }
+}
+
+/// Integer sequence starting at `start`, fills all of `values`
+fn decode_sequence(bits: u128, start: u32, range: Range, values: &mut [u32]) {
+    let n = range.bits;
+    let mut reader = BitReader::new(bits, start, start + range.sequence_bits(values.len() as u32));
+    match range.packing {
+        Packing::Bits => values.iter_mut().for_each(|value| *value = reader.read(n)),
+        Packing::Trits => {
+            for group in values.chunks_mut(5) {
+                let mut low = [0; 5];
+                let mut packed = 0;
+                for (index, (shift, count)) in [(0, 2), (2, 2), (4, 1), (5, 2), (7, 1)].into_iter().enumerate() {
+                    low[index] = reader.read(n);
+                    packed |= reader.read(count) << shift;
+                }
+                for (value, (trit, low)) in group.iter_mut().zip(decode_trits(packed).into_iter().zip(low)) {
+                    *value = (trit << n) | low;
+                }
+            }
+        }
+        Packing::Quints => {
+            for group in values.chunks_mut(3) {
+                let mut low = [0; 3];
+                let mut packed = 0;
+                for (index, (shift, count)) in [(0, 3), (3, 2), (5, 2)].into_iter().enumerate() {
+                    low[index] = reader.read(n);
+                    packed |= reader.read(count) << shift;
+                }
+                for (value, (quint, low)) in group.iter_mut().zip(decode_quints(packed).into_iter().zip(low)) {
+                    *value = (quint << n) | low;
+                }
+            }
+        }
+    }
+}
+
+/// Repeats the `bits` wide `value` until it is `to` bits wide
+fn replicate(value: u32, bits: u32, to: u32) -> u32 {
+    let (mut result, mut filled) = (0, 0);
+    while filled < to {
+        result = (result << bits) | value;
+        filled += bits;
+    }
+    result >> (filled - to)
+}
+
+/// Builds a value from a bit pattern like "cb000cbcb", letters select bits of `low` with `a` being the lowest
+fn pattern(pattern: &str, low: u32) -> u32 {
+    pattern.bytes().fold(0, |result, bit| match bit {
+        b'0' => result << 1,
+        _ => (result << 1) | ((low >> (bit - b'a')) & 1),
+    })
+}
+
+/// Trit or quint value `value` unquantized with the scale `c` and bit pattern `b` of the specification tables
+fn unquantize_packed(value: u32, bits: u32, c: u32, b: &str, top_bit: u32) -> u32 {
+    let low = value & ((1 << bits) - 1);
+    let a = if low & 1 != 0 { (top_bit << 2) - 1 } else { 0 };
+    let t = ((value >> bits) * c + pattern(b, low)) ^ a;
+    (a & top_bit) | (t >> 2)
+}
+
+/// Color endpoint value expanded to 0..=255
+fn unquantize_color(value: u32, range: Range) -> u32 {
+    let n = range.bits as usize;
+    match range.packing {
+        Packing::Bits => replicate(value, range.bits, 8),
+        Packing::Trits => {
+            let c = [204, 93, 44, 22, 11, 5][n - 1];
+            let b = ["000000000", "b000b0bb0", "cb000cbcb", "dcb000dcb", "edcb000ed", "fedcb000f"][n - 1];
+            unquantize_packed(value, range.bits, c, b, 0x80)
+        }
+        Packing::Quints => {
+            let c = [113, 54, 26, 13, 6][n - 1];
+            let b = ["000000000", "b0000bb00", "cb0000cbc", "dcb0000dc", "edcb0000e"][n - 1];
+            unquantize_packed(value, range.bits, c, b, 0x80)
+        }
+    }
+}
+
+/// Weight expanded to 0..=64
+fn unquantize_weight(value: u32, range: Range) -> u32 {
+    let n = range.bits as usize;
+    let weight = match range.packing {
+        Packing::Bits => replicate(value, range.bits, 6),
+        Packing::Trits if n == 0 => return value * 32,
+        Packing::Quints if n == 0 => return value * 16,
+        Packing::Trits => {
+            let b = ["0000000", "b000b0b", "cb000cb"][n - 1];
+            unquantize_packed(value, range.bits, [50, 23, 11][n - 1], b, 0x20)
+        }
+        Packing::Quints => unquantize_packed(value, range.bits, [28, 13][n - 1], ["0000000", "b0000b0"][n - 1], 0x20),
+    };
+    if weight > 32 {
+        weight + 1
+    } else {
+        weight
+    }
+}
+
+/// Weight grid and weight range of a block
+struct BlockMode {
+    grid_width: usize,
+    grid_height: usize,
+    dual_plane: bool,
+    weight_range: Range,
+}
+
+/// `None` for reserved block modes
+fn decode_block_mode(mode: u32) -> Option<BlockMode> {
+    let bit = |index: u32| (mode >> index) & 1;
+    let (a, b) = ((mode >> 5) & 3, (mode >> 7) & 3);
+    let (mut high_precision, mut dual_plane) = (bit(9), bit(10));
+    let (range, width, height) = if mode & 3 != 0 {
+        let (width, height) = match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if bit(8) == 0 => (a + 2, (b & 1) + 6),
+            _ => ((b & 1) + 2, a + 2),
+        };
+        (bit(4) | ((mode & 3) << 1), width, height)
+    } else {
+        let (width, height) = match b {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => {
+                // Large grids take the precision and dual plane bits for their height
+                (high_precision, dual_plane) = (0, 0);
+                (a + 6, ((mode >> 9) & 3) + 6)
+            }
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        };
+        (bit(4) | (((mode >> 2) & 3) << 1), width, height)
+    };
+    if range < 2 {
+        return None;
+    }
+    Some(BlockMode {
+        grid_width: width as usize,
+        grid_height: height as usize,
+        dual_plane: dual_plane != 0,
+        weight_range: Range::new(WEIGHT_LEVELS[(high_precision * 6 + range - 2) as usize]),
+    })
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+/// Partition of the texel at `x`, `y` for the partition pattern `seed`
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, small_block: bool) -> usize {
+    let (x, y) = if small_block { (x << 1, y << 1) } else { (x, y) };
+    let seed = seed + (partitions - 1) * 1024;
+    let random = hash52(seed);
+    let mut seeds: [u32; 8] = std::array::from_fn(|index| (random >> (4 * index)) & 0xf);
+    seeds.iter_mut().for_each(|value| *value *= *value);
+
+    let (shift1, shift2) = if seed & 1 != 0 {
+        (if seed & 2 != 0 { 4 } else { 5 }, if partitions == 3 { 6 } else { 5 })
+    } else {
+        (if partitions == 3 { 6 } else { 5 }, if seed & 2 != 0 { 4 } else { 5 })
+    };
+    for (index, value) in seeds.iter_mut().enumerate() {
+        *value >>= if index % 2 == 0 { shift1 } else { shift2 };
+    }
+
+    // The z terms of the specification vanish for 2D blocks
+    let a = (seeds[0] * x + seeds[1] * y + (random >> 14)) & 0x3f;
+    let b = (seeds[2] * x + seeds[3] * y + (random >> 10)) & 0x3f;
+    let c = if partitions > 2 {
+        (seeds[4] * x + seeds[5] * y + (random >> 6)) & 0x3f
+    } else {
+        0
+    };
+    let d = if partitions > 3 {
+        (seeds[6] * x + seeds[7] * y + (random >> 2)) & 0x3f
+    } else {
+        0
+    };
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+/// Moves the top bit of `base` into `offset`'s place and turns `offset` into a signed 6 bit value
+fn bit_transfer_signed(offset: i32, base: i32) -> (i32, i32) {
+    let base = (base >> 1) | (offset & 0x80);
+    let offset = (offset >> 1) & 0x3f;
+    let offset = if offset & 0x20 != 0 { offset - 0x40 } else { offset };
+    (offset, base)
+}
+
+fn blue_contract(color: [i32; 4]) -> [i32; 4] {
+    [(color[0] + color[2]) >> 1, (color[1] + color[2]) >> 1, color[2], color[3]]
+}
+
+/// Endpoint pair of a color endpoint mode, `None` for the HDR modes
+fn decode_endpoints(mode: u32, values: &[u32]) -> Option<[[u32; 4]; 2]> {
+    let v: [i32; 8] = std::array::from_fn(|index| values.get(index).map_or(0, |&value| value as i32));
+    let endpoints = match mode {
+        // Luminance direct and base + offset
+        0 => [[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]],
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xc0);
+            let l1 = (l0 + (v[1] & 0x3f)).min(255);
+            [[l0, l0, l0, 255], [l1, l1, l1, 255]]
+        }
+        // Luminance and alpha direct and base + offset
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        5 => {
+            let (l_offset, l) = bit_transfer_signed(v[1], v[0]);
+            let (a_offset, a) = bit_transfer_signed(v[3], v[2]);
+            let l1 = l + l_offset;
+            [[l, l, l, a], [l1, l1, l1, a + a_offset]]
+        }
+        // RGB base + scale, optionally with two alpha values
+        6 | 10 => {
+            let alpha = if mode == 10 { [v[4], v[5]] } else { [255, 255] };
+            [
+                [(v[0] * v[3]) >> 8, (v[1] * v[3]) >> 8, (v[2] * v[3]) >> 8, alpha[0]],
+                [v[0], v[1], v[2], alpha[1]],
+            ]
+        }
+        // RGB(A) direct, swapped endpoints select blue contraction
+        8 | 12 => {
+            let alpha = if mode == 12 { [v[6], v[7]] } else { [255, 255] };
+            let e0 = [v[0], v[2], v[4], alpha[0]];
+            let e1 = [v[1], v[3], v[5], alpha[1]];
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [e0, e1]
+            } else {
+                [blue_contract(e1), blue_contract(e0)]
+            }
+        }
+        // RGB(A) base + offset, a negative offset sum selects blue contraction
+        9 | 13 => {
+            let mut base = [0; 4];
+            let mut offset = [0; 4];
+            for channel in 0..4 {
+                (offset[channel], base[channel]) = bit_transfer_signed(v[2 * channel + 1], v[2 * channel]);
+            }
+            if mode == 9 {
+                (base[3], offset[3]) = (255, 0);
+            }
+            let sum: [i32; 4] = std::array::from_fn(|channel| base[channel] + offset[channel]);
+            if offset[0] + offset[1] + offset[2] >= 0 {
+                [base, sum]
+            } else {
+                [blue_contract(sum), blue_contract(base)]
+            }
+        }
+        _ => return None,
+    };
+    Some(endpoints.map(|endpoint| endpoint.map(|value| value.clamp(0, 255) as u32)))
+}
+
+/// Weight of the texel at `x`, `y` bilinearly interpolated from the weight grid
+fn infill_weight(weights: &[u32], mode: &BlockMode, plane: usize, block_size: (usize, usize), x: usize, y: usize) -> u32 {
+    let planes = mode.dual_plane as usize + 1;
+    let grid = |grid_x: usize, grid_y: usize| {
+        let (grid_x, grid_y) = (grid_x.min(mode.grid_width - 1), grid_y.min(mode.grid_height - 1));
+        weights[(grid_y * mode.grid_width + grid_x) * planes + plane]
+    };
+    let position = |texel: usize, size: usize, grid_size: usize| {
+        let scale = (1024 + size / 2) / (size - 1);
+        (scale * texel * (grid_size - 1) + 32) >> 6
+    };
+    let s = position(x, block_size.0, mode.grid_width);
+    let t = position(y, block_size.1, mode.grid_height);
+    let (grid_x, fraction_s) = (s >> 4, (s & 0xf) as u32);
+    let (grid_y, fraction_t) = (t >> 4, (t & 0xf) as u32);
+    let w11 = (fraction_s * fraction_t + 8) >> 4;
+    let w10 = fraction_t - w11;
+    let w01 = fraction_s - w11;
+    let w00 = 16 + w11 - fraction_s - fraction_t;
+    (grid(grid_x, grid_y) * w00 + grid(grid_x + 1, grid_y) * w01 + grid(grid_x, grid_y + 1) * w10 + grid(grid_x + 1, grid_y + 1) * w11 + 8)
+        >> 4
+}
+
+/// Decodes a block of `width` x `height` texels to RGBA8 in row order, `None` for illegal blocks
+fn decode(bits: u128, width: usize, height: usize, srgb: bool, texels: &mut [[u8; 4]]) -> Option<()> {
+    if field(bits, 0, 9) == 0x1fc {
+        // Void extent block with a constant 16 bit UNORM color, HDR ones are FP16 and illegal in the LDR profile
+        if field(bits, 9, 1) != 0 || field(bits, 10, 2) != 3 {
+            return None;
+        }
+        let color = std::array::from_fn(|channel| (field(bits, 64 + 16 * channel as u32, 16) >> 8) as u8);
+        texels.fill(color);
+        return Some(());
+    }
+
+    let mode = decode_block_mode(field(bits, 0, 11))?;
+    let partitions = field(bits, 11, 2) as usize + 1;
+    let weight_count = mode.grid_width * mode.grid_height * (mode.dual_plane as usize + 1);
+    let weight_bits = mode.weight_range.sequence_bits(weight_count as u32);
+    if mode.grid_width > width
+        || mode.grid_height > height
+        || weight_count > 64
+        || !(24..=96).contains(&weight_bits)
+        || (partitions == 4 && mode.dual_plane)
+    {
+        return None;
+    }
+
+    // Endpoint modes of all partitions, the extra bits of differing modes sit right below the weights
+    let mut below_weights = 128 - weight_bits;
+    let mut endpoint_modes = [0u32; 4];
+    let color_start = if partitions == 1 {
+        endpoint_modes[0] = field(bits, 13, 4);
+        17
+    } else {
+        let selector = field(bits, 23, 6);
+        if selector & 3 == 0 {
+            endpoint_modes.fill(selector >> 2);
+        } else {
+            let extra_bits = 3 * partitions as u32 - 4;
+            below_weights -= extra_bits;
+            let encoded = selector | (field(bits, below_weights, extra_bits) << 6);
+            let class = (encoded & 3) - 1;
+            for (partition, endpoint_mode) in endpoint_modes.iter_mut().take(partitions).enumerate() {
+                let class_offset = (encoded >> (2 + partition)) & 1;
+                let low = (encoded >> (2 + partitions + 2 * partition)) & 3;
+                *endpoint_mode = ((class + class_offset) << 2) | low;
+            }
+        }
+        29
+    };
+    let plane2_channel = if mode.dual_plane {
+        below_weights -= 2;
+        Some(field(bits, below_weights, 2) as usize)
+    } else {
+        None
+    };
+
+    let color_bits = below_weights.checked_sub(color_start)?;
+    let value_counts = endpoint_modes.map(|endpoint_mode| 2 * ((endpoint_mode >> 2) as usize + 1));
+    let value_count: usize = value_counts[..partitions].iter().sum();
+    if value_count > 18 {
+        return None;
+    }
+    let color_range = COLOR_LEVELS
+        .iter()
+        .rev()
+        .map(|&levels| (levels, Range::new(levels)))
+        .find(|(_, range)| range.sequence_bits(value_count as u32) <= color_bits)
+        .filter(|(levels, _)| *levels >= 6)?
+        .1;
+    let mut values = [0u32; 18];
+    decode_sequence(bits, color_start, color_range, &mut values[..value_count]);
+    values.iter_mut().for_each(|value| *value = unquantize_color(*value, color_range));
+    let mut endpoints = [[[0u32; 4]; 2]; 4];
+    let mut offset = 0;
+    for partition in 0..partitions {
+        endpoints[partition] = decode_endpoints(endpoint_modes[partition], &values[offset..offset + value_counts[partition]])?;
+        offset += value_counts[partition];
+    }
+
+    // Weights are stored bit reversed from the top of the block down
+    let mut weights = [0u32; 64];
+    decode_sequence(bits.reverse_bits(), 0, mode.weight_range, &mut weights[..weight_count]);
+    weights
+        .iter_mut()
+        .for_each(|weight| *weight = unquantize_weight(*weight, mode.weight_range));
+
+    let seed = field(bits, 13, 10);
+    let small_block = width * height < 31;
+    for (index, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (index % width, index / width);
+        let partition = if partitions > 1 {
+            select_partition(seed, x as u32, y as u32, partitions as u32, small_block)
+        } else {
+            0
+        };
+        let [e0, e1] = endpoints[partition];
+        let plane_weights = [
+            infill_weight(&weights, &mode, 0, (width, height), x, y),
+            plane2_channel.map_or(0, |_| infill_weight(&weights, &mode, 1, (width, height), x, y)),
+        ];
+        for channel in 0..4 {
+            let weight = plane_weights[(plane2_channel == Some(channel)) as usize];
+            // sRGB color channels expand to 16 bits with a rounding bias instead of bit replication
+            let expand = |value: u32| if srgb && channel < 3 { (value << 8) | 0x80 } else { value * 257 };
+            let value = (expand(e0[channel]) * (64 - weight) + expand(e1[channel]) * weight + 32) >> 6;
+            texel[channel] = (value >> 8) as u8;
+        }
+    }
+    Some(())
+}
+
+/// Decodes one 16 byte block of `width` x `height` texels, illegal blocks decode to magenta
+pub fn decode_block(block: &[u8], width: usize, height: usize, srgb: bool, texels: &mut [[u8; 4]]) {
+    let bits = u128::from_le_bytes(block.try_into().unwrap());
+    if decode(bits, width, height, srgb, texels).is_none() {
+        texels.fill(ERROR_COLOR);
+    }
+}
@@ -0,0 +1,689 @@
+//! CPU decoders for the BC formats, used when the device can't sample a compressed format directly.
+//! `decompress` is the entry point for every block compressed format with a decoder, ASTC included.
+
+use crate::texture::{astc, format_block, Error};
+use ash::vk;
+
+/// Decoded 4x4 block, pixels in row order
+type Texels = [[u8; 4]; 16];
+
+/// Subset of every pixel for the 64 two subset BC7 partitions, bit `i` belongs to pixel `i`
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+    0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce, 0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+    0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Three subset BC7 partitions, two bits per pixel starting at the lowest bits
+const PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050, 0xaa550000, 0xaa555500, 0xaaaa5500,
+    0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250, 0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040,
+    0xa4a45000, 0x1a1a0500, 0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200, 0xa9a58000,
+    0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50, 0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0,
+    0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600, 0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414,
+    0x96960000, 0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+/// Anchor pixel of the second subset of the two subset partitions
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15,
+    15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixels of the second and third subset of the three subset partitions
+const ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3,
+    15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+];
+const ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8,
+    9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Layout of a BC7 mode, the field names follow the BC7 specification
+struct Bc7Mode {
+    /// Number of subsets
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// One p-bit per endpoint
+    endpoint_p_bits: bool,
+    /// One p-bit per subset, shared by both endpoints
+    shared_p_bits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const fn bc7_mode(modes: [u32; 10]) -> Bc7Mode {
+    Bc7Mode {
+        subsets: modes[0] as usize,
+        partition_bits: modes[1],
+        rotation_bits: modes[2],
+        index_selection_bits: modes[3],
+        color_bits: modes[4],
+        alpha_bits: modes[5],
+        endpoint_p_bits: modes[6] != 0,
+        shared_p_bits: modes[7] != 0,
+        index_bits: modes[8],
+        secondary_index_bits: modes[9],
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode([3, 4, 0, 0, 4, 0, 1, 0, 3, 0]),
+    bc7_mode([2, 6, 0, 0, 6, 0, 0, 1, 3, 0]),
+    bc7_mode([3, 6, 0, 0, 5, 0, 0, 0, 2, 0]),
+    bc7_mode([2, 6, 0, 0, 7, 0, 1, 0, 2, 0]),
+    bc7_mode([1, 0, 2, 1, 5, 6, 0, 0, 2, 3]),
+    bc7_mode([1, 0, 2, 0, 7, 8, 0, 0, 2, 2]),
+    bc7_mode([1, 0, 0, 0, 7, 7, 1, 0, 4, 0]),
+    bc7_mode([2, 6, 0, 0, 5, 5, 1, 0, 2, 0]),
+];
+
+// Endpoint fields of the BC6H layouts, w and x are the endpoints of the first region and y and z of the second
+const RW: u8 = 0;
+const GW: u8 = 1;
+const BW: u8 = 2;
+const RX: u8 = 3;
+const GX: u8 = 4;
+const BX: u8 = 5;
+const RY: u8 = 6;
+const GY: u8 = 7;
+const BY: u8 = 8;
+const RZ: u8 = 9;
+const GZ: u8 = 10;
+const BZ: u8 = 11;
+/// Partition
+const D: u8 = 12;
+
+/// Layout of a BC6H mode, the field names follow the BC6H specification
+struct Bc6hMode {
+    mode_bits: u32,
+    /// Endpoints other than the first are stored as deltas to it
+    transformed: bool,
+    /// Two regions with 3 bit indices instead of one with 4 bit indices
+    partitioned: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    /// Header fields in stream order as `(field, from, to)`, the first bit read is bit `to` of the field and the
+    /// following ones walk towards bit `from`
+    fields: &'static [(u8, u8, u8)],
+}
+
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode {
+        mode_bits: 0,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 10,
+        delta_bits: [5, 5, 5],
+        fields: &[
+            (GY, 4, 4), (BY, 4, 4), (BZ, 4, 4), (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 4, 0), (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0),
+            (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode_bits: 1,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 7,
+        delta_bits: [6, 6, 6],
+        fields: &[
+            (GY, 5, 5), (GZ, 4, 4), (GZ, 5, 5), (RW, 6, 0), (BZ, 0, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 6, 0), (BY, 5, 5), (BZ, 2, 2),
+            (GY, 4, 4), (BW, 6, 0), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 5, 0), (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 5, 0),
+            (BY, 3, 0), (RY, 5, 0), (RZ, 5, 0), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode_bits: 2,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 11,
+        delta_bits: [5, 4, 4],
+        fields: &[
+            (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 4, 0), (RW, 10, 10), (GY, 3, 0), (GX, 3, 0), (GW, 10, 10), (BZ, 0, 0),
+            (GZ, 3, 0), (BX, 3, 0), (BW, 10, 10), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode_bits: 6,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 5, 4],
+        fields: &[
+            (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 10), (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0), (GW, 10, 10),
+            (GZ, 3, 0), (BX, 3, 0), (BW, 10, 10), (BZ, 1, 1), (BY, 3, 0), (RY, 3, 0), (BZ, 0, 0), (BZ, 2, 2), (RZ, 3, 0), (GY, 4, 4),
+            (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode_bits: 10,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 4, 5],
+        fields: &[
+            (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 10), (BY, 4, 4), (GY, 3, 0), (GX, 3, 0), (GW, 10, 10),
+            (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BW, 10, 10), (BY, 3, 0), (RY, 3, 0), (BZ, 1, 1), (BZ, 2, 2), (RZ, 3, 0), (BZ, 4, 4),
+            (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode_bits: 14,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 9,
+        delta_bits: [5, 5, 5],
+        fields: &[
+            (RW, 8, 0), (BY, 4, 4), (GW, 8, 0), (GY, 4, 4), (BW, 8, 0), (BZ, 4, 4), (RX, 4, 0), (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0),
+            (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode_bits: 18,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 8,
+        delta_bits: [6, 5, 5],
+        fields: &[
+            (RW, 7, 0), (GZ, 4, 4), (BY, 4, 4), (GW, 7, 0), (BZ, 2, 2), (GY, 4, 4), (BW, 7, 0), (BZ, 3, 3), (BZ, 4, 4), (RX, 5, 0),
+            (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1), (BY, 3, 0), (RY, 5, 0), (RZ, 5, 0), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode_bits: 22,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 6, 5],
+        fields: &[
+            (RW, 7, 0), (BZ, 0, 0), (BY, 4, 4), (GW, 7, 0), (GY, 5, 5), (GY, 4, 4), (BW, 7, 0), (GZ, 5, 5), (BZ, 4, 4), (RX, 4, 0),
+            (GZ, 4, 4), (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0),
+            (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode_bits: 26,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 5, 6],
+        fields: &[
+            (RW, 7, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 7, 0), (BY, 5, 5), (GY, 4, 4), (BW, 7, 0), (BZ, 5, 5), (BZ, 4, 4), (RX, 4, 0),
+            (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 5, 0), (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0),
+            (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode_bits: 30,
+        transformed: false,
+        partitioned: true,
+        endpoint_bits: 6,
+        delta_bits: [6, 6, 6],
+        fields: &[
+            (RW, 5, 0), (GZ, 4, 4), (BZ, 0, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 5, 0), (GY, 5, 5), (BY, 5, 5), (BZ, 2, 2), (GY, 4, 4),
+            (BW, 5, 0), (GZ, 5, 5), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 5, 0), (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 5, 0),
+            (BY, 3, 0), (RY, 5, 0), (RZ, 5, 0), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode_bits: 3,
+        transformed: false,
+        partitioned: false,
+        endpoint_bits: 10,
+        delta_bits: [10, 10, 10],
+        fields: &[(RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 9, 0), (GX, 9, 0), (BX, 9, 0)],
+    },
+    Bc6hMode {
+        mode_bits: 7,
+        transformed: true,
+        partitioned: false,
+        endpoint_bits: 11,
+        delta_bits: [9, 9, 9],
+        fields: &[(RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 8, 0), (RW, 10, 10), (GX, 8, 0), (GW, 10, 10), (BX, 8, 0), (BW, 10, 10)],
+    },
+    Bc6hMode {
+        mode_bits: 11,
+        transformed: true,
+        partitioned: false,
+        endpoint_bits: 12,
+        delta_bits: [8, 8, 8],
+        fields: &[(RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 7, 0), (RW, 10, 11), (GX, 7, 0), (GW, 10, 11), (BX, 7, 0), (BW, 10, 11)],
+    },
+    Bc6hMode {
+        mode_bits: 15,
+        transformed: true,
+        partitioned: false,
+        endpoint_bits: 16,
+        delta_bits: [4, 4, 4],
+        fields: &[(RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 15), (GX, 3, 0), (GW, 10, 15), (BX, 3, 0), (BW, 10, 15)],
+    },
+];
+
+/// 1.0 as a half float, the alpha of every BC6H texel
+const HALF_ONE: u16 = 0x3c00;
+
+/// Reads a 128 bit block from the least significant bit up
+struct BlockBits {
+    bits: u128,
+}
+
+impl BlockBits {
+    fn new(block: &[u8]) -> Self {
+        Self {
+            bits: u128::from_le_bytes(block.try_into().unwrap()),
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.bits & ((1u128 << count) - 1)) as u32;
+        self.bits >>= count;
+        value
+    }
+}
+
+fn expand_565(color: u16) -> [u8; 4] {
+    let r = ((color >> 11) & 0x1f) as u8;
+    let g = ((color >> 5) & 0x3f) as u8;
+    let b = (color & 0x1f) as u8;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 255]
+}
+
+/// BC1 color block, `four_color` forces the opaque mode BC2 and BC3 use for their color part
+fn decode_color_block(block: &[u8], four_color: bool, texels: &mut Texels) {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let c0 = expand_565(color0);
+    let c1 = expand_565(color1);
+    let mut palette = [c0, c1, [0; 4], [0; 4]];
+    for channel in 0..3 {
+        let (a, b) = (c0[channel] as u32, c1[channel] as u32);
+        if four_color || color0 > color1 {
+            palette[2][channel] = ((2 * a + b) / 3) as u8;
+            palette[3][channel] = ((a + 2 * b) / 3) as u8;
+        } else {
+            palette[2][channel] = ((a + b) / 2) as u8;
+        }
+    }
+    palette[2][3] = 255;
+    // Transparent black only exists in the three color mode
+    palette[3][3] = if four_color || color0 > color1 { 255 } else { 0 };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (pixel, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (2 * pixel)) & 3) as usize];
+    }
+}
+
+/// BC3/BC4 style interpolated single channel block
+fn decode_channel_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a0 + i as u32 * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a0 + i as u32 * a1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bytes = [0u8; 8];
+    bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bytes);
+    std::array::from_fn(|pixel| palette[((indices >> (3 * pixel)) & 7) as usize])
+}
+
+fn bc7_subset(mode: &Bc7Mode, partition: usize, pixel: usize) -> usize {
+    match mode.subsets {
+        2 => ((PARTITIONS_2[partition] >> pixel) & 1) as usize,
+        3 => ((PARTITIONS_3[partition] >> (2 * pixel)) & 3) as usize,
+        _ => 0,
+    }
+}
+
+fn bc7_is_anchor(mode: &Bc7Mode, partition: usize, pixel: usize) -> bool {
+    pixel == 0
+        || match mode.subsets {
+            2 => pixel == ANCHORS_2[partition] as usize,
+            3 => pixel == ANCHORS_3_SECOND[partition] as usize || pixel == ANCHORS_3_THIRD[partition] as usize,
+            _ => false,
+        }
+}
+
+/// Expands an endpoint with `bits` precision (p-bit included) to 8 bits
+fn bc7_unquantize(value: u32, bits: u32) -> u8 {
+    let value = value << (8 - bits);
+    (value | (value >> bits)) as u8
+}
+
+fn bc7_interpolate(e0: u8, e1: u8, index: u32, index_bits: u32) -> u8 {
+    let weight = match index_bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    };
+    (((64 - weight) * e0 as u32 + weight * e1 as u32 + 32) >> 6) as u8
+}
+
+fn decode_bc7_block(block: &[u8], texels: &mut Texels) {
+    let mut bits = BlockBits::new(block);
+    let Some(mode_index) = (0..8).find(|&mode| block[0] & (1 << mode) != 0) else {
+        // Reserved mode, decodes to transparent black
+        *texels = [[0; 4]; 16];
+        return;
+    };
+    bits.read(mode_index + 1);
+    let mode = &BC7_MODES[mode_index as usize];
+
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = bits.read(mode.color_bits);
+        }
+    }
+    if mode.alpha_bits > 0 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[3] = bits.read(mode.alpha_bits);
+        }
+    }
+
+    let mut p_bits = [None; 6];
+    if mode.endpoint_p_bits {
+        for p_bit in p_bits.iter_mut().take(endpoint_count) {
+            *p_bit = Some(bits.read(1));
+        }
+    } else if mode.shared_p_bits {
+        for subset in 0..mode.subsets {
+            let p_bit = bits.read(1);
+            p_bits[subset * 2] = Some(p_bit);
+            p_bits[subset * 2 + 1] = Some(p_bit);
+        }
+    }
+
+    let mut colors = [[255u8; 4]; 6];
+    for endpoint in 0..endpoint_count {
+        let (p_bit, extra) = match p_bits[endpoint] {
+            Some(p_bit) => (p_bit, 1),
+            None => (0, 0),
+        };
+        for channel in 0..3 {
+            let value = (endpoints[endpoint][channel] << extra) | p_bit;
+            colors[endpoint][channel] = bc7_unquantize(value, mode.color_bits + extra);
+        }
+        if mode.alpha_bits > 0 {
+            let value = (endpoints[endpoint][3] << extra) | p_bit;
+            colors[endpoint][3] = bc7_unquantize(value, mode.alpha_bits + extra);
+        }
+    }
+
+    // Anchor pixels store their index with one bit less, the top bit is implicitly zero
+    let mut indices = [0u32; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        let anchor = bc7_is_anchor(mode, partition, pixel);
+        *index = bits.read(mode.index_bits - anchor as u32);
+    }
+    let mut secondary_indices = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (pixel, index) in secondary_indices.iter_mut().enumerate() {
+            *index = bits.read(mode.secondary_index_bits - (pixel == 0) as u32);
+        }
+    }
+
+    for (pixel, texel) in texels.iter_mut().enumerate() {
+        let subset = bc7_subset(mode, partition, pixel);
+        let (e0, e1) = (colors[subset * 2], colors[subset * 2 + 1]);
+        let (color_index, color_bits, alpha_index, alpha_bits) = if mode.secondary_index_bits == 0 {
+            (indices[pixel], mode.index_bits, indices[pixel], mode.index_bits)
+        } else if index_selection == 0 {
+            (indices[pixel], mode.index_bits, secondary_indices[pixel], mode.secondary_index_bits)
+        } else {
+            (secondary_indices[pixel], mode.secondary_index_bits, indices[pixel], mode.index_bits)
+        };
+        for channel in 0..3 {
+            texel[channel] = bc7_interpolate(e0[channel], e1[channel], color_index, color_bits);
+        }
+        texel[3] = bc7_interpolate(e0[3], e1[3], alpha_index, alpha_bits);
+        match rotation {
+            1 => texel.swap(0, 3),
+            2 => texel.swap(1, 3),
+            3 => texel.swap(2, 3),
+            _ => {}
+        }
+    }
+}
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    (value << (32 - bits)) >> (32 - bits)
+}
+
+/// Expands an endpoint with `bits` precision to the 16 bit range interpolation works in
+fn bc6h_unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if signed {
+        if bits >= 16 {
+            return value;
+        }
+        let magnitude = match value.abs() {
+            0 => 0,
+            magnitude if magnitude >= (1 << (bits - 1)) - 1 => 0x7fff,
+            magnitude => ((magnitude << 15) + 0x4000) >> (bits - 1),
+        };
+        magnitude * value.signum()
+    } else if bits >= 15 {
+        value
+    } else if value == 0 {
+        0
+    } else if value == (1 << bits) - 1 {
+        0xffff
+    } else {
+        ((value << 16) + 0x8000) >> bits
+    }
+}
+
+/// Scales an interpolated value to the half float range, the result are the bits of the half float
+fn bc6h_finish(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        0x8000 | ((-value * 31) >> 5) as u16
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+fn decode_bc6h_block(block: &[u8], signed: bool) -> [[u16; 4]; 16] {
+    let mut bits = BlockBits::new(block);
+    let mut mode_bits = bits.read(2);
+    if mode_bits > 1 {
+        mode_bits |= bits.read(3) << 2;
+    }
+    let Some(mode) = BC6H_MODES.iter().find(|mode| mode.mode_bits == mode_bits) else {
+        // Reserved mode, decodes to black
+        return [[0, 0, 0, HALF_ONE]; 16];
+    };
+
+    let mut fields = [0i32; 13];
+    for &(field, from, to) in mode.fields {
+        for step in 0..=from.abs_diff(to) {
+            let bit = if from >= to { to + step } else { to - step };
+            fields[field as usize] |= (bits.read(1) as i32) << bit;
+        }
+    }
+
+    let endpoint_count = if mode.partitioned { 4 } else { 2 };
+    let mut endpoints = [[0i32; 3]; 4];
+    for channel in 0..3 {
+        let base = fields[channel];
+        endpoints[0][channel] = if signed { sign_extend(base, mode.endpoint_bits) } else { base };
+        for endpoint in 1..endpoint_count {
+            let mut value = fields[endpoint * 3 + channel];
+            if signed || mode.transformed {
+                value = sign_extend(value, mode.delta_bits[channel]);
+            }
+            if mode.transformed {
+                value = (base + value) & ((1 << mode.endpoint_bits) - 1);
+                if signed {
+                    value = sign_extend(value, mode.endpoint_bits);
+                }
+            }
+            endpoints[endpoint][channel] = value;
+        }
+    }
+    for value in endpoints.iter_mut().take(endpoint_count).flatten() {
+        *value = bc6h_unquantize(*value, mode.endpoint_bits, signed);
+    }
+
+    let partition = fields[D as usize] as usize;
+    let mut texels = [[0, 0, 0, HALF_ONE]; 16];
+    for (pixel, texel) in texels.iter_mut().enumerate() {
+        let (subset, anchor, index_bits) = if mode.partitioned {
+            let subset = ((PARTITIONS_2[partition] >> pixel) & 1) as usize;
+            (subset, pixel == 0 || pixel == ANCHORS_2[partition] as usize, 3)
+        } else {
+            (0, pixel == 0, 4)
+        };
+        let index = bits.read(index_bits - anchor as u32) as usize;
+        let weight = if mode.partitioned { WEIGHTS_3[index] } else { WEIGHTS_4[index] } as i32;
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        for channel in 0..3 {
+            let value = ((64 - weight) * e0[channel] + weight * e1[channel] + 32) >> 6;
+            texel[channel] = bc6h_finish(value, signed);
+        }
+    }
+    texels
+}
+
+fn decode_bc_block(format: vk::Format, block: &[u8], texels: &mut Texels) {
+    match format {
+        vk::Format::BC1_RGB_UNORM_BLOCK | vk::Format::BC1_RGB_SRGB_BLOCK => {
+            decode_color_block(block, false, texels);
+            // Without alpha the transparent black of the three color mode is plain black
+            texels.iter_mut().for_each(|texel| texel[3] = 255);
+        }
+        vk::Format::BC1_RGBA_UNORM_BLOCK | vk::Format::BC1_RGBA_SRGB_BLOCK => decode_color_block(block, false, texels),
+        vk::Format::BC2_UNORM_BLOCK | vk::Format::BC2_SRGB_BLOCK => {
+            decode_color_block(&block[8..], true, texels);
+            for (pixel, texel) in texels.iter_mut().enumerate() {
+                let alpha = (block[pixel / 2] >> (4 * (pixel % 2))) & 0xf;
+                texel[3] = alpha * 17;
+            }
+        }
+        vk::Format::BC3_UNORM_BLOCK | vk::Format::BC3_SRGB_BLOCK => {
+            decode_color_block(&block[8..], true, texels);
+            let alpha = decode_channel_block(&block[..8]);
+            texels.iter_mut().zip(alpha).for_each(|(texel, alpha)| texel[3] = alpha);
+        }
+        vk::Format::BC4_UNORM_BLOCK => {
+            let red = decode_channel_block(block);
+            texels.iter_mut().zip(red).for_each(|(texel, red)| *texel = [red, 0, 0, 255]);
+        }
+        vk::Format::BC5_UNORM_BLOCK => {
+            let red = decode_channel_block(&block[..8]);
+            let green = decode_channel_block(&block[8..]);
+            for (pixel, texel) in texels.iter_mut().enumerate() {
+                *texel = [red[pixel], green[pixel], 0, 255];
+            }
+        }
+        vk::Format::BC7_UNORM_BLOCK | vk::Format::BC7_SRGB_BLOCK => decode_bc7_block(block, texels),
+        _ => unreachable!("No CPU decoder for {:?}", format),
+    }
+}
+
+/// Decodes one `width` x `height` block to texels of the format `decompressed_format` reports, in row order
+fn decode_block(format: vk::Format, block: &[u8], width: usize, height: usize, texels: &mut [u8]) {
+    if let Some(srgb) = astc::is_srgb(format) {
+        // Large enough for the 12x12 blocks
+        let mut rgba = [[0u8; 4]; 144];
+        let rgba = &mut rgba[..width * height];
+        astc::decode_block(block, width, height, srgb, rgba);
+        texels.copy_from_slice(rgba.as_flattened());
+    } else if matches!(format, vk::Format::BC6H_UFLOAT_BLOCK | vk::Format::BC6H_SFLOAT_BLOCK) {
+        let rgba = decode_bc6h_block(block, format == vk::Format::BC6H_SFLOAT_BLOCK);
+        for (bytes, value) in texels.chunks_exact_mut(2).zip(rgba.as_flattened()) {
+            bytes.copy_from_slice(&value.to_le_bytes());
+        }
+    } else {
+        let mut rgba = [[0u8; 4]; 16];
+        decode_bc_block(format, block, &mut rgba);
+        texels.copy_from_slice(rgba.as_flattened());
+    }
+}
+
+/// Uncompressed format `decompress` produces for `format`, `None` if there is no CPU decoder for it.
+/// Single and two channel formats decode to RGBA with the missing channels set like a sampler would return them.
+/// BC6H decodes to half floats, ASTC only supports the LDR profile and decodes HDR blocks to magenta.
+pub fn decompressed_format(format: vk::Format) -> Option<vk::Format> {
+    match format {
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC2_UNORM_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC4_UNORM_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK => Some(vk::Format::R8G8B8A8_UNORM),
+        vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC2_SRGB_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK => Some(vk::Format::R8G8B8A8_SRGB),
+        vk::Format::BC6H_UFLOAT_BLOCK | vk::Format::BC6H_SFLOAT_BLOCK => Some(vk::Format::R16G16B16A16_SFLOAT),
+        _ => match astc::is_srgb(format)? {
+            false => Some(vk::Format::R8G8B8A8_UNORM),
+            true => Some(vk::Format::R8G8B8A8_SRGB),
+        },
+    }
+}
+
+/// Decodes one image (all depth slices of one layer and mip level) to tightly packed texels of `decompressed_format`
+pub fn decompress(format: vk::Format, extent: vk::Extent3D, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let output = decompressed_format(format).ok_or(Error::UnsupportedFormat(format))?;
+    let block = format_block(format).unwrap();
+    let texel_bytes = format_block(output).unwrap().bytes as usize;
+    let (width, height) = (extent.width as usize, extent.height as usize);
+    let (block_width, block_height) = (block.width as usize, block.height as usize);
+    let blocks_x = width.div_ceil(block_width);
+    let blocks_y = height.div_ceil(block_height);
+    let block_bytes = block.bytes as usize;
+    let slice_size = blocks_x * blocks_y * block_bytes;
+    if data.len() != slice_size * extent.depth as usize {
+        return Err(Error::InvalidData("Compressed data does not match the image size"));
+    }
+
+    let row_bytes = width * texel_bytes;
+    let mut pixels = vec![0u8; row_bytes * height * extent.depth as usize];
+    let mut texels = vec![0u8; block_width * block_height * texel_bytes];
+    for (slice, slice_data) in data.chunks_exact(slice_size).enumerate() {
+        let slice_pixels = &mut pixels[slice * row_bytes * height..(slice + 1) * row_bytes * height];
+        for (block_index, block_data) in slice_data.chunks_exact(block_bytes).enumerate() {
+            decode_block(format, block_data, block_width, block_height, &mut texels);
+            let (block_x, block_y) = (block_index % blocks_x * block_width, block_index / blocks_x * block_height);
+            // Blocks on the right and bottom edge can hang over the image
+            let copy_bytes = block_width.min(width - block_x) * texel_bytes;
+            for y in 0..block_height.min(height - block_y) {
+                let offset = (block_y + y) * row_bytes + block_x * texel_bytes;
+                let block_row = &texels[y * block_width * texel_bytes..];
+                slice_pixels[offset..offset + copy_bytes].copy_from_slice(&block_row[..copy_bytes]);
+            }
+        }
+    }
+    Ok(pixels)
+}
@@ -0,0 +1,178 @@
+//! DDS parser for the legacy header and the DX10 extension header
+
+use crate::backend::vulkan::image::{full_mip_level_count, ImageKind};
+use crate::texture::{level_size, Error, TextureData};
+use ash::vk;
+
+const HEADER_SIZE: usize = 124;
+const DX10_HEADER_SIZE: usize = 20;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_DEPTH: u32 = 0x800000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Error::InvalidData("Truncated DDS header"))
+}
+
+fn four_cc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+/// Vulkan equivalent of a DXGI_FORMAT
+pub fn dxgi_format(dxgi_format: u32) -> Option<vk::Format> {
+    let format = match dxgi_format {
+        2 => vk::Format::R32G32B32A32_SFLOAT,
+        10 => vk::Format::R16G16B16A16_SFLOAT,
+        28 => vk::Format::R8G8B8A8_UNORM,
+        29 => vk::Format::R8G8B8A8_SRGB,
+        34 => vk::Format::R16G16_SFLOAT,
+        41 => vk::Format::R32_SFLOAT,
+        49 => vk::Format::R8G8_UNORM,
+        54 => vk::Format::R16_SFLOAT,
+        61 => vk::Format::R8_UNORM,
+        71 => vk::Format::BC1_RGBA_UNORM_BLOCK,
+        72 => vk::Format::BC1_RGBA_SRGB_BLOCK,
+        74 => vk::Format::BC2_UNORM_BLOCK,
+        75 => vk::Format::BC2_SRGB_BLOCK,
+        77 => vk::Format::BC3_UNORM_BLOCK,
+        78 => vk::Format::BC3_SRGB_BLOCK,
+        80 => vk::Format::BC4_UNORM_BLOCK,
+        81 => vk::Format::BC4_SNORM_BLOCK,
+        83 => vk::Format::BC5_UNORM_BLOCK,
+        84 => vk::Format::BC5_SNORM_BLOCK,
+        87 => vk::Format::B8G8R8A8_UNORM,
+        91 => vk::Format::B8G8R8A8_SRGB,
+        95 => vk::Format::BC6H_UFLOAT_BLOCK,
+        96 => vk::Format::BC6H_SFLOAT_BLOCK,
+        98 => vk::Format::BC7_UNORM_BLOCK,
+        99 => vk::Format::BC7_SRGB_BLOCK,
+        _ => return None,
+    };
+    Some(format)
+}
+
+/// Format of a file without DX10 header
+fn legacy_format(pixel_format_flags: u32, four_cc_code: u32, bit_count: u32, masks: [u32; 4]) -> Result<vk::Format, Error> {
+    if pixel_format_flags & DDPF_FOURCC != 0 {
+        let format = match four_cc_code {
+            code if code == four_cc(b"DXT1") => vk::Format::BC1_RGBA_UNORM_BLOCK,
+            code if code == four_cc(b"DXT2") || code == four_cc(b"DXT3") => vk::Format::BC2_UNORM_BLOCK,
+            code if code == four_cc(b"DXT4") || code == four_cc(b"DXT5") => vk::Format::BC3_UNORM_BLOCK,
+            code if code == four_cc(b"ATI1") || code == four_cc(b"BC4U") => vk::Format::BC4_UNORM_BLOCK,
+            code if code == four_cc(b"BC4S") => vk::Format::BC4_SNORM_BLOCK,
+            code if code == four_cc(b"ATI2") || code == four_cc(b"BC5U") => vk::Format::BC5_UNORM_BLOCK,
+            code if code == four_cc(b"BC5S") => vk::Format::BC5_SNORM_BLOCK,
+            // D3DFMT_A16B16G16R16F and D3DFMT_A32B32G32R32F
+            113 => vk::Format::R16G16B16A16_SFLOAT,
+            116 => vk::Format::R32G32B32A32_SFLOAT,
+            _ => return Err(Error::Unsupported("DDS FourCC code")),
+        };
+        return Ok(format);
+    }
+    if pixel_format_flags & DDPF_RGB != 0 && bit_count == 32 {
+        match masks {
+            [0xff, 0xff00, 0xff0000, 0xff000000] => return Ok(vk::Format::R8G8B8A8_UNORM),
+            [0xff0000, 0xff00, 0xff, 0xff000000] => return Ok(vk::Format::B8G8R8A8_UNORM),
+            _ => {}
+        }
+    }
+    Err(Error::Unsupported("DDS pixel format"))
+}
+
+pub fn parse_dds(bytes: &[u8]) -> Result<TextureData, Error> {
+    if !bytes.starts_with(b"DDS ") {
+        return Err(Error::InvalidData("Missing DDS magic"));
+    }
+    let header = 4;
+    if read_u32(bytes, header)? as usize != HEADER_SIZE {
+        return Err(Error::InvalidData("Unexpected DDS header size"));
+    }
+    let flags = read_u32(bytes, header + 4)?;
+    let height = read_u32(bytes, header + 8)?;
+    let width = read_u32(bytes, header + 12)?;
+    let depth = read_u32(bytes, header + 20)?;
+    let mip_count = read_u32(bytes, header + 24)?;
+    let pixel_format_flags = read_u32(bytes, header + 76)?;
+    let four_cc_code = read_u32(bytes, header + 80)?;
+    let bit_count = read_u32(bytes, header + 84)?;
+    let masks = [
+        read_u32(bytes, header + 88)?,
+        read_u32(bytes, header + 92)?,
+        read_u32(bytes, header + 96)?,
+        read_u32(bytes, header + 100)?,
+    ];
+    let caps2 = read_u32(bytes, header + 108)?;
+    let mut data_offset = header + HEADER_SIZE;
+
+    let level_count = if flags & DDSD_MIPMAPCOUNT != 0 { mip_count.max(1) } else { 1 };
+    let (format, kind, layers, depth) = if pixel_format_flags & DDPF_FOURCC != 0 && four_cc_code == four_cc(b"DX10") {
+        let dx10 = data_offset;
+        data_offset += DX10_HEADER_SIZE;
+        let format = dxgi_format(read_u32(bytes, dx10)?).ok_or(Error::Unsupported("DDS DXGI format"))?;
+        let dimension = read_u32(bytes, dx10 + 4)?;
+        let misc_flags = read_u32(bytes, dx10 + 8)?;
+        let array_size = read_u32(bytes, dx10 + 12)?.max(1);
+        if dimension == RESOURCE_DIMENSION_TEXTURE3D {
+            (format, ImageKind::D3, 1, depth.max(1))
+        } else if misc_flags & RESOURCE_MISC_TEXTURECUBE != 0 {
+            if array_size > 1 {
+                return Err(Error::Unsupported("DDS cube map arrays"));
+            }
+            (format, ImageKind::Cube, 6, 1)
+        } else if array_size > 1 {
+            (format, ImageKind::D2Array, array_size, 1)
+        } else {
+            (format, ImageKind::D2, 1, 1)
+        }
+    } else {
+        let format = legacy_format(pixel_format_flags, four_cc_code, bit_count, masks)?;
+        if caps2 & DDSCAPS2_CUBEMAP != 0 {
+            // Legacy cube maps can leave out faces, only complete ones can be uploaded
+            if caps2 & 0xfc00 != 0xfc00 {
+                return Err(Error::Unsupported("DDS cube map with missing faces"));
+            }
+            (format, ImageKind::Cube, 6, 1)
+        } else if caps2 & DDSCAPS2_VOLUME != 0 && flags & DDSD_DEPTH != 0 {
+            (format, ImageKind::D3, 1, depth.max(1))
+        } else {
+            (format, ImageKind::D2, 1, 1)
+        }
+    };
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidData("DDS with zero size"));
+    }
+    let extent = vk::Extent3D { width, height, depth };
+    if level_count > full_mip_level_count(extent) {
+        return Err(Error::InvalidData("DDS mip count exceeds the full mip chain"));
+    }
+
+    let mut data = TextureData {
+        format,
+        kind,
+        extent,
+        array_layers: layers,
+        levels: vec![Vec::new(); level_count as usize],
+    };
+    // DDS stores every layer with its full mip chain, regroup to all layers of one level
+    let mut offset = data_offset;
+    for _ in 0..layers {
+        for level in 0..level_count {
+            let size = level_size(format, data.level_extent(level)).ok_or(Error::UnsupportedFormat(format))?;
+            let level_data = bytes
+                .get(offset..offset + size)
+                .ok_or(Error::InvalidData("Truncated DDS image data"))?;
+            data.levels[level as usize].extend_from_slice(level_data);
+            offset += size;
+        }
+    }
+    Ok(data)
+}
@@ -0,0 +1,447 @@
+use crate::image::RgbaImage;
+use crate::texture::Error;
+use std::sync::OnceLock;
+
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57,
+    50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// Canonical Huffman table, decoded bit by bit against the largest code of every length
+#[derive(Clone)]
+struct HuffmanTable {
+    /// Largest code of each length 1..=16, -1 if there is none
+    max_code: [i32; 17],
+    /// Index into `values` of the first code of each length, minus that code
+    value_offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl Default for HuffmanTable {
+    /// Table the file never defined, it has no codes so decoding with it fails
+    fn default() -> Self {
+        Self::new(&[0; 16], Vec::new())
+    }
+}
+
+impl HuffmanTable {
+    fn new(counts: &[u8; 16], values: Vec<u8>) -> Self {
+        let mut table = Self {
+            max_code: [-1; 17],
+            value_offset: [0; 17],
+            values,
+        };
+        let mut code = 0i32;
+        let mut index = 0i32;
+        for length in 1..=16 {
+            let count = counts[length - 1] as i32;
+            if count > 0 {
+                table.value_offset[length] = index - code;
+                code += count;
+                index += count;
+                table.max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        table
+    }
+
+    fn is_defined(&self) -> bool {
+        !self.values.is_empty()
+    }
+}
+
+struct Component {
+    id: u8,
+    horizontal: usize,
+    vertical: usize,
+    quantization_table: usize,
+    dc_table: usize,
+    ac_table: usize,
+    dc_prediction: i32,
+    /// Samples covering all MCUs, `stride` wide
+    samples: Vec<u8>,
+    stride: usize,
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bits: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        Self {
+            data,
+            position,
+            bits: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn bit(&mut self) -> Result<u32, Error> {
+        if self.bit_count == 0 {
+            let byte = *self.data.get(self.position).ok_or(Error::InvalidData("Truncated JPEG scan"))?;
+            self.position += 1;
+            if byte == 0xff {
+                match self.data.get(self.position) {
+                    // Stuffed zero byte
+                    Some(0x00) => self.position += 1,
+                    _ => return Err(Error::InvalidData("Unexpected marker in JPEG scan")),
+                }
+            }
+            self.bits = byte as u32;
+            self.bit_count = 8;
+        }
+        self.bit_count -= 1;
+        Ok((self.bits >> self.bit_count) & 1)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, Error> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.bit()?;
+        }
+        Ok(value)
+    }
+
+    fn decode(&mut self, table: &HuffmanTable) -> Result<u8, Error> {
+        let mut code = 0i32;
+        for length in 1..=16 {
+            code = (code << 1) | self.bit()? as i32;
+            if code <= table.max_code[length] {
+                return table
+                    .values
+                    .get((code + table.value_offset[length]) as usize)
+                    .copied()
+                    .ok_or(Error::InvalidData("Invalid JPEG Huffman code"));
+            }
+        }
+        Err(Error::InvalidData("Invalid JPEG Huffman code"))
+    }
+
+    /// Skips to the next restart marker, dropping the remaining bits of the current byte
+    fn restart(&mut self) -> Result<(), Error> {
+        self.bit_count = 0;
+        match self.data.get(self.position..self.position + 2) {
+            Some([0xff, marker]) if (0xd0..=0xd7).contains(marker) => {
+                self.position += 2;
+                Ok(())
+            }
+            _ => Err(Error::InvalidData("Missing JPEG restart marker")),
+        }
+    }
+}
+
+/// Sign extends a `size` bit magnitude category value, `size` is at most 15
+fn extend(value: u32, size: u32) -> i32 {
+    if size == 0 {
+        0
+    } else if value < (1 << (size - 1)) {
+        value as i32 - (1 << size) + 1
+    } else {
+        value as i32
+    }
+}
+
+/// `cosines[x][u]` of the 8 point IDCT, the DC term includes its 1/sqrt(2) scale
+fn idct_cosines() -> &'static [[f32; 8]; 8] {
+    static COSINES: OnceLock<[[f32; 8]; 8]> = OnceLock::new();
+    COSINES.get_or_init(|| {
+        let mut cosines = [[0f32; 8]; 8];
+        for (x, row) in cosines.iter_mut().enumerate() {
+            for (u, cosine) in row.iter_mut().enumerate() {
+                let scale = if u == 0 { std::f32::consts::FRAC_1_SQRT_2 } else { 1.0 };
+                *cosine = scale * (((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI) / 16.0).cos();
+            }
+        }
+        cosines
+    })
+}
+
+fn idct_block(coefficients: &[i32; 64], output: &mut [u8], stride: usize) {
+    let cosines = idct_cosines();
+
+    // Rows, then columns
+    let mut temporary = [0f32; 64];
+    for y in 0..8 {
+        for x in 0..8 {
+            temporary[y * 8 + x] = (0..8).map(|u| cosines[x][u] * coefficients[y * 8 + u] as f32).sum::<f32>() / 2.0;
+        }
+    }
+    for x in 0..8 {
+        for y in 0..8 {
+            let value = (0..8).map(|v| cosines[y][v] * temporary[v * 8 + x]).sum::<f32>() / 2.0;
+            output[y * stride + x] = (value + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+fn read_u16(data: &[u8], position: usize) -> Result<usize, Error> {
+    match data.get(position..position + 2) {
+        Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]]) as usize),
+        None => Err(Error::InvalidData("Truncated JPEG segment")),
+    }
+}
+
+/// Decodes baseline and extended sequential Huffman JPEGs with 1 (grayscale) or 3 (YCbCr) components and any
+/// chroma subsampling. Progressive and arithmetic coded files are rejected.
+pub fn decode_jpeg(data: &[u8]) -> Result<RgbaImage, Error> {
+    if data.get(0..2) != Some(&[0xff, 0xd8]) {
+        return Err(Error::InvalidData("Missing JPEG start of image marker"));
+    }
+    let mut quantization_tables = [[0u16; 64]; 4];
+    let mut dc_tables: [HuffmanTable; 4] = Default::default();
+    let mut ac_tables: [HuffmanTable; 4] = Default::default();
+    let mut components: Vec<Component> = Vec::new();
+    let (mut width, mut height) = (0usize, 0usize);
+    let mut restart_interval = 0usize;
+    let mut position = 2;
+
+    loop {
+        // Fill bytes before markers are allowed
+        while data.get(position) == Some(&0xff) && data.get(position + 1) == Some(&0xff) {
+            position += 1;
+        }
+        let marker = match data.get(position..position + 2) {
+            Some([0xff, marker]) => *marker,
+            _ => return Err(Error::InvalidData("Expected a JPEG marker")),
+        };
+        position += 2;
+        if marker == 0xd9 {
+            return Err(Error::InvalidData("JPEG without a scan"));
+        }
+        let length = read_u16(data, position)?;
+        let segment = data
+            .get(position + 2..position + length)
+            .ok_or(Error::InvalidData("Truncated JPEG segment"))?;
+
+        match marker {
+            // DQT
+            0xdb => {
+                let mut offset = 0;
+                while offset < segment.len() {
+                    let precision = segment[offset] >> 4;
+                    let table = (segment[offset] & 0x0f) as usize;
+                    offset += 1;
+                    let size = if precision == 0 { 64 } else { 128 };
+                    let values = segment.get(offset..offset + size).ok_or(Error::InvalidData("Truncated JPEG quantization table"))?;
+                    let entry = quantization_tables.get_mut(table).ok_or(Error::InvalidData("Invalid JPEG table index"))?;
+                    for (index, value) in entry.iter_mut().enumerate() {
+                        *value = if precision == 0 {
+                            values[index] as u16
+                        } else {
+                            u16::from_be_bytes([values[index * 2], values[index * 2 + 1]])
+                        };
+                    }
+                    offset += size;
+                }
+            }
+            // DHT
+            0xc4 => {
+                let mut offset = 0;
+                while offset + 17 <= segment.len() {
+                    let class = segment[offset] >> 4;
+                    let table = (segment[offset] & 0x0f) as usize;
+                    let counts: [u8; 16] = segment[offset + 1..offset + 17].try_into().unwrap();
+                    let total: usize = counts.iter().map(|count| *count as usize).sum();
+                    let values = segment
+                        .get(offset + 17..offset + 17 + total)
+                        .ok_or(Error::InvalidData("Truncated JPEG Huffman table"))?;
+                    if table > 3 {
+                        return Err(Error::InvalidData("Invalid JPEG table index"));
+                    }
+                    let huffman = HuffmanTable::new(&counts, values.to_vec());
+                    if class == 0 {
+                        dc_tables[table] = huffman;
+                    } else {
+                        ac_tables[table] = huffman;
+                    }
+                    offset += 17 + total;
+                }
+            }
+            // SOF0 baseline, SOF1 extended sequential
+            0xc0 | 0xc1 => {
+                if segment.len() < 6 || segment[0] != 8 {
+                    return Err(Error::Unsupported("JPEG with a sample precision other than 8 bits"));
+                }
+                height = read_u16(segment, 1)?;
+                width = read_u16(segment, 3)?;
+                let count = segment[5] as usize;
+                if width == 0 || height == 0 || !(count == 1 || count == 3) || segment.len() < 6 + count * 3 {
+                    return Err(Error::Unsupported("JPEG component layout"));
+                }
+                for index in 0..count {
+                    let entry = &segment[6 + index * 3..9 + index * 3];
+                    let (horizontal, vertical) = ((entry[1] >> 4) as usize, (entry[1] & 0x0f) as usize);
+                    if !(1..=4).contains(&horizontal) || !(1..=4).contains(&vertical) || entry[2] > 3 {
+                        return Err(Error::InvalidData("Invalid JPEG component"));
+                    }
+                    components.push(Component {
+                        id: entry[0],
+                        horizontal,
+                        vertical,
+                        quantization_table: entry[2] as usize,
+                        dc_table: 0,
+                        ac_table: 0,
+                        dc_prediction: 0,
+                        samples: Vec::new(),
+                        stride: 0,
+                    });
+                }
+            }
+            0xc2 | 0xc3 | 0xc5..=0xc7 | 0xc9..=0xcb | 0xcd..=0xcf => return Err(Error::Unsupported("Progressive, lossless or arithmetic coded JPEG")),
+            // DRI
+            0xdd => restart_interval = read_u16(segment, 0)?,
+            // SOS
+            0xda => {
+                if components.is_empty() {
+                    return Err(Error::InvalidData("JPEG scan before the frame header"));
+                }
+                let count = *segment.first().ok_or(Error::InvalidData("Truncated JPEG scan header"))? as usize;
+                if count != components.len() {
+                    return Err(Error::Unsupported("Non interleaved JPEG scans"));
+                }
+                let selectors = segment
+                    .get(1..1 + count * 2)
+                    .ok_or(Error::InvalidData("Truncated JPEG scan header"))?;
+                for selector in selectors.chunks_exact(2) {
+                    let (id, tables) = (selector[0], selector[1]);
+                    let component = components
+                        .iter_mut()
+                        .find(|component| component.id == id)
+                        .ok_or(Error::InvalidData("JPEG scan references an unknown component"))?;
+                    component.dc_table = (tables >> 4) as usize;
+                    component.ac_table = (tables & 0x0f) as usize;
+                    if !dc_tables.get(component.dc_table).is_some_and(HuffmanTable::is_defined)
+                        || !ac_tables.get(component.ac_table).is_some_and(HuffmanTable::is_defined)
+                    {
+                        return Err(Error::InvalidData("JPEG scan references an undefined Huffman table"));
+                    }
+                }
+                decode_scan(
+                    data,
+                    position + length,
+                    &mut components,
+                    &quantization_tables,
+                    &dc_tables,
+                    &ac_tables,
+                    (width, height),
+                    restart_interval,
+                )?;
+                return Ok(to_rgba(&components, width, height));
+            }
+            // APPn, COM and everything else without meaning for the decoder
+            _ => {}
+        }
+        position += length;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn decode_scan(
+    data: &[u8],
+    position: usize,
+    components: &mut [Component],
+    quantization_tables: &[[u16; 64]; 4],
+    dc_tables: &[HuffmanTable; 4],
+    ac_tables: &[HuffmanTable; 4],
+    (width, height): (usize, usize),
+    restart_interval: usize,
+) -> Result<(), Error> {
+    let max_horizontal = components.iter().map(|component| component.horizontal).max().unwrap();
+    let max_vertical = components.iter().map(|component| component.vertical).max().unwrap();
+    let mcus_x = width.div_ceil(8 * max_horizontal);
+    let mcus_y = height.div_ceil(8 * max_vertical);
+    for component in components.iter_mut() {
+        component.stride = mcus_x * component.horizontal * 8;
+        component.samples = vec![0; component.stride * mcus_y * component.vertical * 8];
+    }
+
+    let mut reader = BitReader::new(data, position);
+    let mut coefficients = [0i32; 64];
+    for mcu in 0..mcus_x * mcus_y {
+        if restart_interval > 0 && mcu > 0 && mcu % restart_interval == 0 {
+            reader.restart()?;
+            for component in components.iter_mut() {
+                component.dc_prediction = 0;
+            }
+        }
+        let (mcu_x, mcu_y) = (mcu % mcus_x, mcu / mcus_x);
+        for component in components.iter_mut() {
+            let quantization = &quantization_tables[component.quantization_table];
+            for block_y in 0..component.vertical {
+                for block_x in 0..component.horizontal {
+                    coefficients.fill(0);
+                    let size = reader.decode(&dc_tables[component.dc_table])? as u32;
+                    if size > 15 {
+                        return Err(Error::InvalidData("JPEG DC difference out of range"));
+                    }
+                    component.dc_prediction = component.dc_prediction.wrapping_add(extend(reader.bits(size)?, size));
+                    coefficients[0] = component.dc_prediction.wrapping_mul(quantization[0] as i32);
+
+                    let mut index = 1;
+                    while index < 64 {
+                        let symbol = reader.decode(&ac_tables[component.ac_table])?;
+                        let (run, size) = ((symbol >> 4) as usize, (symbol & 0x0f) as u32);
+                        if size == 0 {
+                            if run == 15 {
+                                index += 16;
+                                continue;
+                            }
+                            // End of block
+                            break;
+                        }
+                        index += run;
+                        if index > 63 {
+                            return Err(Error::InvalidData("JPEG coefficient out of range"));
+                        }
+                        coefficients[ZIGZAG[index]] = extend(reader.bits(size)?, size).wrapping_mul(quantization[index] as i32);
+                        index += 1;
+                    }
+
+                    let x = (mcu_x * component.horizontal + block_x) * 8;
+                    let y = (mcu_y * component.vertical + block_y) * 8;
+                    let offset = y * component.stride + x;
+                    idct_block(&coefficients, &mut component.samples[offset..], component.stride);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Upsamples subsampled chroma by replication and converts YCbCr to RGB
+fn to_rgba(components: &[Component], width: usize, height: usize) -> RgbaImage {
+    let max_horizontal = components.iter().map(|component| component.horizontal).max().unwrap();
+    let max_vertical = components.iter().map(|component| component.vertical).max().unwrap();
+    let sample = |component: &Component, x: usize, y: usize| {
+        let x = x * component.horizontal / max_horizontal;
+        let y = y * component.vertical / max_vertical;
+        component.samples[y * component.stride + x] as f32
+    };
+
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            if components.len() == 1 {
+                let luma = sample(&components[0], x, y) as u8;
+                pixels.extend_from_slice(&[luma, luma, luma, 255]);
+                continue;
+            }
+            let luma = sample(&components[0], x, y);
+            let cb = sample(&components[1], x, y) - 128.0;
+            let cr = sample(&components[2], x, y) - 128.0;
+            let to_u8 = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+            pixels.extend_from_slice(&[
+                to_u8(luma + 1.402 * cr),
+                to_u8(luma - 0.344136 * cb - 0.714136 * cr),
+                to_u8(luma + 1.772 * cb),
+                255,
+            ]);
+        }
+    }
+    RgbaImage::from_pixels(width as u32, height as u32, pixels)
+}
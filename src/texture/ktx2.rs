@@ -0,0 +1,99 @@
+//! KTX2 container parser. Supercompressed (Basis Universal, zstd, zlib) files are not supported.
+
+use crate::backend::vulkan::image::{full_mip_level_count, ImageKind};
+use crate::texture::{level_size, Error, TextureData};
+use ash::vk;
+
+pub const IDENTIFIER: [u8; 12] = [0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n'];
+
+/// Identifier, 9 header fields, the data format descriptor/key value/supercompression global data index
+const HEADER_SIZE: usize = 12 + 9 * 4 + 4 * 4 + 2 * 8;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 3 * 8;
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Error::InvalidData("Truncated KTX2 header"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, Error> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Error::InvalidData("Truncated KTX2 header"))
+}
+
+pub fn parse_ktx2(bytes: &[u8]) -> Result<TextureData, Error> {
+    if !bytes.starts_with(&IDENTIFIER) {
+        return Err(Error::InvalidData("Missing KTX2 identifier"));
+    }
+    if bytes.len() < HEADER_SIZE {
+        return Err(Error::InvalidData("Truncated KTX2 header"));
+    }
+    let format = vk::Format::from_raw(read_u32(bytes, 12)? as i32);
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    let depth = read_u32(bytes, 28)?;
+    let layer_count = read_u32(bytes, 32)?;
+    let face_count = read_u32(bytes, 36)?;
+    let level_count = read_u32(bytes, 40)?;
+    let supercompression = read_u32(bytes, 44)?;
+
+    if format == vk::Format::UNDEFINED {
+        return Err(Error::Unsupported("KTX2 without a Vulkan format (Basis Universal)"));
+    }
+    if supercompression != 0 {
+        return Err(Error::Unsupported("Supercompressed KTX2"));
+    }
+    if width == 0 {
+        return Err(Error::InvalidData("KTX2 with zero width"));
+    }
+    if face_count != 1 && face_count != 6 {
+        return Err(Error::InvalidData("KTX2 face count has to be 1 or 6"));
+    }
+
+    let kind = match (face_count, depth, layer_count) {
+        (6, _, 0) => ImageKind::Cube,
+        (6, _, _) => return Err(Error::Unsupported("KTX2 cube map arrays")),
+        (_, 0, 0) => ImageKind::D2,
+        (_, 0, _) => ImageKind::D2Array,
+        (_, _, 0) => ImageKind::D3,
+        _ => return Err(Error::Unsupported("KTX2 3D texture arrays")),
+    };
+    let extent = vk::Extent3D {
+        width,
+        // 1D textures are loaded as 2D textures with a single row
+        height: height.max(1),
+        depth: depth.max(1),
+    };
+    let array_layers = layer_count.max(1) * face_count;
+    if level_count > full_mip_level_count(extent) {
+        return Err(Error::InvalidData("KTX2 level count exceeds the full mip chain"));
+    }
+
+    // A level count of zero asks the loader to generate the mip chain, only the base level is stored
+    let mut data = TextureData {
+        format,
+        kind,
+        extent,
+        array_layers,
+        levels: Vec::new(),
+    };
+    for level in 0..level_count.max(1) {
+        let entry = HEADER_SIZE + level as usize * LEVEL_INDEX_ENTRY_SIZE;
+        let offset = read_u64(bytes, entry)? as usize;
+        let length = read_u64(bytes, entry + 8)? as usize;
+        let expected = level_size(format, data.level_extent(level)).ok_or(Error::UnsupportedFormat(format))? * array_layers as usize;
+        if length != expected {
+            return Err(Error::InvalidData("KTX2 level size does not match its extent"));
+        }
+        let level_data = offset
+            .checked_add(length)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or(Error::InvalidData("KTX2 level outside of the file"))?;
+        // Levels store layers, then faces, then depth slices which is the layer order Vulkan uses
+        data.levels.push(level_data.to_vec());
+    }
+    Ok(data)
+}
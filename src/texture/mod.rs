@@ -0,0 +1,196 @@
+pub mod astc;
+pub mod bc;
+pub mod dds;
+pub mod jpeg;
+pub mod ktx2;
+
+use crate::backend::vulkan::image::{mip_extent, ImageKind};
+use crate::image::RgbaImage;
+use ash::vk;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// The file is damaged or not what its extension claims
+    InvalidData(&'static str),
+    /// Valid file using a feature the loaders don't implement
+    Unsupported(&'static str),
+    /// Pixel format without size information or CPU decoder
+    UnsupportedFormat(vk::Format),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "Failed to read texture: {}", error),
+            Error::InvalidData(reason) => write!(f, "Invalid texture data: {}", reason),
+            Error::Unsupported(feature) => write!(f, "Unsupported texture feature: {}", feature),
+            Error::UnsupportedFormat(format) => write!(f, "Unsupported texture format {:?}", format),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// Texel block of a format, 1x1 for uncompressed formats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatBlock {
+    pub width: u32,
+    pub height: u32,
+    pub bytes: u32,
+}
+
+/// Block size of the formats the loaders can produce, `None` for everything else
+pub fn format_block(format: vk::Format) -> Option<FormatBlock> {
+    let texel = |bytes| FormatBlock { width: 1, height: 1, bytes };
+    let block = |width, height, bytes| FormatBlock { width, height, bytes };
+    let block = match format {
+        vk::Format::R8_UNORM | vk::Format::R8_SRGB => texel(1),
+        vk::Format::R8G8_UNORM | vk::Format::R16_SFLOAT => texel(2),
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R32_SFLOAT => texel(4),
+        vk::Format::R16G16B16A16_SFLOAT => texel(8),
+        vk::Format::R32G32B32A32_SFLOAT => texel(16),
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC4_UNORM_BLOCK
+        | vk::Format::BC4_SNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK
+        | vk::Format::EAC_R11_UNORM_BLOCK => block(4, 4, 8),
+        vk::Format::BC2_UNORM_BLOCK
+        | vk::Format::BC2_SRGB_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC5_SNORM_BLOCK
+        | vk::Format::BC6H_UFLOAT_BLOCK
+        | vk::Format::BC6H_SFLOAT_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK
+        | vk::Format::EAC_R11G11_UNORM_BLOCK => block(4, 4, 16),
+        vk::Format::ASTC_4X4_UNORM_BLOCK | vk::Format::ASTC_4X4_SRGB_BLOCK => block(4, 4, 16),
+        vk::Format::ASTC_5X4_UNORM_BLOCK | vk::Format::ASTC_5X4_SRGB_BLOCK => block(5, 4, 16),
+        vk::Format::ASTC_5X5_UNORM_BLOCK | vk::Format::ASTC_5X5_SRGB_BLOCK => block(5, 5, 16),
+        vk::Format::ASTC_6X5_UNORM_BLOCK | vk::Format::ASTC_6X5_SRGB_BLOCK => block(6, 5, 16),
+        vk::Format::ASTC_6X6_UNORM_BLOCK | vk::Format::ASTC_6X6_SRGB_BLOCK => block(6, 6, 16),
+        vk::Format::ASTC_8X5_UNORM_BLOCK | vk::Format::ASTC_8X5_SRGB_BLOCK => block(8, 5, 16),
+        vk::Format::ASTC_8X6_UNORM_BLOCK | vk::Format::ASTC_8X6_SRGB_BLOCK => block(8, 6, 16),
+        vk::Format::ASTC_8X8_UNORM_BLOCK | vk::Format::ASTC_8X8_SRGB_BLOCK => block(8, 8, 16),
+        vk::Format::ASTC_10X5_UNORM_BLOCK | vk::Format::ASTC_10X5_SRGB_BLOCK => block(10, 5, 16),
+        vk::Format::ASTC_10X6_UNORM_BLOCK | vk::Format::ASTC_10X6_SRGB_BLOCK => block(10, 6, 16),
+        vk::Format::ASTC_10X8_UNORM_BLOCK | vk::Format::ASTC_10X8_SRGB_BLOCK => block(10, 8, 16),
+        vk::Format::ASTC_10X10_UNORM_BLOCK | vk::Format::ASTC_10X10_SRGB_BLOCK => block(10, 10, 16),
+        vk::Format::ASTC_12X10_UNORM_BLOCK | vk::Format::ASTC_12X10_SRGB_BLOCK => block(12, 10, 16),
+        vk::Format::ASTC_12X12_UNORM_BLOCK | vk::Format::ASTC_12X12_SRGB_BLOCK => block(12, 12, 16),
+        _ => return None,
+    };
+    Some(block)
+}
+
+pub fn is_block_compressed(format: vk::Format) -> bool {
+    format_block(format).is_some_and(|block| block.width > 1)
+}
+
+/// Bytes of one layer of a mip level with the given extent
+pub fn level_size(format: vk::Format, extent: vk::Extent3D) -> Option<usize> {
+    let block = format_block(format)?;
+    let blocks_x = extent.width.div_ceil(block.width) as usize;
+    let blocks_y = extent.height.div_ceil(block.height) as usize;
+    Some(blocks_x * blocks_y * extent.depth as usize * block.bytes as usize)
+}
+
+/// Decoded texture ready for upload. Every level holds all layers back to back, cube faces count as layers
+/// (+X, -X, +Y, -Y, +Z, -Z for every cube). Level 0 is the largest.
+#[derive(Clone, Debug)]
+pub struct TextureData {
+    pub format: vk::Format,
+    pub kind: ImageKind,
+    pub extent: vk::Extent3D,
+    pub array_layers: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl TextureData {
+    pub fn from_rgba(image: RgbaImage, srgb: bool) -> Self {
+        Self {
+            format: if srgb {
+                vk::Format::R8G8B8A8_SRGB
+            } else {
+                vk::Format::R8G8B8A8_UNORM
+            },
+            kind: ImageKind::D2,
+            extent: vk::Extent3D {
+                width: image.width,
+                height: image.height,
+                depth: 1,
+            },
+            array_layers: 1,
+            levels: vec![image.pixels],
+        }
+    }
+
+    /// Picks the decoder by content, PNG and JPEG are color data and loaded as sRGB if `srgb` is set.
+    /// KTX2 and DDS carry their own format.
+    pub fn from_bytes(bytes: &[u8], srgb: bool) -> Result<Self, Error> {
+        if bytes.starts_with(&ktx2::IDENTIFIER) {
+            ktx2::parse_ktx2(bytes)
+        } else if bytes.starts_with(b"DDS ") {
+            dds::parse_dds(bytes)
+        } else if bytes.starts_with(&[0x89, b'P', b'N', b'G']) {
+            let image = RgbaImage::decode_png(bytes).map_err(Error::Io)?;
+            Ok(Self::from_rgba(image, srgb))
+        } else if bytes.starts_with(&[0xff, 0xd8]) {
+            Ok(Self::from_rgba(jpeg::decode_jpeg(bytes)?, srgb))
+        } else {
+            Err(Error::Unsupported("Unknown texture container"))
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, srgb: bool) -> Result<Self, Error> {
+        Self::from_bytes(&std::fs::read(path)?, srgb)
+    }
+
+    pub fn level_extent(&self, level: u32) -> vk::Extent3D {
+        mip_extent(self.extent, level)
+    }
+
+    /// Checks that every level has the size its extent, format and layer count require
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.levels.is_empty() {
+            return Err(Error::InvalidData("Texture without levels"));
+        }
+        for (level, data) in self.levels.iter().enumerate() {
+            let size = level_size(self.format, self.level_extent(level as u32)).ok_or(Error::UnsupportedFormat(self.format))?;
+            if data.len() != size * self.array_layers as usize {
+                return Err(Error::InvalidData("Texture level size does not match its extent"));
+            }
+        }
+        Ok(())
+    }
+}
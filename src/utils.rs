@@ -1,34 +1,17 @@
-use ash::vk::{CommandBuffer, ComponentMapping, ImageSubresourceRange, PhysicalDevice, SurfaceFormatKHR, SurfaceKHR};
+use crate::backend::vulkan::depth::has_stencil;
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::errors::Error;
+use crate::backend::vulkan::image::ImageViewConfig;
+use crate::backend::vulkan::pipeline::{BlendMode, DepthStencilConfig, GraphicsPipelineBuilder, VertexInputLayout};
+use crate::backend::vulkan::shaders::create_embedded_module;
+use crate::backend::vulkan::swapchain::{choose_present_mode, choose_surface_format, SwapchainConfig};
+use ash::vk::{CommandBuffer, PhysicalDevice, SurfaceFormatKHR, SurfaceKHR};
 use ash::{ext, khr, vk};
 use std::collections::HashMap;
-use std::ffi::{c_char, c_void, CStr, CString};
+use std::ffi::{c_char, c_void, CStr};
 use std::ptr::null;
-use std::{fs, ptr};
-use winit::raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
-#[macro_export]
-macro_rules! fatal_assert {
-    ($($arg:tt)+) => {{
-        error!($($arg)+);
-        std::process::exit(1);
-    }};
-}
-
-#[macro_export]
-macro_rules! fatal_unwrap_e {
-    ($e:expr, $str:expr) => {
-        $e.unwrap_or_else(|e| {
-            fatal_assert!($str, e);
-        })
-    };
-}
-#[macro_export]
-macro_rules! fatal_unwrap {
-    ($e:expr, $str:expr) => {
-        $e.unwrap_or_else(|| {
-            fatal_assert!($str);
-        })
-    };
-}
+use std::ptr;
+use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
 
 pub struct PipelineInfo {
     pub shaders: HashMap<&'static str, vk::ShaderModule>,
@@ -37,6 +20,21 @@ pub struct PipelineInfo {
     pub pipeline: Vec<vk::Pipeline>,
 }
 
+impl PipelineInfo {
+    /// # Safety
+    /// The pipeline must not be in use by the device
+    pub unsafe fn destroy(&self, logical_device: &ash::Device) {
+        for pipeline in self.pipeline.iter() {
+            logical_device.destroy_pipeline(*pipeline, None);
+        }
+        logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
+        logical_device.destroy_render_pass(self.render_pass, None);
+        for shader_module in self.shaders.values() {
+            logical_device.destroy_shader_module(*shader_module, None);
+        }
+    }
+}
+
 pub struct QueueFamilyIndices {
     pub graphics_family: Option<u32>,
     pub surface_family: Option<u32>,
@@ -79,6 +77,10 @@ pub fn create_validation_layers_requirements() -> HashMap<&'static str, (&'stati
     layers
 }
 
+/// Prints debug messenger messages.
+///
+/// # Safety
+/// Called by the Vulkan loader, `p_callback_data` has to point to valid callback data.
 pub unsafe extern "system" fn debug_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_type: vk::DebugUtilsMessageTypeFlagsEXT,
@@ -207,10 +209,10 @@ pub fn create_surface(
     instance: &ash::Instance,
     window: &winit::window::Window,
 ) -> vk::SurfaceKHR {
-    let raw_window_handle = window.raw_window_handle().expect("Failed to get raw window handle");
+    let raw_window_handle = window.window_handle().expect("Failed to get raw window handle").as_raw();
     match raw_window_handle {
         RawWindowHandle::Win32(raw_handle) => {
-            let win32_surface_loader = khr::win32_surface::Instance::new(&entry, &instance);
+            let win32_surface_loader = khr::win32_surface::Instance::new(entry, instance);
 
             let surface_info = vk::Win32SurfaceCreateInfoKHR {
                 s_type: vk::StructureType::WIN32_SURFACE_CREATE_INFO_KHR,
@@ -221,12 +223,11 @@ pub fn create_surface(
                 _marker: Default::default(),
             };
 
-            let platform_surface = unsafe {
+            unsafe {
                 win32_surface_loader
                     .create_win32_surface(&surface_info, None)
                     .expect("Failed to create surface!")
-            };
-            platform_surface
+            }
         }
         _ => panic!("Unsupported window handle type"),
     }
@@ -246,11 +247,9 @@ pub fn get_queue_families(
             break;
         }
 
-        if queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-            if graphics_family.graphics_family.is_none() {
-                println!("Set graphics queue family idx: {}", index);
-                graphics_family.graphics_family = Some(index as u32);
-            }
+        if queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS) && graphics_family.graphics_family.is_none() {
+            println!("Set graphics queue family idx: {}", index);
+            graphics_family.graphics_family = Some(index as u32);
         }
 
         if unsafe { surface_loader.get_physical_device_surface_support(*device, index as u32, surface) }
@@ -291,7 +290,7 @@ pub fn check_device_extension_support(
         }
     }
 
-    if requested_extensions.len() > 0 {
+    if !requested_extensions.is_empty() {
         return false;
     }
     println!("All requested device extensions supported!");
@@ -307,7 +306,7 @@ pub fn pick_physical_device(
             .enumerate_physical_devices()
             .expect("Failed to enumerate physical devices")
     };
-    if physical_devices.len() == 0 {
+    if physical_devices.is_empty() {
         panic!("No physical devices supporting Vulkan found!");
     }
 
@@ -333,7 +332,7 @@ pub fn pick_physical_device(
             if surface_properties.formats.is_empty() | surface_properties.present_modes.is_empty() {
                 return false;
             }
-            return true;
+            true
         })
         .collect();
 
@@ -384,12 +383,10 @@ pub fn create_logical_device(
         flags: Default::default(),
         queue_create_info_count: count as u32,
         p_queue_create_infos: device_queues.as_ptr(),
-        enabled_layer_count: 0,
-        pp_enabled_layer_names: null(),
         enabled_extension_count: 1,
         pp_enabled_extension_names: REQUIRED_DEVICE_EXTENSIONS.as_ptr(),
         p_enabled_features: &physical_device_features,
-        _marker: Default::default(),
+        ..Default::default()
     };
     unsafe {
         vulcan_instance
@@ -398,22 +395,12 @@ pub fn create_logical_device(
     }
 }
 
-pub fn select_surface_format(swapchain_support: &SurfaceProperties) -> vk::SurfaceFormatKHR {
-    for format in swapchain_support.formats.iter() {
-        if format.format == vk::Format::B8G8R8_SRGB && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR {
-            return *format;
-        }
-    }
-    swapchain_support.formats[0]
+pub fn select_surface_format(swapchain_support: &SurfaceProperties) -> Result<vk::SurfaceFormatKHR, Error> {
+    choose_surface_format(&swapchain_support.formats, &SwapchainConfig::default()).ok_or(Error::NoSurfaceFormats)
 }
 
 pub fn select_present_mode(swapchain_support: &SurfaceProperties) -> vk::PresentModeKHR {
-    for mode in swapchain_support.present_modes.iter() {
-        if *mode == vk::PresentModeKHR::FIFO {
-            return *mode;
-        }
-    }
-    swapchain_support.present_modes[0]
+    choose_present_mode(&swapchain_support.present_modes, &SwapchainConfig::default())
 }
 
 pub fn select_swap_size(
@@ -442,10 +429,10 @@ pub fn create_swap_chain(
     surface: SurfaceKHR,
     queue_family_indices: &QueueFamilyIndices,
     window: &winit::window::Window,
-) -> (vk::SwapchainKHR, SurfaceFormatKHR, vk::Extent2D) {
-    let surface_format = select_surface_format(surface_properties);
+) -> Result<(vk::SwapchainKHR, SurfaceFormatKHR, vk::Extent2D), Error> {
+    let surface_format = select_surface_format(surface_properties)?;
     let present_mode = select_present_mode(surface_properties);
-    let extent = select_swap_size(surface_properties, &window);
+    let extent = select_swap_size(surface_properties, window);
     let mut image_sharing_mode = vk::SharingMode::EXCLUSIVE;
     let mut queue_family_index_count = 0;
     let mut p_queue_family_indices = null();
@@ -479,8 +466,8 @@ pub fn create_swap_chain(
         old_swapchain: vk::SwapchainKHR::null(),
         _marker: Default::default(),
     };
-    let swap_chain = unsafe { swap_chain_loader.create_swapchain(&swapchain_create_info, None) };
-    (swap_chain.expect("Failed to create Swapchain!"), surface_format, extent)
+    let swap_chain = unsafe { swap_chain_loader.create_swapchain(&swapchain_create_info, None)? };
+    Ok((swap_chain, surface_format, extent))
 }
 
 pub fn create_image_views(
@@ -492,28 +479,7 @@ pub fn create_image_views(
     let swapchain_images = unsafe { swapchain_loader.get_swapchain_images(*swapchain) }.expect("Failed to get Swapchain Images.");
     let mut swapchain_image_views = Vec::with_capacity(swapchain_images.len());
     for image in swapchain_images {
-        let create_info = vk::ImageViewCreateInfo {
-            s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
-            p_next: null(),
-            flags: Default::default(),
-            image,
-            view_type: vk::ImageViewType::TYPE_2D,
-            format: format.format,
-            components: ComponentMapping {
-                r: vk::ComponentSwizzle::IDENTITY,
-                g: vk::ComponentSwizzle::IDENTITY,
-                b: vk::ComponentSwizzle::IDENTITY,
-                a: vk::ComponentSwizzle::IDENTITY,
-            },
-            subresource_range: ImageSubresourceRange {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                base_mip_level: 0,
-                level_count: 1,
-                base_array_layer: 0,
-                layer_count: 1,
-            },
-            _marker: Default::default(),
-        };
+        let create_info = ImageViewConfig::color_2d(format.format).create_info(image);
         let image_view = unsafe { device.create_image_view(&create_info, None) }.expect("Failed to create Image View!");
         swapchain_image_views.push(image_view)
     }
@@ -521,56 +487,69 @@ pub fn create_image_views(
     swapchain_image_views
 }
 
-pub fn load_shaders(
-    logical_device: &ash::Device,
-    shader_dir: &str,
-) -> HashMap<&'static str, vk::ShaderModule> {
-    let fragment_shader = fs::read(shader_dir.to_string() + "/fshader.spv").expect("Failed to read shader file");
-    let vertex_shader = fs::read(shader_dir.to_string() + "/vshader.spv").expect("Failed to read shader file");
-    let mut byte_shaders = Vec::with_capacity(2);
-    byte_shaders.push((fragment_shader, "fshader"));
-    byte_shaders.push((vertex_shader, "vshader"));
-    let mut shader_modules = HashMap::with_capacity(2);
-
-    for (shader, name) in byte_shaders.iter() {
-        if (shader.len() % 4) != 0 {
-            panic!("Shader {} is not 4 byte aligned", name);
-        }
-
-        let shader = vk::ShaderModuleCreateInfo {
-            s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
-            p_next: null(),
-            flags: Default::default(),
-            code_size: shader.len(),
-            p_code: shader.as_ptr() as *const u32,
-            _marker: Default::default(),
-        };
-        let shader_module = unsafe { logical_device.create_shader_module(&shader, None) }.expect("Failed to create shader module!");
-        println!("Created shader module: {}", name);
-        shader_modules.insert(*name, shader_module);
-    }
-    shader_modules
-}
 pub fn create_render_pass(
     device: &ash::Device,
     surface_format: &SurfaceFormatKHR,
+) -> vk::RenderPass {
+    create_color_render_pass(device, surface_format.format, vk::ImageLayout::PRESENT_SRC_KHR)
+}
+
+/// Single color attachment render pass. With `TRANSFER_SRC_OPTIMAL` as the final layout the attachment
+/// can be copied out right after the pass (offscreen rendering).
+pub fn create_color_render_pass(
+    device: &ash::Device,
+    format: vk::Format,
+    final_layout: vk::ImageLayout,
+) -> vk::RenderPass {
+    create_render_pass_with_depth(device, format, None, final_layout)
+}
+
+/// `create_color_render_pass` with an optional depth attachment at index 1. Depth (and stencil) is cleared on load
+/// and discarded after the pass, framebuffers pass the depth view as their second attachment.
+pub fn create_render_pass_with_depth(
+    device: &ash::Device,
+    format: vk::Format,
+    depth_format: Option<vk::Format>,
+    final_layout: vk::ImageLayout,
 ) -> vk::RenderPass {
     let color_attachment = vk::AttachmentDescription {
         flags: Default::default(),
-        format: surface_format.format,
+        format,
         samples: vk::SampleCountFlags::TYPE_1,
         load_op: vk::AttachmentLoadOp::CLEAR,
         store_op: vk::AttachmentStoreOp::STORE,
         stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
         stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
         initial_layout: vk::ImageLayout::UNDEFINED,
-        final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        final_layout,
     };
+    let mut attachments = vec![color_attachment];
+    if let Some(depth_format) = depth_format {
+        attachments.push(vk::AttachmentDescription {
+            flags: Default::default(),
+            format: depth_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::DONT_CARE,
+            stencil_load_op: if has_stencil(depth_format) {
+                vk::AttachmentLoadOp::CLEAR
+            } else {
+                vk::AttachmentLoadOp::DONT_CARE
+            },
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        });
+    }
 
     let color_attachment_ref = vk::AttachmentReference {
         attachment: 0,
         layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
     };
+    let depth_attachment_ref = vk::AttachmentReference {
+        attachment: 1,
+        layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
 
     let subpass = vk::SubpassDescription {
         flags: Default::default(),
@@ -580,32 +559,67 @@ pub fn create_render_pass(
         color_attachment_count: 1,
         p_color_attachments: &color_attachment_ref,
         p_resolve_attachments: null(),
-        p_depth_stencil_attachment: null(),
+        p_depth_stencil_attachment: if depth_format.is_some() {
+            &depth_attachment_ref
+        } else {
+            null()
+        },
         preserve_attachment_count: 0,
         p_preserve_attachments: null(),
         _marker: Default::default(),
     };
 
-    let subpass_dependency = vk::SubpassDependency {
-        src_subpass: vk::SUBPASS_EXTERNAL,
-        dst_subpass: 0,
-        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-        src_access_mask: vk::AccessFlags::empty(),
-        dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-        dependency_flags: vk::DependencyFlags::BY_REGION,
+    // With depth the pass also has to wait for the depth writes of the previous frame, they share the depth image
+    let fragment_tests = vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+    let subpass_dependency = if depth_format.is_some() {
+        vk::SubpassDependency {
+            src_subpass: vk::SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | fragment_tests,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | fragment_tests,
+            src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            dependency_flags: vk::DependencyFlags::BY_REGION,
+        }
+    } else {
+        vk::SubpassDependency {
+            src_subpass: vk::SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dependency_flags: vk::DependencyFlags::BY_REGION,
+        }
     };
 
+    // Makes the attachment writes visible to a copy recorded after the pass
+    let transfer_dependency = vk::SubpassDependency {
+        src_subpass: 0,
+        dst_subpass: vk::SUBPASS_EXTERNAL,
+        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+        src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+        dependency_flags: Default::default(),
+    };
+    let mut dependencies = vec![subpass_dependency];
+    if final_layout == vk::ImageLayout::TRANSFER_SRC_OPTIMAL {
+        dependencies.push(transfer_dependency);
+    }
+
     let render_pass = vk::RenderPassCreateInfo {
         s_type: vk::StructureType::RENDER_PASS_CREATE_INFO,
         p_next: null(),
         flags: Default::default(),
-        attachment_count: 1,
-        p_attachments: &color_attachment as *const vk::AttachmentDescription,
+        attachment_count: attachments.len() as u32,
+        p_attachments: attachments.as_ptr(),
         subpass_count: 1,
         p_subpasses: &subpass as *const vk::SubpassDescription,
-        dependency_count: 1,
-        p_dependencies: &subpass_dependency as *const vk::SubpassDependency,
+        dependency_count: dependencies.len() as u32,
+        p_dependencies: dependencies.as_ptr(),
         _marker: Default::default(),
     };
 
@@ -613,189 +627,81 @@ pub fn create_render_pass(
 }
 
 pub fn create_pipeline(
-    logical_device: &ash::Device,
+    logical_device: &Device,
     format: &SurfaceFormatKHR,
-) -> PipelineInfo {
-    let shaders = load_shaders(&logical_device, "cshaders");
-    let render_pass = create_render_pass(&logical_device, format);
-
-    let main_function_name = CString::new("main").unwrap(); // the beginning function name in shader code.
-
-    let vertex_shader_stage_info = vk::PipelineShaderStageCreateInfo {
-        s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        module: shaders["vshader"],
-        p_name: main_function_name.as_ptr(),
-        p_specialization_info: null(),
-        stage: vk::ShaderStageFlags::VERTEX,
-        _marker: Default::default(),
-    };
-
-    let fragment_shader_stage_info = vk::PipelineShaderStageCreateInfo {
-        s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        module: shaders["fshader"],
-        p_name: main_function_name.as_ptr(),
-        p_specialization_info: null(),
-        stage: vk::ShaderStageFlags::FRAGMENT,
-        _marker: Default::default(),
-    };
-    let stages = [vertex_shader_stage_info, fragment_shader_stage_info];
-
-    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-
-    let pipeline_vertex_input_state = vk::PipelineVertexInputStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        vertex_binding_description_count: 0,
-        p_vertex_binding_descriptions: null(),
-        vertex_attribute_description_count: 0,
-        p_vertex_attribute_descriptions: null(),
-        _marker: Default::default(),
-    };
-
-    let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-        primitive_restart_enable: vk::FALSE,
-        _marker: Default::default(),
-    };
-
-    let viewport_state = vk::PipelineViewportStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_VIEWPORT_STATE_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        viewport_count: 1,
-        p_viewports: null(),
-        scissor_count: 1,
-        p_scissors: null(),
-        _marker: Default::default(),
-    };
-
-    let rasterizer = vk::PipelineRasterizationStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        depth_clamp_enable: vk::FALSE,
-        rasterizer_discard_enable: vk::FALSE,
-        polygon_mode: vk::PolygonMode::FILL,
-        cull_mode: vk::CullModeFlags::NONE,
-        front_face: vk::FrontFace::CLOCKWISE,
-        depth_bias_enable: 0,
-        depth_bias_constant_factor: 0.0,
-        depth_bias_clamp: 0.0,
-        depth_bias_slope_factor: 0.0,
-        line_width: 1.0,
-        _marker: Default::default(),
-    };
-
-    let multisample_state = vk::PipelineMultisampleStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        rasterization_samples: vk::SampleCountFlags::TYPE_1,
-        sample_shading_enable: vk::FALSE,
-        min_sample_shading: 1.0,
-        p_sample_mask: null(),
-        alpha_to_coverage_enable: vk::FALSE,
-        alpha_to_one_enable: vk::FALSE,
-        _marker: Default::default(),
-    };
-
-    let color_blending_attachment = vk::PipelineColorBlendAttachmentState {
-        blend_enable: vk::TRUE,
-        src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
-        dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
-        color_blend_op: vk::BlendOp::ADD,
-        src_alpha_blend_factor: vk::BlendFactor::ONE,
-        dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-        alpha_blend_op: Default::default(), // vk::BlendOp::ADD,
-        color_write_mask: vk::ColorComponentFlags::R | vk::ColorComponentFlags::G | vk::ColorComponentFlags::B | vk::ColorComponentFlags::A,
-    };
-
-    let color_blending_state = vk::PipelineColorBlendStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        logic_op_enable: vk::FALSE,
-        logic_op: vk::LogicOp::COPY,
-        attachment_count: 1,
-        p_attachments: &color_blending_attachment as *const vk::PipelineColorBlendAttachmentState,
-        blend_constants: [0.0, 0.0, 0.0, 0.0],
-        _marker: Default::default(),
-    };
-
-    let pipline_dynamic_state = vk::PipelineDynamicStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_DYNAMIC_STATE_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        dynamic_state_count: 2,
-        p_dynamic_states: dynamic_states.as_ptr(),
-        _marker: Default::default(),
-    };
+) -> Result<PipelineInfo, Error> {
+    let render_pass = create_render_pass(logical_device, format);
+    create_pipeline_with_render_pass(logical_device, render_pass).inspect_err(|_| unsafe {
+        logical_device.destroy_render_pass(render_pass, None);
+    })
+}
 
-    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
-        s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        set_layout_count: 0,
-        p_set_layouts: null(),
-        push_constant_range_count: 0,
-        p_push_constant_ranges: null(),
-        _marker: Default::default(),
-    };
+/// Builds the triangle pipeline for an existing render pass. The returned `PipelineInfo` takes ownership of the render pass,
+/// on error it stays with the caller.
+pub fn create_pipeline_with_render_pass(
+    logical_device: &Device,
+    render_pass: vk::RenderPass,
+) -> Result<PipelineInfo, Error> {
+    create_pipeline_with_vertex_input(logical_device, render_pass, "vshader", "fshader", VertexInputLayout::new(), DepthStencilConfig::disabled())
+}
 
-    let pipeline_layout = unsafe {
-        logical_device
-            .create_pipeline_layout(&pipeline_layout_create_info, None)
-            .expect("Failed to create pipeline layout!")
+/// Same fixed function state as `create_pipeline_with_render_pass` with the given embedded shaders, vertex input and
+/// depth state.
+/// The returned `PipelineInfo` takes ownership of the render pass, on error it stays with the caller.
+pub fn create_pipeline_with_vertex_input(
+    logical_device: &Device,
+    render_pass: vk::RenderPass,
+    vertex_shader: &'static str,
+    fragment_shader: &'static str,
+    vertex_input: VertexInputLayout,
+    depth_stencil: DepthStencilConfig,
+) -> Result<PipelineInfo, Error> {
+    let mut shaders: HashMap<&'static str, vk::ShaderModule> = HashMap::new();
+    let destroy_shaders = |shaders: &HashMap<&'static str, vk::ShaderModule>| unsafe {
+        for shader_module in shaders.values() {
+            logical_device.destroy_shader_module(*shader_module, None);
+        }
     };
+    for name in [vertex_shader, fragment_shader] {
+        match create_embedded_module(logical_device, name) {
+            Ok(shader_module) => {
+                shaders.insert(name, shader_module);
+            }
+            Err(error) => {
+                destroy_shaders(&shaders);
+                return Err(error);
+            }
+        }
+    }
 
-    let pipline_info = vk::GraphicsPipelineCreateInfo {
-        s_type: vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
-        p_next: null(),
-        flags: Default::default(),
-        stage_count: stages.len() as u32,
-        p_stages: stages.as_ptr(),
-        p_vertex_input_state: &pipeline_vertex_input_state as *const vk::PipelineVertexInputStateCreateInfo,
-        p_input_assembly_state: &input_assembly_state as *const vk::PipelineInputAssemblyStateCreateInfo,
-        p_tessellation_state: null(),
-        p_viewport_state: &viewport_state as *const vk::PipelineViewportStateCreateInfo,
-        p_rasterization_state: &rasterizer as *const vk::PipelineRasterizationStateCreateInfo,
-        p_multisample_state: &multisample_state as *const vk::PipelineMultisampleStateCreateInfo,
-        p_depth_stencil_state: null(),
-        p_color_blend_state: &color_blending_state as *const vk::PipelineColorBlendStateCreateInfo,
-        p_dynamic_state: &pipline_dynamic_state as *const vk::PipelineDynamicStateCreateInfo,
-        layout: pipeline_layout,
-        render_pass,
-        subpass: 0,
-        base_pipeline_handle: vk::Pipeline::null(),
-        base_pipeline_index: 0,
-        _marker: Default::default(),
-    };
-    let pipeline = unsafe {
-        logical_device
-            .create_graphics_pipelines(vk::PipelineCache::null(), &[pipline_info], None)
-            .expect("Failed to create pipeline!")
+    let pipeline = GraphicsPipelineBuilder::new(render_pass)
+        .vertex_shader(shaders[vertex_shader])
+        .fragment_shader(shaders[fragment_shader])
+        .vertex_input(vertex_input)
+        .depth_stencil(depth_stencil)
+        .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::CLOCKWISE)
+        .color_attachments(&[BlendMode::Alpha])
+        .build(logical_device);
+    let (pipeline, pipeline_layout) = match pipeline {
+        Ok(pipeline) => pipeline.into_raw(),
+        Err(error) => {
+            destroy_shaders(&shaders);
+            return Err(error);
+        }
     };
-    PipelineInfo {
+    Ok(PipelineInfo {
         shaders,
         pipeline_layout,
         render_pass,
-        pipeline,
-    }
+        pipeline: vec![pipeline],
+    })
 }
 
 pub fn create_framebuffer(
     device: &ash::Device,
     swapchain_size: vk::Extent2D,
     render_pass: vk::RenderPass,
-    image_views: &Vec<vk::ImageView>,
+    image_views: &[vk::ImageView],
 ) -> Vec<vk::Framebuffer> {
     let mut vec = Vec::with_capacity(image_views.len());
     for view in image_views.iter() {
@@ -843,14 +749,12 @@ pub fn create_command_buffers(
         level: vk::CommandBufferLevel::PRIMARY,
         _marker: Default::default(),
     };
-    let command_buffers =
-        unsafe { device.allocate_command_buffers(&vk_command_buffer_allocate_info) }.expect("Failed to allocate command buffers!");
-    command_buffers
+    unsafe { device.allocate_command_buffers(&vk_command_buffer_allocate_info) }.expect("Failed to allocate command buffers!")
 }
 
 pub fn create_sync_objects(
     device: &ash::Device,
-    queue_family_indices: &QueueFamilyIndices,
+    _queue_family_indices: &QueueFamilyIndices,
 ) -> (vk::Semaphore, vk::Semaphore, vk::Fence) {
     let semaphore_create_info = vk::SemaphoreCreateInfo {
         s_type: vk::StructureType::SEMAPHORE_CREATE_INFO,